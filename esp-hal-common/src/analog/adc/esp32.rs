@@ -4,7 +4,9 @@ use embedded_hal::adc::{Channel, OneShot};
 
 use crate::{
     analog::{ADC1, ADC2},
+    gpio::AdcPin,
     pac::{RTCIO, SENS},
+    peripherals::{Peripheral, PeripheralRef},
 };
 
 /// The sampling/readout resolution of the ADC
@@ -39,7 +41,7 @@ where
         Self::default()
     }
 
-    pub fn enable_pin<PIN: Channel<ADCI, ID = u8>>(
+    pub fn enable_pin<PIN: Channel<ADCI, ID = u8> + AdcPin>(
         &mut self,
         _pin: &PIN,
         attenuation: Attenuation,
@@ -71,6 +73,10 @@ pub trait RegisterAccess {
 
     fn set_en_pad_force();
 
+    fn clear_start_force();
+
+    fn clear_en_pad_force();
+
     fn set_en_pad(channel: u8);
 
     fn clear_start_sar();
@@ -129,6 +135,20 @@ impl RegisterAccess for ADC1 {
             .modify(|_, w| w.sar1_en_pad_force().set_bit());
     }
 
+    fn clear_start_force() {
+        let sensors = unsafe { &*SENS::ptr() };
+        sensors
+            .sar_meas_start1
+            .modify(|_, w| w.meas1_start_force().clear_bit());
+    }
+
+    fn clear_en_pad_force() {
+        let sensors = unsafe { &*SENS::ptr() };
+        sensors
+            .sar_meas_start1
+            .modify(|_, w| w.sar1_en_pad_force().clear_bit());
+    }
+
     fn set_en_pad(channel: u8) {
         let sensors = unsafe { &*SENS::ptr() };
         sensors
@@ -207,6 +227,20 @@ impl RegisterAccess for ADC2 {
             .modify(|_, w| w.sar2_en_pad_force().set_bit());
     }
 
+    fn clear_start_force() {
+        let sensors = unsafe { &*SENS::ptr() };
+        sensors
+            .sar_meas_start2
+            .modify(|_, w| w.meas2_start_force().clear_bit());
+    }
+
+    fn clear_en_pad_force() {
+        let sensors = unsafe { &*SENS::ptr() };
+        sensors
+            .sar_meas_start2
+            .modify(|_, w| w.sar2_en_pad_force().clear_bit());
+    }
+
     fn set_en_pad(channel: u8) {
         let sensors = unsafe { &*SENS::ptr() };
         sensors
@@ -239,17 +273,20 @@ impl RegisterAccess for ADC2 {
     }
 }
 
-pub struct ADC<ADC> {
-    adc: PhantomData<ADC>,
+pub struct ADC<'d, ADC> {
+    _adc: PeripheralRef<'d, ADC>,
     attenuations: [Option<Attenuation>; 10],
     active_channel: Option<u8>,
 }
 
-impl<ADCI> ADC<ADCI>
+impl<'d, ADCI> ADC<'d, ADCI>
 where
     ADCI: RegisterAccess,
 {
-    pub fn adc(_adc_instance: ADCI, config: AdcConfig<ADCI>) -> Result<Self, ()> {
+    pub fn adc(
+        adc_instance: impl Peripheral<P = ADCI> + 'd,
+        config: AdcConfig<ADCI>,
+    ) -> Result<Self, ()> {
         let sensors = unsafe { &*SENS::ptr() };
 
         // Set reading and sampling resolution
@@ -307,7 +344,7 @@ where
             .modify(|_, w| unsafe { w.sar_amp_wait3().bits(1) });
 
         let adc = ADC {
-            adc: PhantomData,
+            _adc: adc_instance.into_ref(),
             attenuations: config.attenuations,
             active_channel: None,
         };
@@ -316,7 +353,7 @@ where
     }
 }
 
-impl<ADC1> ADC<ADC1> {
+impl<'d, ADC1> ADC<'d, ADC1> {
     pub fn enable_hall_sensor() {
         // Connect hall sensor
         let rtcio = unsafe { &*RTCIO::ptr() };
@@ -330,7 +367,7 @@ impl<ADC1> ADC<ADC1> {
     }
 }
 
-impl<ADCI, WORD, PIN> OneShot<ADCI, WORD, PIN> for ADC<ADCI>
+impl<'d, ADCI, WORD, PIN> OneShot<ADCI, WORD, PIN> for ADC<'d, ADCI>
 where
     WORD: From<u16>,
     PIN: Channel<ADCI, ID = u8>,
@@ -4,8 +4,10 @@ use embedded_hal::adc::{Channel, OneShot};
 
 use crate::{
     analog::{ADC1, ADC2},
+    gpio::AdcPin,
     pac::APB_SARADC,
-    system::{Peripheral, PeripheralClockControl},
+    peripherals::{Peripheral, PeripheralRef},
+    system::PeripheralClockControl,
 };
 
 /// The sampling/readout resolution of the ADC
@@ -37,7 +39,7 @@ where
         Self::default()
     }
 
-    pub fn enable_pin<PIN: Channel<ADCI, ID = u8>>(
+    pub fn enable_pin<PIN: Channel<ADCI, ID = u8> + AdcPin>(
         &mut self,
         _pin: &PIN,
         attenuation: Attenuation,
@@ -149,22 +151,22 @@ impl RegisterAccess for ADC2 {
     }
 }
 
-pub struct ADC<ADC> {
-    adc: PhantomData<ADC>,
+pub struct ADC<'d, ADC> {
+    _adc: PeripheralRef<'d, ADC>,
     attenuations: [Option<Attenuation>; 5],
     active_channel: Option<u8>,
 }
 
-impl<ADCI> ADC<ADCI>
+impl<'d, ADCI> ADC<'d, ADCI>
 where
     ADCI: RegisterAccess,
 {
     pub fn adc(
         peripheral_clock_controller: &mut PeripheralClockControl,
-        _adc_instance: ADCI,
+        adc_instance: impl Peripheral<P = ADCI> + 'd,
         config: AdcConfig<ADCI>,
     ) -> Result<Self, ()> {
-        peripheral_clock_controller.enable(Peripheral::ApbSarAdc);
+        peripheral_clock_controller.enable(crate::system::Peripheral::ApbSarAdc);
 
         let sar_adc = unsafe { &*APB_SARADC::PTR };
         sar_adc.ctrl.modify(|_, w| unsafe {
@@ -178,7 +180,7 @@ where
                 .bits(0b11)
         });
         let adc = ADC {
-            adc: PhantomData,
+            _adc: adc_instance.into_ref(),
             attenuations: config.attenuations,
             active_channel: None,
         };
@@ -187,7 +189,7 @@ where
     }
 }
 
-impl<ADCI, WORD, PIN> OneShot<ADCI, WORD, PIN> for ADC<ADCI>
+impl<'d, ADCI, WORD, PIN> OneShot<ADCI, WORD, PIN> for ADC<'d, ADCI>
 where
     WORD: From<u16>,
     PIN: Channel<ADCI, ID = u8>,
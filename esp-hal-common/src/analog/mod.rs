@@ -6,6 +6,22 @@ pub mod adc;
 #[cfg(not(any(feature = "esp32c3", feature = "esp32s3")))]
 pub mod dac;
 
+macro_rules! impl_peripheral {
+    ($($name:ident),+) => {
+        $(
+            impl crate::peripherals::Peripheral for $name {
+                type P = $name;
+
+                unsafe fn clone_unchecked(&mut self) -> Self::P {
+                    $name {
+                        _private: PhantomData,
+                    }
+                }
+            }
+        )+
+    };
+}
+
 cfg_if::cfg_if! {
     if #[cfg(any(feature = "esp32", feature = "esp32s2"))] {
         use core::marker::PhantomData;
@@ -27,6 +43,8 @@ cfg_if::cfg_if! {
             _private: PhantomData<()>,
         }
 
+        impl_peripheral!(ADC1, ADC2);
+
         pub struct AvailableAnalog {
             pub adc1: ADC1,
             pub adc2: ADC2,
@@ -73,6 +91,8 @@ cfg_if::cfg_if! {
             _private: PhantomData<()>,
         }
 
+        impl_peripheral!(ADC1, ADC2);
+
         pub struct AvailableAnalog {
             pub adc1: ADC1,
             pub adc2: ADC2,
@@ -0,0 +1,108 @@
+//! Helpers shared by the interrupt-driven async APIs of the drivers
+//!
+//! A driver keeps a [WakerSlot] per direction in a `static`, registers the
+//! waker of a pending operation in it, and wakes it from its interrupt
+//! handler. Operations without an interrupt are turned into a
+//! [PollingFuture] instead.
+
+use core::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use critical_section::Mutex;
+
+/// Storage for the waker of a pending async operation
+pub(crate) struct WakerSlot(Mutex<RefCell<Option<Waker>>>);
+
+impl WakerSlot {
+    pub(crate) const fn new() -> Self {
+        Self(Mutex::new(RefCell::new(None)))
+    }
+
+    fn register(&self, waker: &Waker) {
+        critical_section::with(|cs| {
+            let mut slot = self.0.borrow(cs).borrow_mut();
+
+            match slot.as_ref() {
+                Some(current) if current.will_wake(waker) => {}
+                _ => *slot = Some(waker.clone()),
+            }
+        });
+    }
+
+    pub(crate) fn wake(&self) {
+        if let Some(waker) = critical_section::with(|cs| self.0.borrow(cs).borrow_mut().take()) {
+            waker.wake();
+        }
+    }
+}
+
+/// Turns a non-blocking operation into a future, which is woken through
+/// `waker` whenever the operation may be able to make progress
+pub(crate) struct NbFuture<'a, F> {
+    waker: &'a WakerSlot,
+    operation: F,
+}
+
+impl<'a, F> NbFuture<'a, F> {
+    pub(crate) fn new(waker: &'a WakerSlot, operation: F) -> Self {
+        Self { waker, operation }
+    }
+}
+
+impl<'a, F, T, E> Future for NbFuture<'a, F>
+where
+    F: FnMut() -> nb::Result<T, E> + Unpin,
+{
+    type Output = Result<T, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        // Register before trying, so a completion in between is not missed
+        this.waker.register(cx.waker());
+
+        match (this.operation)() {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(nb::Error::Other(error)) => Poll::Ready(Err(error)),
+            Err(nb::Error::WouldBlock) => Poll::Pending,
+        }
+    }
+}
+
+/// Turns a non-blocking operation which cannot wake its task into a future,
+/// which asks to be polled again as long as the operation would block
+#[allow(unused)]
+pub(crate) struct PollingFuture<F> {
+    operation: F,
+}
+
+#[allow(unused)]
+impl<F> PollingFuture<F> {
+    pub(crate) fn new(operation: F) -> Self {
+        Self { operation }
+    }
+}
+
+impl<F, T, E> Future for PollingFuture<F>
+where
+    F: FnMut() -> nb::Result<T, E> + Unpin,
+{
+    type Output = Result<T, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        match (this.operation)() {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(nb::Error::Other(error)) => Poll::Ready(Err(error)),
+            Err(nb::Error::WouldBlock) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
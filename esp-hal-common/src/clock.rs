@@ -1,7 +1,112 @@
 //! # Clock Control
+//!
+//! The clock configuration is applied with [ClockControl::freeze], which
+//! returns the frozen [Clocks]. Drivers which depend on a clock frequency take
+//! a `&Clocks` reference.
+//!
+//! Alternatively, the frozen clocks can be installed globally with
+//! [Clocks::freeze_global]. Their frequencies can then be queried from
+//! anywhere, e.g. from an interrupt handler, with [cpu], [apb], [xtal] and
+//! [i2c]:
+//!
+//! ```no_run
+//! let clocks = ClockControl::boot_defaults(system.clock_control)
+//!     .freeze()
+//!     .freeze_global();
+//!
+//! // elsewhere
+//! let ticks_per_ms = clock::apb().to_kHz();
+//! ```
+//!
+//! ## Changing the CPU clock at runtime
+//!
+//! The CPU clock can still be changed after freezing, e.g. to save power
+//! while idle, through a mutable reference to the [Clocks]:
+//!
+//! ```no_run
+//! let mut clocks = ClockControl::configure(system.clock_control, CpuClock::Clock240MHz).freeze();
+//!
+//! clock::on_apb_change(|apb| {
+//!     // reconfigure the baud rates and tick rates derived from APB_CLK
+//! });
+//!
+//! // idle: the CPU and APB_CLK run from XTAL_CLK
+//! clocks.switch_cpu_to_xtal();
+//! // busy: back to the PLL, with APB_CLK at 80 MHz
+//! clocks.set_cpu_clock(CpuClock::Clock240MHz);
+//! ```
+//!
+//! The drivers take the frequencies from the [Clocks] when they are created,
+//! so the drivers which derive a baud rate or a tick rate from APB_CLK have to
+//! be reconfigured when it changes. The listeners registered with
+//! [on_apb_change] are called for that. Switching between PLL frequencies
+//! leaves APB_CLK at 80 MHz. The globally installed clocks can not be
+//! changed.
+//!
+//! The current frequencies, including such changes, can be queried from
+//! anywhere with [snapshot]:
+//!
+//! ```no_run
+//! if let Some(clocks) = clock::snapshot() {
+//!     let cycles_per_us = clocks.cpu_clock.to_MHz();
+//! }
+//! ```
+//!
+//! ## 26 MHz crystals (ESP32)
+//!
+//! [ClockControl::configure] uses the crystal frequency detected by the
+//! bootloader. Without a bootloader which stores it, the frequency can be
+//! given or estimated at startup instead:
+//!
+//! ```no_run
+//! let clocks = ClockControl::configure_with_xtal(
+//!     system.clock_control,
+//!     CpuClock::Clock240MHz,
+//!     XtalFrequency::Detect,
+//! )
+//! .freeze();
+//! ```
+//!
+//! ## Audio PLL (ESP32, ESP32-S2)
+//!
+//! The APLL generates clocks which can not be divided from the PLL, like the
+//! master clocks of the 44.1 kHz and 48 kHz sample rate families:
+//!
+//! ```no_run
+//! // 256 * 44.1 kHz
+//! let mclk = clock::enable_apll(&clocks, 11_289_600.Hz()).unwrap();
+//! ```
+//!
+//! ## Clock output (ESP32 only)
+//!
+//! The internal clocks can be output on the `CLK_OUT` pads, e.g. to clock an
+//! external codec or to check the clock configuration with a scope.
+//! `CLK_OUT1` is on GPIO0, `CLK_OUT2` on GPIO3 and `CLK_OUT3` on GPIO1:
+//!
+//! ```no_run
+//! let clk_out = ClockOutput::clk_out1(io.pins.gpio0, ClockOutSource::Xtal);
+//! ```
+//!
+//! The output stops when the [ClockOutput] is dropped.
+//!
+//! `ClockOutput` is not available on the ESP32-C3, ESP32-S2 and ESP32-S3 yet.
+//! Their `CLK_OUT` signals select from a different set of clocks, which is
+//! not supported by this driver.
+use core::{
+    cell::{Cell, RefCell, UnsafeCell},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use critical_section::Mutex;
 use fugit::HertzU32;
 
-use crate::system::SystemClockControl;
+use crate::{rtc_cntl::RtcClock, system::SystemClockControl};
+#[cfg(feature = "esp32")]
+use crate::{
+    gpio::{types::OutputSignal, ClkOut1Pin, ClkOut2Pin, ClkOut3Pin, OutputPin},
+    into_ref,
+    peripherals::{Peripheral, PeripheralRef},
+};
 
 #[cfg_attr(feature = "esp32", path = "clocks_ll/esp32.rs")]
 #[cfg_attr(feature = "esp32c3", path = "clocks_ll/esp32c3.rs")]
@@ -23,6 +128,7 @@ pub trait Clock {
 
 /// CPU clock speed
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CpuClock {
     Clock80MHz,
     Clock160MHz,
@@ -42,6 +148,40 @@ impl Clock for CpuClock {
     }
 }
 
+/// Frequency of the main crystal (ESP32)
+///
+/// ESP32 modules use 40 MHz crystals, but many boards ship 26 MHz ones.
+#[cfg(feature = "esp32")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum XtalFrequency {
+    /// The frequency the bootloader has detected, or 40 MHz if it did not
+    /// store one
+    Bootloader,
+    /// Estimate the frequency against the internal 8 MHz oscillator
+    Detect,
+    /// 40 MHz
+    Xtal40MHz,
+    /// 26 MHz
+    Xtal26MHz,
+}
+
+#[cfg(feature = "esp32")]
+impl XtalFrequency {
+    fn resolve(self) -> XtalClock {
+        match self {
+            XtalFrequency::Bootloader => RtcClock::get_xtal_freq(),
+            // the estimate is only accurate to a few MHz
+            XtalFrequency::Detect => match RtcClock::estimate_xtal_frequency() {
+                mhz if mhz > 33 => XtalClock::RtcXtalFreq40M,
+                _ => XtalClock::RtcXtalFreq26M,
+            },
+            XtalFrequency::Xtal40MHz => XtalClock::RtcXtalFreq40M,
+            XtalFrequency::Xtal26MHz => XtalClock::RtcXtalFreq26M,
+        }
+    }
+}
+
 #[allow(unused)]
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum XtalClock {
@@ -95,8 +235,9 @@ impl Clock for ApbClock {
 
 /// Frozen clock frequencies
 ///
-/// The existence of this value indicates that the clock configuration can no
-/// longer be changed
+/// The existence of this value indicates that the clock configuration has
+/// been applied. Only the CPU clock can still be changed, see
+/// [Clocks::set_cpu_clock].
 pub struct Clocks {
     _private: (),
     pub cpu_clock: HertzU32,
@@ -113,14 +254,233 @@ impl Clocks {
     /// (i.e. esp-wifi) create `Clocks`
     #[doc(hidden)]
     pub fn from_raw_clocks(raw_clocks: RawClocks) -> Clocks {
-        Self {
+        let clocks = Self {
             _private: (),
             cpu_clock: raw_clocks.cpu_clock,
             apb_clock: raw_clocks.apb_clock,
             xtal_clock: raw_clocks.xtal_clock,
             i2c_clock: raw_clocks.i2c_clock,
+        };
+        clocks.publish();
+
+        clocks
+    }
+}
+
+impl Clocks {
+    /// Install the frozen clocks globally, making the frequencies available
+    /// through [cpu], [apb], [xtal] and [i2c]
+    ///
+    /// # Panics
+    ///
+    /// Panics if clocks have already been installed.
+    pub fn freeze_global(self) -> &'static Clocks {
+        GLOBAL_CLOCKS.install(self)
+    }
+
+    /// Run the CPU from the PLL at `cpu_clock`, with APB_CLK at 80 MHz
+    ///
+    /// The listeners registered with [on_apb_change] are called if APB_CLK
+    /// changes.
+    pub fn set_cpu_clock(&mut self, cpu_clock: CpuClock) {
+        // the PLL can not be reconfigured while the CPU runs from it
+        switch_cpu_to_xtal();
+        restore_cpu_clock(cpu_clock);
+
+        self.update(cpu_clock.frequency(), HertzU32::MHz(80));
+    }
+
+    /// Run the CPU and APB_CLK from XTAL_CLK, and stop using the PLL
+    ///
+    /// The listeners registered with [on_apb_change] are called if APB_CLK
+    /// changes.
+    pub fn switch_cpu_to_xtal(&mut self) {
+        switch_cpu_to_xtal();
+
+        self.update(self.xtal_clock, self.xtal_clock);
+    }
+
+    fn update(&mut self, cpu_clock: HertzU32, apb_clock: HertzU32) {
+        let apb_changed = apb_clock != self.apb_clock;
+
+        self.cpu_clock = cpu_clock;
+        self.apb_clock = apb_clock;
+        // the I2C controllers of the ESP32 are clocked by APB_CLK
+        #[cfg(feature = "esp32")]
+        {
+            self.i2c_clock = apb_clock;
+        }
+        self.publish();
+
+        if apb_changed {
+            let listeners = critical_section::with(|cs| *APB_LISTENERS.borrow_ref(cs));
+            for listener in listeners.iter().flatten() {
+                listener(apb_clock);
+            }
+        }
+    }
+
+    /// Make the frequencies available through [snapshot]
+    fn publish(&self) {
+        let snapshot = ClockSnapshot {
+            cpu_clock: self.cpu_clock,
+            apb_clock: self.apb_clock,
+            xtal_clock: self.xtal_clock,
+            i2c_clock: self.i2c_clock,
+        };
+
+        critical_section::with(|cs| CURRENT_CLOCKS.borrow(cs).set(Some(snapshot)));
+    }
+}
+
+/// The clock frequencies at one moment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ClockSnapshot {
+    pub cpu_clock: HertzU32,
+    pub apb_clock: HertzU32,
+    pub xtal_clock: HertzU32,
+    pub i2c_clock: HertzU32,
+}
+
+static CURRENT_CLOCKS: Mutex<Cell<Option<ClockSnapshot>>> = Mutex::new(Cell::new(None));
+
+/// The current clock frequencies, or `None` if the clocks have not been
+/// frozen yet
+///
+/// Unlike the [globally installed clocks](global), this follows the changes
+/// made with [Clocks::set_cpu_clock] and [Clocks::switch_cpu_to_xtal], and
+/// can be called from anywhere, e.g. from an interrupt handler.
+pub fn snapshot() -> Option<ClockSnapshot> {
+    critical_section::with(|cs| CURRENT_CLOCKS.borrow(cs).get())
+}
+
+/// Maximum number of listeners registered with [on_apb_change]
+pub const MAX_APB_LISTENERS: usize = 8;
+
+static APB_LISTENERS: Mutex<RefCell<[Option<fn(HertzU32)>; MAX_APB_LISTENERS]>> =
+    Mutex::new(RefCell::new([None; MAX_APB_LISTENERS]));
+
+/// A listener registered with [on_apb_change]
+#[derive(Debug)]
+pub struct ApbListener {
+    index: usize,
+}
+
+impl ApbListener {
+    /// Stop calling the listener
+    pub fn remove(self) {
+        critical_section::with(|cs| APB_LISTENERS.borrow_ref_mut(cs)[self.index] = None);
+    }
+}
+
+/// Call `listener` with the new frequency whenever APB_CLK is changed with
+/// [Clocks::set_cpu_clock] or [Clocks::switch_cpu_to_xtal]
+///
+/// # Panics
+///
+/// Panics if [MAX_APB_LISTENERS] listeners are registered already.
+pub fn on_apb_change(listener: fn(HertzU32)) -> ApbListener {
+    critical_section::with(|cs| {
+        let mut listeners = APB_LISTENERS.borrow_ref_mut(cs);
+        let index = listeners
+            .iter()
+            .position(Option::is_none)
+            .expect("too many APB_CLK listeners");
+
+        listeners[index] = Some(listener);
+
+        ApbListener { index }
+    })
+}
+
+/// Set-once storage of the globally installed clocks
+struct GlobalClocks {
+    installed: AtomicBool,
+    clocks: UnsafeCell<Option<Clocks>>,
+}
+
+// The clocks are written once, inside a critical section and before
+// `installed` is set, and only read after `installed` has been set
+unsafe impl Sync for GlobalClocks {}
+
+impl GlobalClocks {
+    const fn new() -> Self {
+        Self {
+            installed: AtomicBool::new(false),
+            clocks: UnsafeCell::new(None),
         }
     }
+
+    fn install(&'static self, clocks: Clocks) -> &'static Clocks {
+        critical_section::with(|_| {
+            if self.installed.load(Ordering::Acquire) {
+                panic!("Clocks have already been installed globally");
+            }
+
+            // Nothing reads the cell before `installed` is set
+            let clocks = unsafe { (*self.clocks.get()).insert(clocks) };
+            self.installed.store(true, Ordering::Release);
+
+            &*clocks
+        })
+    }
+
+    fn get(&'static self) -> Option<&'static Clocks> {
+        if self.installed.load(Ordering::Acquire) {
+            // Never written again once installed
+            unsafe { (*self.clocks.get()).as_ref() }
+        } else {
+            None
+        }
+    }
+}
+
+static GLOBAL_CLOCKS: GlobalClocks = GlobalClocks::new();
+
+/// The globally installed clocks, if [Clocks::freeze_global] has been called
+pub fn global() -> Option<&'static Clocks> {
+    GLOBAL_CLOCKS.get()
+}
+
+fn installed() -> &'static Clocks {
+    global().expect("Clocks::freeze_global has not been called")
+}
+
+/// CPU clock frequency of the globally installed clocks
+///
+/// # Panics
+///
+/// Panics if [Clocks::freeze_global] has not been called.
+pub fn cpu() -> HertzU32 {
+    installed().cpu_clock
+}
+
+/// APB clock frequency of the globally installed clocks
+///
+/// # Panics
+///
+/// Panics if [Clocks::freeze_global] has not been called.
+pub fn apb() -> HertzU32 {
+    installed().apb_clock
+}
+
+/// Crystal frequency of the globally installed clocks
+///
+/// # Panics
+///
+/// Panics if [Clocks::freeze_global] has not been called.
+pub fn xtal() -> HertzU32 {
+    installed().xtal_clock
+}
+
+/// I2C source clock frequency of the globally installed clocks
+///
+/// # Panics
+///
+/// Panics if [Clocks::freeze_global] has not been called.
+pub fn i2c() -> HertzU32 {
+    installed().i2c_clock
 }
 
 #[doc(hidden)]
@@ -159,18 +519,34 @@ impl ClockControl {
             desired_rates: RawClocks {
                 cpu_clock: HertzU32::MHz(80),
                 apb_clock: HertzU32::MHz(80),
-                xtal_clock: HertzU32::MHz(40),
+                xtal_clock: RtcClock::get_xtal_freq().frequency(),
                 i2c_clock: HertzU32::MHz(80),
             },
         }
     }
 
     /// Configure the CPU clock speed.
+    ///
+    /// The PLL is configured for the crystal frequency the bootloader has
+    /// detected, 40 MHz, 26 MHz or 24 MHz.
     #[allow(unused)]
     pub fn configure(clock_control: SystemClockControl, cpu_clock_speed: CpuClock) -> ClockControl {
-        // like NuttX use 40M hardcoded - if it turns out to be a problem
-        // we will take care then
-        let xtal_freq = XtalClock::RtcXtalFreq40M;
+        Self::configure_with_xtal(clock_control, cpu_clock_speed, XtalFrequency::Bootloader)
+    }
+
+    /// Configure the CPU clock speed for the given crystal frequency.
+    ///
+    /// The frequency is stored for the RTC and for later changes of the CPU
+    /// clock. APB_CLK runs at 80 MHz from the PLL with either crystal, so the
+    /// baud rates derived from it do not depend on the crystal.
+    #[allow(unused)]
+    pub fn configure_with_xtal(
+        clock_control: SystemClockControl,
+        cpu_clock_speed: CpuClock,
+        xtal: XtalFrequency,
+    ) -> ClockControl {
+        let xtal_freq = xtal.resolve();
+        RtcClock::set_xtal_freq(xtal_freq);
         let pll_freq = match cpu_clock_speed {
             CpuClock::Clock80MHz => PllClock::Pll320MHz,
             CpuClock::Clock160MHz => PllClock::Pll320MHz,
@@ -187,8 +563,9 @@ impl ClockControl {
             desired_rates: RawClocks {
                 cpu_clock: cpu_clock_speed.frequency(),
                 apb_clock: HertzU32::MHz(80),
-                xtal_clock: HertzU32::MHz(40),
-                i2c_clock: HertzU32::MHz(40),
+                xtal_clock: xtal_freq.frequency(),
+                // the I2C controllers are clocked by APB_CLK
+                i2c_clock: HertzU32::MHz(80),
             },
         }
     }
@@ -246,6 +623,8 @@ impl ClockControl {
     /// Use what is considered the default settings after boot.
     #[allow(unused)]
     pub fn boot_defaults(clock_control: SystemClockControl) -> ClockControl {
+        crate::systimer::set_fixed_tick_rate();
+
         ClockControl {
             _private: (),
             desired_rates: RawClocks {
@@ -258,9 +637,16 @@ impl ClockControl {
     }
 
     /// Configure the CPU clock speed.
+    ///
+    /// The PLL is configured for 480 MHz, from which all CPU clock speeds
+    /// are divided.
     #[allow(unused)]
     pub fn configure(clock_control: SystemClockControl, cpu_clock_speed: CpuClock) -> ClockControl {
-        clocks_ll::set_cpu_clock(cpu_clock_speed);
+        crate::systimer::set_fixed_tick_rate();
+
+        // the PLL can not be reconfigured while the CPU runs from it
+        switch_cpu_to_xtal();
+        restore_cpu_clock(cpu_clock_speed);
 
         ClockControl {
             _private: (),
@@ -291,9 +677,14 @@ impl ClockControl {
     }
 
     /// Configure the CPU clock speed.
+    ///
+    /// The PLL is configured for 480 MHz, from which all CPU clock speeds
+    /// are divided.
     #[allow(unused)]
     pub fn configure(clock_control: SystemClockControl, cpu_clock_speed: CpuClock) -> ClockControl {
-        clocks_ll::set_cpu_clock(cpu_clock_speed);
+        // the PLL can not be reconfigured while the CPU runs from it
+        switch_cpu_to_xtal();
+        restore_cpu_clock(cpu_clock_speed);
 
         ClockControl {
             _private: (),
@@ -306,3 +697,342 @@ impl ClockControl {
         }
     }
 }
+
+/// Coefficients of the audio PLL (APLL)
+///
+/// The APLL runs at `xtal * (4 + sdm2 + sdm1 / 2^8 + sdm0 / 2^16) / (2 *
+/// (o_div + 2))`, where the multiplied crystal frequency in the numerator has
+/// to lie between 350 MHz and 500 MHz.
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ApllConfig {
+    /// Output divider, 0 to 31
+    pub o_div: u8,
+    /// Fractional part of the multiplier, in 1/65536
+    pub sdm0: u8,
+    /// Fractional part of the multiplier, in 1/256
+    pub sdm1: u8,
+    /// Integer part of the multiplier minus 4, 0 to 63
+    pub sdm2: u8,
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+impl ApllConfig {
+    const MULTIPLIER_OUT_MIN_HZ: u64 = 350_000_000;
+    const MULTIPLIER_OUT_MAX_HZ: u64 = 500_000_000;
+
+    /// The coefficients giving the frequency closest to `frequency` with the
+    /// crystal frequency `xtal`
+    ///
+    /// Returns `None` if `frequency` is out of the range of the APLL, which
+    /// is about 5.3 MHz to 125 MHz.
+    pub fn for_frequency(xtal: HertzU32, frequency: HertzU32) -> Option<Self> {
+        let xtal = xtal.to_Hz() as u64;
+        let frequency = frequency.to_Hz() as u64;
+        if frequency == 0 {
+            return None;
+        }
+
+        // the smallest output divider which keeps the multiplied frequency
+        // above its minimum
+        let half_divider =
+            ((Self::MULTIPLIER_OUT_MIN_HZ + 2 * frequency - 1) / (2 * frequency)).max(2);
+        let numerator = 2 * half_divider * frequency;
+        if half_divider > 33 || numerator > Self::MULTIPLIER_OUT_MAX_HZ {
+            return None;
+        }
+
+        // multiplier with 16 fractional bits
+        let multiplier = ((numerator << 16) + xtal / 2) / xtal;
+        let sdm2 = (multiplier >> 16)
+            .checked_sub(4)
+            .filter(|sdm2| *sdm2 <= 63)?;
+
+        Some(Self {
+            o_div: (half_divider - 2) as u8,
+            sdm0: multiplier as u8,
+            sdm1: (multiplier >> 8) as u8,
+            sdm2: sdm2 as u8,
+        })
+    }
+
+    /// The frequency the APLL runs at with these coefficients and the crystal
+    /// frequency `xtal`
+    pub fn frequency(&self, xtal: HertzU32) -> HertzU32 {
+        let multiplier =
+            ((self.sdm2 as u64 + 4) << 16) | ((self.sdm1 as u64) << 8) | self.sdm0 as u64;
+        let divider = 2 * (self.o_div as u64 + 2);
+
+        HertzU32::Hz((((xtal.to_Hz() as u64 * multiplier) >> 16) / divider) as u32)
+    }
+}
+
+/// Start the APLL at the frequency closest to `frequency`, and return the
+/// frequency it runs at
+///
+/// Returns `None` if `frequency` is out of the range of the APLL, see
+/// [ApllConfig::for_frequency]. Revision 0 of the ESP32 does not support the
+/// fractional part of the multiplier, so its APLL frequencies are coarser.
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+pub fn enable_apll(clocks: &Clocks, frequency: HertzU32) -> Option<HertzU32> {
+    #[allow(unused_mut)]
+    let mut config = ApllConfig::for_frequency(clocks.xtal_clock, frequency)?;
+
+    #[cfg(feature = "esp32")]
+    if clocks_ll::is_chip_rev0() {
+        if config.sdm1 >= 0x80 && config.sdm2 < 63 {
+            config.sdm2 += 1;
+        }
+        config.sdm1 = 0;
+        config.sdm0 = 0;
+    }
+
+    let ApllConfig {
+        o_div,
+        sdm0,
+        sdm1,
+        sdm2,
+    } = config;
+
+    #[cfg(feature = "esp32")]
+    clocks_ll::esp32_rtc_apll_enable(sdm0 as u32, sdm1 as u32, sdm2 as u32, o_div as u32);
+    #[cfg(feature = "esp32s2")]
+    clocks_ll::esp32s2_rtc_apll_enable(sdm0 as u32, sdm1 as u32, sdm2 as u32, o_div as u32);
+
+    Some(config.frequency(clocks.xtal_clock))
+}
+
+/// Power down the APLL
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+pub fn disable_apll() {
+    #[cfg(feature = "esp32")]
+    clocks_ll::esp32_rtc_apll_disable();
+    #[cfg(feature = "esp32s2")]
+    clocks_ll::esp32s2_rtc_apll_disable();
+}
+
+/// A clock which can be output on the `CLK_OUT` pads of the ESP32
+#[cfg(feature = "esp32")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClockOutSource {
+    /// PLL_CLK, 320 MHz or 480 MHz
+    Pll     = 1,
+    /// RTC_SLOW_CLK
+    RtcSlow = 4,
+    /// XTAL_CLK
+    Xtal    = 5,
+    /// The audio PLL, see [enable_apll]
+    Apll    = 6,
+    /// REF_TICK, 1 MHz
+    RefTick = 12,
+    /// PLL_F80M_CLK, 80 MHz
+    Pll80M  = 13,
+    /// RC_FAST_CLK, about 8 MHz, only while it is enabled
+    RcFast  = 14,
+}
+
+/// A `CLK_OUT` pad outputting an internal clock
+///
+/// Only available on the ESP32, see the [module docs](self).
+#[cfg(feature = "esp32")]
+pub struct ClockOutput<'d, P> {
+    _pin: PeripheralRef<'d, P>,
+    shift: u32,
+}
+
+#[cfg(feature = "esp32")]
+impl<'d, P> ClockOutput<'d, P>
+where
+    P: OutputPin,
+{
+    /// Output `source` on `CLK_OUT1`
+    pub fn clk_out1(pin: impl Peripheral<P = P> + 'd, source: ClockOutSource) -> Self
+    where
+        P: ClkOut1Pin,
+    {
+        Self::new(pin, OutputSignal::CLK_OUT1, 0, source)
+    }
+
+    /// Output `source` on `CLK_OUT2`
+    pub fn clk_out2(pin: impl Peripheral<P = P> + 'd, source: ClockOutSource) -> Self
+    where
+        P: ClkOut2Pin,
+    {
+        Self::new(pin, OutputSignal::CLK_OUT2, 4, source)
+    }
+
+    /// Output `source` on `CLK_OUT3`
+    pub fn clk_out3(pin: impl Peripheral<P = P> + 'd, source: ClockOutSource) -> Self
+    where
+        P: ClkOut3Pin,
+    {
+        Self::new(pin, OutputSignal::CLK_OUT3, 8, source)
+    }
+
+    fn new(
+        pin: impl Peripheral<P = P> + 'd,
+        signal: OutputSignal,
+        shift: u32,
+        source: ClockOutSource,
+    ) -> Self {
+        into_ref!(pin);
+
+        set_clock_out(shift, source as u32);
+        pin.set_to_push_pull_output()
+            .connect_peripheral_to_output(signal);
+
+        Self { _pin: pin, shift }
+    }
+}
+
+#[cfg(feature = "esp32")]
+impl<'d, P> Drop for ClockOutput<'d, P> {
+    fn drop(&mut self) {
+        set_clock_out(self.shift, CLOCK_OUT_DISABLED);
+    }
+}
+
+/// `PIN_CTRL` value which outputs no clock
+#[cfg(feature = "esp32")]
+const CLOCK_OUT_DISABLED: u32 = 0xf;
+
+/// Select the clock of the 4 bit field at `shift` in `PIN_CTRL`
+#[cfg(feature = "esp32")]
+fn set_clock_out(shift: u32, value: u32) {
+    let io_mux = unsafe { &*crate::pac::IO_MUX::ptr() };
+
+    io_mux
+        .pin_ctrl
+        .modify(|r, w| unsafe { w.bits((r.bits() & !(0xf << shift)) | (value << shift)) });
+}
+
+/// Switch the CPU to XTAL_CLK, e.g. before entering light sleep
+///
+/// Returns the PLL configuration the CPU ran from, to be restored with
+/// [restore_cpu_clock], or `None` if it already ran from XTAL_CLK.
+#[cfg(feature = "esp32")]
+pub(crate) fn switch_cpu_to_xtal() -> Option<CpuClock> {
+    let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
+    let dport = unsafe { &*crate::pac::DPORT::ptr() };
+
+    if !rtc_cntl.clk_conf.read().soc_clk_sel().is_pll() {
+        return None;
+    }
+
+    let cpu_clock = match dport.cpu_per_conf.read().cpuperiod_sel().bits() {
+        0 => CpuClock::Clock80MHz,
+        1 => CpuClock::Clock160MHz,
+        _ => CpuClock::Clock240MHz,
+    };
+
+    clocks_ll::esp32_rtc_update_to_xtal(RtcClock::get_xtal_freq(), 1);
+
+    Some(cpu_clock)
+}
+
+/// Switch the CPU back to the PLL configuration returned by
+/// [switch_cpu_to_xtal]
+///
+/// The PLL is powered down during sleep, so it is enabled and configured
+/// again first.
+#[cfg(feature = "esp32")]
+pub(crate) fn restore_cpu_clock(cpu_clock: CpuClock) {
+    let xtal_freq = RtcClock::get_xtal_freq();
+    let pll_freq = match cpu_clock {
+        CpuClock::Clock240MHz => PllClock::Pll480MHz,
+        _ => PllClock::Pll320MHz,
+    };
+
+    clocks_ll::esp32_rtc_bbpll_enable();
+    clocks_ll::esp32_rtc_bbpll_configure(xtal_freq, pll_freq);
+    clocks_ll::set_cpu_freq(cpu_clock);
+}
+
+/// Switch the CPU to XTAL_CLK, e.g. before entering light sleep
+///
+/// Returns the PLL configuration the CPU ran from, to be restored with
+/// [restore_cpu_clock], or `None` if it already ran from XTAL_CLK.
+#[cfg(feature = "esp32c3")]
+pub(crate) fn switch_cpu_to_xtal() -> Option<CpuClock> {
+    let system_control = unsafe { &*crate::pac::SYSTEM::ptr() };
+
+    if system_control.sysclk_conf.read().soc_clk_sel().bits() != 1 {
+        return None;
+    }
+
+    let cpu_clock = match system_control.cpu_per_conf.read().cpuperiod_sel().bits() {
+        0 => CpuClock::Clock80MHz,
+        _ => CpuClock::Clock160MHz,
+    };
+
+    let xtal_freq = RtcClock::get_xtal_freq();
+    clocks_ll::esp32c3_rtc_update_to_xtal(xtal_freq, 1);
+    clocks_ll::esp32c3_rtc_apb_freq_update(ApbClock::ApbFreqOther(xtal_freq.mhz()));
+
+    Some(cpu_clock)
+}
+
+/// Switch the CPU back to the PLL configuration returned by
+/// [switch_cpu_to_xtal]
+///
+/// The PLL is powered down during sleep, so it is enabled and configured
+/// again first.
+#[cfg(feature = "esp32c3")]
+pub(crate) fn restore_cpu_clock(cpu_clock: CpuClock) {
+    clocks_ll::esp32c3_rtc_bbpll_enable();
+    clocks_ll::esp32c3_rtc_bbpll_configure(RtcClock::get_xtal_freq(), PllClock::Pll480MHz);
+    clocks_ll::esp32c3_rtc_freq_to_pll_mhz(cpu_clock);
+    clocks_ll::esp32c3_rtc_apb_freq_update(ApbClock::ApbFreq80MHz);
+}
+
+/// Switch the CPU to XTAL_CLK, e.g. before entering light sleep
+///
+/// Returns the PLL configuration the CPU ran from, to be restored with
+/// [restore_cpu_clock], or `None` if it already ran from XTAL_CLK.
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+pub(crate) fn switch_cpu_to_xtal() -> Option<CpuClock> {
+    let system_control = unsafe { &*crate::pac::SYSTEM::PTR };
+
+    if system_control.sysclk_conf.read().soc_clk_sel().bits() != 1 {
+        return None;
+    }
+
+    let cpu_clock = match system_control.cpu_per_conf.read().cpuperiod_sel().bits() {
+        0 => CpuClock::Clock80MHz,
+        1 => CpuClock::Clock160MHz,
+        _ => CpuClock::Clock240MHz,
+    };
+
+    system_control
+        .sysclk_conf
+        .modify(|_, w| unsafe { w.pre_div_cnt().bits(0).soc_clk_sel().bits(0) });
+    unsafe { crate::rom::ets_update_cpu_frequency(RtcClock::get_xtal_freq().mhz()) };
+
+    Some(cpu_clock)
+}
+
+/// Switch the CPU back to the PLL configuration returned by
+/// [switch_cpu_to_xtal]
+///
+/// The PLL is powered down during sleep, so it is enabled and configured
+/// again first.
+#[cfg(feature = "esp32s2")]
+pub(crate) fn restore_cpu_clock(cpu_clock: CpuClock) {
+    clocks_ll::esp32s2_rtc_bbpll_enable();
+    clocks_ll::esp32s2_rtc_bbpll_configure(RtcClock::get_xtal_freq());
+    clocks_ll::set_cpu_clock(cpu_clock);
+}
+
+/// Switch the CPU back to the PLL configuration returned by
+/// [switch_cpu_to_xtal]
+///
+/// The PLL is powered down during sleep, so it is enabled and configured
+/// again first.
+#[cfg(feature = "esp32s3")]
+pub(crate) fn restore_cpu_clock(cpu_clock: CpuClock) {
+    clocks_ll::esp32s3_rtc_bbpll_enable();
+    clocks_ll::esp32s3_rtc_bbpll_configure(RtcClock::get_xtal_freq(), PllClock::Pll480MHz);
+    clocks_ll::set_cpu_clock(cpu_clock);
+}
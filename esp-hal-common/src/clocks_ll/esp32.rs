@@ -1,4 +1,11 @@
-use crate::clock::{Clock, PllClock, XtalClock};
+use paste::paste;
+
+use crate::{
+    clock::{Clock, PllClock, XtalClock},
+    regi2c_write,
+    regi2c_write_mask,
+    rom::{esp_rom_delay_us, regi2c_ctrl_write_reg, regi2c_ctrl_write_reg_mask},
+};
 
 const REF_CLK_FREQ: u32 = 1000000;
 
@@ -38,6 +45,35 @@ const I2C_BBPLL_OC_LREF: u32 = 2;
 const I2C_BBPLL_OC_DIV_7_0: u32 = 3;
 const I2C_BBPLL_OC_DCUR: u32 = 5;
 
+const I2C_APLL: u32 = 0x6d;
+const I2C_APLL_HOSTID: u32 = 3;
+
+const I2C_APLL_IR_CAL_DELAY: u32 = 0;
+const I2C_APLL_OR_OUTPUT_DIV: u32 = 4;
+const I2C_APLL_OR_OUTPUT_DIV_MSB: u32 = 4;
+const I2C_APLL_OR_OUTPUT_DIV_LSB: u32 = 0;
+const I2C_APLL_SDM_STOP: u32 = 5;
+const I2C_APLL_DSDM2: u32 = 7;
+const I2C_APLL_DSDM2_MSB: u32 = 5;
+const I2C_APLL_DSDM2_LSB: u32 = 0;
+const I2C_APLL_DSDM1: u32 = 8;
+const I2C_APLL_DSDM1_MSB: u32 = 7;
+const I2C_APLL_DSDM1_LSB: u32 = 0;
+const I2C_APLL_DSDM0: u32 = 9;
+const I2C_APLL_DSDM0_MSB: u32 = 7;
+const I2C_APLL_DSDM0_LSB: u32 = 0;
+
+const APLL_SDM_STOP_VAL_1: u32 = 0x09;
+const APLL_SDM_STOP_VAL_2_REV0: u32 = 0x69;
+const APLL_SDM_STOP_VAL_2_REV1: u32 = 0x49;
+const APLL_CAL_DELAY_1: u32 = 0x0f;
+const APLL_CAL_DELAY_2: u32 = 0x3f;
+const APLL_CAL_DELAY_3: u32 = 0x1f;
+
+/// Time to wait for the calibration of the APLL, which ESP-IDF polls
+/// `I2C_APLL_OR_CAL_END` for instead
+const APLL_CAL_WAIT_US: u32 = 100;
+
 pub(crate) fn esp32_rtc_bbpll_configure(xtal_freq: XtalClock, pll_freq: PllClock) {
     let efuse = unsafe { &*crate::pac::EFUSE::ptr() };
     let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
@@ -348,3 +384,63 @@ fn esp32_update_cpu_freq(mhz: u32) {
         (G_TICKS_PER_US_PRO as *mut u32).write_volatile(mhz);
     }
 }
+
+/// Power up the APLL with the given coefficients, see [crate::clock::ApllConfig]
+///
+/// Revision 0 of the ESP32 ignores the fractional parts `sdm0` and `sdm1`.
+pub(crate) fn esp32_rtc_apll_enable(sdm0: u32, sdm1: u32, sdm2: u32, o_div: u32) {
+    let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .ana_conf
+        .modify(|_, w| w.plla_force_pd().clear_bit().plla_force_pu().set_bit());
+    rtc_cntl
+        .options0
+        .modify(|_, w| w.bias_i2c_force_pd().clear_bit());
+
+    let (sdm0, sdm1, sdm_stop_val_2) = if is_chip_rev0() {
+        (0, 0, APLL_SDM_STOP_VAL_2_REV0)
+    } else {
+        (sdm0, sdm1, APLL_SDM_STOP_VAL_2_REV1)
+    };
+
+    unsafe {
+        regi2c_write_mask!(I2C_APLL, I2C_APLL_DSDM2, sdm2);
+        regi2c_write_mask!(I2C_APLL, I2C_APLL_DSDM0, sdm0);
+        regi2c_write_mask!(I2C_APLL, I2C_APLL_DSDM1, sdm1);
+        regi2c_write!(I2C_APLL, I2C_APLL_SDM_STOP, APLL_SDM_STOP_VAL_1);
+        regi2c_write!(I2C_APLL, I2C_APLL_SDM_STOP, sdm_stop_val_2);
+        regi2c_write_mask!(I2C_APLL, I2C_APLL_OR_OUTPUT_DIV, o_div);
+
+        // calibration
+        regi2c_write!(I2C_APLL, I2C_APLL_IR_CAL_DELAY, APLL_CAL_DELAY_1);
+        regi2c_write!(I2C_APLL, I2C_APLL_IR_CAL_DELAY, APLL_CAL_DELAY_2);
+        regi2c_write!(I2C_APLL, I2C_APLL_IR_CAL_DELAY, APLL_CAL_DELAY_3);
+
+        esp_rom_delay_us(APLL_CAL_WAIT_US);
+    }
+}
+
+/// Power down the APLL
+pub(crate) fn esp32_rtc_apll_disable() {
+    let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .ana_conf
+        .modify(|_, w| w.plla_force_pd().set_bit().plla_force_pu().clear_bit());
+
+    // the I2C bias is still needed by the PLL the CPU runs from
+    if !rtc_cntl.clk_conf.read().soc_clk_sel().is_pll() {
+        rtc_cntl
+            .options0
+            .modify(|_, w| w.bias_i2c_force_pd().set_bit());
+    }
+}
+
+/// Whether this is revision 0 of the ESP32
+pub(crate) fn is_chip_rev0() -> bool {
+    let efuse = unsafe { &*crate::pac::EFUSE::ptr() };
+
+    // EFUSE_RD_CHIP_VER_REV1
+    efuse.blk0_rdata3.read().bits() & (1 << 15) == 0
+}
@@ -1,4 +1,16 @@
-use crate::clock::CpuClock;
+use paste::paste;
+
+use crate::{
+    clock::{Clock, CpuClock, XtalClock},
+    regi2c_write,
+    regi2c_write_mask,
+    rom::{
+        esp_rom_delay_us,
+        ets_update_cpu_frequency,
+        regi2c_ctrl_write_reg,
+        regi2c_ctrl_write_reg_mask,
+    },
+};
 
 const MHZ: u32 = 1000000;
 const UINT16_MAX: u32 = 0xffff;
@@ -10,6 +22,56 @@ const RTC_CNTL_DBIAS_1V25: u32 = 7;
 const DIG_DBIAS_80M_160M: u32 = RTC_CNTL_DBIAS_1V25;
 const DIG_DBIAS_240M: u32 = RTC_CNTL_DBIAS_1V25;
 
+const I2C_BBPLL: u32 = 0x66;
+const I2C_BBPLL_HOSTID: u32 = 0;
+
+const I2C_BBPLL_MODE_HF: u32 = 4;
+
+const I2C_BBPLL_OC_REF_DIV: u32 = 2;
+const I2C_BBPLL_OC_DCHGP_LSB: u32 = 4;
+const I2C_BBPLL_OC_DIV_7_0: u32 = 3;
+
+const I2C_BBPLL_OC_DR1: u32 = 5;
+const I2C_BBPLL_OC_DR1_MSB: u32 = 2;
+const I2C_BBPLL_OC_DR1_LSB: u32 = 0;
+
+const I2C_BBPLL_OC_DR3: u32 = 5;
+const I2C_BBPLL_OC_DR3_MSB: u32 = 6;
+const I2C_BBPLL_OC_DR3_LSB: u32 = 4;
+
+const I2C_BBPLL_OC_DCUR: u32 = 6;
+
+const I2C_BBPLL_OC_DHREF_SEL_LSB: u32 = 4;
+const I2C_BBPLL_OC_DLREF_SEL_LSB: u32 = 6;
+
+const I2C_APLL: u32 = 0x6d;
+const I2C_APLL_HOSTID: u32 = 1;
+
+const I2C_APLL_IR_CAL_DELAY: u32 = 0;
+const I2C_APLL_OR_OUTPUT_DIV: u32 = 4;
+const I2C_APLL_OR_OUTPUT_DIV_MSB: u32 = 4;
+const I2C_APLL_OR_OUTPUT_DIV_LSB: u32 = 0;
+const I2C_APLL_SDM_STOP: u32 = 5;
+const I2C_APLL_DSDM2: u32 = 7;
+const I2C_APLL_DSDM2_MSB: u32 = 5;
+const I2C_APLL_DSDM2_LSB: u32 = 0;
+const I2C_APLL_DSDM1: u32 = 8;
+const I2C_APLL_DSDM1_MSB: u32 = 7;
+const I2C_APLL_DSDM1_LSB: u32 = 0;
+const I2C_APLL_DSDM0: u32 = 9;
+const I2C_APLL_DSDM0_MSB: u32 = 7;
+const I2C_APLL_DSDM0_LSB: u32 = 0;
+
+const APLL_SDM_STOP_VAL_1: u32 = 0x09;
+const APLL_SDM_STOP_VAL_2: u32 = 0x49;
+const APLL_CAL_DELAY_1: u32 = 0x0f;
+const APLL_CAL_DELAY_2: u32 = 0x3f;
+const APLL_CAL_DELAY_3: u32 = 0x1f;
+
+/// Time to wait for the calibration of the APLL, which ESP-IDF polls
+/// `I2C_APLL_OR_CAL_END` for instead
+const APLL_CAL_WAIT_US: u32 = 100;
+
 pub(crate) fn set_cpu_clock(cpu_clock_speed: CpuClock) {
     let system_control = unsafe { &*crate::pac::SYSTEM::PTR };
     let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
@@ -41,5 +103,100 @@ pub(crate) fn set_cpu_clock(cpu_clock_speed: CpuClock) {
         rtc_cntl
             .store5
             .modify(|_, w| w.scratch5().bits(value as u32));
+
+        ets_update_cpu_frequency(cpu_clock_speed.mhz());
+    }
+}
+
+/// Configure the BBPLL for 480 MHz, from which all CPU clock speeds are
+/// divided
+///
+/// The ESP32-S2 only supports a 40 MHz crystal.
+pub(crate) fn esp32s2_rtc_bbpll_configure(_xtal_freq: XtalClock) {
+    let system = unsafe { &*crate::pac::SYSTEM::ptr() };
+
+    unsafe {
+        // Set this register to let the digital part know 480M PLL is used
+        system
+            .cpu_per_conf
+            .modify(|_, w| w.pll_freq_sel().set_bit());
+
+        // Configure 480M PLL
+        let div_ref: u32 = 0;
+        let div7_0: u32 = 8;
+        let dr1: u32 = 0;
+        let dr3: u32 = 0;
+        let dchgp: u32 = 5;
+        let dcur: u32 = 4;
+
+        regi2c_write!(I2C_BBPLL, I2C_BBPLL_MODE_HF, 0x6b);
+
+        let i2c_bbpll_lref = (dchgp << I2C_BBPLL_OC_DCHGP_LSB) | div_ref;
+        let i2c_bbpll_div_7_0 = div7_0;
+        let i2c_bbpll_dcur =
+            (2 << I2C_BBPLL_OC_DLREF_SEL_LSB) | (1 << I2C_BBPLL_OC_DHREF_SEL_LSB) | dcur;
+
+        regi2c_write!(I2C_BBPLL, I2C_BBPLL_OC_REF_DIV, i2c_bbpll_lref);
+
+        regi2c_write!(I2C_BBPLL, I2C_BBPLL_OC_DIV_7_0, i2c_bbpll_div_7_0);
+
+        regi2c_write_mask!(I2C_BBPLL, I2C_BBPLL_OC_DR1, dr1);
+
+        regi2c_write_mask!(I2C_BBPLL, I2C_BBPLL_OC_DR3, dr3);
+
+        regi2c_write!(I2C_BBPLL, I2C_BBPLL_OC_DCUR, i2c_bbpll_dcur);
+
+        // ESP-IDF additionally searches for the capacitor of the VCO by
+        // software here. That is not repeated: the 2nd stage bootloader has
+        // already done so for the 480 MHz PLL, which is the only frequency
+        // programmed here, and the result stays in I2C_BBPLL_IR_CAL_EXT_CAP,
+        // which is not written.
+    }
+}
+
+pub(crate) fn esp32s2_rtc_bbpll_enable() {
+    let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
+
+    rtc_cntl.options0.modify(|_, w| {
+        w.bb_i2c_force_pd()
+            .clear_bit()
+            .bbpll_force_pd()
+            .clear_bit()
+            .bbpll_i2c_force_pd()
+            .clear_bit()
+    });
+}
+
+/// Power up the APLL with the given coefficients, see [crate::clock::ApllConfig]
+pub(crate) fn esp32s2_rtc_apll_enable(sdm0: u32, sdm1: u32, sdm2: u32, o_div: u32) {
+    let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .ana_conf
+        .modify(|_, w| w.plla_force_pd().clear_bit().plla_force_pu().set_bit());
+
+    unsafe {
+        regi2c_write_mask!(I2C_APLL, I2C_APLL_DSDM2, sdm2);
+        regi2c_write_mask!(I2C_APLL, I2C_APLL_DSDM0, sdm0);
+        regi2c_write_mask!(I2C_APLL, I2C_APLL_DSDM1, sdm1);
+        regi2c_write!(I2C_APLL, I2C_APLL_SDM_STOP, APLL_SDM_STOP_VAL_1);
+        regi2c_write!(I2C_APLL, I2C_APLL_SDM_STOP, APLL_SDM_STOP_VAL_2);
+        regi2c_write_mask!(I2C_APLL, I2C_APLL_OR_OUTPUT_DIV, o_div);
+
+        // calibration
+        regi2c_write!(I2C_APLL, I2C_APLL_IR_CAL_DELAY, APLL_CAL_DELAY_1);
+        regi2c_write!(I2C_APLL, I2C_APLL_IR_CAL_DELAY, APLL_CAL_DELAY_2);
+        regi2c_write!(I2C_APLL, I2C_APLL_IR_CAL_DELAY, APLL_CAL_DELAY_3);
+
+        esp_rom_delay_us(APLL_CAL_WAIT_US);
     }
 }
+
+/// Power down the APLL
+pub(crate) fn esp32s2_rtc_apll_disable() {
+    let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .ana_conf
+        .modify(|_, w| w.plla_force_pd().set_bit().plla_force_pu().clear_bit());
+}
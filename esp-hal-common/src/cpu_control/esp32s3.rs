@@ -21,6 +21,7 @@ impl<'a> Drop for AppCoreGuard<'a> {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
     CoreAlreadyRunning,
 }
@@ -0,0 +1,168 @@
+//! # Crash counters
+//!
+//! Records crashes in RTC fast memory, which keeps its contents across
+//! software resets, so that the application can tell after boot how often it
+//! has crashed and why, e.g. to fall back to a safe mode after repeated
+//! crashes.
+//!
+//! Nothing is recorded unless the application reports its crashes, typically
+//! from its panic handler and from the interrupt of the RTC watchdog:
+//!
+//! ```no_run
+//! #[panic_handler]
+//! fn panic(_info: &core::panic::PanicInfo) -> ! {
+//!     crash::reset_after_crash(CrashReason::Panic)
+//! }
+//!
+//! #[interrupt]
+//! fn RTC_CORE() {
+//!     crash::reset_after_crash(CrashReason::Watchdog)
+//! }
+//! ```
+//!
+//! and checks the history after boot:
+//!
+//! ```no_run
+//! let history = crash::history();
+//! if history.count >= 3 {
+//!     // enter safe mode
+//! }
+//!
+//! // once the application has been running fine for a while
+//! crash::clear_history();
+//! ```
+//!
+//! The history is lost on power-up and on resets of the RTC domain. On the
+//! ESP32, RTC fast memory is only accessible by the PRO CPU, so crashes can
+//! only be recorded from there.
+
+use core::ptr::{addr_of, addr_of_mut};
+
+use crate::reset::software_reset;
+
+/// Marks a valid record, `"CRSH"`
+const RECORD_MAGIC: u32 = 0x4853_5243;
+
+/// The reason of a crash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CrashReason {
+    /// The application panicked
+    Panic,
+    /// A watchdog interrupt fired
+    Watchdog,
+    /// An application-defined reason
+    Other(u8),
+}
+
+impl CrashReason {
+    fn to_raw(self) -> u32 {
+        match self {
+            CrashReason::Panic => 1,
+            CrashReason::Watchdog => 2,
+            CrashReason::Other(code) => 0x100 | code as u32,
+        }
+    }
+
+    fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(CrashReason::Panic),
+            2 => Some(CrashReason::Watchdog),
+            raw if raw & !0xff == 0x100 => Some(CrashReason::Other(raw as u8)),
+            _ => None,
+        }
+    }
+}
+
+/// The crashes recorded since the history was last cleared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CrashHistory {
+    /// Number of crashes
+    pub count: u32,
+    /// Reason of the last crash, if there was one
+    pub last_reason: Option<CrashReason>,
+}
+
+#[repr(C)]
+struct Record {
+    magic: u32,
+    count: u32,
+    reason: u32,
+    /// Inverted XOR of the other fields, to tell a record from the random
+    /// contents of RTC fast memory after power-up
+    check: u32,
+}
+
+impl Record {
+    fn checksum(&self) -> u32 {
+        !(self.magic ^ self.count ^ self.reason)
+    }
+}
+
+#[link_section = ".rtc_fast.noinit"]
+static mut RECORD: Record = Record {
+    magic: 0,
+    count: 0,
+    reason: 0,
+    check: 0,
+};
+
+fn read_record() -> Record {
+    let record = unsafe { addr_of!(RECORD).read_volatile() };
+
+    if record.magic == RECORD_MAGIC && record.check == record.checksum() {
+        record
+    } else {
+        Record {
+            magic: RECORD_MAGIC,
+            count: 0,
+            reason: 0,
+            check: 0,
+        }
+    }
+}
+
+fn write_record(mut record: Record) {
+    record.magic = RECORD_MAGIC;
+    record.check = record.checksum();
+
+    unsafe { addr_of_mut!(RECORD).write_volatile(record) };
+}
+
+/// The crashes recorded since the history was last cleared
+pub fn history() -> CrashHistory {
+    let record = read_record();
+
+    CrashHistory {
+        count: record.count,
+        last_reason: CrashReason::from_raw(record.reason),
+    }
+}
+
+/// Forget the recorded crashes
+pub fn clear_history() {
+    write_record(Record {
+        magic: RECORD_MAGIC,
+        count: 0,
+        reason: 0,
+        check: 0,
+    });
+}
+
+/// Record a crash with the given reason
+pub fn record_crash(reason: CrashReason) {
+    critical_section::with(|_| {
+        let mut record = read_record();
+        record.count = record.count.saturating_add(1);
+        record.reason = reason.to_raw();
+
+        write_record(record);
+    });
+}
+
+/// Record a crash with the given reason and reset the chip
+pub fn reset_after_crash(reason: CrashReason) -> ! {
+    record_crash(reason);
+    software_reset()
+}
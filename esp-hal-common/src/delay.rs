@@ -1,10 +1,42 @@
 //! Delay driver
 //!
-//! Implement the `DelayMs` and `DelayUs` traits from [embedded-hal].
+//! Implement the `DelayMs` and `DelayUs` traits from [embedded-hal], and with
+//! the `eh1` feature the `DelayUs` trait of the embedded-hal 1.0 alphas, which
+//! the general-purpose [Timer](crate::timer::Timer)s implement as well.
+//!
+//! [Delay] is `Copy`, and can be created with `Delay::from(&clocks)`
+//! wherever the [Clocks](crate::clock::Clocks) are at hand.
+//!
+//! [Delay] busy-waits. With the `async` feature, [AsyncDelay] implements the
+//! `DelayNs` trait from [embedded-hal-async] on a general-purpose timer
+//! instead, and parks the task until the alarm of the timer fires:
+//!
+//! ```no_run
+//! let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+//! let mut delay = AsyncDelay::new(timer_group0.timer0);
+//!
+//! delay.delay_ms(500).await;
+//! ```
+//!
+//! The alarm interrupt of the timer has to be enabled, and its handler has to
+//! call [timer::handle_interrupt](crate::timer::handle_interrupt), which the
+//! `timer-interrupt-handlers` feature does.
 //!
 //! [embedded-hal]: https://docs.rs/embedded-hal/latest/embedded_hal/
+//! [embedded-hal-async]: https://docs.rs/embedded-hal-async/latest/embedded_hal_async/
 
+#[cfg(feature = "async")]
+pub use self::asynch::AsyncDelay;
 pub use self::delay::Delay;
+use crate::clock::Clocks;
+
+/// [Delay] only keeps the CPU clock frequency, so a copy can be handed to
+/// every driver which needs one
+impl From<&Clocks> for Delay {
+    fn from(clocks: &Clocks) -> Self {
+        Delay::new(clocks)
+    }
+}
 
 impl<T> embedded_hal::blocking::delay::DelayMs<T> for Delay
 where
@@ -37,50 +69,90 @@ impl embedded_hal_1::delay::blocking::DelayUs for Delay {
     }
 }
 
-#[cfg(feature = "esp32c3")]
+#[cfg(any(feature = "esp32c3", feature = "esp32s2", feature = "esp32s3"))]
 mod delay {
-    use fugit::HertzU64;
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    use fugit::HertzU32;
 
     use crate::{clock::Clocks, systimer::SystemTimer};
 
-    /// Uses the `SYSTIMER` peripheral for counting clock cycles, as
-    /// unfortunately the ESP32-C3 does NOT implement the `mcycle` CSR, which is
-    /// how we would normally do this.
+    /// Delay driver
+    ///
+    /// Uses the `SYSTIMER` peripheral, which counts independently of the CPU
+    /// clock, so that delays stay accurate when the CPU frequency changes. The
+    /// ESP32-C3 does not implement the `mcycle` CSR, which is how we would
+    /// otherwise count clock cycles.
+    #[derive(Debug, Clone, Copy)]
     pub struct Delay {
-        freq: HertzU64,
+        #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+        cpu_freq: HertzU32,
+        #[cfg(feature = "esp32c3")]
+        _private: (),
     }
 
     impl Delay {
         /// Create a new Delay instance
+        #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
         pub fn new(clocks: &Clocks) -> Self {
-            // The counters and comparators are driven using `XTAL_CLK`. The average clock
-            // frequency is fXTAL_CLK/2.5, which is 16 MHz. The timer counting is
-            // incremented by 1/16 μs on each `CNT_CLK` cycle.
-
             Self {
-                freq: HertzU64::MHz((clocks.xtal_clock.to_MHz() * 10 / 25) as u64),
+                cpu_freq: clocks.cpu_clock,
             }
         }
 
+        /// Create a new Delay instance
+        #[cfg(feature = "esp32c3")]
+        pub fn new(_clocks: &Clocks) -> Self {
+            Self { _private: () }
+        }
+
+        /// Delay for the specified number of nanoseconds
+        ///
+        /// Counts the cycles of the CPU clock frequency given to
+        /// [Delay::new], which has to stay the same during the delay. The
+        /// time it takes to call this adds to the delay.
+        #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+        pub fn delay_nanos(&self, ns: u32) {
+            // round up, so that the delay is never shorter than requested
+            let cycles = (ns as u64 * self.cpu_freq.raw() as u64 + 999_999_999) / 1_000_000_000;
+            xtensa_lx::timer::delay(cycles as u32);
+        }
+
+        /// Delay for the specified number of nanoseconds
+        ///
+        /// The ESP32-C3 has no cycle counter, so this is timed with the
+        /// `SYSTIMER`, and rounded up to its resolution of 62.5 ns.
+        #[cfg(feature = "esp32c3")]
+        pub fn delay_nanos(&self, ns: u32) {
+            let t0 = SystemTimer::now();
+            let ticks = (ns as u64 * SystemTimer::TICKS_PER_SECOND + 999_999_999) / 1_000_000_000;
+
+            while SystemTimer::now().wrapping_sub(t0) < ticks {}
+        }
+
         /// Delay for the specified number of microseconds
         pub fn delay(&self, us: u32) {
             let t0 = SystemTimer::now();
-            let clocks = (us as u64 * self.freq.raw()) / HertzU64::MHz(1).raw();
+            let ticks = us as u64 * SystemTimer::TICKS_PER_SECOND / 1_000_000;
 
-            while SystemTimer::now().wrapping_sub(t0) <= clocks {}
+            while SystemTimer::now().wrapping_sub(t0) <= ticks {}
         }
     }
 }
 
-#[cfg(not(feature = "esp32c3"))]
+#[cfg(feature = "esp32")]
 mod delay {
     use fugit::HertzU64;
 
-    use crate::clock::Clocks;
+    use crate::{clock::Clocks, time};
 
     /// Delay driver
     ///
-    /// Uses the built-in Xtensa timer from the `xtensa_lx` crate.
+    /// The ESP32 has no `SYSTIMER`. If a timer group timer has been installed
+    /// as the global timestamp source with [time::init], delays are timed
+    /// with it, so that they stay accurate when the CPU frequency changes.
+    /// Otherwise the built-in Xtensa timer from the `xtensa_lx` crate counts
+    /// the cycles of the CPU clock frequency given to [Delay::new].
+    #[derive(Debug, Clone, Copy)]
     pub struct Delay {
         freq: HertzU64,
     }
@@ -95,8 +167,82 @@ mod delay {
 
         /// Delay for the specified number of microseconds
         pub fn delay(&self, us: u32) {
-            let clocks = (us as u64 * self.freq.raw()) / HertzU64::MHz(1).raw();
-            xtensa_lx::timer::delay(clocks as u32);
+            match time::current_fine_grained_ticks() {
+                Some((t0, ticks_per_second)) => {
+                    let ticks = us as u64 * ticks_per_second / 1_000_000;
+
+                    while time::current_fine_grained_ticks()
+                        .map_or(u64::MAX, |(now, _)| now.wrapping_sub(t0))
+                        <= ticks
+                    {}
+                }
+                None => {
+                    let clocks = (us as u64 * self.freq.raw()) / HertzU64::MHz(1).raw();
+                    xtensa_lx::timer::delay(clocks as u32);
+                }
+            }
+        }
+
+        /// Delay for the specified number of nanoseconds
+        ///
+        /// Counts the cycles of the CPU clock frequency given to
+        /// [Delay::new], which has to stay the same during the delay. The
+        /// time it takes to call this adds to the delay.
+        pub fn delay_nanos(&self, ns: u32) {
+            // round up, so that the delay is never shorter than requested
+            let cycles = (ns as u64 * self.freq.raw() + 999_999_999) / 1_000_000_000;
+            xtensa_lx::timer::delay(cycles as u32);
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+mod asynch {
+    use fugit::MicrosDurationU64;
+
+    use crate::timer::{Instance, Timer};
+
+    /// Delay driver which waits for the alarm of a general-purpose timer
+    /// without blocking the executor
+    pub struct AsyncDelay<T> {
+        timer: Timer<T>,
+    }
+
+    impl<T> AsyncDelay<T>
+    where
+        T: Instance,
+    {
+        /// Use `timer` for the delays
+        pub fn new(timer: Timer<T>) -> Self {
+            Self { timer }
+        }
+
+        /// Return the timer
+        pub fn free(self) -> Timer<T> {
+            self.timer
+        }
+    }
+
+    impl<T> embedded_hal_async::delay::DelayNs for AsyncDelay<T>
+    where
+        T: Instance,
+    {
+        async fn delay_ns(&mut self, ns: u32) {
+            embedded_hal_async::delay::DelayNs::delay_ns(&mut self.timer, ns).await;
+        }
+
+        // The default implementations split long delays into several alarms,
+        // which the timer does not need
+        async fn delay_us(&mut self, us: u32) {
+            self.timer
+                .delay_async(MicrosDurationU64::micros(us as u64))
+                .await;
+        }
+
+        async fn delay_ms(&mut self, ms: u32) {
+            self.timer
+                .delay_async(MicrosDurationU64::millis(ms as u64))
+                .await;
         }
     }
 }
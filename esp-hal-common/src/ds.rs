@@ -0,0 +1,224 @@
+//! Digital Signature (DS) peripheral driver
+//!
+//! The DS peripheral produces RSA signatures using a private key that is never
+//! exposed to software. The key is stored AES-encrypted (the "encrypted
+//! parameters", generated once during provisioning). The AES key used to
+//! decrypt them is derived by the HMAC module from an eFuse key block burnt
+//! with the `HMAC_DOWN_DIGITAL_SIGNATURE` (or `HMAC_DOWN_ALL`) purpose, and
+//! is handed to the DS peripheral directly in hardware.
+//!
+//! As with the [RSA driver](crate::rsa), the message and signature are
+//! big-endian byte slices. Their length must match the key length the
+//! parameters were generated for.
+//!
+//! # Example
+//!
+//! ```no_run
+//! let mut hmac = Hmac::new(peripherals.HMAC, &mut system.peripheral_clock_control);
+//! let mut ds = Ds::new(peripherals.DS, &mut system.peripheral_clock_control);
+//!
+//! let mut signature = [0u8; 256];
+//! ds.sign(&mut hmac, KeyId::Key1, &PARAMS, &digest, &mut signature)
+//!     .unwrap();
+//! ```
+
+use crate::{
+    hmac::{self, Hmac, HmacPurpose, KeyId},
+    pac::DS,
+    rsa::word_from_be_bytes,
+    system::{Peripheral, PeripheralClockControl},
+};
+
+/// Maximum supported RSA key length, in bytes
+#[cfg(feature = "esp32c3")]
+pub const MAX_KEY_BYTES: usize = 384;
+/// Maximum supported RSA key length, in bytes
+#[cfg(feature = "esp32s3")]
+pub const MAX_KEY_BYTES: usize = 512;
+
+const BOX_BYTES: usize = 48;
+
+/// Length of the encrypted private key parameters, in bytes
+pub const CIPHERTEXT_BYTES: usize = MAX_KEY_BYTES * 3 + BOX_BYTES;
+
+// Offsets of the memory blocks, relative to the peripheral base
+const Y_MEM: usize = 0x000;
+const M_MEM: usize = 0x200;
+const RB_MEM: usize = 0x400;
+const BOX_MEM: usize = 0x600;
+const IV_MEM: usize = 0x630;
+const X_MEM: usize = 0x800;
+const Z_MEM: usize = 0xa00;
+
+/// DS-specific errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The HMAC module could not derive the decryption key
+    Hmac(hmac::Error),
+    /// The decryption key was not made available to the DS peripheral
+    KeyNotReady,
+    /// The message or signature length is invalid
+    InvalidLength,
+    /// The parameters were decrypted with the wrong key, or are corrupted
+    InvalidDigest,
+    /// The decrypted parameters have invalid padding
+    InvalidPadding,
+}
+
+impl From<hmac::Error> for Error {
+    fn from(error: hmac::Error) -> Self {
+        Error::Hmac(error)
+    }
+}
+
+/// Encrypted private key parameters
+///
+/// Generated during provisioning (e.g. with `esp_efuse_gen_ds_params` or the
+/// `configure_ds.py` script) and usually stored in flash.
+pub struct EncryptedParams {
+    /// AES initialization vector
+    pub iv: [u8; 16],
+    /// Ciphertext of the private key parameters
+    pub ciphertext: [u8; CIPHERTEXT_BYTES],
+}
+
+/// Digital Signature peripheral
+pub struct Ds {
+    ds: DS,
+}
+
+impl Ds {
+    /// Create a new DS instance
+    ///
+    /// Also enables the HMAC, SHA and RSA modules which the DS peripheral
+    /// depends on.
+    pub fn new(ds: DS, peripheral_clock_control: &mut PeripheralClockControl) -> Self {
+        peripheral_clock_control.enable(Peripheral::Ds);
+
+        Self { ds }
+    }
+
+    /// Return the raw interface to the underlying DS instance
+    pub fn free(self) -> DS {
+        self.ds
+    }
+
+    /// Sign `message` with the private key contained in `params`
+    ///
+    /// `key_id` selects the eFuse key block from which the parameter
+    /// decryption key is derived. `message` is usually a padded digest of the
+    /// data to sign; `signature` must have the same length.
+    pub fn sign(
+        &mut self,
+        hmac: &mut Hmac,
+        key_id: KeyId,
+        params: &EncryptedParams,
+        message: &[u8],
+        signature: &mut [u8],
+    ) -> Result<(), Error> {
+        if message.is_empty() || message.len() > MAX_KEY_BYTES {
+            return Err(Error::InvalidLength);
+        }
+        if signature.len() != message.len() {
+            return Err(Error::InvalidLength);
+        }
+
+        hmac.configure_downstream(HmacPurpose::ToDs, key_id)?;
+
+        let result = self.sign_internal(params, message, signature);
+
+        hmac.invalidate_downstream();
+
+        result
+    }
+
+    fn sign_internal(
+        &mut self,
+        params: &EncryptedParams,
+        message: &[u8],
+        signature: &mut [u8],
+    ) -> Result<(), Error> {
+        self.ds.set_start.write(|w| unsafe { w.bits(1) });
+        self.wait_for_idle();
+
+        if self.ds.query_key_wrong.read().bits() != 0 {
+            self.finish();
+            return Err(Error::KeyNotReady);
+        }
+
+        self.write_bytes(IV_MEM, &params.iv);
+
+        let words = (message.len() + 3) / 4;
+        for i in 0..words {
+            unsafe {
+                self.block_ptr(X_MEM)
+                    .add(i)
+                    .write_volatile(word_from_be_bytes(message, i))
+            };
+        }
+
+        // The ciphertext is laid out as Y, M and Rb (each `MAX_KEY_BYTES`
+        // long), followed by the parameter box.
+        let (y, rest) = params.ciphertext.split_at(MAX_KEY_BYTES);
+        let (m, rest) = rest.split_at(MAX_KEY_BYTES);
+        let (rb, parameter_box) = rest.split_at(MAX_KEY_BYTES);
+        self.write_bytes(Y_MEM, y);
+        self.write_bytes(M_MEM, m);
+        self.write_bytes(RB_MEM, rb);
+        self.write_bytes(BOX_MEM, parameter_box);
+
+        self.ds.set_continue.write(|w| unsafe { w.bits(1) });
+        self.wait_for_idle();
+
+        let check = self.ds.query_check.read().bits();
+        if check & 0b01 != 0 {
+            self.finish();
+            return Err(Error::InvalidDigest);
+        }
+        if check & 0b10 != 0 {
+            self.finish();
+            return Err(Error::InvalidPadding);
+        }
+
+        let len = signature.len();
+        for i in 0..words {
+            let word = unsafe { self.block_ptr(Z_MEM).add(i).read_volatile() }.to_le_bytes();
+
+            for (j, byte) in word.iter().enumerate() {
+                let pos = i * 4 + j;
+                if pos < len {
+                    signature[len - 1 - pos] = *byte;
+                }
+            }
+        }
+
+        self.finish();
+
+        Ok(())
+    }
+
+    fn finish(&mut self) {
+        self.ds.set_finish.write(|w| unsafe { w.bits(1) });
+        self.wait_for_idle();
+    }
+
+    fn wait_for_idle(&self) {
+        while self.ds.query_busy.read().bits() != 0 {}
+    }
+
+    fn block_ptr(&self, offset: usize) -> *mut u32 {
+        (DS::PTR as usize + offset) as *mut u32
+    }
+
+    /// Copy raw bytes into a memory block, as the hardware expects for the
+    /// encrypted parameters
+    fn write_bytes(&mut self, offset: usize, bytes: &[u8]) {
+        let block = self.block_ptr(offset);
+
+        for (i, word) in bytes.chunks_exact(4).enumerate() {
+            let word = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+            unsafe { block.add(i).write_volatile(word) };
+        }
+    }
+}
@@ -1,12 +1,21 @@
-//! Reading of eFuses
+//! Reading of eFuses and management of their protection bits
 
 use fugit::{HertzU32, RateExtU32};
 
 use crate::pac::EFUSE;
 
+const EFUSE_WRITE_OP_CODE: u32 = 0x5a5a;
+const EFUSE_READ_OP_CODE: u32 = 0x5aa5;
+
+/// Bit in WR_DIS which protects RD_DIS
+const WR_DIS_RD_DIS: u32 = 0;
+/// Bit in WR_DIS which protects WR_DIS itself
+const WR_DIS_WR_DIS: u32 = 1;
+
 pub struct Efuse;
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ChipType {
     Esp32D0wdq6,
     Esp32D0wdq5,
@@ -16,6 +25,71 @@ pub enum ChipType {
     Unknown,
 }
 
+/// eFuse blocks which can hold keys
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeyBlock {
+    /// Flash encryption key
+    Block1 = 1,
+    /// Secure boot key (V1) or public key digest (V2)
+    Block2 = 2,
+    /// User data or custom MAC address
+    Block3 = 3,
+}
+
+/// eFuse blocks
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EfuseBlock {
+    /// System configuration, including the protection bits themselves
+    Block0,
+    Block1,
+    Block2,
+    Block3,
+}
+
+impl From<KeyBlock> for EfuseBlock {
+    fn from(block: KeyBlock) -> Self {
+        match block {
+            KeyBlock::Block1 => EfuseBlock::Block1,
+            KeyBlock::Block2 => EfuseBlock::Block2,
+            KeyBlock::Block3 => EfuseBlock::Block3,
+        }
+    }
+}
+
+impl EfuseBlock {
+    /// Bit in RD_DIS which disables software reads of this block
+    fn read_disable_bit(self) -> Option<u32> {
+        match self {
+            EfuseBlock::Block0 => None,
+            EfuseBlock::Block1 => Some(0),
+            EfuseBlock::Block2 => Some(1),
+            EfuseBlock::Block3 => Some(2),
+        }
+    }
+
+    /// Bit in WR_DIS which disables programming of this block
+    fn write_disable_bit(self) -> Option<u32> {
+        match self {
+            EfuseBlock::Block0 => None,
+            EfuseBlock::Block1 => Some(7),
+            EfuseBlock::Block2 => Some(8),
+            EfuseBlock::Block3 => Some(9),
+        }
+    }
+}
+
+/// eFuse-specific errors
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The block does not support the requested protection
+    NotSupported,
+    /// The protection bits themselves are write-protected
+    WriteProtected,
+}
+
 impl Efuse {
     /// Reads chip's MAC address from the eFuse storage.
     ///
@@ -119,4 +193,134 @@ impl Efuse {
             % 2)
             != 0
     }
+
+    /// Returns whether secure boot (either V1 or V2) is enabled.
+    pub fn is_secure_boot_enabled() -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        let reg = efuse.blk0_rdata6.read();
+
+        reg.rd_abs_done_0().bit() || reg.rd_abs_done_1().bit()
+    }
+
+    /// Returns whether the JTAG interface is permanently disabled.
+    pub fn is_jtag_disabled() -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        efuse.blk0_rdata6.read().rd_disable_jtag().bit()
+    }
+
+    /// Returns whether encrypting flash contents via the UART download mode is
+    /// disabled.
+    pub fn is_download_encryption_disabled() -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        efuse.blk0_rdata6.read().rd_disable_dl_encrypt().bit()
+    }
+
+    /// Returns whether software reads of the given key block are disabled.
+    pub fn is_key_read_protected(block: KeyBlock) -> bool {
+        Self::is_read_protected(block.into())
+    }
+
+    /// Returns whether the given key block is protected against writing.
+    pub fn is_key_write_protected(block: KeyBlock) -> bool {
+        Self::is_write_protected(block.into())
+    }
+
+    /// Returns whether software reads of the given block are disabled.
+    pub fn is_read_protected(block: EfuseBlock) -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+
+        match block.read_disable_bit() {
+            Some(bit) => efuse.blk0_rdata0.read().rd_efuse_rd_dis().bits() as u32 & (1 << bit) != 0,
+            None => false,
+        }
+    }
+
+    /// Returns whether the given block is protected against programming.
+    pub fn is_write_protected(block: EfuseBlock) -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+
+        match block.write_disable_bit() {
+            Some(bit) => efuse.blk0_rdata0.read().rd_efuse_wr_dis().bits() as u32 & (1 << bit) != 0,
+            None => false,
+        }
+    }
+
+    /// Permanently disables software reads of the given block.
+    ///
+    /// Only BLOCK1 to BLOCK3 can be read-protected. The flash encryption key
+    /// in BLOCK1 stays usable by the hardware.
+    ///
+    /// The eFuse programming timing assumes an APB clock of 80 MHz.
+    ///
+    /// # Safety
+    ///
+    /// Burning eFuses is irreversible. Read-protecting a block which holds
+    /// data the firmware needs (e.g. a secure boot digest) can brick the
+    /// chip.
+    pub unsafe fn read_protect(block: EfuseBlock) -> Result<(), Error> {
+        let bit = block.read_disable_bit().ok_or(Error::NotSupported)?;
+
+        if Self::is_wr_dis_bit_set(WR_DIS_RD_DIS) {
+            return Err(Error::WriteProtected);
+        }
+
+        // RD_DIS starts at bit 16 of BLK0_WDATA0
+        Self::burn_block0(1 << (bit + 16));
+
+        Ok(())
+    }
+
+    /// Permanently disables programming of the given block.
+    ///
+    /// Fails with [Error::WriteProtected] if WR_DIS itself has been
+    /// write-protected.
+    ///
+    /// The eFuse programming timing assumes an APB clock of 80 MHz.
+    ///
+    /// # Safety
+    ///
+    /// Burning eFuses is irreversible. Unused bits in a write-protected block
+    /// can never be programmed afterwards.
+    pub unsafe fn write_protect(block: EfuseBlock) -> Result<(), Error> {
+        let bit = block.write_disable_bit().ok_or(Error::NotSupported)?;
+
+        if Self::is_wr_dis_bit_set(WR_DIS_WR_DIS) {
+            return Err(Error::WriteProtected);
+        }
+
+        Self::burn_block0(1 << bit);
+
+        Ok(())
+    }
+
+    /// Returns whether the given bit of WR_DIS is burnt
+    fn is_wr_dis_bit_set(bit: u32) -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+
+        efuse.blk0_rdata0.read().rd_efuse_wr_dis().bits() as u32 & (1 << bit) != 0
+    }
+
+    /// Programs the given bits of BLK0_WDATA0 (WR_DIS and RD_DIS), then
+    /// reloads the eFuse read registers.
+    unsafe fn burn_block0(wdata0: u32) {
+        let efuse = &*EFUSE::ptr();
+
+        // Programming timing for an APB clock of 80 MHz
+        efuse
+            .clk
+            .modify(|_, w| w.clk_sel0().bits(0x50).clk_sel1().bits(0x80));
+        efuse.dac_conf.modify(|_, w| w.dac_clk_div().bits(0x64));
+
+        efuse.blk0_wdata0.write(|w| w.bits(wdata0));
+
+        efuse.conf.write(|w| w.bits(EFUSE_WRITE_OP_CODE));
+        efuse.cmd.write(|w| w.pgm_cmd().set_bit());
+        while efuse.cmd.read().pgm_cmd().bit_is_set() {}
+
+        efuse.blk0_wdata0.write(|w| w.bits(0));
+
+        efuse.conf.write(|w| w.bits(EFUSE_READ_OP_CODE));
+        efuse.cmd.write(|w| w.read_cmd().set_bit());
+        while efuse.cmd.read().read_cmd().bit_is_set() {}
+    }
 }
@@ -1,9 +1,137 @@
-//! Reading of eFuses
+//! Reading of eFuses and management of their protection bits
 
 use crate::pac::EFUSE;
 
+const EFUSE_WRITE_OP_CODE: u16 = 0x5a5a;
+const EFUSE_READ_OP_CODE: u16 = 0x5aa5;
+
 pub struct Efuse;
 
+#[path = "keys.rs"]
+mod keys;
+
+pub use keys::{KeyBlock, KeyPurpose};
+
+/// eFuse blocks
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EfuseBlock {
+    /// System configuration, including the protection bits themselves
+    Block0,
+    /// MAC address and SPI pad configuration
+    Block1,
+    /// System data part 1
+    Block2,
+    /// User data
+    Block3,
+    Key0,
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    /// System data part 2
+    Block10,
+}
+
+impl From<KeyBlock> for EfuseBlock {
+    fn from(block: KeyBlock) -> Self {
+        match block {
+            KeyBlock::Key0 => EfuseBlock::Key0,
+            KeyBlock::Key1 => EfuseBlock::Key1,
+            KeyBlock::Key2 => EfuseBlock::Key2,
+            KeyBlock::Key3 => EfuseBlock::Key3,
+            KeyBlock::Key4 => EfuseBlock::Key4,
+            KeyBlock::Key5 => EfuseBlock::Key5,
+        }
+    }
+}
+
+impl EfuseBlock {
+    /// Bit in RD_DIS which disables software reads of this block
+    fn read_disable_bit(self) -> Option<u32> {
+        match self {
+            EfuseBlock::Key0 => Some(0),
+            EfuseBlock::Key1 => Some(1),
+            EfuseBlock::Key2 => Some(2),
+            EfuseBlock::Key3 => Some(3),
+            EfuseBlock::Key4 => Some(4),
+            EfuseBlock::Key5 => Some(5),
+            EfuseBlock::Block10 => Some(6),
+            _ => None,
+        }
+    }
+
+    /// Bit in WR_DIS which disables programming of this block
+    fn write_disable_bit(self) -> Option<u32> {
+        match self {
+            EfuseBlock::Block0 => None,
+            EfuseBlock::Block1 => Some(20),
+            EfuseBlock::Block2 => Some(21),
+            EfuseBlock::Block3 => Some(22),
+            EfuseBlock::Key0 => Some(23),
+            EfuseBlock::Key1 => Some(24),
+            EfuseBlock::Key2 => Some(25),
+            EfuseBlock::Key3 => Some(26),
+            EfuseBlock::Key4 => Some(27),
+            EfuseBlock::Key5 => Some(28),
+            EfuseBlock::Block10 => Some(29),
+        }
+    }
+}
+
+/// eFuse-specific errors
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The block does not support the requested protection
+    NotSupported,
+    /// The protection bits themselves are write-protected
+    WriteProtected,
+    /// The clocks have not been frozen, so the programming timing can not be
+    /// derived from the APB_CLK frequency
+    ClocksNotFrozen,
+}
+
+/// eFuse programming timing for an APB_CLK frequency, as set by
+/// `efuse_hal_set_timing` of ESP-IDF
+struct Timing {
+    dac_clk_div: u8,
+    pwr_on_num: u16,
+    tpgm: u16,
+    tpgm_inactive: u8,
+    trd: u8,
+}
+
+impl Timing {
+    fn for_apb(apb_mhz: u32) -> Self {
+        match apb_mhz {
+            80 => Timing {
+                dac_clk_div: 0xa0,
+                pwr_on_num: 0xa200,
+                tpgm: 0x320,
+                tpgm_inactive: 2,
+                trd: 2,
+            },
+            40 => Timing {
+                dac_clk_div: 0x50,
+                pwr_on_num: 0x5100,
+                tpgm: 0x190,
+                tpgm_inactive: 1,
+                trd: 1,
+            },
+            // 20 MHz and below
+            _ => Timing {
+                dac_clk_div: 0x28,
+                pwr_on_num: 0x2880,
+                tpgm: 0xc8,
+                tpgm_inactive: 1,
+                trd: 1,
+            },
+        }
+    }
+}
+
 impl Efuse {
     /// Reads chip's MAC address from the eFuse storage.
     ///
@@ -54,6 +182,190 @@ impl Efuse {
             != 0
     }
 
+    /// Returns whether secure boot is enabled.
+    pub fn is_secure_boot_enabled() -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        efuse.rd_repeat_data2.read().secure_boot_en().bit()
+    }
+
+    /// Returns whether the secure boot digest with the given index (0 to 2)
+    /// has been revoked.
+    pub fn is_secure_boot_key_revoked(digest: u8) -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        let reg = efuse.rd_repeat_data1.read();
+
+        match digest {
+            0 => reg.secure_boot_key_revoke0().bit(),
+            1 => reg.secure_boot_key_revoke1().bit(),
+            2 => reg.secure_boot_key_revoke2().bit(),
+            _ => false,
+        }
+    }
+
+    /// Returns whether encrypting flash contents via the UART download mode is
+    /// disabled.
+    pub fn is_download_encryption_disabled() -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        efuse
+            .rd_repeat_data0
+            .read()
+            .dis_download_manual_encrypt()
+            .bit()
+    }
+
+    /// Returns the purpose the given key block was burnt with.
+    pub fn get_key_purpose(block: KeyBlock) -> KeyPurpose {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        let data1 = efuse.rd_repeat_data1.read();
+        let data2 = efuse.rd_repeat_data2.read();
+
+        let purpose = match block {
+            KeyBlock::Key0 => data1.key_purpose_0().bits(),
+            KeyBlock::Key1 => data1.key_purpose_1().bits(),
+            KeyBlock::Key2 => data2.key_purpose_2().bits(),
+            KeyBlock::Key3 => data2.key_purpose_3().bits(),
+            KeyBlock::Key4 => data2.key_purpose_4().bits(),
+            KeyBlock::Key5 => data2.key_purpose_5().bits(),
+        };
+
+        KeyPurpose::from(purpose)
+    }
+
+    /// Returns whether the given key block is in use, i.e. it has a purpose
+    /// other than [KeyPurpose::User] or is protected against reading.
+    pub fn is_key_block_used(block: KeyBlock) -> bool {
+        Self::get_key_purpose(block) != KeyPurpose::User || Self::is_key_read_protected(block)
+    }
+
+    /// Returns whether software reads of the given key block are disabled.
+    pub fn is_key_read_protected(block: KeyBlock) -> bool {
+        Self::is_read_protected(block.into())
+    }
+
+    /// Returns whether the given key block is protected against writing.
+    pub fn is_key_write_protected(block: KeyBlock) -> bool {
+        Self::is_write_protected(block.into())
+    }
+
+    /// Returns whether software reads of the given block are disabled.
+    pub fn is_read_protected(block: EfuseBlock) -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+
+        match block.read_disable_bit() {
+            Some(bit) => efuse.rd_repeat_data0.read().rd_dis().bits() as u32 & (1 << bit) != 0,
+            None => false,
+        }
+    }
+
+    /// Returns whether the given block is protected against programming.
+    pub fn is_write_protected(block: EfuseBlock) -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+
+        match block.write_disable_bit() {
+            Some(bit) => efuse.rd_wr_dis.read().wr_dis().bits() & (1 << bit) != 0,
+            None => false,
+        }
+    }
+
+    /// Permanently disables software reads of the given block.
+    ///
+    /// Only the key blocks and BLOCK10 can be read-protected. Keys stay
+    /// usable by the hardware peripherals (HMAC, DS, flash encryption).
+    ///
+    /// The programming timing is derived from the APB_CLK frequency, so the
+    /// clocks have to be frozen, and must not change while this runs.
+    ///
+    /// # Safety
+    ///
+    /// Burning eFuses is irreversible. Read-protecting a block which holds
+    /// data the firmware needs (e.g. a secure boot digest) can brick the
+    /// chip.
+    pub unsafe fn read_protect(block: EfuseBlock) -> Result<(), Error> {
+        let bit = block.read_disable_bit().ok_or(Error::NotSupported)?;
+
+        // WR_DIS bit 0 protects RD_DIS
+        if Self::rd_wr_dis() & 1 != 0 {
+            return Err(Error::WriteProtected);
+        }
+
+        Self::burn_block0(0, 1 << bit)
+    }
+
+    /// Permanently disables programming of the given block.
+    ///
+    /// The programming timing is derived from the APB_CLK frequency, so the
+    /// clocks have to be frozen, and must not change while this runs.
+    ///
+    /// # Safety
+    ///
+    /// Burning eFuses is irreversible. Unused bits in a write-protected block
+    /// can never be programmed afterwards.
+    pub unsafe fn write_protect(block: EfuseBlock) -> Result<(), Error> {
+        let bit = block.write_disable_bit().ok_or(Error::NotSupported)?;
+
+        Self::burn_block0(1 << bit, 0)
+    }
+
+    fn rd_wr_dis() -> u32 {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        efuse.rd_wr_dis.read().wr_dis().bits()
+    }
+
+    /// Programs the given bits of WR_DIS and RD_DIS in BLOCK0, then reloads the
+    /// eFuse read registers.
+    unsafe fn burn_block0(wr_dis: u32, rd_dis: u32) -> Result<(), Error> {
+        let efuse = &*EFUSE::ptr();
+
+        let apb = crate::clock::snapshot()
+            .ok_or(Error::ClocksNotFrozen)?
+            .apb_clock;
+        let timing = Timing::for_apb(apb.to_MHz());
+
+        efuse
+            .dac_conf
+            .modify(|_, w| w.dac_clk_div().bits(timing.dac_clk_div));
+        efuse.wr_tim_conf0.modify(|_, w| {
+            w.thp_a()
+                .bits(1)
+                .tpgm()
+                .bits(timing.tpgm)
+                .tpgm_inactive()
+                .bits(timing.tpgm_inactive)
+        });
+        efuse
+            .wr_tim_conf1
+            .modify(|_, w| w.tsup_a().bits(1).pwr_on_num().bits(timing.pwr_on_num));
+        efuse
+            .rd_tim_conf
+            .modify(|_, w| w.tsur_a().bits(1).thr_a().bits(1).trd().bits(timing.trd));
+
+        // PGM_DATA0 maps to WR_DIS, PGM_DATA1 to the register holding RD_DIS
+        efuse.pgm_data0.write(|w| w.bits(wr_dis));
+        efuse.pgm_data1.write(|w| w.bits(rd_dis));
+        efuse.pgm_data2.write(|w| w.bits(0));
+        efuse.pgm_data3.write(|w| w.bits(0));
+        efuse.pgm_data4.write(|w| w.bits(0));
+        efuse.pgm_data5.write(|w| w.bits(0));
+        efuse.pgm_data6.write(|w| w.bits(0));
+        efuse.pgm_data7.write(|w| w.bits(0));
+        efuse.pgm_check_value0.write(|w| w.bits(0));
+        efuse.pgm_check_value1.write(|w| w.bits(0));
+        efuse.pgm_check_value2.write(|w| w.bits(0));
+
+        efuse.conf.write(|w| w.op_code().bits(EFUSE_WRITE_OP_CODE));
+        efuse.cmd.write(|w| w.blk_num().bits(0).pgm_cmd().set_bit());
+        while efuse.cmd.read().pgm_cmd().bit_is_set() {}
+
+        efuse.pgm_data0.write(|w| w.bits(0));
+        efuse.pgm_data1.write(|w| w.bits(0));
+
+        efuse.conf.write(|w| w.op_code().bits(EFUSE_READ_OP_CODE));
+        efuse.cmd.write(|w| w.read_cmd().set_bit());
+        while efuse.cmd.read().read_cmd().bit_is_set() {}
+
+        Ok(())
+    }
+
     /// Get the multiplier for the timeout value of the RWDT STAGE 0 register.
     pub fn get_rwdt_multiplier() -> u8 {
         let efuse = unsafe { &*EFUSE::ptr() };
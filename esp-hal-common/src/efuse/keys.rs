@@ -0,0 +1,53 @@
+//! eFuse key blocks and key purposes shared by the chips with six key blocks
+
+/// eFuse key blocks which can hold keys or secure boot digests
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeyBlock {
+    Key0 = 0,
+    Key1 = 1,
+    Key2 = 2,
+    Key3 = 3,
+    Key4 = 4,
+    Key5 = 5,
+}
+
+/// The purpose a key block was burnt with
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeyPurpose {
+    /// Unused, or general user data
+    User,
+    Reserved,
+    XtsAes256Key1,
+    XtsAes256Key2,
+    XtsAes128Key,
+    HmacDownAll,
+    HmacDownJtag,
+    HmacDownDigitalSignature,
+    HmacUp,
+    SecureBootDigest0,
+    SecureBootDigest1,
+    SecureBootDigest2,
+    Unknown,
+}
+
+impl From<u8> for KeyPurpose {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => KeyPurpose::User,
+            1 => KeyPurpose::Reserved,
+            2 => KeyPurpose::XtsAes256Key1,
+            3 => KeyPurpose::XtsAes256Key2,
+            4 => KeyPurpose::XtsAes128Key,
+            5 => KeyPurpose::HmacDownAll,
+            6 => KeyPurpose::HmacDownJtag,
+            7 => KeyPurpose::HmacDownDigitalSignature,
+            8 => KeyPurpose::HmacUp,
+            9 => KeyPurpose::SecureBootDigest0,
+            10 => KeyPurpose::SecureBootDigest1,
+            11 => KeyPurpose::SecureBootDigest2,
+            _ => KeyPurpose::Unknown,
+        }
+    }
+}
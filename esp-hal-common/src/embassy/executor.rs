@@ -0,0 +1,234 @@
+//! Executors
+//!
+//! [Executor] polls its tasks from the main loop, and puts the CPU to sleep
+//! while none of them can make progress.
+//!
+//! [InterruptExecutor] polls its tasks from the handler of one of the four
+//! software interrupts (`FROM_CPU_INTR0` to `FROM_CPU_INTR3`), so they preempt
+//! the tasks of the thread-mode executor and any interrupt of a lower
+//! priority.
+
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use embassy_executor::{raw, SendSpawner, Spawner};
+
+use crate::{interrupt, pac};
+
+/// Context passed to [__pender] by the thread-mode executor
+const THREAD_MODE_CONTEXT: usize = 0;
+
+/// Context passed to [__pender] by an interrupt executor: the number of its
+/// software interrupt, offset by one
+const fn interrupt_mode_context(swi: u8) -> usize {
+    swi as usize + 1
+}
+
+/// Set when a task of the thread-mode executor has been woken
+static SIGNAL_WORK_THREAD_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Called by embassy when a task of the executor identified by `context` has
+/// been woken
+#[export_name = "__pender"]
+fn __pender(context: *mut ()) {
+    match context as usize {
+        THREAD_MODE_CONTEXT => SIGNAL_WORK_THREAD_MODE.store(true, Ordering::SeqCst),
+        context => set_software_interrupt((context - 1) as u8, true),
+    }
+}
+
+/// Executor running tasks in thread mode
+pub struct Executor {
+    inner: raw::Executor,
+    not_send: PhantomData<*mut ()>,
+}
+
+impl Executor {
+    /// Create a new executor
+    pub fn new() -> Self {
+        Self {
+            inner: raw::Executor::new(THREAD_MODE_CONTEXT as *mut ()),
+            not_send: PhantomData,
+        }
+    }
+
+    /// Run the executor, after spawning the initial tasks from `init`
+    ///
+    /// The executor must be `'static`, e.g. placed in a `StaticCell`.
+    pub fn run(&'static mut self, init: impl FnOnce(Spawner)) -> ! {
+        init(self.inner.spawner());
+
+        loop {
+            unsafe { self.inner.poll() };
+            wait_for_work();
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sleep until a task has been woken
+///
+/// Checking the flag and going to sleep must happen with interrupts disabled,
+/// or a wake-up from an interrupt in between would be missed. A pending
+/// interrupt still ends the sleep.
+#[cfg(target_arch = "riscv32")]
+fn wait_for_work() {
+    critical_section::with(|_| {
+        if SIGNAL_WORK_THREAD_MODE.load(Ordering::SeqCst) {
+            SIGNAL_WORK_THREAD_MODE.store(false, Ordering::SeqCst);
+        } else {
+            unsafe { core::arch::asm!("wfi") };
+        }
+    });
+}
+
+/// Sleep until a task has been woken
+///
+/// Checking the flag and going to sleep must happen with interrupts disabled,
+/// or a wake-up from an interrupt in between would be missed. `waiti` enables
+/// interrupts while sleeping.
+#[cfg(target_arch = "xtensa")]
+fn wait_for_work() {
+    // A critical section can't be used: on multi-core chips it takes a lock
+    // which the interrupt handlers would need as well
+    let token: u32;
+    unsafe { core::arch::asm!("rsil {0}, 5", out(reg) token) };
+
+    if SIGNAL_WORK_THREAD_MODE.load(Ordering::SeqCst) {
+        SIGNAL_WORK_THREAD_MODE.store(false, Ordering::SeqCst);
+    } else {
+        unsafe { core::arch::asm!("waiti 0") };
+    }
+
+    unsafe { core::arch::asm!("wsr.ps {0}", "rsync", in(reg) token) };
+}
+
+/// Executor running tasks in the handler of software interrupt `SWI` (0 to 3)
+///
+/// The handler has to be defined by the application, and call
+/// [InterruptExecutor::on_interrupt]:
+///
+/// ```no_run
+/// static EXECUTOR: InterruptExecutor<0> = InterruptExecutor::new();
+///
+/// #[interrupt]
+/// fn FROM_CPU_INTR0() {
+///     unsafe { EXECUTOR.on_interrupt() }
+/// }
+///
+/// let spawner = EXECUTOR.start(interrupt::Priority::Priority2);
+/// spawner.must_spawn(latency_critical());
+/// ```
+pub struct InterruptExecutor<const SWI: u8> {
+    /// Set once [InterruptExecutor::start] has been called
+    started: AtomicBool,
+    /// Set once the executor has been initialized, so that it can be polled
+    ready: AtomicBool,
+    executor: UnsafeCell<MaybeUninit<raw::Executor>>,
+}
+
+unsafe impl<const SWI: u8> Send for InterruptExecutor<SWI> {}
+unsafe impl<const SWI: u8> Sync for InterruptExecutor<SWI> {}
+
+impl<const SWI: u8> InterruptExecutor<SWI> {
+    /// The software interrupt, checked at compile time to be one of the four
+    /// the chips have
+    const SOFTWARE_INTERRUPT: u8 = {
+        assert!(SWI <= 3, "InterruptExecutor: SWI must be 0, 1, 2 or 3");
+        SWI
+    };
+
+    /// Create a new, not yet started, executor
+    pub const fn new() -> Self {
+        Self {
+            started: AtomicBool::new(false),
+            ready: AtomicBool::new(false),
+            executor: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Poll the tasks of the executor
+    ///
+    /// # Safety
+    ///
+    /// This must only be called from the handler of software interrupt `SWI`.
+    pub unsafe fn on_interrupt(&'static self) {
+        set_software_interrupt(Self::SOFTWARE_INTERRUPT, false);
+
+        if self.ready.load(Ordering::Acquire) {
+            let executor = (*self.executor.get()).assume_init_ref();
+            executor.poll();
+        }
+    }
+
+    /// Start the executor, running its tasks at `priority`
+    ///
+    /// The returned spawner can be used from any context to spawn tasks on
+    /// this executor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the executor has already been started.
+    pub fn start(&'static self, priority: interrupt::Priority) -> SendSpawner {
+        if self.started.swap(true, Ordering::AcqRel) {
+            panic!("InterruptExecutor::start() called multiple times on the same executor");
+        }
+
+        let context = interrupt_mode_context(Self::SOFTWARE_INTERRUPT) as *mut ();
+        unsafe {
+            (*self.executor.get()).write(raw::Executor::new(context));
+        }
+        self.ready.store(true, Ordering::Release);
+
+        let software_interrupt = match Self::SOFTWARE_INTERRUPT {
+            0 => pac::Interrupt::FROM_CPU_INTR0,
+            1 => pac::Interrupt::FROM_CPU_INTR1,
+            2 => pac::Interrupt::FROM_CPU_INTR2,
+            3 => pac::Interrupt::FROM_CPU_INTR3,
+            _ => unreachable!(),
+        };
+        interrupt::enable(software_interrupt, priority).unwrap();
+
+        #[cfg(target_arch = "riscv32")]
+        unsafe {
+            riscv::interrupt::enable();
+        }
+
+        let executor = unsafe { (*self.executor.get()).assume_init_ref() };
+        executor.spawner().make_send()
+    }
+}
+
+/// Raise (`pending == true`) or clear software interrupt `swi`
+fn set_software_interrupt(swi: u8, pending: bool) {
+    #[cfg(feature = "esp32")]
+    let system = unsafe { &*pac::DPORT::PTR };
+    #[cfg(not(feature = "esp32"))]
+    let system = unsafe { &*pac::SYSTEM::PTR };
+    let bits = pending as u32;
+
+    match swi {
+        0 => system
+            .cpu_intr_from_cpu_0
+            .write(|w| unsafe { w.bits(bits) }),
+        1 => system
+            .cpu_intr_from_cpu_1
+            .write(|w| unsafe { w.bits(bits) }),
+        2 => system
+            .cpu_intr_from_cpu_2
+            .write(|w| unsafe { w.bits(bits) }),
+        3 => system
+            .cpu_intr_from_cpu_3
+            .write(|w| unsafe { w.bits(bits) }),
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,67 @@
+//! [embassy] support
+//!
+//! [init] installs the embassy time driver, which runs on alarm 0 of the
+//! SYSTIMER, or on timer 0 of timer group 0 on the ESP32, which has no
+//! SYSTIMER. [Executor] runs async tasks in thread mode. Latency-critical
+//! tasks can be run by an [InterruptExecutor] instead, preempting the
+//! thread-mode tasks:
+//!
+//! ```no_run
+//! embassy::init(&clocks, SystemTimer::new(peripherals.SYSTIMER));
+//!
+//! static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+//! EXECUTOR
+//!     .init(Executor::new())
+//!     .run(|spawner| spawner.must_spawn(run()));
+//! ```
+//!
+//! On the ESP32, the timer is passed instead:
+//!
+//! ```no_run
+//! let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+//! embassy::init(&clocks, timer_group0.timer0);
+//! ```
+//!
+//! The `embassy` feature also enables the `async` feature, providing the
+//! async APIs of the drivers.
+//!
+//! The `SYSTIMER_TARGET0` interrupt, or `TG0_T0_LEVEL` on the ESP32, is
+//! handled by this module, so it must not be handled by the application.
+//!
+//! [embassy]: https://embassy.dev
+
+use crate::clock::Clocks;
+#[cfg(not(feature = "esp32"))]
+use crate::systimer::SystemTimer;
+#[cfg(feature = "esp32")]
+use crate::{
+    pac::TIMG0,
+    timer::{Timer, Timer0},
+};
+
+mod executor;
+mod time_driver;
+
+pub use executor::{Executor, InterruptExecutor};
+
+/// Install the embassy time driver, which takes over the SYSTIMER
+///
+/// This must be called once, before any embassy timers are used. The SYSTIMER
+/// also becomes the global [timestamp source](crate::time), unless one has
+/// already been installed.
+#[cfg(not(feature = "esp32"))]
+pub fn init(_clocks: &Clocks, systimer: SystemTimer) {
+    time_driver::EmbassyTimer::init(systimer.alarm0);
+    crate::time::install(SystemTimer::now, SystemTimer::TICKS_PER_SECOND, true);
+}
+
+/// Install the embassy time driver, which takes over timer 0 of timer group 0
+///
+/// This must be called once, before any embassy timers are used. The timer
+/// also becomes the global [timestamp source](crate::time), unless one has
+/// already been installed.
+#[cfg(feature = "esp32")]
+pub fn init(clocks: &Clocks, timer: Timer<Timer0<TIMG0>>) {
+    time_driver::EmbassyTimer::init(clocks, timer);
+    crate::time::install(time_driver::now, time_driver::TICK_HZ, true);
+}
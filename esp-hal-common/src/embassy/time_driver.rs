@@ -0,0 +1,197 @@
+//! embassy time driver
+//!
+//! The driver ticks at 16 MHz. It uses SYSTIMER alarm 0 on the chips which
+//! have a SYSTIMER, and timer 0 of timer group 0 on the ESP32, which is
+//! prescaled from APB_CLK to 16 MHz.
+
+use core::{cell::RefCell, task::Waker};
+
+use critical_section::{CriticalSection, Mutex};
+use embassy_time_driver::Driver;
+use embassy_time_queue_utils::Queue;
+
+#[cfg(not(feature = "esp32"))]
+use crate::systimer::{Alarm, SystemTimer, Target};
+#[cfg(feature = "esp32c3")]
+use crate::Cpu;
+#[cfg(feature = "esp32")]
+use crate::{
+    clock::Clocks,
+    pac::TIMG0,
+    timer::{Instance, Timer, Timer0},
+};
+use crate::{interrupt, macros::interrupt, pac};
+
+/// Frequency of the driver ticks
+pub(super) const TICK_HZ: u64 = embassy_time_driver::TICK_HZ;
+
+/// SYSTIMER ticks per driver tick
+#[cfg(not(feature = "esp32"))]
+const SYSTIMER_TICKS_PER_TICK: u64 = SystemTimer::TICKS_PER_SECOND / TICK_HZ;
+
+#[cfg(not(feature = "esp32"))]
+type AlarmTimer = Alarm<Target, 0>;
+#[cfg(feature = "esp32")]
+type AlarmTimer = Timer0<TIMG0>;
+
+pub(super) struct EmbassyTimer {
+    alarm: Mutex<RefCell<Option<AlarmTimer>>>,
+    queue: Mutex<RefCell<Queue>>,
+}
+
+embassy_time_driver::time_driver_impl!(static DRIVER: EmbassyTimer = EmbassyTimer {
+    alarm: Mutex::new(RefCell::new(None)),
+    queue: Mutex::new(RefCell::new(Queue::new())),
+});
+
+/// The current time in driver ticks
+#[cfg(not(feature = "esp32"))]
+pub(super) fn now() -> u64 {
+    SystemTimer::now() / SYSTIMER_TICKS_PER_TICK
+}
+
+/// The current time in driver ticks
+#[cfg(feature = "esp32")]
+pub(super) fn now() -> u64 {
+    use crate::time::TimestampSource;
+
+    Timer::<Timer0<TIMG0>>::now()
+}
+
+impl EmbassyTimer {
+    #[cfg(not(feature = "esp32"))]
+    pub(super) fn init(alarm: AlarmTimer) {
+        alarm.clear_interrupt();
+        critical_section::with(|cs| DRIVER.alarm.borrow(cs).replace(Some(alarm)));
+
+        interrupt::enable(
+            pac::Interrupt::SYSTIMER_TARGET0,
+            interrupt::Priority::Priority1,
+        )
+        .unwrap();
+
+        #[cfg(feature = "esp32c3")]
+        {
+            interrupt::set_kind(
+                Cpu::ProCpu,
+                interrupt::CpuInterrupt::Interrupt1, // Interrupt 1 handles priority one interrupts
+                interrupt::InterruptKind::Level,
+            );
+            unsafe { riscv::interrupt::enable() };
+        }
+    }
+
+    /// Start `timer` counting at the tick rate of the driver
+    ///
+    /// # Panics
+    ///
+    /// Panics if APB_CLK is not a multiple of the tick rate.
+    #[cfg(feature = "esp32")]
+    pub(super) fn init(clocks: &Clocks, mut timer: Timer<Timer0<TIMG0>>) {
+        let apb = clocks.apb_clock.to_Hz() as u64;
+        assert!(
+            apb % TICK_HZ == 0,
+            "APB_CLK must be a multiple of the embassy tick rate"
+        );
+
+        timer.set_divider((apb / TICK_HZ) as u32);
+        timer.start_free_running();
+
+        let mut alarm = timer.free();
+        alarm.clear_interrupt();
+        critical_section::with(|cs| DRIVER.alarm.borrow(cs).replace(Some(alarm)));
+
+        interrupt::enable(pac::Interrupt::TG0_T0_LEVEL, interrupt::Priority::Priority1).unwrap();
+    }
+
+    /// Set the alarm to fire at `timestamp`, returning `false` if that moment
+    /// has already passed
+    fn set_alarm(&self, cs: CriticalSection, timestamp: u64) -> bool {
+        let mut alarm = self.alarm.borrow(cs).borrow_mut();
+        let alarm = match alarm.as_mut() {
+            Some(alarm) => alarm,
+            None => return true,
+        };
+
+        // Nothing is scheduled
+        if timestamp == u64::MAX {
+            disarm(alarm);
+            return true;
+        }
+
+        arm(alarm, timestamp);
+
+        now() < timestamp
+    }
+
+    /// Wake the expired timers and set the alarm for the next one
+    fn update_alarm(&self, cs: CriticalSection) {
+        let mut queue = self.queue.borrow(cs).borrow_mut();
+
+        let mut next = queue.next_expiration(self.now());
+        while !self.set_alarm(cs, next) {
+            next = queue.next_expiration(self.now());
+        }
+    }
+
+    fn on_interrupt(&self) {
+        critical_section::with(|cs| {
+            if let Some(alarm) = self.alarm.borrow(cs).borrow_mut().as_mut() {
+                alarm.clear_interrupt();
+            }
+
+            self.update_alarm(cs);
+        });
+    }
+}
+
+#[cfg(not(feature = "esp32"))]
+fn arm(alarm: &mut AlarmTimer, timestamp: u64) {
+    alarm.set_target(timestamp.saturating_mul(SYSTIMER_TICKS_PER_TICK));
+    alarm.enable_interrupt();
+}
+
+#[cfg(not(feature = "esp32"))]
+fn disarm(alarm: &mut AlarmTimer) {
+    alarm.disable_interrupt();
+}
+
+#[cfg(feature = "esp32")]
+fn arm(alarm: &mut AlarmTimer, timestamp: u64) {
+    alarm.set_alarm_active(false);
+    alarm.load_alarm_value(timestamp);
+    alarm.set_alarm_active(true);
+    alarm.listen();
+}
+
+#[cfg(feature = "esp32")]
+fn disarm(alarm: &mut AlarmTimer) {
+    alarm.unlisten();
+    alarm.set_alarm_active(false);
+}
+
+impl Driver for EmbassyTimer {
+    fn now(&self) -> u64 {
+        now()
+    }
+
+    fn schedule_wake(&self, at: u64, waker: &Waker) {
+        critical_section::with(|cs| {
+            if self.queue.borrow(cs).borrow_mut().schedule_wake(at, waker) {
+                self.update_alarm(cs);
+            }
+        });
+    }
+}
+
+#[cfg(not(feature = "esp32"))]
+#[interrupt]
+fn SYSTIMER_TARGET0() {
+    DRIVER.on_interrupt();
+}
+
+#[cfg(feature = "esp32")]
+#[interrupt]
+fn TG0_T0_LEVEL() {
+    DRIVER.on_interrupt();
+}
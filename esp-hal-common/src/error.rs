@@ -0,0 +1,118 @@
+//! Crate-level error type
+//!
+//! The drivers report their own error types, e.g. [serial::Error] or
+//! [i2c::Error]. They all convert into [Error], so an application can
+//! propagate errors from different drivers with `?` and match on them in one
+//! place:
+//!
+//! ```no_run
+//! fn read_sensor(i2c: &mut I2C<'_, I2C0>, timer: &mut Timer<Timer0<TIMG0>>) -> Result<u8, Error> {
+//!     timer.cancel()?;
+//!     let mut data = [0u8];
+//!     i2c.read(0x42, &mut data)?;
+//!     Ok(data[0])
+//! }
+//! ```
+//!
+//! All error types are `#[non_exhaustive]`, so new error conditions can be
+//! added without breaking applications.
+
+use core::fmt;
+
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+use crate::rtc_i2c;
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+use crate::ulp;
+use crate::{i2c, rtc_cntl, serial, spi, timer};
+
+/// Errors reported by the HAL drivers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// UART error
+    Serial(serial::Error),
+    /// SPI error
+    Spi(spi::Error),
+    /// I2C transmission error
+    I2c(i2c::Error),
+    /// I2C setup error
+    I2cSetup(i2c::SetupError),
+    /// Timer error
+    Timer(timer::Error),
+    /// RTC error
+    Rtc(rtc_cntl::Error),
+    /// RTC I2C error
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    RtcI2c(rtc_i2c::Error),
+    /// ULP error
+    #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+    Ulp(ulp::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Serial(e) => write!(f, "UART: {}", e),
+            Error::Spi(e) => write!(f, "SPI: {}", e),
+            Error::I2c(e) => write!(f, "I2C: {}", e),
+            Error::I2cSetup(e) => write!(f, "I2C setup: {}", e),
+            Error::Timer(e) => write!(f, "timer: {}", e),
+            Error::Rtc(e) => write!(f, "RTC: {}", e),
+            #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+            Error::RtcI2c(e) => write!(f, "RTC I2C: {}", e),
+            #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+            Error::Ulp(e) => write!(f, "ULP: {}", e),
+        }
+    }
+}
+
+impl From<serial::Error> for Error {
+    fn from(e: serial::Error) -> Self {
+        Error::Serial(e)
+    }
+}
+
+impl From<spi::Error> for Error {
+    fn from(e: spi::Error) -> Self {
+        Error::Spi(e)
+    }
+}
+
+impl From<i2c::Error> for Error {
+    fn from(e: i2c::Error) -> Self {
+        Error::I2c(e)
+    }
+}
+
+impl From<i2c::SetupError> for Error {
+    fn from(e: i2c::SetupError) -> Self {
+        Error::I2cSetup(e)
+    }
+}
+
+impl From<timer::Error> for Error {
+    fn from(e: timer::Error) -> Self {
+        Error::Timer(e)
+    }
+}
+
+impl From<rtc_cntl::Error> for Error {
+    fn from(e: rtc_cntl::Error) -> Self {
+        Error::Rtc(e)
+    }
+}
+
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+impl From<rtc_i2c::Error> for Error {
+    fn from(e: rtc_i2c::Error) -> Self {
+        Error::RtcI2c(e)
+    }
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+impl From<ulp::Error> for Error {
+    fn from(e: ulp::Error) -> Self {
+        Error::Ulp(e)
+    }
+}
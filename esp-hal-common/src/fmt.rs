@@ -0,0 +1,22 @@
+//! Internal diagnostics
+//!
+//! The drivers report computed register values (clock dividers, calibration
+//! results, DMA descriptor setup, ...) at the trace level. These are emitted
+//! through `log` when the `log` feature is enabled, or otherwise through
+//! `defmt` when the `defmt` feature is enabled, and compiled out if neither
+//! is.
+//!
+//! Only `{}` placeholders may be used, as they are understood by both.
+
+macro_rules! trace {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{
+        #[cfg(feature = "log")]
+        ::log::trace!($fmt $(, $arg)*);
+        #[cfg(all(feature = "defmt", not(feature = "log")))]
+        ::defmt::trace!($fmt $(, $arg)*);
+        #[cfg(not(any(feature = "log", feature = "defmt")))]
+        {
+            $( let _ = &$arg; )*
+        }
+    }};
+}
@@ -0,0 +1,359 @@
+//! General Direct Memory Access (GDMA)
+//!
+//! The GDMA controller moves data between memory and peripherals without CPU
+//! involvement. Each channel has a transmit (memory to peripheral) and a
+//! receive (peripheral to memory) direction, which are connected to a
+//! peripheral and follow a linked list of [DmaDescriptor]s describing the
+//! memory buffers.
+//!
+//! Drivers which support DMA take a [Channel] and the descriptors to use;
+//! buffers and descriptors must be located in internal RAM.
+//!
+//! # Example
+//!
+//! ```no_run
+//! static mut DESCRIPTORS: [DmaDescriptor; 8] = [DmaDescriptor::EMPTY; 8];
+//!
+//! let gdma = Gdma::new(peripherals.DMA, &mut system.peripheral_clock_control);
+//! let channel = gdma.channel0;
+//! ```
+
+use paste::paste;
+
+use crate::{
+    pac::{dma::RegisterBlock, DMA},
+    system::{Peripheral, PeripheralClockControl},
+};
+
+/// Maximum number of bytes a single descriptor can hold
+///
+/// Kept word-aligned, as required for receive buffers.
+pub const MAX_DESCRIPTOR_BUFFER_SIZE: usize = 4092;
+
+// Descriptor flag bits
+const DESCRIPTOR_SIZE_MASK: u32 = 0xfff;
+const DESCRIPTOR_LENGTH_SHIFT: u32 = 12;
+const DESCRIPTOR_SUC_EOF: u32 = 1 << 30;
+const DESCRIPTOR_OWNER_DMA: u32 = 1 << 31;
+
+/// GDMA-specific errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DmaError {
+    /// The buffer needs more descriptors than were provided
+    TooFewDescriptors,
+    /// The buffer is empty
+    EmptyBuffer,
+    /// The hardware encountered an invalid descriptor
+    DescriptorError,
+}
+
+/// Peripherals which can be connected to a GDMA channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DmaPeripheral {
+    Spi2   = 0,
+    Spi3   = 1,
+    Uhci0  = 2,
+    I2s0   = 3,
+    I2s1   = 4,
+    LcdCam = 5,
+    Aes    = 6,
+    Sha    = 7,
+    Adc    = 8,
+    Rmt    = 9,
+}
+
+/// A linked-list descriptor, describing one memory buffer of a transfer
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DmaDescriptor {
+    flags: u32,
+    buffer: *mut u8,
+    next: *mut DmaDescriptor,
+}
+
+impl DmaDescriptor {
+    /// An unused descriptor, to initialize descriptor arrays with
+    pub const EMPTY: Self = Self {
+        flags: 0,
+        buffer: core::ptr::null_mut(),
+        next: core::ptr::null_mut(),
+    };
+
+    /// Number of valid bytes in the buffer
+    pub fn len(&self) -> usize {
+        ((self.flags >> DESCRIPTOR_LENGTH_SHIFT) & DESCRIPTOR_SIZE_MASK) as usize
+    }
+
+    /// Whether the buffer holds no valid bytes
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Number of descriptors required for a buffer of `len` bytes
+pub const fn descriptor_count(len: usize) -> usize {
+    (len + MAX_DESCRIPTOR_BUFFER_SIZE - 1) / MAX_DESCRIPTOR_BUFFER_SIZE
+}
+
+/// The GDMA controller, split into its channels
+pub struct Gdma {
+    _dma: DMA,
+    pub channel0: Channel,
+    pub channel1: Channel,
+    pub channel2: Channel,
+    pub channel3: Channel,
+    pub channel4: Channel,
+}
+
+impl Gdma {
+    /// Enable the GDMA controller and split it into its channels
+    pub fn new(dma: DMA, peripheral_clock_control: &mut PeripheralClockControl) -> Self {
+        peripheral_clock_control.enable(Peripheral::Gdma);
+
+        dma.misc_conf.modify(|_, w| w.clk_en().set_bit());
+
+        Self {
+            _dma: dma,
+            channel0: Channel { number: 0 },
+            channel1: Channel { number: 1 },
+            channel2: Channel { number: 2 },
+            channel3: Channel { number: 3 },
+            channel4: Channel { number: 4 },
+        }
+    }
+}
+
+/// A GDMA channel
+pub struct Channel {
+    number: u8,
+}
+
+/// Evaluate `$body` with `$reg` bound to the register `$name` of channel
+/// `$number`
+///
+/// Each channel has its own register types in the PAC, so `$body` is expanded
+/// once per channel.
+macro_rules! channel_reg {
+    ($number:expr, $name:ident, |$reg:ident| $body:expr) => {
+        paste! {
+            match $number {
+                0 => { let $reg = &register_block().[<$name _ch0>]; $body }
+                1 => { let $reg = &register_block().[<$name _ch1>]; $body }
+                2 => { let $reg = &register_block().[<$name _ch2>]; $body }
+                3 => { let $reg = &register_block().[<$name _ch3>]; $body }
+                4 => { let $reg = &register_block().[<$name _ch4>]; $body }
+                _ => unreachable!(),
+            }
+        }
+    };
+}
+
+impl Channel {
+    /// Set up the transmit direction to send `data` to `peripheral`
+    ///
+    /// With `circular`, the last descriptor links back to the first one, so
+    /// `data` is sent repeatedly until the transfer is stopped.
+    pub(crate) fn prepare_transmit(
+        &mut self,
+        descriptors: &mut [DmaDescriptor],
+        data: &[u8],
+        circular: bool,
+        peripheral: DmaPeripheral,
+    ) -> Result<(), DmaError> {
+        let last = fill_descriptors(descriptors, data.as_ptr() as *mut u8, data.len(), true)?;
+        if circular {
+            descriptors[last].next = descriptors.as_mut_ptr();
+        }
+
+        let link = descriptors.as_ptr() as u32;
+
+        channel_reg!(self.number, out_conf0, |conf0| {
+            conf0.write(|w| w.out_rst().set_bit());
+            conf0.write(|w| {
+                w.out_eof_mode()
+                    .set_bit()
+                    .outdscr_burst_en()
+                    .set_bit()
+                    .out_data_burst_en()
+                    .set_bit()
+            });
+        });
+        channel_reg!(self.number, out_peri_sel, |peri_sel| {
+            peri_sel.write(|w| unsafe { w.peri_out_sel().bits(peripheral as u8) });
+        });
+        channel_reg!(self.number, out_int_clr, |int_clr| {
+            int_clr.write(|w| {
+                w.out_done()
+                    .set_bit()
+                    .out_eof()
+                    .set_bit()
+                    .out_dscr_err()
+                    .set_bit()
+                    .out_total_eof()
+                    .set_bit()
+            });
+        });
+        channel_reg!(self.number, out_link, |out_link| {
+            out_link.write(|w| unsafe { w.outlink_addr().bits(link) });
+        });
+
+        Ok(())
+    }
+
+    /// Start the prepared transmit transfer
+    pub(crate) fn start_transmit(&mut self) {
+        channel_reg!(self.number, out_link, |out_link| {
+            out_link.modify(|_, w| w.outlink_start().set_bit());
+        });
+    }
+
+    /// Stop an ongoing transmit transfer
+    pub(crate) fn stop_transmit(&mut self) {
+        channel_reg!(self.number, out_link, |out_link| {
+            out_link.modify(|_, w| w.outlink_stop().set_bit());
+        });
+    }
+
+    /// Whether the transmit direction encountered an invalid descriptor
+    pub(crate) fn has_transmit_error(&self) -> bool {
+        channel_reg!(self.number, out_int_raw, |int_raw| {
+            int_raw.read().out_dscr_err().bit_is_set()
+        })
+    }
+
+    /// Set up the receive direction to store data from `peripheral` into
+    /// `buffer`
+    ///
+    /// The length of `buffer` should be a multiple of 4 bytes.
+    pub(crate) fn prepare_receive(
+        &mut self,
+        descriptors: &mut [DmaDescriptor],
+        buffer: &mut [u8],
+        peripheral: DmaPeripheral,
+    ) -> Result<(), DmaError> {
+        fill_descriptors(descriptors, buffer.as_mut_ptr(), buffer.len(), false)?;
+
+        let link = descriptors.as_ptr() as u32;
+
+        channel_reg!(self.number, in_conf0, |conf0| {
+            conf0.write(|w| w.in_rst().set_bit());
+            conf0.write(|w| w.indscr_burst_en().set_bit().in_data_burst_en().set_bit());
+        });
+        channel_reg!(self.number, in_peri_sel, |peri_sel| {
+            peri_sel.write(|w| unsafe { w.peri_in_sel().bits(peripheral as u8) });
+        });
+        channel_reg!(self.number, in_int_clr, |int_clr| {
+            int_clr.write(|w| {
+                w.in_done()
+                    .set_bit()
+                    .in_suc_eof()
+                    .set_bit()
+                    .in_err_eof()
+                    .set_bit()
+                    .in_dscr_err()
+                    .set_bit()
+                    .in_dscr_empty()
+                    .set_bit()
+            });
+        });
+        channel_reg!(self.number, in_link, |in_link| {
+            in_link.write(|w| unsafe { w.inlink_addr().bits(link) });
+        });
+
+        Ok(())
+    }
+
+    /// Start the prepared receive transfer
+    pub(crate) fn start_receive(&mut self) {
+        channel_reg!(self.number, in_link, |in_link| {
+            in_link.modify(|_, w| w.inlink_start().set_bit());
+        });
+    }
+
+    /// Stop an ongoing receive transfer
+    pub(crate) fn stop_receive(&mut self) {
+        channel_reg!(self.number, in_link, |in_link| {
+            in_link.modify(|_, w| w.inlink_stop().set_bit());
+        });
+    }
+
+    /// Reset the receive direction, discarding an ongoing transfer
+    pub(crate) fn reset_receive(&mut self) {
+        channel_reg!(self.number, in_conf0, |conf0| {
+            conf0.modify(|_, w| w.in_rst().set_bit());
+            conf0.modify(|_, w| w.in_rst().clear_bit());
+        });
+    }
+
+    /// Whether the peripheral signalled the end of the received data
+    pub(crate) fn is_receive_done(&self) -> bool {
+        channel_reg!(self.number, in_int_raw, |int_raw| {
+            int_raw.read().in_suc_eof().bit_is_set()
+        })
+    }
+
+    /// Whether the receive direction encountered an invalid descriptor
+    pub(crate) fn has_receive_error(&self) -> bool {
+        channel_reg!(self.number, in_int_raw, |int_raw| {
+            int_raw.read().in_dscr_err().bit_is_set()
+        })
+    }
+}
+
+fn register_block() -> &'static RegisterBlock {
+    unsafe { &*DMA::PTR }
+}
+
+/// Build a descriptor chain for `len` bytes at `buffer`, returning the index
+/// of the last descriptor used
+fn fill_descriptors(
+    descriptors: &mut [DmaDescriptor],
+    buffer: *mut u8,
+    len: usize,
+    transmit: bool,
+) -> Result<usize, DmaError> {
+    if len == 0 {
+        return Err(DmaError::EmptyBuffer);
+    }
+
+    let count = descriptor_count(len);
+    if count > descriptors.len() {
+        return Err(DmaError::TooFewDescriptors);
+    }
+
+    trace!(
+        "GDMA {} descriptors for {} bytes at {}",
+        count,
+        len,
+        buffer as u32
+    );
+
+    let base = descriptors.as_mut_ptr();
+    for (i, descriptor) in descriptors[..count].iter_mut().enumerate() {
+        let offset = i * MAX_DESCRIPTOR_BUFFER_SIZE;
+        let size = (len - offset).min(MAX_DESCRIPTOR_BUFFER_SIZE) as u32;
+        let is_last = i + 1 == count;
+
+        // Transmit descriptors carry the number of valid bytes; receive
+        // descriptors are filled in by the hardware
+        let mut flags = size | DESCRIPTOR_OWNER_DMA;
+        if transmit {
+            flags |= size << DESCRIPTOR_LENGTH_SHIFT;
+            if is_last {
+                flags |= DESCRIPTOR_SUC_EOF;
+            }
+        }
+
+        descriptor.flags = flags;
+        descriptor.buffer = unsafe { buffer.add(offset) };
+        descriptor.next = if is_last {
+            core::ptr::null_mut()
+        } else {
+            unsafe { base.add(i + 1) }
+        };
+    }
+
+    Ok(count - 1)
+}
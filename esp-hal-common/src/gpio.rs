@@ -82,7 +82,65 @@ pub enum AlternateFunction {
     Function5 = 5,
 }
 
-pub trait RTCPin {}
+/// A pin which is connected to the RTC domain, so it can e.g. be used while
+/// the chip is in deep sleep or by the ULP coprocessor
+pub trait RtcPin: Pin {
+    /// Configure the RTC IO_MUX of the pin: enable its input, connect it to
+    /// the RTC domain (`mux`) and select its RTC function `func`
+    #[doc(hidden)]
+    fn rtc_set_config(&mut self, input_enable: bool, mux: bool, func: u8);
+
+    /// The number of the pin in the RTC domain
+    #[doc(hidden)]
+    fn rtc_number(&self) -> u8;
+
+    /// Configure the pull-up and pull-down resistors of the pin in the RTC
+    /// IO_MUX, if it has them
+    #[doc(hidden)]
+    fn rtc_set_pull(&mut self, pull_up: bool, pull_down: bool);
+}
+
+/// A pin which is connected to a channel of one of the ADCs
+pub trait AdcPin: Pin {}
+
+/// A pin which is connected to a channel of the touch sensor
+pub trait TouchPin: Pin {}
+
+/// A pin which can be used as the SDA line of the RTC I2C controller
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+pub trait RtcI2cSda: RtcPin {}
+
+/// A pin which can be used as the SCL line of the RTC I2C controller
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+pub trait RtcI2cScl: RtcPin {}
+
+/// A pin which the IO_MUX can connect directly to the clock signal of SPI2,
+/// bypassing the GPIO matrix
+pub trait IoMuxSpiSck: OutputPin {}
+
+/// A pin which the IO_MUX can connect directly to the MOSI signal of SPI2,
+/// bypassing the GPIO matrix
+pub trait IoMuxSpiMosi: OutputPin {}
+
+/// A pin which the IO_MUX can connect directly to the MISO signal of SPI2,
+/// bypassing the GPIO matrix
+pub trait IoMuxSpiMiso: InputPin {}
+
+/// A pin which the IO_MUX can connect directly to the CS signal of SPI2,
+/// bypassing the GPIO matrix
+pub trait IoMuxSpiCs: OutputPin {}
+
+/// A pin which the IO_MUX can connect to `CLK_OUT1`
+#[cfg(feature = "esp32")]
+pub trait ClkOut1Pin: OutputPin {}
+
+/// A pin which the IO_MUX can connect to `CLK_OUT2`
+#[cfg(feature = "esp32")]
+pub trait ClkOut2Pin: OutputPin {}
+
+/// A pin which the IO_MUX can connect to `CLK_OUT3`
+#[cfg(feature = "esp32")]
+pub trait ClkOut3Pin: OutputPin {}
 
 pub trait AnalogPin {}
 
@@ -117,6 +175,8 @@ pub trait Pin {
 
     fn is_acore_non_maskable_interrupt_set(&self) -> bool;
 
+    /// Latch the state of the pad, so that it is kept while the chip sleeps,
+    /// or release it
     fn enable_hold(&mut self, on: bool);
 }
 
@@ -433,6 +493,11 @@ pub fn connect_high_to_peripheral(signal: InputSignal) {
     });
 }
 
+#[doc(hidden)]
+pub fn set_pad_hold(gpio: u8, hold: bool) {
+    crate::rtc_cntl::rtc::set_gpio_hold(gpio, hold);
+}
+
 // Only for ESP32 in order to workaround errata 3.6
 #[doc(hidden)]
 #[macro_export]
@@ -734,8 +799,8 @@ macro_rules! impl_input {
                 (self.app_cpu_nmi_status_read() & (1 << $bit)) !=0
             }
 
-            fn enable_hold(&mut self, _on: bool) {
-                todo!();
+            fn enable_hold(&mut self, on: bool) {
+                set_pad_hold($pin_num, on);
             }
         }
     };
@@ -882,6 +947,20 @@ macro_rules! impl_output {
                 $pxi { _mode: PhantomData }
             }
 
+            /// Keep the state of the pin, e.g. its output level, while the
+            /// chip is in light or deep sleep
+            ///
+            /// The pin cannot be changed until it is released with
+            /// [Self::unhold].
+            pub fn hold_during_sleep(&mut self) {
+                self.enable_hold(true);
+            }
+
+            /// Release the pin held by [Self::hold_during_sleep]
+            pub fn unhold(&mut self) {
+                self.enable_hold(false);
+            }
+
             pub fn into_alternate_1(self) -> $pxi<Alternate<AF1>> {
                 self.init_output(AlternateFunction::Function1, false);
                 $pxi { _mode: PhantomData }
@@ -1151,6 +1230,14 @@ macro_rules! gpio {
                 _mode: PhantomData<MODE>,
             }
 
+            impl<MODE> $crate::peripherals::Peripheral for $pxi<MODE> {
+                type P = $pxi<MODE>;
+
+                unsafe fn clone_unchecked(&mut self) -> Self::P {
+                    $pxi { _mode: PhantomData }
+                }
+            }
+
             impl_gpio_register_access!($bank, $pxi);
 
             impl_interrupt_status_register_access!($cores, $pxi);
@@ -1240,6 +1327,43 @@ macro_rules! analog {
                     $pxi { _mode: PhantomData }
                 }
             }
+
+            impl<MODE> $crate::gpio::RtcPin for $pxi<MODE> {
+                fn rtc_set_config(&mut self, input_enable: bool, mux: bool, func: u8) {
+                    use crate::pac::RTCIO;
+                    let rtcio = unsafe{ &*RTCIO::ptr() };
+
+                    $crate::gpio::enable_iomux_clk_gate();
+
+                    paste! {
+                        rtcio.$pin_reg.modify(|_,w| unsafe {
+                            w.$fun_ie().bit(input_enable)
+                                .$mux_sel().bit(mux)
+                                .$fun_sel().bits(func)
+                        });
+                    }
+                }
+
+                fn rtc_number(&self) -> u8 {
+                    $pin_num
+                }
+
+                #[allow(unused_variables)]
+                fn rtc_set_pull(&mut self, pull_up: bool, pull_down: bool) {
+                    $(
+                        use crate::pac::RTCIO;
+                        let rtcio = unsafe{ &*RTCIO::ptr() };
+
+                        paste! {
+                            rtcio.$pin_reg.modify(|_,w| {
+                                w
+                                .$rue().bit(pull_up)
+                                .$rde().bit(pull_down)
+                            });
+                        }
+                    )?
+                }
+            }
         )+
     }
 }
@@ -1272,10 +1396,49 @@ macro_rules! analog {
                     $pxi { _mode: PhantomData }
                 }
             }
+
+            impl<MODE> $crate::gpio::RtcPin for $pxi<MODE> {
+                fn rtc_set_config(&mut self, input_enable: bool, _mux: bool, func: u8) {
+                    use crate::pac::IO_MUX;
+
+                    // the RTC pins of the ESP32-C3 have no separate RTC IO_MUX
+                    let io_mux = unsafe{ &*IO_MUX::PTR };
+                    io_mux.gpio[$pin_num].modify(|_,w| unsafe {
+                        w.fun_ie().bit(input_enable).mcu_sel().bits(func)
+                    });
+                }
+
+                fn rtc_number(&self) -> u8 {
+                    $pin_num
+                }
+
+                fn rtc_set_pull(&mut self, pull_up: bool, pull_down: bool) {
+                    use crate::pac::IO_MUX;
+
+                    let io_mux = unsafe{ &*IO_MUX::PTR };
+                    io_mux.gpio[$pin_num].modify(|_,w| {
+                        w.fun_wpu().bit(pull_up).fun_wpd().bit(pull_down)
+                    });
+                }
+            }
         )+
     }
 }
 
+/// Implement the marker traits of the special functions a pin supports, e.g.
+/// [AdcPin] or [RtcPin]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! pin_functions {
+    ($($function:ident: [$($pxi:ident),+ $(,)?],)+) => {
+        $(
+            $(
+                impl<MODE> $crate::gpio::$function for $pxi<MODE> {}
+            )+
+        )+
+    };
+}
+
 pub use analog;
 pub use gpio;
 pub use impl_errata36;
@@ -1284,5 +1447,6 @@ pub use impl_input;
 pub use impl_interrupt_status_register_access;
 pub use impl_output;
 pub use impl_output_wrap;
+pub use pin_functions;
 
 use self::types::{InputSignal, OutputSignal};
@@ -53,6 +53,7 @@ pub enum InputSignal {
     FSPIIO6           = 115,
     FSPIIO7           = 116,
     FSPICS0           = 117,
+    TWAI_RX           = 123,
     SUBSPIQ           = 127,
     SUBSPID           = 128,
     SUBSPIHD          = 129,
@@ -139,6 +140,9 @@ pub enum OutputSignal {
     FSPICS3         = 120,
     FSPICS4         = 121,
     FSPICS5         = 122,
+    TWAI_TX         = 123,
+    TWAI_BUS_OFF_ON = 124,
+    TWAI_CLKOUT     = 125,
     SUBSPICLK       = 126,
     SUBSPIQ         = 127,
     SUBSPID         = 128,
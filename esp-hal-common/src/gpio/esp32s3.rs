@@ -68,10 +68,31 @@ pub enum InputSignal {
     FSPIIO6           = 108,
     FSPIIO7           = 109,
     FSPICS0           = 110,
+    TWAI_RX           = 116,
     SUBSPIQ           = 120,
     SUBSPID           = 121,
     SUBSPIHD          = 122,
     SUBSPIWP          = 123,
+    CAM_DATA_IN0      = 133,
+    CAM_DATA_IN1      = 134,
+    CAM_DATA_IN2      = 135,
+    CAM_DATA_IN3      = 136,
+    CAM_DATA_IN4      = 137,
+    CAM_DATA_IN5      = 138,
+    CAM_DATA_IN6      = 139,
+    CAM_DATA_IN7      = 140,
+    CAM_DATA_IN8      = 141,
+    CAM_DATA_IN9      = 142,
+    CAM_DATA_IN10     = 143,
+    CAM_DATA_IN11     = 144,
+    CAM_DATA_IN12     = 145,
+    CAM_DATA_IN13     = 146,
+    CAM_DATA_IN14     = 147,
+    CAM_DATA_IN15     = 148,
+    CAM_PCLK          = 149,
+    CAM_H_ENABLE      = 150,
+    CAM_H_SYNC        = 151,
+    CAM_V_SYNC        = 152,
     SUBSPID4          = 155,
     SUBSPID5          = 156,
     SUBSPID6          = 157,
@@ -182,6 +203,9 @@ pub enum OutputSignal {
     FSPICS3         = 113,
     FSPICS4         = 114,
     FSPICS5         = 115,
+    TWAI_TX         = 116,
+    TWAI_BUS_OFF_ON = 117,
+    TWAI_CLKOUT     = 118,
     SUBSPICLK       = 119,
     SUBSPIQ         = 120,
     SUBSPID         = 121,
@@ -192,6 +216,29 @@ pub enum OutputSignal {
     FSPIDQS         = 126,
     SPI3_CS2        = 127,
     I2S0O_SD1       = 128,
+    LCD_CS          = 132,
+    LCD_DATA_OUT0   = 133,
+    LCD_DATA_OUT1   = 134,
+    LCD_DATA_OUT2   = 135,
+    LCD_DATA_OUT3   = 136,
+    LCD_DATA_OUT4   = 137,
+    LCD_DATA_OUT5   = 138,
+    LCD_DATA_OUT6   = 139,
+    LCD_DATA_OUT7   = 140,
+    LCD_DATA_OUT8   = 141,
+    LCD_DATA_OUT9   = 142,
+    LCD_DATA_OUT10  = 143,
+    LCD_DATA_OUT11  = 144,
+    LCD_DATA_OUT12  = 145,
+    LCD_DATA_OUT13  = 146,
+    LCD_DATA_OUT14  = 147,
+    LCD_DATA_OUT15  = 148,
+    CAM_CLK         = 149,
+    LCD_H_ENABLE    = 150,
+    LCD_H_SYNC      = 151,
+    LCD_V_SYNC      = 152,
+    LCD_DC          = 153,
+    LCD_PCLK        = 154,
     SUBSPID4        = 155,
     SUBSPID5        = 156,
     SUBSPID6        = 157,
@@ -0,0 +1,292 @@
+//! HMAC accelerator driver
+//!
+//! The HMAC module computes HMAC-SHA256 using a key stored in one of the eFuse
+//! key blocks. The key itself is never readable by software.
+//!
+//! The result can either be returned to the user (upstream mode, key purpose
+//! `HMAC_UP`), or be passed directly to another peripheral (downstream mode):
+//! to the Digital Signature peripheral as the key used to decrypt its
+//! parameters, or to the JTAG interface to re-enable a soft-disabled JTAG.
+//!
+//! # Example
+//!
+//! ```no_run
+//! let mut hmac = Hmac::new(peripherals.HMAC, &mut system.peripheral_clock_control);
+//!
+//! let mut output = [0u8; 32];
+//! hmac.calculate(KeyId::Key0, b"message", &mut output)
+//!     .unwrap();
+//! ```
+
+use crate::{
+    pac::HMAC,
+    system::{Peripheral, PeripheralClockControl},
+};
+
+const SHA256_BLOCK_SIZE: usize = 64;
+const SHA256_LENGTH_SIZE: usize = 8;
+
+// Offsets of the message and result memory, relative to the peripheral base
+const WR_MESSAGE_MEM: usize = 0x080;
+const RD_RESULT_MEM: usize = 0x0c0;
+
+/// HMAC-specific errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The purpose of the selected key does not match the requested operation
+    KeyPurposeMismatch,
+}
+
+/// The purpose of an HMAC calculation
+///
+/// Must match the purpose the eFuse key block was burnt with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HmacPurpose {
+    /// Downstream, the result re-enables JTAG or is used by the DS peripheral
+    ToDsOrJtag = 5,
+    /// Downstream, the result is used to re-enable JTAG
+    ToJtag     = 6,
+    /// Downstream, the result is used by the Digital Signature peripheral
+    ToDs       = 7,
+    /// Upstream, the result is returned to the user
+    ToUser     = 8,
+}
+
+/// eFuse key block holding the HMAC key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeyId {
+    Key0 = 0,
+    Key1 = 1,
+    Key2 = 2,
+    Key3 = 3,
+    Key4 = 4,
+    Key5 = 5,
+}
+
+/// HMAC accelerator
+pub struct Hmac {
+    hmac: HMAC,
+}
+
+impl Hmac {
+    /// Create a new HMAC instance
+    pub fn new(hmac: HMAC, peripheral_clock_control: &mut PeripheralClockControl) -> Self {
+        peripheral_clock_control.enable(Peripheral::Hmac);
+
+        Self { hmac }
+    }
+
+    /// Return the raw interface to the underlying HMAC instance
+    pub fn free(self) -> HMAC {
+        self.hmac
+    }
+
+    /// Calculate the HMAC-SHA256 of `message` using the key in `key_id`
+    ///
+    /// The key block must have been burnt with the `HMAC_UP` purpose.
+    pub fn calculate(
+        &mut self,
+        key_id: KeyId,
+        message: &[u8],
+        output: &mut [u8; 32],
+    ) -> Result<(), Error> {
+        self.configure(HmacPurpose::ToUser, key_id)?;
+
+        // SHA-256 padding; the key block already processed by the hardware
+        // counts towards the message length.
+        let bit_length = ((message.len() + SHA256_BLOCK_SIZE) as u64 * 8).to_be_bytes();
+
+        let chunks = message.chunks_exact(SHA256_BLOCK_SIZE);
+        let remainder = chunks.remainder();
+
+        if message.len() + 1 + SHA256_LENGTH_SIZE <= SHA256_BLOCK_SIZE {
+            // The message and its padding fit into a single block
+            let mut block = [0u8; SHA256_BLOCK_SIZE];
+            block[..remainder.len()].copy_from_slice(remainder);
+            block[remainder.len()] = 0x80;
+            block[SHA256_BLOCK_SIZE - SHA256_LENGTH_SIZE..].copy_from_slice(&bit_length);
+
+            self.write_block(&block);
+            self.one_block();
+        } else {
+            let mut first = true;
+
+            for block in chunks {
+                self.push_block(block, &mut first);
+            }
+
+            if remainder.is_empty() {
+                // A message of whole blocks is padded by the hardware
+                self.end_message();
+            } else {
+                let mut block = [0u8; SHA256_BLOCK_SIZE];
+                block[..remainder.len()].copy_from_slice(remainder);
+                block[remainder.len()] = 0x80;
+
+                if remainder.len() + 1 + SHA256_LENGTH_SIZE <= SHA256_BLOCK_SIZE {
+                    block[SHA256_BLOCK_SIZE - SHA256_LENGTH_SIZE..].copy_from_slice(&bit_length);
+                    self.pad_message();
+                    self.write_block(&block);
+                } else {
+                    // The length does not fit behind the remainder and goes
+                    // into a block of its own
+                    self.push_block(&block, &mut first);
+
+                    let mut block = [0u8; SHA256_BLOCK_SIZE];
+                    block[SHA256_BLOCK_SIZE - SHA256_LENGTH_SIZE..].copy_from_slice(&bit_length);
+                    self.pad_message();
+                    self.write_block(&block);
+                }
+
+                self.one_block();
+            }
+        }
+
+        self.wait_for_idle();
+        self.read_result(output);
+
+        self.hmac.set_result_finish.write(|w| unsafe { w.bits(2) });
+        self.hmac.set_invalidate_ds.write(|w| unsafe { w.bits(1) });
+        self.hmac
+            .set_invalidate_jtag
+            .write(|w| unsafe { w.bits(1) });
+
+        Ok(())
+    }
+
+    /// Calculate an HMAC in downstream mode
+    ///
+    /// The result is not readable; it is handed to the Digital Signature
+    /// peripheral and/or the JTAG interface, depending on `purpose`. The
+    /// result stays valid until [`Hmac::invalidate_downstream`] is called.
+    pub fn configure_downstream(
+        &mut self,
+        purpose: HmacPurpose,
+        key_id: KeyId,
+    ) -> Result<(), Error> {
+        if purpose == HmacPurpose::ToUser {
+            return Err(Error::KeyPurposeMismatch);
+        }
+
+        self.configure(purpose, key_id)?;
+        self.wait_for_idle();
+
+        Ok(())
+    }
+
+    /// Invalidate the downstream results passed to the DS peripheral and JTAG
+    pub fn invalidate_downstream(&mut self) {
+        self.hmac.set_invalidate_ds.write(|w| unsafe { w.bits(1) });
+        self.hmac
+            .set_invalidate_jtag
+            .write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Temporarily re-enable a soft-disabled JTAG interface
+    ///
+    /// `token` is the HMAC-SHA256 of 32 zero bytes, computed offline with the
+    /// key stored in `key_id`. JTAG is only enabled if the token matches the
+    /// value computed by the hardware.
+    pub fn enable_jtag(&mut self, key_id: KeyId, token: &[u8; 32]) -> Result<(), Error> {
+        self.configure(HmacPurpose::ToJtag, key_id)?;
+        self.wait_for_idle();
+
+        self.hmac.soft_jtag_ctrl.write(|w| unsafe { w.bits(1) });
+
+        for word in token.chunks_exact(4) {
+            let word = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+            self.hmac.wr_jtag.write(|w| unsafe { w.bits(word) });
+        }
+
+        Ok(())
+    }
+
+    /// Disable a JTAG interface previously enabled with [`Hmac::enable_jtag`]
+    pub fn disable_jtag(&mut self) {
+        self.hmac
+            .set_invalidate_jtag
+            .write(|w| unsafe { w.bits(1) });
+    }
+
+    fn configure(&mut self, purpose: HmacPurpose, key_id: KeyId) -> Result<(), Error> {
+        self.hmac.set_start.write(|w| unsafe { w.bits(1) });
+        self.hmac
+            .set_para_purpose
+            .write(|w| unsafe { w.bits(purpose as u32) });
+        self.hmac
+            .set_para_key
+            .write(|w| unsafe { w.bits(key_id as u32) });
+        self.hmac.set_para_finish.write(|w| unsafe { w.bits(1) });
+
+        if self.hmac.query_error.read().bits() != 0 {
+            return Err(Error::KeyPurposeMismatch);
+        }
+
+        Ok(())
+    }
+
+    fn wait_for_idle(&self) {
+        while self.hmac.query_busy.read().bits() != 0 {}
+    }
+
+    /// Write a 512-bit message block, announcing it as the continuation of
+    /// the message unless it is the `first` one
+    fn push_block(&mut self, block: &[u8], first: &mut bool) {
+        if !*first {
+            self.next_block();
+        }
+        *first = false;
+
+        self.write_block(block);
+    }
+
+    /// Write a 512-bit message block and let the hardware process it
+    fn write_block(&mut self, block: &[u8]) {
+        self.wait_for_idle();
+
+        let mem = (HMAC::PTR as usize + WR_MESSAGE_MEM) as *mut u32;
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            let word = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+            unsafe { mem.add(i).write_volatile(word) };
+        }
+
+        self.hmac.set_message_one.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Signal that another message block follows
+    fn next_block(&mut self) {
+        self.wait_for_idle();
+        self.hmac.set_message_ing.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Signal that all message blocks have been written and let the hardware
+    /// apply the padding
+    fn end_message(&mut self) {
+        self.wait_for_idle();
+        self.hmac.set_message_end.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Signal that the following blocks hold the software padding
+    fn pad_message(&mut self) {
+        self.wait_for_idle();
+        self.hmac.set_message_pad.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Signal that the last written block completes the padded message
+    fn one_block(&mut self) {
+        self.wait_for_idle();
+        self.hmac.one_block.write(|w| unsafe { w.bits(1) });
+    }
+
+    fn read_result(&self, output: &mut [u8; 32]) {
+        let mem = (HMAC::PTR as usize + RD_RESULT_MEM) as *const u32;
+
+        for (i, chunk) in output.chunks_exact_mut(4).enumerate() {
+            let word = unsafe { mem.add(i).read_volatile() };
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+}
@@ -10,6 +10,7 @@ use crate::{
     clock::Clocks,
     gpio::{InputPin, OutputPin},
     pac::i2c0::{RegisterBlock, COMD},
+    peripherals::{Peripheral, PeripheralRef},
     system::PeripheralClockControl,
     types::{InputSignal, OutputSignal},
 };
@@ -23,16 +24,37 @@ cfg_if::cfg_if! {
 }
 
 /// I2C-specific transmission errors
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
 pub enum Error {
+    /// The data doesn't fit into the FIFO
     ExceedingFifo,
+    /// The addressed device didn't acknowledge
     AckCheckFailed,
+    /// The transmission didn't complete in time
     TimeOut,
+    /// Another master took over the bus
     ArbitrationLost,
+    /// The transmission ended before all commands were executed
     ExecIncomplete,
+    /// The transmission needs more commands than the hardware supports
     CommandNrExceeded,
 }
 
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Error::ExceedingFifo => "data exceeds the FIFO size",
+            Error::AckCheckFailed => "no acknowledgement from the device",
+            Error::TimeOut => "transmission timed out",
+            Error::ArbitrationLost => "bus arbitration lost",
+            Error::ExecIncomplete => "transmission incomplete",
+            Error::CommandNrExceeded => "too many commands",
+        })
+    }
+}
+
 #[cfg(feature = "eh1")]
 impl embedded_hal_1::i2c::Error for Error {
     fn kind(&self) -> embedded_hal_1::i2c::ErrorKind {
@@ -47,12 +69,25 @@ impl embedded_hal_1::i2c::Error for Error {
 }
 
 /// I2C-specific setup errors
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
 pub enum SetupError {
+    /// The bus frequency can't be derived from the source clock
     InvalidClkConfig,
+    /// The peripheral isn't enabled
     PeripheralDisabled,
 }
 
+impl core::fmt::Display for SetupError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            SetupError::InvalidClkConfig => "unsupported bus frequency",
+            SetupError::PeripheralDisabled => "peripheral disabled",
+        })
+    }
+}
+
 /// A generic I2C Command
 enum Command {
     Start,
@@ -133,6 +168,7 @@ impl From<Command> for u16 {
     }
 }
 
+#[derive(Eq, PartialEq, Copy, Clone)]
 enum OperationType {
     Write = 0,
     Read  = 1,
@@ -161,11 +197,11 @@ enum Opcode {
 }
 
 /// I2C peripheral container (I2C)
-pub struct I2C<T> {
-    peripheral: T,
+pub struct I2C<'d, T> {
+    peripheral: PeripheralRef<'d, T>,
 }
 
-impl<T> embedded_hal::blocking::i2c::Read for I2C<T>
+impl<'d, T> embedded_hal::blocking::i2c::Read for I2C<'d, T>
 where
     T: Instance,
 {
@@ -176,7 +212,7 @@ where
     }
 }
 
-impl<T> embedded_hal::blocking::i2c::Write for I2C<T>
+impl<'d, T> embedded_hal::blocking::i2c::Write for I2C<'d, T>
 where
     T: Instance,
 {
@@ -187,7 +223,7 @@ where
     }
 }
 
-impl<T> embedded_hal::blocking::i2c::WriteRead for I2C<T>
+impl<'d, T> embedded_hal::blocking::i2c::WriteRead for I2C<'d, T>
 where
     T: Instance,
 {
@@ -204,12 +240,12 @@ where
 }
 
 #[cfg(feature = "eh1")]
-impl<T> embedded_hal_1::i2c::ErrorType for I2C<T> {
+impl<'d, T> embedded_hal_1::i2c::ErrorType for I2C<'d, T> {
     type Error = Error;
 }
 
 #[cfg(feature = "eh1")]
-impl<T> embedded_hal_1::i2c::blocking::I2c for I2C<T>
+impl<'d, T> embedded_hal_1::i2c::blocking::I2c for I2C<'d, T>
 where
     T: Instance,
 {
@@ -221,11 +257,23 @@ where
         self.peripheral.master_write(address, bytes)
     }
 
-    fn write_iter<B>(&mut self, _address: u8, _bytes: B) -> Result<(), Self::Error>
+    fn write_iter<B>(&mut self, address: u8, bytes: B) -> Result<(), Self::Error>
     where
         B: IntoIterator<Item = u8>,
     {
-        todo!()
+        // The bytes are sent in a single transmission, so they have to fit
+        // into the FIFO together with the address
+        let mut buffer = [0u8; 31];
+        let mut len = 0;
+        for byte in bytes {
+            if len == buffer.len() {
+                return Err(Error::ExceedingFifo);
+            }
+            buffer[len] = byte;
+            len += 1;
+        }
+
+        self.peripheral.master_write(address, &buffer[..len])
     }
 
     fn write_read(
@@ -239,33 +287,226 @@ where
 
     fn write_iter_read<B>(
         &mut self,
-        _address: u8,
-        _bytes: B,
-        _buffer: &mut [u8],
+        address: u8,
+        bytes: B,
+        buffer: &mut [u8],
     ) -> Result<(), Self::Error>
     where
         B: IntoIterator<Item = u8>,
     {
-        todo!()
+        let mut write_buffer = [0u8; 31];
+        let mut len = 0;
+        for byte in bytes {
+            if len == write_buffer.len() {
+                return Err(Error::ExceedingFifo);
+            }
+            write_buffer[len] = byte;
+            len += 1;
+        }
+
+        self.peripheral
+            .master_write_read(address, &write_buffer[..len], buffer)
     }
 
+    /// Execute the operations in order, in a single transmission
+    ///
+    /// The transmission starts with a START condition and ends with a single
+    /// STOP condition. Consecutive operations of the same direction are
+    /// merged, a change of direction is done with a repeated START condition.
     fn transaction<'a>(
         &mut self,
-        _address: u8,
-        _operations: &mut [embedded_hal_1::i2c::blocking::Operation<'a>],
+        address: u8,
+        operations: &mut [embedded_hal_1::i2c::blocking::Operation<'a>],
     ) -> Result<(), Self::Error> {
-        todo!()
+        use embedded_hal_1::i2c::blocking::Operation;
+
+        self.execute_operations(
+            address,
+            operations.iter_mut().map(|operation| match operation {
+                Operation::Read(buffer) => Operation::Read(&mut **buffer),
+                Operation::Write(bytes) => Operation::Write(*bytes),
+            }),
+        )
+    }
+
+    /// Execute the operations in order, like `transaction`
+    fn transaction_iter<'a, O>(&mut self, address: u8, operations: O) -> Result<(), Self::Error>
+    where
+        O: IntoIterator<Item = embedded_hal_1::i2c::blocking::Operation<'a>>,
+    {
+        self.execute_operations(address, operations)
     }
+}
+
+/// Maximum number of read operations in a transaction
+#[cfg(feature = "eh1")]
+const MAX_READ_OPERATIONS: usize = 16;
 
-    fn transaction_iter<'a, O>(&mut self, _address: u8, _operations: O) -> Result<(), Self::Error>
+#[cfg(feature = "eh1")]
+impl<'d, T> I2C<'d, T>
+where
+    T: Instance,
+{
+    /// Execute `operations` in a single transmission, see
+    /// `embedded_hal_1::i2c::blocking::I2c::transaction`
+    ///
+    /// All written and read bytes have to fit into the FIFOs at once.
+    fn execute_operations<'a, O>(&mut self, address: u8, operations: O) -> Result<(), Error>
     where
         O: IntoIterator<Item = embedded_hal_1::i2c::blocking::Operation<'a>>,
     {
-        todo!()
+        use embedded_hal_1::i2c::blocking::Operation;
+
+        let i2c = &mut *self.peripheral;
+        i2c.reset_fifo();
+        i2c.reset_command_list();
+
+        let mut read_buffers: [Option<&'a mut [u8]>; MAX_READ_OPERATIONS] = Default::default();
+
+        {
+            let register_block = i2c.register_block();
+            let mut cmd_iterator = register_block.comd.iter();
+            let mut read_count = 0;
+            let mut tx_len = 0;
+            let mut rx_len = 0;
+            // Direction and number of bytes of the operations since the last
+            // START condition
+            let mut segment: Option<(OperationType, usize)> = None;
+
+            for operation in operations {
+                let kind = match operation {
+                    Operation::Read(_) => OperationType::Read,
+                    Operation::Write(_) => OperationType::Write,
+                };
+
+                if segment.map(|(segment_kind, _)| segment_kind) != Some(kind) {
+                    if let Some((segment_kind, len)) = segment {
+                        add_segment_commands(&mut cmd_iterator, segment_kind, len)?;
+                    }
+
+                    // (repeated) START and the address
+                    tx_len += 1;
+                    if tx_len > 31 {
+                        return Err(Error::ExceedingFifo);
+                    }
+                    cmd_iterator
+                        .next()
+                        .ok_or(Error::CommandNrExceeded)?
+                        .write(|w| unsafe { w.command().bits(Command::Start.into()) });
+                    write_fifo(register_block, address << 1 | kind as u8);
+
+                    segment = Some((kind, 0));
+                }
+
+                let len = match operation {
+                    Operation::Write(bytes) => {
+                        tx_len += bytes.len();
+                        if tx_len > 31 {
+                            return Err(Error::ExceedingFifo);
+                        }
+                        for byte in bytes {
+                            write_fifo(register_block, *byte);
+                        }
+
+                        bytes.len()
+                    }
+                    Operation::Read(buffer) => {
+                        rx_len += buffer.len();
+                        if rx_len > 31 {
+                            return Err(Error::ExceedingFifo);
+                        }
+                        let len = buffer.len();
+                        *read_buffers
+                            .get_mut(read_count)
+                            .ok_or(Error::CommandNrExceeded)? = Some(buffer);
+                        read_count += 1;
+
+                        len
+                    }
+                };
+
+                if let Some((_, segment_len)) = segment.as_mut() {
+                    *segment_len += len;
+                }
+            }
+
+            let (segment_kind, len) = match segment {
+                Some(segment) => segment,
+                // Nothing to transmit
+                None => return Ok(()),
+            };
+            add_segment_commands(&mut cmd_iterator, segment_kind, len)?;
+
+            cmd_iterator
+                .next()
+                .ok_or(Error::CommandNrExceeded)?
+                .write(|w| unsafe { w.command().bits(Command::Stop.into()) });
+        }
+
+        i2c.execute_transmission()?;
+
+        for buffer in read_buffers.iter_mut().flatten() {
+            for byte in buffer.iter_mut() {
+                *byte = read_fifo(i2c.register_block());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Add the commands transferring the address and the `len` bytes of a
+/// segment of a transaction, after its START command
+#[cfg(feature = "eh1")]
+fn add_segment_commands<'a, I>(
+    cmd_iterator: &mut I,
+    kind: OperationType,
+    len: usize,
+) -> Result<(), Error>
+where
+    I: Iterator<Item = &'a COMD>,
+{
+    let mut add = |command: Command| {
+        cmd_iterator
+            .next()
+            .ok_or(Error::CommandNrExceeded)
+            .map(|cmd| cmd.write(|w| unsafe { w.command().bits(command.into()) }))
+    };
+
+    match kind {
+        OperationType::Write => add(Command::Write {
+            ack_exp: Ack::Ack,
+            ack_check_en: true,
+            length: 1 + len as u8,
+        }),
+        OperationType::Read => {
+            add(Command::Write {
+                ack_exp: Ack::Ack,
+                ack_check_en: true,
+                length: 1,
+            })?;
+
+            // All but the last byte are acknowledged, the last one is not, to
+            // end the read
+            if len > 1 {
+                add(Command::Read {
+                    ack_value: Ack::Ack,
+                    length: len as u8 - 1,
+                })?;
+            }
+            if len > 0 {
+                add(Command::Read {
+                    ack_value: Ack::Nack,
+                    length: 1,
+                })?;
+            }
+
+            Ok(())
+        }
     }
 }
 
-impl<T> I2C<T>
+impl<'d, T> I2C<'d, T>
 where
     T: Instance,
 {
@@ -273,37 +514,67 @@ where
     /// This will enable the peripheral but the peripheral won't get
     /// automatically disabled when this gets dropped.
     pub fn new<SDA: OutputPin + InputPin, SCL: OutputPin + InputPin>(
-        i2c: T,
-        mut sda: SDA,
-        mut scl: SCL,
+        i2c: impl Peripheral<P = T> + 'd,
+        sda: impl Peripheral<P = SDA> + 'd,
+        scl: impl Peripheral<P = SCL> + 'd,
+        frequency: HertzU32,
+        peripheral_clock_control: &mut PeripheralClockControl,
+        clocks: &Clocks,
+    ) -> Result<Self, SetupError> {
+        Ok(
+            Self::new_without_pins(i2c, frequency, peripheral_clock_control, clocks)?
+                .with_sda(sda)
+                .with_scl(scl),
+        )
+    }
+
+    /// Create a new I2C instance without any pins connected
+    /// The pins can be connected with [I2C::with_sda] and [I2C::with_scl].
+    pub fn new_without_pins(
+        i2c: impl Peripheral<P = T> + 'd,
         frequency: HertzU32,
         peripheral_clock_control: &mut PeripheralClockControl,
         clocks: &Clocks,
     ) -> Result<Self, SetupError> {
-        enable_peripheral(&i2c, peripheral_clock_control);
+        crate::into_ref!(i2c);
+        enable_peripheral(&*i2c, peripheral_clock_control);
 
         let mut i2c = I2C { peripheral: i2c };
+        i2c.peripheral.setup(frequency, clocks)?;
+
+        Ok(i2c)
+    }
 
+    /// Use `sda` as the data pin
+    pub fn with_sda<SDA: OutputPin + InputPin>(self, sda: impl Peripheral<P = SDA> + 'd) -> Self {
+        crate::into_ref!(sda);
         sda.set_to_open_drain_output()
             .enable_input(true)
             .internal_pull_up(true)
             .connect_peripheral_to_output(OutputSignal::I2CEXT0_SDA)
             .connect_input_to_peripheral(InputSignal::I2CEXT0_SDA);
+        self
+    }
 
+    /// Use `scl` as the clock pin
+    pub fn with_scl<SCL: OutputPin + InputPin>(self, scl: impl Peripheral<P = SCL> + 'd) -> Self {
+        crate::into_ref!(scl);
         scl.set_to_open_drain_output()
             .enable_input(true)
             .internal_pull_up(true)
             .connect_peripheral_to_output(OutputSignal::I2CEXT0_SCL)
             .connect_input_to_peripheral(InputSignal::I2CEXT0_SCL);
-
-        i2c.peripheral.setup(frequency, clocks)?;
-
-        Ok(i2c)
+        self
     }
 
-    /// Return the raw interface to the underlying peripheral
-    pub fn free(self) -> T {
-        self.peripheral
+    /// Change the bus frequency
+    pub fn with_frequency(
+        mut self,
+        frequency: HertzU32,
+        clocks: &Clocks,
+    ) -> Result<Self, SetupError> {
+        self.peripheral.setup(frequency, clocks)?;
+        Ok(self)
     }
 }
 
@@ -485,6 +756,12 @@ pub trait Instance {
         let setup = half_cycle;
         let hold = half_cycle;
 
+        trace!(
+            "I2C frequency {} Hz: half cycle {} source clock cycles",
+            bus_freq.raw(),
+            half_cycle
+        );
+
         #[cfg(not(feature = "esp32"))]
         let scl_low = scl_low as u16 - 1;
 
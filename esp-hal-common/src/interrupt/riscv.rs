@@ -67,6 +67,7 @@ pub enum InterruptKind {
 /// `interrupt3`)
 #[repr(u32)]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CpuInterrupt {
     Interrupt1 = 1,
     Interrupt2,
@@ -220,6 +221,10 @@ pub fn get_status(_core: Cpu) -> u128 {
 #[cfg(feature = "vectored")]
 pub use vectored::*;
 
+#[cfg(feature = "interrupt-trace")]
+#[path = "trace.rs"]
+pub mod trace;
+
 #[cfg(feature = "vectored")]
 mod vectored {
     use procmacros::ram;
@@ -274,6 +279,7 @@ mod vectored {
 
     /// Interrupt Error
     #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub enum Error {
         InvalidInterruptPriority,
     }
@@ -296,6 +302,9 @@ mod vectored {
 
     #[ram]
     unsafe fn handle_interrupts(cpu_intr: CpuInterrupt, context: &mut TrapFrame) {
+        #[cfg(feature = "interrupt-trace")]
+        trace::dispatch_entered(cpu_intr as u32);
+
         let status = get_status(crate::get_core());
 
         // this has no effect on level interrupts, but the interrupt may be an edge one
@@ -309,6 +318,11 @@ mod vectored {
             // Interrupt::try_from can fail if interrupt already de-asserted:
             // silently ignore
             if let Ok(interrupt) = pac::Interrupt::try_from(interrupt_nr as u8) {
+                #[cfg(feature = "interrupt-trace")]
+                trace::traced(cpu_intr as u32, interrupt, || {
+                    handle_interrupt(interrupt, context)
+                });
+                #[cfg(not(feature = "interrupt-trace"))]
                 handle_interrupt(interrupt, context)
             }
             interrupt_mask &= !(1u128 << interrupt_nr);
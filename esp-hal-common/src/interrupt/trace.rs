@@ -0,0 +1,172 @@
+//! Interrupt tracing
+//!
+//! With the `interrupt-trace` feature, the vectored interrupt dispatcher
+//! timestamps the handling of each peripheral interrupt and keeps statistics
+//! per interrupt source:
+//!
+//! - the latency, from the entry into the dispatcher to the start of the
+//!   handler, which includes the handlers dispatched before it
+//! - the duration of the handler
+//!
+//! ```no_run
+//! let stats = interrupt::trace::stats(pac::Interrupt::TG0_T0_LEVEL);
+//! println!("max latency {} ticks, max duration {} ticks", stats.max_latency, stats.max_duration);
+//! ```
+//!
+//! The timestamps are taken from the CPU cycle counter on the Xtensa chips,
+//! and from the SYSTIMER, which counts at [TICKS_PER_SECOND], on the
+//! ESP32-C3, which has no cycle counter. Note that the cycle counter counts
+//! at the current CPU frequency.
+//!
+//! External tracers can be notified about each handler with
+//! [set_hooks].
+
+use core::{
+    cell::RefCell,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU32, Ordering},
+};
+
+use critical_section::Mutex;
+
+use crate::pac::Interrupt;
+
+/// Frequency of the timestamps on the ESP32-C3
+#[cfg(feature = "esp32c3")]
+pub const TICKS_PER_SECOND: u64 = crate::systimer::SystemTimer::TICKS_PER_SECOND;
+
+/// Number of interrupt sources which are traced
+const SOURCES: usize = 128;
+/// Number of dispatcher entry timestamps per core, one per interrupt level or
+/// CPU interrupt
+const ENTRY_SLOTS: usize = 32;
+
+/// Statistics of an interrupt source, in timestamp ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InterruptStats {
+    /// Number of handled interrupts
+    pub count: u32,
+    /// Longest latency
+    pub max_latency: u32,
+    /// Longest handler duration
+    pub max_duration: u32,
+    /// Total duration of the handlers
+    pub total_duration: u64,
+}
+
+/// Functions called around each traced interrupt handler
+#[derive(Clone, Copy)]
+pub struct TraceHooks {
+    /// Called right before the handler
+    pub enter: fn(Interrupt),
+    /// Called right after the handler, with its duration in timestamp ticks
+    pub exit: fn(Interrupt, u32),
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ENTRY: AtomicU32 = AtomicU32::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const CORE_ENTRIES: [AtomicU32; ENTRY_SLOTS] = [ENTRY; ENTRY_SLOTS];
+
+/// Dispatcher entry timestamps, per core and slot. Interrupts only nest at
+/// higher levels, so a slot is never overwritten while it is in use.
+static DISPATCH_ENTRY: [[AtomicU32; ENTRY_SLOTS]; 2] = [CORE_ENTRIES; 2];
+
+static STATS: Mutex<RefCell<[InterruptStats; SOURCES]>> = Mutex::new(RefCell::new(
+    [InterruptStats {
+        count: 0,
+        max_latency: 0,
+        max_duration: 0,
+        total_duration: 0,
+    }; SOURCES],
+));
+
+/// The installed hooks, or null. Only ever points to a `&'static TraceHooks`,
+/// and is read without a critical section by the dispatcher.
+static HOOKS: AtomicPtr<TraceHooks> = AtomicPtr::new(ptr::null_mut());
+
+/// The statistics of `interrupt` since the last [reset_stats]
+pub fn stats(interrupt: Interrupt) -> InterruptStats {
+    critical_section::with(|cs| STATS.borrow_ref(cs)[source(interrupt)])
+}
+
+/// Reset the statistics of all interrupt sources
+pub fn reset_stats() {
+    critical_section::with(|cs| {
+        *STATS.borrow_ref_mut(cs) = [InterruptStats::default(); SOURCES];
+    });
+}
+
+/// Install `hooks` to be called around each interrupt handler, or remove the
+/// installed hooks with `None`
+///
+/// The hooks are called from the interrupt dispatcher, so they have to be
+/// short. They are passed by reference, so that the dispatcher can read both
+/// of them with a single atomic load:
+///
+/// ```no_run
+/// static HOOKS: TraceHooks = TraceHooks {
+///     enter: |interrupt| { /* ... */ },
+///     exit: |interrupt, duration| { /* ... */ },
+/// };
+///
+/// interrupt::trace::set_hooks(Some(&HOOKS));
+/// ```
+pub fn set_hooks(hooks: Option<&'static TraceHooks>) {
+    let hooks = hooks.map_or(ptr::null_mut(), |hooks| hooks as *const _ as *mut _);
+    HOOKS.store(hooks, Ordering::Release);
+}
+
+fn source(interrupt: Interrupt) -> usize {
+    interrupt as usize % SOURCES
+}
+
+#[inline(always)]
+fn timestamp() -> u32 {
+    #[cfg(target_arch = "xtensa")]
+    return xtensa_lx::timer::get_cycle_count();
+
+    #[cfg(target_arch = "riscv32")]
+    return crate::systimer::SystemTimer::now() as u32;
+}
+
+/// Record the entry into the dispatcher of interrupt level or CPU interrupt
+/// `slot`
+#[inline(always)]
+pub(crate) fn dispatch_entered(slot: u32) {
+    DISPATCH_ENTRY[crate::get_core() as usize][slot as usize % ENTRY_SLOTS]
+        .store(timestamp(), Ordering::Relaxed);
+}
+
+/// Run the handler of `interrupt`, dispatched from `slot`, and record its
+/// latency and duration
+#[inline(always)]
+pub(crate) fn traced(slot: u32, interrupt: Interrupt, handler: impl FnOnce()) {
+    // SAFETY: `HOOKS` is either null or points to a `&'static TraceHooks`
+    let hooks = unsafe { HOOKS.load(Ordering::Acquire).as_ref() };
+    if let Some(hooks) = hooks {
+        (hooks.enter)(interrupt);
+    }
+
+    let entry = DISPATCH_ENTRY[crate::get_core() as usize][slot as usize % ENTRY_SLOTS]
+        .load(Ordering::Relaxed);
+    let start = timestamp();
+    handler();
+    let end = timestamp();
+
+    let latency = start.wrapping_sub(entry);
+    let duration = end.wrapping_sub(start);
+
+    critical_section::with(|cs| {
+        let stats = &mut STATS.borrow_ref_mut(cs)[source(interrupt)];
+        stats.count = stats.count.wrapping_add(1);
+        stats.max_latency = stats.max_latency.max(latency);
+        stats.max_duration = stats.max_duration.max(duration);
+        stats.total_duration = stats.total_duration.wrapping_add(duration as u64);
+    });
+
+    if let Some(hooks) = hooks {
+        (hooks.exit)(interrupt, duration);
+    }
+}
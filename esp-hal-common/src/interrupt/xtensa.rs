@@ -11,6 +11,7 @@ use crate::{
 /// `level1_interrupt`)
 #[allow(unused)]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u32)]
 pub enum CpuInterrupt {
     Interrupt0LevelPriority1 = 0,
@@ -183,6 +184,10 @@ unsafe fn core1_interrupt_peripheral() -> *const crate::pac::interrupt_core1::Re
 #[cfg(feature = "vectored")]
 pub use vectored::*;
 
+#[cfg(feature = "interrupt-trace")]
+#[path = "trace.rs"]
+pub mod trace;
+
 #[cfg(feature = "vectored")]
 mod vectored {
     use procmacros::ram;
@@ -191,12 +196,14 @@ mod vectored {
     use crate::get_core;
 
     #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub enum Error {
         InvalidInterrupt,
     }
 
     /// Interrupt priority levels.
     #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     #[repr(u8)]
     pub enum Priority {
         None = 0,
@@ -365,6 +372,9 @@ mod vectored {
 
     #[ram]
     unsafe fn handle_interrupts(level: u32, save_frame: &mut Context) {
+        #[cfg(feature = "interrupt-trace")]
+        trace::dispatch_entered(level);
+
         let cpu_interrupt_mask =
             interrupt::get() & interrupt::get_mask() & CPU_INTERRUPT_LEVELS[level as usize];
 
@@ -394,7 +404,7 @@ mod vectored {
                 loop {
                     let interrupt_nr = interrupt_mask.trailing_zeros();
                     if let Ok(interrupt) = pac::Interrupt::try_from(interrupt_nr as u16) {
-                        handle_interrupt(level, interrupt, save_frame)
+                        dispatch(level, interrupt, save_frame)
                     } else {
                         break;
                     }
@@ -411,12 +421,22 @@ mod vectored {
                 // Interrupt::try_from can fail if interrupt already de-asserted:
                 // silently ignore
                 if let Ok(interrupt) = pac::Interrupt::try_from(interrupt_nr as u16) {
-                    handle_interrupt(level, interrupt, save_frame);
+                    dispatch(level, interrupt, save_frame);
                 }
             }
         }
     }
 
+    #[ram]
+    unsafe fn dispatch(level: u32, interrupt: Interrupt, save_frame: &mut Context) {
+        #[cfg(feature = "interrupt-trace")]
+        trace::traced(level, interrupt, || {
+            handle_interrupt(level, interrupt, save_frame)
+        });
+        #[cfg(not(feature = "interrupt-trace"))]
+        handle_interrupt(level, interrupt, save_frame)
+    }
+
     #[ram]
     unsafe fn handle_interrupt(level: u32, interrupt: Interrupt, save_frame: &mut Context) {
         extern "C" {
@@ -0,0 +1,343 @@
+//! Camera controller of the LCD_CAM peripheral
+//!
+//! The camera controller captures from DVP (parallel) camera sensors. Pixel
+//! data is sampled on the pixel clock while HREF is active, and a frame ends
+//! with the next VSYNC pulse. Frames are written to a user buffer with GDMA.
+//!
+//! Most sensors need a master clock (XCLK), which the controller can provide
+//! on a pin of choice.
+//!
+//! # Example
+//!
+//! ```no_run
+//! let mut camera = Camera::new(
+//!     lcd_cam.cam,
+//!     gdma.channel1,
+//!     unsafe { &mut DESCRIPTORS },
+//!     RxEightBits::new(d0, d1, d2, d3, d4, d5, d6, d7),
+//!     20u32.MHz(),
+//!     CameraConfig::default(),
+//! )
+//! .with_master_clock(xclk)
+//! .with_ctrl_pins(vsync, href, pclk);
+//!
+//! let len = camera.capture(unsafe { &mut FRAME }).unwrap();
+//! ```
+
+use fugit::HertzU32;
+
+use super::{register_block, Cam, CLOCK_SOURCE_HZ, CLOCK_SOURCE_PLL_F160M};
+use crate::{
+    gdma::{descriptor_count, Channel, DmaDescriptor, DmaError, DmaPeripheral},
+    gpio::{connect_high_to_peripheral, InputPin, OutputPin},
+    types::{InputSignal, OutputSignal},
+};
+
+/// Data pins of the camera bus
+pub trait RxPins {
+    /// Width of the bus, in bits
+    const BUS_WIDTH: u8;
+
+    #[doc(hidden)]
+    fn configure(&mut self);
+}
+
+macro_rules! rx_pins {
+    ($doc:literal, $name:ident, $width:literal, $( $pin:ident : $field:ident : $signal:ident ),+) => {
+        #[doc = $doc]
+        pub struct $name<$($pin),+> {
+            $( $field: $pin, )+
+        }
+
+        impl<$($pin: InputPin),+> $name<$($pin),+> {
+            #[allow(clippy::too_many_arguments)]
+            pub fn new($($field: $pin),+) -> Self {
+                Self { $($field),+ }
+            }
+        }
+
+        impl<$($pin: InputPin),+> RxPins for $name<$($pin),+> {
+            const BUS_WIDTH: u8 = $width;
+
+            fn configure(&mut self) {
+                $(
+                    self.$field
+                        .set_to_input()
+                        .connect_input_to_peripheral(InputSignal::$signal);
+                )+
+            }
+        }
+    };
+}
+
+rx_pins!(
+    "Data pins of an 8-bit camera bus",
+    RxEightBits,
+    8,
+    P0: pin_0: CAM_DATA_IN0,
+    P1: pin_1: CAM_DATA_IN1,
+    P2: pin_2: CAM_DATA_IN2,
+    P3: pin_3: CAM_DATA_IN3,
+    P4: pin_4: CAM_DATA_IN4,
+    P5: pin_5: CAM_DATA_IN5,
+    P6: pin_6: CAM_DATA_IN6,
+    P7: pin_7: CAM_DATA_IN7
+);
+
+rx_pins!(
+    "Data pins of a 16-bit camera bus",
+    RxSixteenBits,
+    16,
+    P0: pin_0: CAM_DATA_IN0,
+    P1: pin_1: CAM_DATA_IN1,
+    P2: pin_2: CAM_DATA_IN2,
+    P3: pin_3: CAM_DATA_IN3,
+    P4: pin_4: CAM_DATA_IN4,
+    P5: pin_5: CAM_DATA_IN5,
+    P6: pin_6: CAM_DATA_IN6,
+    P7: pin_7: CAM_DATA_IN7,
+    P8: pin_8: CAM_DATA_IN8,
+    P9: pin_9: CAM_DATA_IN9,
+    P10: pin_10: CAM_DATA_IN10,
+    P11: pin_11: CAM_DATA_IN11,
+    P12: pin_12: CAM_DATA_IN12,
+    P13: pin_13: CAM_DATA_IN13,
+    P14: pin_14: CAM_DATA_IN14,
+    P15: pin_15: CAM_DATA_IN15
+);
+
+/// Configuration of the camera interface
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CameraConfig {
+    /// VSYNC is active low
+    pub vsync_inverted: bool,
+    /// HREF is active low
+    pub href_inverted: bool,
+    /// Sample the data lines on the falling instead of the rising edge of the
+    /// pixel clock
+    pub pclk_inverted: bool,
+    /// Swap the two bytes of each 16-bit word
+    pub swap_bytes: bool,
+}
+
+/// Camera controller capturing DVP frames
+pub struct Camera<'d, P> {
+    channel: Channel,
+    descriptors: &'d mut [DmaDescriptor],
+    buffer: Option<&'d mut [u8]>,
+    capture_len: usize,
+    _pins: P,
+}
+
+impl<'d, P> Camera<'d, P>
+where
+    P: RxPins,
+{
+    /// Create a new camera interface, generating a master clock of
+    /// (approximately) `master_clock`
+    ///
+    /// `descriptors` must be large enough for a frame buffer, see
+    /// [descriptor_count].
+    pub fn new(
+        _cam: Cam,
+        channel: Channel,
+        descriptors: &'d mut [DmaDescriptor],
+        mut pins: P,
+        master_clock: HertzU32,
+        config: CameraConfig,
+    ) -> Self {
+        let regs = register_block();
+
+        // The master clock only has an integer divider of at least 2
+        let divider = (CLOCK_SOURCE_HZ / master_clock.raw().max(1)).clamp(2, 255);
+
+        // A frame ends with the VSYNC pulse instead of after a fixed length
+        regs.cam_ctrl.write(|w| unsafe {
+            w.cam_clk_sel()
+                .bits(CLOCK_SOURCE_PLL_F160M as u8)
+                .cam_clkm_div_num()
+                .bits(divider as u8)
+                .cam_vs_eof_en()
+                .set_bit()
+                .cam_byte_order()
+                .bit(config.swap_bytes)
+        });
+        regs.cam_ctrl1.write(|w| {
+            w.cam_vh_de_mode_en()
+                .set_bit()
+                .cam_2byte_en()
+                .bit(P::BUS_WIDTH == 16)
+                .cam_vsync_inv()
+                .bit(config.vsync_inverted)
+                .cam_de_inv()
+                .bit(config.href_inverted)
+                .cam_clk_inv()
+                .bit(config.pclk_inverted)
+        });
+        regs.cam_ctrl.modify(|_, w| w.cam_update().set_bit());
+
+        pins.configure();
+
+        Self {
+            channel,
+            descriptors,
+            buffer: None,
+            capture_len: 0,
+            _pins: pins,
+        }
+    }
+
+    /// Output the master clock on `xclk`
+    pub fn with_master_clock<XCLK: OutputPin>(self, mut xclk: XCLK) -> Self {
+        xclk.set_to_push_pull_output()
+            .connect_peripheral_to_output(OutputSignal::CAM_CLK);
+        self
+    }
+
+    /// Use `vsync`, `href` and `pclk` as the sync and pixel clock lines
+    pub fn with_ctrl_pins<VSYNC: InputPin, HREF: InputPin, PCLK: InputPin>(
+        self,
+        mut vsync: VSYNC,
+        mut href: HREF,
+        mut pclk: PCLK,
+    ) -> Self {
+        vsync
+            .set_to_input()
+            .connect_input_to_peripheral(InputSignal::CAM_V_SYNC);
+        href.set_to_input()
+            .connect_input_to_peripheral(InputSignal::CAM_H_ENABLE);
+        pclk.set_to_input()
+            .connect_input_to_peripheral(InputSignal::CAM_PCLK);
+
+        // DVP sensors have no separate HSYNC line, HREF gates the data
+        connect_high_to_peripheral(InputSignal::CAM_H_SYNC);
+
+        self
+    }
+
+    /// Capture a single frame into `buffer`, returning the number of bytes
+    /// received
+    ///
+    /// The length of `buffer` should be a multiple of 4 bytes.
+    pub fn capture(&mut self, buffer: &mut [u8]) -> Result<usize, DmaError> {
+        // A capture started with `start_capture` is abandoned
+        self.buffer = None;
+        self.start(buffer)?;
+
+        while !self.is_frame_complete() {}
+
+        self.stop()
+    }
+
+    /// Start capturing the next frame into `buffer`, without waiting for it
+    ///
+    /// Use [Camera::is_frame_complete] or the frame-complete interrupt to find
+    /// out when the frame has been received, then call
+    /// [Camera::finish_capture] to get the buffer back.
+    pub fn start_capture(&mut self, buffer: &'d mut [u8]) -> Result<(), DmaError> {
+        self.start(buffer)?;
+        self.buffer = Some(buffer);
+
+        Ok(())
+    }
+
+    /// Whether the frame being captured is complete, or the transfer failed
+    pub fn is_frame_complete(&self) -> bool {
+        self.channel.is_receive_done() || self.channel.has_receive_error()
+    }
+
+    /// Stop the capture started by [Camera::start_capture], returning the part
+    /// of the buffer holding the received frame
+    ///
+    /// Returns `None` if no capture was started with [Camera::start_capture].
+    pub fn finish_capture(&mut self) -> Option<Result<&'d mut [u8], DmaError>> {
+        let buffer = self.buffer.take()?;
+
+        Some(self.stop().map(|len| &mut buffer[..len]))
+    }
+
+    /// Listen for the frame-complete (VSYNC) interrupt
+    ///
+    /// The interrupt is raised as LCD_CAM.
+    pub fn listen_frame_complete(&mut self) {
+        register_block()
+            .lc_dma_int_ena
+            .modify(|_, w| w.cam_vsync_int_ena().set_bit());
+    }
+
+    /// Stop listening for the frame-complete (VSYNC) interrupt
+    pub fn unlisten_frame_complete(&mut self) {
+        register_block()
+            .lc_dma_int_ena
+            .modify(|_, w| w.cam_vsync_int_ena().clear_bit());
+    }
+
+    /// Whether the frame-complete (VSYNC) interrupt is pending
+    pub fn is_frame_complete_interrupt_set(&self) -> bool {
+        register_block()
+            .lc_dma_int_st
+            .read()
+            .cam_vsync_int_st()
+            .bit_is_set()
+    }
+
+    /// Clear the frame-complete (VSYNC) interrupt
+    pub fn reset_frame_complete_interrupt(&mut self) {
+        register_block()
+            .lc_dma_int_clr
+            .write(|w| w.cam_vsync_int_clr().set_bit());
+    }
+
+    fn start(&mut self, buffer: &mut [u8]) -> Result<(), DmaError> {
+        let regs = register_block();
+
+        self.channel
+            .prepare_receive(self.descriptors, buffer, DmaPeripheral::LcdCam)?;
+        self.capture_len = buffer.len();
+
+        regs.cam_ctrl1
+            .modify(|_, w| w.cam_reset().set_bit().cam_afifo_reset().set_bit());
+        regs.cam_ctrl1
+            .modify(|_, w| w.cam_reset().clear_bit().cam_afifo_reset().clear_bit());
+
+        self.channel.start_receive();
+
+        regs.cam_ctrl1.modify(|_, w| w.cam_start().set_bit());
+        regs.cam_ctrl.modify(|_, w| w.cam_update().set_bit());
+
+        Ok(())
+    }
+
+    /// Stop capturing and return the number of bytes received
+    fn stop(&mut self) -> Result<usize, DmaError> {
+        stop_capture();
+        self.channel.stop_receive();
+
+        if self.channel.has_receive_error() {
+            return Err(DmaError::DescriptorError);
+        }
+
+        // The hardware stores the number of bytes written in each descriptor
+        Ok(self.descriptors[..descriptor_count(self.capture_len)]
+            .iter()
+            .map(DmaDescriptor::len)
+            .sum())
+    }
+}
+
+impl<'d, P> Drop for Camera<'d, P> {
+    fn drop(&mut self) {
+        // The DMA must not write into a buffer handed to `start_capture` once
+        // the borrow has ended
+        stop_capture();
+        self.channel.reset_receive();
+    }
+}
+
+fn stop_capture() {
+    let regs = register_block();
+
+    regs.cam_ctrl1.modify(|_, w| w.cam_start().clear_bit());
+    regs.cam_ctrl.modify(|_, w| w.cam_update().set_bit());
+}
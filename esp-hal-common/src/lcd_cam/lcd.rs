@@ -0,0 +1,577 @@
+//! LCD controller of the LCD_CAM peripheral
+//!
+//! Two modes are supported:
+//! - [I8080]: the Intel 8080 parallel interface, used by displays with their
+//!   own frame memory. Commands and pixel data are sent on demand, optionally
+//!   synchronized to the display's tear-effect output.
+//! - [Rgb]: the RGB (DPI) interface, used by displays without frame memory. A
+//!   frame buffer is streamed continuously, together with the pixel clock and
+//!   the HSYNC, VSYNC and DE signals.
+//!
+//! Data is 8 or 16 bits wide ([TxEightBits], [TxSixteenBits]) and transferred
+//! with GDMA.
+
+use fugit::HertzU32;
+
+use super::{clock_dividers, register_block, Lcd, CLOCK_SOURCE_PLL_F160M};
+use crate::{
+    gdma::{Channel, DmaDescriptor, DmaError, DmaPeripheral},
+    gpio::{InputPin, OutputPin},
+    rom::esp_rom_delay_us,
+    types::OutputSignal,
+};
+
+// Widths of the RGB timing fields of LCD_CTRL, LCD_CTRL1 and LCD_CTRL2, as
+// the largest value each can hold (the fields store the value minus one)
+const MAX_TOTAL_WIDTH: u32 = 1 << 12;
+const MAX_HORIZONTAL_BLANK: u32 = 1 << 11;
+const MAX_TOTAL_HEIGHT: u32 = 1 << 10;
+const MAX_VERTICAL_BLANK: u32 = 1 << 8;
+const MAX_SYNC_PULSE_WIDTH: u32 = 1 << 7;
+
+/// LCD-specific errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The active area or a sync pulse is empty, or the timing does not fit
+    /// into the timing registers
+    InvalidTiming,
+}
+
+/// Data pins of the LCD bus
+pub trait TxPins {
+    /// Width of the bus, in bits
+    const BUS_WIDTH: u8;
+
+    #[doc(hidden)]
+    fn configure(&mut self);
+}
+
+macro_rules! tx_pins {
+    ($doc:literal, $name:ident, $width:literal, $( $pin:ident : $field:ident : $signal:ident ),+) => {
+        #[doc = $doc]
+        pub struct $name<$($pin),+> {
+            $( $field: $pin, )+
+        }
+
+        impl<$($pin: OutputPin),+> $name<$($pin),+> {
+            #[allow(clippy::too_many_arguments)]
+            pub fn new($($field: $pin),+) -> Self {
+                Self { $($field),+ }
+            }
+        }
+
+        impl<$($pin: OutputPin),+> TxPins for $name<$($pin),+> {
+            const BUS_WIDTH: u8 = $width;
+
+            fn configure(&mut self) {
+                $(
+                    self.$field
+                        .set_to_push_pull_output()
+                        .connect_peripheral_to_output(OutputSignal::$signal);
+                )+
+            }
+        }
+    };
+}
+
+tx_pins!(
+    "Data pins of an 8-bit bus",
+    TxEightBits,
+    8,
+    P0: pin_0: LCD_DATA_OUT0,
+    P1: pin_1: LCD_DATA_OUT1,
+    P2: pin_2: LCD_DATA_OUT2,
+    P3: pin_3: LCD_DATA_OUT3,
+    P4: pin_4: LCD_DATA_OUT4,
+    P5: pin_5: LCD_DATA_OUT5,
+    P6: pin_6: LCD_DATA_OUT6,
+    P7: pin_7: LCD_DATA_OUT7
+);
+
+tx_pins!(
+    "Data pins of a 16-bit bus",
+    TxSixteenBits,
+    16,
+    P0: pin_0: LCD_DATA_OUT0,
+    P1: pin_1: LCD_DATA_OUT1,
+    P2: pin_2: LCD_DATA_OUT2,
+    P3: pin_3: LCD_DATA_OUT3,
+    P4: pin_4: LCD_DATA_OUT4,
+    P5: pin_5: LCD_DATA_OUT5,
+    P6: pin_6: LCD_DATA_OUT6,
+    P7: pin_7: LCD_DATA_OUT7,
+    P8: pin_8: LCD_DATA_OUT8,
+    P9: pin_9: LCD_DATA_OUT9,
+    P10: pin_10: LCD_DATA_OUT10,
+    P11: pin_11: LCD_DATA_OUT11,
+    P12: pin_12: LCD_DATA_OUT12,
+    P13: pin_13: LCD_DATA_OUT13,
+    P14: pin_14: LCD_DATA_OUT14,
+    P15: pin_15: LCD_DATA_OUT15
+);
+
+/// Command phase of an i8080 transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Command {
+    /// No command, only data
+    None,
+    /// A single command word
+    One(u16),
+    /// Two command words, sent in consecutive cycles
+    Two(u16, u16),
+}
+
+/// Configuration of the i8080 interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct I8080Config {
+    /// Level of the DC line while idle
+    pub dc_idle_level: bool,
+    /// Level of the DC line while sending a command
+    pub dc_cmd_level: bool,
+    /// Level of the DC line while sending data
+    pub dc_data_level: bool,
+    /// Level of the WR (pixel clock) line while idle
+    pub clock_idle_level: bool,
+    /// Change the data lines on the falling instead of the rising edge of WR
+    pub clock_active_negative_edge: bool,
+}
+
+impl Default for I8080Config {
+    fn default() -> Self {
+        Self {
+            dc_idle_level: false,
+            dc_cmd_level: false,
+            dc_data_level: true,
+            clock_idle_level: false,
+            clock_active_negative_edge: false,
+        }
+    }
+}
+
+/// LCD controller in i8080 mode
+pub struct I8080<'d, P> {
+    channel: Channel,
+    descriptors: &'d mut [DmaDescriptor],
+    _pins: P,
+}
+
+impl<'d, P> I8080<'d, P>
+where
+    P: TxPins,
+{
+    /// Create a new i8080 interface clocked at (approximately) `frequency`
+    ///
+    /// `descriptors` must be large enough for the longest transfer, see
+    /// [crate::gdma::descriptor_count].
+    pub fn new(
+        lcd: Lcd,
+        channel: Channel,
+        descriptors: &'d mut [DmaDescriptor],
+        mut pins: P,
+        frequency: HertzU32,
+        config: I8080Config,
+    ) -> Self {
+        let _ = lcd;
+        let regs = register_block();
+
+        configure_clock(
+            frequency,
+            config.clock_idle_level,
+            config.clock_active_negative_edge,
+        );
+
+        // The DC line levels are programmed relative to the idle level
+        regs.lcd_misc.write(|w| {
+            w.lcd_cd_idle_edge()
+                .bit(config.dc_idle_level)
+                .lcd_cd_cmd_set()
+                .bit(config.dc_cmd_level != config.dc_idle_level)
+                .lcd_cd_data_set()
+                .bit(config.dc_data_level != config.dc_idle_level)
+        });
+
+        regs.lcd_ctrl.write(|w| w.lcd_rgb_mode_en().clear_bit());
+        write_user_config::<P>();
+
+        pins.configure();
+
+        Self {
+            channel,
+            descriptors,
+            _pins: pins,
+        }
+    }
+
+    /// Use `cs` as the chip select line, which is active (low) during
+    /// transfers
+    pub fn with_cs<CS: OutputPin>(self, mut cs: CS) -> Self {
+        cs.set_to_push_pull_output()
+            .connect_peripheral_to_output(OutputSignal::LCD_CS);
+        self
+    }
+
+    /// Use `dc` as the data/command line and `wr` as the write strobe
+    pub fn with_ctrl_pins<DC: OutputPin, WR: OutputPin>(self, mut dc: DC, mut wr: WR) -> Self {
+        dc.set_to_push_pull_output()
+            .connect_peripheral_to_output(OutputSignal::LCD_DC);
+        wr.set_to_push_pull_output()
+            .connect_peripheral_to_output(OutputSignal::LCD_PCLK);
+        self
+    }
+
+    /// Send `cmd` followed by `data`, and wait until the transfer is complete
+    pub fn send(&mut self, cmd: Command, data: &[u8]) -> Result<(), DmaError> {
+        let regs = register_block();
+
+        regs.lcd_misc.modify(|_, w| w.lcd_afifo_reset().set_bit());
+
+        let cmd_value = match cmd {
+            Command::None => None,
+            Command::One(value) => Some(value as u32),
+            Command::Two(first, second) => Some(first as u32 | (second as u32) << 16),
+        };
+        if let Some(value) = cmd_value {
+            regs.lcd_cmd_val
+                .write(|w| unsafe { w.lcd_cmd_value().bits(value) });
+        }
+
+        if !data.is_empty() {
+            self.channel
+                .prepare_transmit(self.descriptors, data, false, DmaPeripheral::LcdCam)?;
+            self.channel.start_transmit();
+
+            // Give the DMA time to fill the LCD FIFO before starting
+            unsafe { esp_rom_delay_us(1) };
+        } else if cmd == Command::None {
+            return Ok(());
+        }
+
+        regs.lc_dma_int_clr
+            .write(|w| w.lcd_trans_done_int_clr().set_bit());
+        regs.lcd_user.modify(|_, w| {
+            w.lcd_cmd()
+                .bit(cmd_value.is_some())
+                .lcd_cmd_2_cycle_en()
+                .bit(matches!(cmd, Command::Two(..)))
+                .lcd_dout()
+                .bit(!data.is_empty())
+                .lcd_update()
+                .set_bit()
+        });
+        regs.lcd_user
+            .modify(|_, w| w.lcd_update().clear_bit().lcd_start().set_bit());
+
+        while regs
+            .lc_dma_int_raw
+            .read()
+            .lcd_trans_done_int_raw()
+            .bit_is_clear()
+        {
+            if self.channel.has_transmit_error() {
+                self.channel.stop_transmit();
+                return Err(DmaError::DescriptorError);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wait for the start of a tear-effect pulse on `te`, then send `cmd`
+    /// followed by `data`
+    ///
+    /// The display raises its TE output while it is not reading its frame
+    /// memory, so writing a frame right after the rising edge avoids tearing.
+    pub fn send_synchronized<TE: InputPin>(
+        &mut self,
+        te: &TE,
+        cmd: Command,
+        data: &[u8],
+    ) -> Result<(), DmaError> {
+        while te.is_input_high() {}
+        while !te.is_input_high() {}
+
+        self.send(cmd, data)
+    }
+}
+
+/// Horizontal and vertical timing of an RGB display, in pixel clock cycles
+/// and lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RgbTiming {
+    pub horizontal_active_width: u16,
+    pub hsync_pulse_width: u16,
+    pub hsync_back_porch: u16,
+    pub hsync_front_porch: u16,
+    pub vertical_active_height: u16,
+    pub vsync_pulse_width: u16,
+    pub vsync_back_porch: u16,
+    pub vsync_front_porch: u16,
+}
+
+/// Configuration of the RGB interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RgbConfig {
+    pub timing: RgbTiming,
+    /// Level of HSYNC outside of the sync pulse
+    pub hsync_idle_level: bool,
+    /// Level of VSYNC outside of the sync pulse
+    pub vsync_idle_level: bool,
+    /// Level of DE outside of the active area
+    pub de_idle_level: bool,
+    /// Level of the pixel clock while idle
+    pub clock_idle_level: bool,
+    /// Change the data lines on the falling instead of the rising edge of the
+    /// pixel clock
+    pub clock_active_negative_edge: bool,
+}
+
+/// LCD controller in RGB mode
+pub struct Rgb<'d, P> {
+    channel: Channel,
+    descriptors: &'d mut [DmaDescriptor],
+    _pins: P,
+}
+
+impl<'d, P> Rgb<'d, P>
+where
+    P: TxPins,
+{
+    /// Create a new RGB interface with a pixel clock of (approximately)
+    /// `frequency`
+    ///
+    /// `descriptors` must be large enough for the frame buffer, see
+    /// [crate::gdma::descriptor_count].
+    pub fn new(
+        lcd: Lcd,
+        channel: Channel,
+        descriptors: &'d mut [DmaDescriptor],
+        mut pins: P,
+        frequency: HertzU32,
+        config: RgbConfig,
+    ) -> Result<Self, Error> {
+        let _ = lcd;
+        let regs = register_block();
+        let timing = config.timing;
+
+        let hsync_width = timing.hsync_pulse_width as u32;
+        let hsync_blank = hsync_width + timing.hsync_back_porch as u32;
+        let active_width = timing.horizontal_active_width as u32;
+        let total_width = hsync_blank + active_width + timing.hsync_front_porch as u32;
+
+        let vsync_width = timing.vsync_pulse_width as u32;
+        let vsync_blank = vsync_width + timing.vsync_back_porch as u32;
+        let active_height = timing.vertical_active_height as u32;
+        let total_height = vsync_blank + active_height + timing.vsync_front_porch as u32;
+
+        // The registers hold all of these values minus one
+        if active_width == 0
+            || total_width > MAX_TOTAL_WIDTH
+            || hsync_blank > MAX_HORIZONTAL_BLANK
+            || !(1..=MAX_SYNC_PULSE_WIDTH).contains(&hsync_width)
+            || active_height == 0
+            || total_height > MAX_TOTAL_HEIGHT
+            || vsync_blank > MAX_VERTICAL_BLANK
+            || !(1..=MAX_SYNC_PULSE_WIDTH).contains(&vsync_width)
+        {
+            return Err(Error::InvalidTiming);
+        }
+
+        configure_clock(
+            frequency,
+            config.clock_idle_level,
+            config.clock_active_negative_edge,
+        );
+
+        regs.lcd_ctrl.write(|w| unsafe {
+            w.lcd_rgb_mode_en()
+                .set_bit()
+                .lcd_vt_height()
+                .bits((total_height - 1) as u16)
+                .lcd_va_height()
+                .bits((active_height - 1) as u16)
+                .lcd_hb_front()
+                .bits((hsync_blank - 1) as u16)
+        });
+        regs.lcd_ctrl1.write(|w| unsafe {
+            w.lcd_ht_width()
+                .bits((total_width - 1) as u16)
+                .lcd_ha_width()
+                .bits((active_width - 1) as u16)
+                .lcd_vb_front()
+                .bits((vsync_blank - 1) as u8)
+        });
+        regs.lcd_ctrl2.write(|w| unsafe {
+            w.lcd_hsync_width()
+                .bits((hsync_width - 1) as u8)
+                .lcd_hs_blank_en()
+                .set_bit()
+                .lcd_vsync_width()
+                .bits((vsync_width - 1) as u8)
+                .lcd_hsync_idle_pol()
+                .bit(config.hsync_idle_level)
+                .lcd_vsync_idle_pol()
+                .bit(config.vsync_idle_level)
+                .lcd_de_idle_pol()
+                .bit(config.de_idle_level)
+        });
+
+        // Start the next frame automatically after each one
+        regs.lcd_misc.write(|w| w.lcd_next_frame_en().set_bit());
+        write_user_config::<P>();
+
+        pins.configure();
+
+        Ok(Self {
+            channel,
+            descriptors,
+            _pins: pins,
+        })
+    }
+
+    /// Use `pclk`, `hsync` and `vsync` as the pixel clock and sync lines
+    pub fn with_ctrl_pins<PCLK: OutputPin, HSYNC: OutputPin, VSYNC: OutputPin>(
+        self,
+        mut pclk: PCLK,
+        mut hsync: HSYNC,
+        mut vsync: VSYNC,
+    ) -> Self {
+        pclk.set_to_push_pull_output()
+            .connect_peripheral_to_output(OutputSignal::LCD_PCLK);
+        hsync
+            .set_to_push_pull_output()
+            .connect_peripheral_to_output(OutputSignal::LCD_H_SYNC);
+        vsync
+            .set_to_push_pull_output()
+            .connect_peripheral_to_output(OutputSignal::LCD_V_SYNC);
+        self
+    }
+
+    /// Use `de` as the data enable line
+    pub fn with_de<DE: OutputPin>(self, mut de: DE) -> Self {
+        de.set_to_push_pull_output()
+            .connect_peripheral_to_output(OutputSignal::LCD_H_ENABLE);
+        self
+    }
+
+    /// Start streaming `framebuffer` to the display, repeating it every frame
+    /// until the returned transfer is stopped or dropped
+    ///
+    /// The frame buffer stays borrowed for as long as the transfer runs.
+    pub fn start<'t>(
+        &'t mut self,
+        framebuffer: &'t [u8],
+    ) -> Result<RgbTransfer<'t, 'd, P>, DmaError> {
+        let regs = register_block();
+
+        regs.lcd_misc.modify(|_, w| w.lcd_afifo_reset().set_bit());
+
+        self.channel.prepare_transmit(
+            self.descriptors,
+            framebuffer,
+            true,
+            DmaPeripheral::LcdCam,
+        )?;
+        self.channel.start_transmit();
+
+        // Give the DMA time to fill the LCD FIFO before starting
+        unsafe { esp_rom_delay_us(1) };
+
+        regs.lcd_user
+            .modify(|_, w| w.lcd_dout().set_bit().lcd_update().set_bit());
+        regs.lcd_user
+            .modify(|_, w| w.lcd_update().clear_bit().lcd_start().set_bit());
+
+        Ok(RgbTransfer {
+            rgb: self,
+            _framebuffer: framebuffer,
+        })
+    }
+
+    /// Wait for the start of the next vertical sync pulse
+    pub fn wait_for_vsync(&mut self) {
+        wait_for_vsync();
+    }
+}
+
+/// A frame buffer being streamed to the display by [Rgb::start]
+///
+/// Dropping the transfer stops it, like [RgbTransfer::stop].
+pub struct RgbTransfer<'t, 'd, P> {
+    rgb: &'t mut Rgb<'d, P>,
+    _framebuffer: &'t [u8],
+}
+
+impl<'t, 'd, P> RgbTransfer<'t, 'd, P> {
+    /// Stop streaming at the end of the current frame, releasing the frame
+    /// buffer
+    pub fn stop(self) {
+        // done by `drop`
+    }
+
+    /// Wait for the start of the next vertical sync pulse
+    ///
+    /// Updating the frame buffer right after this reduces visible tearing.
+    pub fn wait_for_vsync(&mut self) {
+        wait_for_vsync();
+    }
+}
+
+impl<'t, 'd, P> Drop for RgbTransfer<'t, 'd, P> {
+    fn drop(&mut self) {
+        register_block()
+            .lcd_user
+            .modify(|_, w| w.lcd_start().clear_bit());
+        self.rgb.channel.stop_transmit();
+    }
+}
+
+fn wait_for_vsync() {
+    let regs = register_block();
+
+    regs.lc_dma_int_clr
+        .write(|w| w.lcd_vsync_int_clr().set_bit());
+    while regs
+        .lc_dma_int_raw
+        .read()
+        .lcd_vsync_int_raw()
+        .bit_is_clear()
+    {}
+}
+
+/// Configure the LCD clock to produce (approximately) `frequency`
+fn configure_clock(frequency: HertzU32, idle_level: bool, active_negative_edge: bool) {
+    let (divider, counter) = clock_dividers(frequency.raw());
+
+    register_block().lcd_clock.write(|w| unsafe {
+        w.clk_en()
+            .set_bit()
+            .lcd_clk_sel()
+            .bits(CLOCK_SOURCE_PLL_F160M as u8)
+            .lcd_clkm_div_num()
+            .bits(divider as u8)
+            .lcd_clkcnt_n()
+            .bits(counter as u8)
+            .lcd_clk_equ_sysclk()
+            .bit(counter == 0)
+            .lcd_ck_idle_edge()
+            .bit(idle_level)
+            .lcd_ck_out_edge()
+            .bit(active_negative_edge)
+    });
+}
+
+/// Write the LCD_USER settings common to all transfers: the length of a
+/// transfer is given by the DMA descriptors
+fn write_user_config<P: TxPins>() {
+    register_block().lcd_user.write(|w| {
+        w.lcd_always_out_en()
+            .set_bit()
+            .lcd_2byte_en()
+            .bit(P::BUS_WIDTH == 16)
+    });
+}
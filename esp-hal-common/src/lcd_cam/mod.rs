@@ -0,0 +1,98 @@
+//! LCD and camera interface (LCD_CAM)
+//!
+//! The LCD_CAM peripheral of the ESP32-S3 consists of an LCD controller,
+//! driving parallel displays in i8080 or RGB mode, and a camera controller,
+//! capturing from DVP camera sensors. Both stream their data through GDMA.
+//!
+//! # Example
+//!
+//! ```no_run
+//! let lcd_cam = LcdCam::new(peripherals.LCD_CAM, &mut system.peripheral_clock_control);
+//! let gdma = Gdma::new(peripherals.DMA, &mut system.peripheral_clock_control);
+//!
+//! let mut i8080 = I8080::new(
+//!     lcd_cam.lcd,
+//!     gdma.channel0,
+//!     unsafe { &mut DESCRIPTORS },
+//!     TxEightBits::new(d0, d1, d2, d3, d4, d5, d6, d7),
+//!     20u32.MHz(),
+//!     I8080Config::default(),
+//! )
+//! .with_ctrl_pins(dc, wr);
+//!
+//! i8080.send(Command::One(0x2c), &pixels).unwrap();
+//! ```
+
+use crate::{
+    pac::{lcd_cam::RegisterBlock, LCD_CAM},
+    system::{Peripheral, PeripheralClockControl},
+};
+
+pub mod cam;
+pub mod lcd;
+
+// Clock sources of the LCD and camera controllers
+const CLOCK_SOURCE_PLL_F160M: u32 = 3;
+const CLOCK_SOURCE_HZ: u32 = 160_000_000;
+
+/// The LCD_CAM peripheral, split into its LCD and camera halves
+pub struct LcdCam {
+    pub lcd: Lcd,
+    pub cam: Cam,
+}
+
+impl LcdCam {
+    /// Enable the LCD_CAM peripheral
+    pub fn new(lcd_cam: LCD_CAM, peripheral_clock_control: &mut PeripheralClockControl) -> Self {
+        peripheral_clock_control.enable(Peripheral::LcdCam);
+
+        // Ownership is handed to the halves, which only touch their own
+        // registers
+        let _ = lcd_cam;
+
+        Self {
+            lcd: Lcd { _private: () },
+            cam: Cam { _private: () },
+        }
+    }
+}
+
+/// The LCD half of the LCD_CAM peripheral, to be used by [lcd::I8080] or
+/// [lcd::Rgb]
+pub struct Lcd {
+    _private: (),
+}
+
+/// The camera half of the LCD_CAM peripheral, to be used by [cam::Camera]
+pub struct Cam {
+    _private: (),
+}
+
+fn register_block() -> &'static RegisterBlock {
+    unsafe { &*LCD_CAM::PTR }
+}
+
+/// Divider settings producing (approximately) `frequency` from the 160 MHz
+/// clock source, as `(integer divider, counter)`
+///
+/// The resulting clock is `160 MHz / divider / (counter + 1)`.
+fn clock_dividers(frequency: u32) -> (u32, u32) {
+    let total = (CLOCK_SOURCE_HZ / frequency.max(1)).max(2);
+
+    // The integer divider must be at least 2, the counter is 6 bits wide
+    let mut divider = 2;
+    while total / divider > 64 && divider < 255 {
+        divider += 1;
+    }
+
+    let counter = (total / divider).clamp(1, 64) - 1;
+
+    trace!(
+        "LCD_CAM clock {} Hz: divider {}, counter {}",
+        frequency,
+        divider,
+        counter
+    );
+
+    (divider, counter)
+}
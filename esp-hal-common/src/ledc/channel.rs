@@ -13,6 +13,7 @@ use crate::{
 
 /// Channel errors
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
     /// Invalid duty % value
     Duty,
@@ -24,6 +25,7 @@ pub enum Error {
 
 /// Channel number
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Number {
     Channel0,
     Channel1,
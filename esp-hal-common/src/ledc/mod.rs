@@ -79,6 +79,7 @@ pub mod timer;
 
 /// Global slow clock source
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum LSGlobalClkSource {
     APBClk,
 }
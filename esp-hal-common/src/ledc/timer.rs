@@ -9,6 +9,7 @@ const LEDC_TIMER_DIV_NUM_MAX: u64 = 0x3FFFF;
 
 /// Timer errors
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
     /// Invalid Divisor
     Divisor,
@@ -17,6 +18,7 @@ pub enum Error {
 #[cfg(feature = "esp32")]
 /// Clock source for HS Timers
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum HSClockSource {
     APBClk,
     // TODO RefTick,
@@ -24,6 +26,7 @@ pub enum HSClockSource {
 
 /// Clock source for LS Timers
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum LSClockSource {
     APBClk,
     // TODO SLOWClk
@@ -31,6 +34,7 @@ pub enum LSClockSource {
 
 /// Timer number
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Number {
     Timer0,
     Timer1,
@@ -44,6 +48,7 @@ pub mod config {
 
     /// Number of bits reserved for duty cycle adjustment
     #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub enum Duty {
         Duty1Bit = 1,
         Duty2Bit,
@@ -171,6 +176,13 @@ where
             return Err(Error::Divisor);
         }
 
+        trace!(
+            "LEDC timer frequency {} Hz: divisor {} (REF_TICK: {})",
+            frequency,
+            divisor,
+            self.use_ref_tick
+        );
+
         self.configure_hw(divisor as u32);
         self.update_hw();
 
@@ -28,38 +28,78 @@ pub use esp32s2 as pac;
 #[cfg(feature = "esp32s3")]
 pub use esp32s3 as pac;
 
+#[macro_use]
+mod fmt;
+
+mod asynch;
+pub mod crash;
 pub mod delay;
+#[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+pub mod ds;
+#[cfg(feature = "embassy")]
+pub mod embassy;
 
 #[cfg_attr(feature = "esp32", path = "efuse/esp32.rs")]
 #[cfg_attr(feature = "esp32c3", path = "efuse/esp32c3.rs")]
 #[cfg_attr(feature = "esp32s2", path = "efuse/esp32s2.rs")]
 #[cfg_attr(feature = "esp32s3", path = "efuse/esp32s3.rs")]
 pub mod efuse;
+pub mod error;
 
+#[cfg(feature = "esp32s3")]
+pub mod gdma;
 pub mod gpio;
+#[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+pub mod hmac;
 pub mod i2c;
 #[cfg_attr(target_arch = "riscv32", path = "interrupt/riscv.rs")]
 #[cfg_attr(target_arch = "xtensa", path = "interrupt/xtensa.rs")]
 pub mod interrupt;
+#[cfg(feature = "esp32s3")]
+pub mod lcd_cam;
 pub mod ledc;
+#[cfg(feature = "rtic")]
+pub mod monotonic;
+#[cfg(all(any(feature = "esp32s2", feature = "esp32s3"), feature = "usb_otg"))]
+pub mod otg_fs;
+pub mod peripherals;
 pub mod prelude;
 pub mod pulse_control;
+pub mod reset;
 pub mod rng;
 pub mod rom;
+pub mod rsa;
 pub mod rtc_cntl;
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+pub mod rtc_i2c;
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+pub mod rtc_io;
 pub mod serial;
 pub mod spi;
+pub mod time;
 pub mod timer;
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+pub mod touch;
+pub mod twai;
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+pub mod ulp;
 #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
 pub mod usb_serial_jtag;
 pub mod utils;
+pub mod watchdog;
 
 pub use delay::Delay;
+#[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+pub use ds::Ds;
+pub use error::Error;
 pub use gpio::*;
+#[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+pub use hmac::Hmac;
 pub use interrupt::*;
 pub use procmacros as macros;
 pub use pulse_control::PulseControl;
 pub use rng::Rng;
+pub use rsa::Rsa;
 pub use rtc_cntl::{Rtc, Rwdt};
 pub use serial::Serial;
 pub use spi::Spi;
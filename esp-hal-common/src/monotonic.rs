@@ -0,0 +1,165 @@
+//! # RTIC monotonic timers
+//!
+//! With the `rtic` feature, the SYSTIMER and the general-purpose timers can
+//! be used as the monotonic timer of an RTIC 1.0 application, which schedules
+//! its tasks on the alarm of the timer:
+//!
+//! ```no_run
+//! #[monotonic(binds = SYSTIMER_TARGET0, default = true)]
+//! type Mono = SystemTimerMonotonic;
+//!
+//! #[init]
+//! fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+//!     let syst = SystemTimer::new(cx.device.SYSTIMER);
+//!     (Shared {}, Local {}, init::Monotonics(SystemTimerMonotonic::new(syst.alarm0)))
+//! }
+//! ```
+//!
+//! The ESP32 has no SYSTIMER, so a general-purpose timer is used instead,
+//! prescaled to a tick rate chosen by the application:
+//!
+//! ```no_run
+//! #[monotonic(binds = TG0_T0_LEVEL, default = true)]
+//! type Mono = TimerMonotonic<Timer0<TIMG0>, 1_000_000>;
+//!
+//! let timer_group0 = TimerGroup::new(cx.device.TIMG0, &clocks);
+//! let mono = TimerMonotonic::new(timer_group0.timer0);
+//! ```
+//!
+//! Neither counter wraps around in practice: the SYSTIMER counts at 16 MHz
+//! for about nine years before its 52 bits overflow (the 64 bits of the
+//! ESP32-S2 even longer), and the 54 bits of a general-purpose timer last
+//! more than 500 years at 1 MHz.
+
+use fugit::{TimerDurationU64, TimerInstantU64};
+use rtic_monotonic::Monotonic;
+
+#[cfg(any(feature = "esp32c3", feature = "esp32s2", feature = "esp32s3"))]
+use crate::systimer::{Alarm, SystemTimer, Target};
+use crate::timer::{Instance, Timer};
+
+/// Tick rate of [SystemTimerMonotonic]
+#[cfg(any(feature = "esp32c3", feature = "esp32s2", feature = "esp32s3"))]
+pub const SYSTIMER_HZ: u32 = SystemTimer::TICKS_PER_SECOND as u32;
+
+/// Monotonic timer on alarm 0 of the SYSTIMER
+///
+/// Its interrupt is `SYSTIMER_TARGET0`.
+#[cfg(any(feature = "esp32c3", feature = "esp32s2", feature = "esp32s3"))]
+pub struct SystemTimerMonotonic {
+    alarm: Alarm<Target, 0>,
+}
+
+#[cfg(any(feature = "esp32c3", feature = "esp32s2", feature = "esp32s3"))]
+impl SystemTimerMonotonic {
+    /// Use `alarm` to schedule the tasks
+    ///
+    /// The SYSTIMER is shared with the other alarms, so its counter is not
+    /// reset: the instants count from boot.
+    pub fn new(alarm: Alarm<Target, 0>) -> Self {
+        Self { alarm }
+    }
+}
+
+#[cfg(any(feature = "esp32c3", feature = "esp32s2", feature = "esp32s3"))]
+impl Monotonic for SystemTimerMonotonic {
+    type Instant = TimerInstantU64<SYSTIMER_HZ>;
+    type Duration = TimerDurationU64<SYSTIMER_HZ>;
+
+    fn now(&mut self) -> Self::Instant {
+        Self::Instant::from_ticks(SystemTimer::now())
+    }
+
+    fn set_compare(&mut self, instant: Self::Instant) {
+        self.alarm.set_target(instant.ticks());
+    }
+
+    fn clear_compare_flag(&mut self) {
+        self.alarm.clear_interrupt();
+    }
+
+    fn zero() -> Self::Instant {
+        Self::Instant::from_ticks(0)
+    }
+
+    unsafe fn reset(&mut self) {
+        self.alarm.clear_interrupt();
+        self.alarm.enable_interrupt();
+    }
+}
+
+/// Monotonic timer on a general-purpose timer, counting at `FREQ_HZ`
+///
+/// Its interrupt is the `TGn_Tm_LEVEL` interrupt of the timer.
+pub struct TimerMonotonic<T, const FREQ_HZ: u32> {
+    timer: T,
+}
+
+impl<T, const FREQ_HZ: u32> TimerMonotonic<T, FREQ_HZ>
+where
+    T: Instance,
+{
+    /// Prescale `timer` to `FREQ_HZ` and use it to schedule the tasks
+    ///
+    /// # Panics
+    ///
+    /// Panics if `FREQ_HZ` can not be reached by dividing the source clock of
+    /// the timer by 2 to 65536.
+    pub fn new(mut timer: Timer<T>) -> Self {
+        let clock = timer.clock_frequency().to_Hz();
+        assert!(
+            clock % FREQ_HZ == 0,
+            "the timer clock is not a multiple of the monotonic tick rate"
+        );
+
+        timer.set_divider(clock / FREQ_HZ);
+
+        Self {
+            timer: timer.free(),
+        }
+    }
+
+    /// Stop scheduling and return the timer
+    pub fn free(self) -> T {
+        self.timer
+    }
+}
+
+impl<T, const FREQ_HZ: u32> Monotonic for TimerMonotonic<T, FREQ_HZ>
+where
+    T: Instance,
+{
+    type Instant = TimerInstantU64<FREQ_HZ>;
+    type Duration = TimerDurationU64<FREQ_HZ>;
+
+    fn now(&mut self) -> Self::Instant {
+        Self::Instant::from_ticks(self.timer.read_raw())
+    }
+
+    fn set_compare(&mut self, instant: Self::Instant) {
+        // the alarm is disabled by the hardware when it fires, so it is armed
+        // again for every compare value
+        self.timer.set_alarm_active(false);
+        self.timer.load_alarm_value(instant.ticks());
+        self.timer.set_alarm_active(true);
+    }
+
+    fn clear_compare_flag(&mut self) {
+        self.timer.clear_interrupt();
+    }
+
+    fn zero() -> Self::Instant {
+        Self::Instant::from_ticks(0)
+    }
+
+    unsafe fn reset(&mut self) {
+        self.timer.set_counter_active(false);
+        self.timer.set_alarm_active(false);
+        self.timer.reset_counter();
+        self.timer.set_counter_decrementing(false);
+        self.timer.set_auto_reload(false);
+        self.timer.clear_interrupt();
+        self.timer.listen();
+        self.timer.set_counter_active(true);
+    }
+}
@@ -0,0 +1,821 @@
+//! USB OTG host mode
+//!
+//! In host mode, the OTG_FS peripheral powers and resets its single port and
+//! talks to the attached device through host channels. Each endpoint used is
+//! represented by a [Pipe], which occupies one of the [CHANNEL_COUNT]
+//! channels while allocated.
+//!
+//! Transfers are polled: control transfers block until they are complete,
+//! while bulk and interrupt transfers return [nb::Error::WouldBlock] when the
+//! device has no data or cannot accept any (it answers with NAK). Hubs are
+//! not supported.
+//!
+//! # Example
+//!
+//! ```no_run
+//! let usb = USB::new(
+//!     peripherals.USB0,
+//!     io.pins.gpio19,
+//!     io.pins.gpio20,
+//!     &mut system.peripheral_clock_control,
+//! );
+//! let mut host = UsbHost::new(usb);
+//!
+//! while !host.is_connected() {}
+//! host.reset_port().unwrap();
+//!
+//! let mut control = host.alloc_control_pipe(0, 8).unwrap();
+//! let mut descriptor = [0; 18];
+//! host.control_in(
+//!     &mut control,
+//!     &SetupPacket::get_descriptor(DESCRIPTOR_TYPE_DEVICE, 0, 18),
+//!     &mut descriptor,
+//! )
+//! .unwrap();
+//! ```
+
+use super::{UsbDm, UsbDp, USB};
+use crate::{
+    gpio::{connect_high_to_peripheral, connect_low_to_peripheral},
+    pac,
+    rom::esp_rom_delay_us,
+    types::InputSignal,
+};
+
+/// Number of host channels, i.e. of pipes that can be allocated at once
+pub const CHANNEL_COUNT: usize = 8;
+
+/// Descriptor type of a device descriptor, for [SetupPacket::get_descriptor]
+pub const DESCRIPTOR_TYPE_DEVICE: u8 = 1;
+/// Descriptor type of a configuration descriptor, for
+/// [SetupPacket::get_descriptor]
+pub const DESCRIPTOR_TYPE_CONFIGURATION: u8 = 2;
+
+// Core registers
+const GUSBCFG: usize = 0x00c;
+const GRSTCTL: usize = 0x010;
+const GINTSTS: usize = 0x014;
+const GRXSTSP: usize = 0x020;
+const GRXFSIZ: usize = 0x024;
+const GNPTXFSIZ: usize = 0x028;
+const HPTXFSIZ: usize = 0x100;
+
+// Host registers
+const HCFG: usize = 0x400;
+const HFIR: usize = 0x404;
+const HFNUM: usize = 0x408;
+const HPRT: usize = 0x440;
+
+// Channel registers, relative to the channel's register block
+const CHANNEL_BASE: usize = 0x500;
+const CHANNEL_STRIDE: usize = 0x20;
+const HCCHAR: usize = 0x00;
+const HCINT: usize = 0x08;
+const HCTSIZ: usize = 0x10;
+
+// Data FIFOs; received data is popped from the first one
+const FIFO_BASE: usize = 0x1000;
+const FIFO_STRIDE: usize = 0x1000;
+
+// FIFO sizes, in words
+const RX_FIFO_DEPTH: u32 = 128;
+const NON_PERIODIC_TX_FIFO_DEPTH: u32 = 64;
+const PERIODIC_TX_FIFO_DEPTH: u32 = 64;
+
+// GUSBCFG bits
+const GUSBCFG_PHYSEL: u32 = 1 << 6;
+const GUSBCFG_FORCE_HOST_MODE: u32 = 1 << 29;
+
+// GRSTCTL bits
+const GRSTCTL_CSFTRST: u32 = 1;
+const GRSTCTL_RXFFLSH: u32 = 1 << 4;
+const GRSTCTL_TXFFLSH: u32 = 1 << 5;
+const GRSTCTL_TXFNUM_ALL: u32 = 0x10 << 6;
+const GRSTCTL_AHBIDLE: u32 = 1 << 31;
+
+// GINTSTS bits
+const GINTSTS_CURMOD_HOST: u32 = 1;
+const GINTSTS_RXFLVL: u32 = 1 << 4;
+
+// GRXSTSP fields
+const GRXSTSP_BCNT_SHIFT: u32 = 4;
+const GRXSTSP_BCNT_MASK: u32 = 0x7ff;
+const GRXSTSP_PKTSTS_SHIFT: u32 = 17;
+const GRXSTSP_PKTSTS_MASK: u32 = 0xf;
+const PKTSTS_IN_DATA: u32 = 2;
+
+// HCFG bits
+const HCFG_FSLSPCS_48MHZ: u32 = 1;
+const HCFG_FSLSPCS_6MHZ: u32 = 2;
+const HCFG_FSLSPCS_MASK: u32 = 0b11;
+const HCFG_FSLSSUPP: u32 = 1 << 2;
+
+// HPRT bits
+const HPRT_PRTCONNSTS: u32 = 1;
+const HPRT_PRTCONNDET: u32 = 1 << 1;
+const HPRT_PRTENA: u32 = 1 << 2;
+const HPRT_PRTENCHNG: u32 = 1 << 3;
+const HPRT_PRTOVRCURRCHNG: u32 = 1 << 5;
+const HPRT_PRTRST: u32 = 1 << 8;
+const HPRT_PRTPWR: u32 = 1 << 12;
+const HPRT_PRTSPD_SHIFT: u32 = 17;
+const HPRT_PRTSPD_LOW: u32 = 2;
+// Writing 1 to these bits clears them, or disables the port
+const HPRT_W1C_MASK: u32 = HPRT_PRTCONNDET | HPRT_PRTENA | HPRT_PRTENCHNG | HPRT_PRTOVRCURRCHNG;
+
+// HCCHAR fields
+const HCCHAR_EPNUM_SHIFT: u32 = 11;
+const HCCHAR_EPDIR_IN: u32 = 1 << 15;
+const HCCHAR_EPTYPE_SHIFT: u32 = 18;
+const HCCHAR_MC_SHIFT: u32 = 20;
+const HCCHAR_DEVADDR_SHIFT: u32 = 22;
+const HCCHAR_ODDFRM: u32 = 1 << 29;
+const HCCHAR_CHDIS: u32 = 1 << 30;
+const HCCHAR_CHENA: u32 = 1 << 31;
+
+// HCINT bits
+const HCINT_XFERCOMPL: u32 = 1;
+const HCINT_CHHLTD: u32 = 1 << 1;
+const HCINT_STALL: u32 = 1 << 3;
+const HCINT_NAK: u32 = 1 << 4;
+const HCINT_XACTERR: u32 = 1 << 7;
+const HCINT_BBLERR: u32 = 1 << 8;
+const HCINT_FRMOVRUN: u32 = 1 << 9;
+const HCINT_DATATGLERR: u32 = 1 << 10;
+const HCINT_DONE: u32 = HCINT_XFERCOMPL
+    | HCINT_STALL
+    | HCINT_NAK
+    | HCINT_XACTERR
+    | HCINT_BBLERR
+    | HCINT_FRMOVRUN
+    | HCINT_DATATGLERR;
+
+// HCTSIZ fields
+const HCTSIZ_PKTCNT_SHIFT: u32 = 19;
+const HCTSIZ_PID_SHIFT: u32 = 29;
+const PID_DATA0: u32 = 0;
+const PID_DATA1: u32 = 2;
+const PID_SETUP: u32 = 3;
+
+// Timing, in microseconds
+const PORT_RESET_US: u32 = 50_000;
+const RESET_RECOVERY_US: u32 = 10_000;
+
+// Consecutive transaction errors (CRC, timeout, ...) after which a transfer
+// fails
+const MAX_TRANSACTION_ERRORS: u8 = 3;
+
+/// USB host errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// No device is connected, or it was disconnected during the transfer
+    Disconnected,
+    /// All host channels are in use
+    NoFreeChannel,
+    /// The endpoint answered with STALL
+    Stall,
+    /// The transfer failed after repeated transaction errors
+    Transaction,
+    /// The device sent more data than expected
+    Babble,
+    /// A packet with the wrong data toggle was received
+    DataToggle,
+    /// The device sent more data than fits into the buffer
+    Overflow,
+}
+
+/// Speed of the attached device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Speed {
+    Low,
+    Full,
+}
+
+/// Transfer type of an endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransferType {
+    Control   = 0,
+    Bulk      = 2,
+    Interrupt = 3,
+}
+
+/// Direction of a transaction, as seen from the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    In,
+    Out,
+}
+
+/// The setup packet starting a control transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SetupPacket {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+    pub length: u16,
+}
+
+impl SetupPacket {
+    /// GET_DESCRIPTOR, reading `length` bytes of descriptor `index` of type
+    /// `descriptor_type`
+    pub const fn get_descriptor(descriptor_type: u8, index: u8, length: u16) -> Self {
+        Self {
+            request_type: 0x80,
+            request: 0x06,
+            value: (descriptor_type as u16) << 8 | index as u16,
+            index: 0,
+            length,
+        }
+    }
+
+    /// SET_ADDRESS, assigning `address` to the device
+    pub const fn set_address(address: u8) -> Self {
+        Self {
+            request_type: 0x00,
+            request: 0x05,
+            value: address as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    /// SET_CONFIGURATION, selecting configuration `value`
+    pub const fn set_configuration(value: u8) -> Self {
+        Self {
+            request_type: 0x00,
+            request: 0x09,
+            value: value as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; 8] {
+        let [value_lo, value_hi] = self.value.to_le_bytes();
+        let [index_lo, index_hi] = self.index.to_le_bytes();
+        let [length_lo, length_hi] = self.length.to_le_bytes();
+
+        [
+            self.request_type,
+            self.request,
+            value_lo,
+            value_hi,
+            index_lo,
+            index_hi,
+            length_lo,
+            length_hi,
+        ]
+    }
+}
+
+/// An endpoint of the attached device, bound to a host channel
+///
+/// Return it with [UsbHost::free_pipe] once it is no longer needed.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Pipe {
+    channel: u8,
+    device_address: u8,
+    endpoint: u8,
+    transfer_type: TransferType,
+    max_packet_size: u16,
+    data_toggle: bool,
+}
+
+impl Pipe {
+    /// Change the address of the device, e.g. after SET_ADDRESS
+    pub fn set_device_address(&mut self, address: u8) {
+        self.device_address = address;
+    }
+
+    /// Change the maximum packet size, e.g. once the device descriptor of a
+    /// control endpoint has been read
+    pub fn set_max_packet_size(&mut self, max_packet_size: u16) {
+        self.max_packet_size = max_packet_size;
+    }
+
+    /// Address of the device
+    pub fn device_address(&self) -> u8 {
+        self.device_address
+    }
+
+    fn pid(&self) -> u32 {
+        if self.data_toggle {
+            PID_DATA1
+        } else {
+            PID_DATA0
+        }
+    }
+}
+
+/// Result of a single transaction
+enum Response {
+    /// The transaction succeeded, moving the given number of bytes IN
+    Ack(usize),
+    /// The device was not ready
+    Nak,
+}
+
+/// Destination of the data of IN transactions
+struct Receiver<'b> {
+    buffer: &'b mut [u8],
+    len: usize,
+    overflow: bool,
+}
+
+impl Receiver<'_> {
+    fn push(&mut self, byte: u8) {
+        match self.buffer.get_mut(self.len) {
+            Some(slot) => {
+                *slot = byte;
+                self.len += 1;
+            }
+            None => self.overflow = true,
+        }
+    }
+}
+
+/// The OTG_FS peripheral in host mode
+pub struct UsbHost<M, P> {
+    usb: USB<M, P>,
+    allocated_channels: u8,
+}
+
+impl<M, P> UsbHost<M, P>
+where
+    M: UsbDm,
+    P: UsbDp,
+{
+    /// Put the peripheral into host mode and power the port
+    pub fn new(usb: USB<M, P>) -> Self {
+        let usb_wrap = unsafe { &*pac::USB_WRAP::PTR };
+
+        usb_wrap.otg_conf.modify(|_, w| {
+            w.usb_pad_enable()
+                .set_bit()
+                .phy_sel()
+                .clear_bit()
+                .clk_en()
+                .set_bit()
+                .ahb_clk_force_on()
+                .set_bit()
+                .phy_clk_force_on()
+                .set_bit()
+        });
+
+        // The internal PHY is shared with the USB Serial/JTAG controller
+        unsafe { &*pac::RTC_CNTL::PTR }
+            .usb_conf
+            .modify(|_, w| w.sw_hw_usb_phy_sel().set_bit().sw_usb_phy_sel().set_bit());
+
+        // Force host mode: A-device (mini-A connector side), valid session and
+        // valid VBUS
+        connect_low_to_peripheral(InputSignal::USB_OTG_IDDIG);
+        connect_high_to_peripheral(InputSignal::USB_OTG_AVALID);
+        connect_high_to_peripheral(InputSignal::USB_OTG_VBUSVALID);
+        connect_low_to_peripheral(InputSignal::USB_SRP_BVALID);
+
+        // A host pulls both data lines down
+        usb_wrap.otg_conf.modify(|_, w| {
+            w.pad_pull_override()
+                .set_bit()
+                .dp_pullup()
+                .clear_bit()
+                .dp_pulldown()
+                .set_bit()
+                .dm_pullup()
+                .clear_bit()
+                .dm_pulldown()
+                .set_bit()
+        });
+
+        let mut host = Self {
+            usb,
+            allocated_channels: 0,
+        };
+        host.init_core();
+        host
+    }
+
+    fn init_core(&mut self) {
+        while read(GRSTCTL) & GRSTCTL_AHBIDLE == 0 {}
+        write(GRSTCTL, GRSTCTL_CSFTRST);
+        while read(GRSTCTL) & GRSTCTL_CSFTRST != 0 {}
+
+        write(
+            GUSBCFG,
+            read(GUSBCFG) | GUSBCFG_PHYSEL | GUSBCFG_FORCE_HOST_MODE,
+        );
+        while read(GINTSTS) & GINTSTS_CURMOD_HOST == 0 {}
+
+        write(HCFG, HCFG_FSLSSUPP | HCFG_FSLSPCS_48MHZ);
+        write(HFIR, 48_000);
+
+        write(GRXFSIZ, RX_FIFO_DEPTH);
+        write(GNPTXFSIZ, NON_PERIODIC_TX_FIFO_DEPTH << 16 | RX_FIFO_DEPTH);
+        write(
+            HPTXFSIZ,
+            PERIODIC_TX_FIFO_DEPTH << 16 | (RX_FIFO_DEPTH + NON_PERIODIC_TX_FIFO_DEPTH),
+        );
+
+        write(GRSTCTL, GRSTCTL_TXFFLSH | GRSTCTL_TXFNUM_ALL);
+        while read(GRSTCTL) & GRSTCTL_TXFFLSH != 0 {}
+        write(GRSTCTL, GRSTCTL_RXFFLSH);
+        while read(GRSTCTL) & GRSTCTL_RXFFLSH != 0 {}
+
+        for channel in 0..CHANNEL_COUNT {
+            write(channel_reg(channel, HCINT), u32::MAX);
+        }
+        write(GINTSTS, u32::MAX);
+
+        modify_hprt(HPRT_PRTPWR, 0);
+    }
+
+    /// Whether a device is connected to the port
+    pub fn is_connected(&self) -> bool {
+        read(HPRT) & HPRT_PRTCONNSTS != 0
+    }
+
+    /// Reset the port and the attached device, and return the device's speed
+    ///
+    /// This is required after a device has been connected, before any
+    /// transfers.
+    pub fn reset_port(&mut self) -> Result<Speed, Error> {
+        loop {
+            if !self.is_connected() {
+                return Err(Error::Disconnected);
+            }
+
+            modify_hprt(HPRT_PRTRST, 0);
+            unsafe { esp_rom_delay_us(PORT_RESET_US) };
+            modify_hprt(0, HPRT_PRTRST);
+
+            while read(HPRT) & HPRT_PRTENA == 0 {
+                if !self.is_connected() {
+                    return Err(Error::Disconnected);
+                }
+            }
+            unsafe { esp_rom_delay_us(RESET_RECOVERY_US) };
+
+            let speed = if (read(HPRT) >> HPRT_PRTSPD_SHIFT) & 0b11 == HPRT_PRTSPD_LOW {
+                Speed::Low
+            } else {
+                Speed::Full
+            };
+
+            // The PHY clock must match the speed of the device; changing it
+            // requires another reset
+            let (clock, frame_interval) = match speed {
+                Speed::Low => (HCFG_FSLSPCS_6MHZ, 6_000),
+                Speed::Full => (HCFG_FSLSPCS_48MHZ, 48_000),
+            };
+            if read(HCFG) & HCFG_FSLSPCS_MASK == clock {
+                return Ok(speed);
+            }
+
+            write(HCFG, HCFG_FSLSSUPP | clock);
+            write(HFIR, frame_interval);
+        }
+    }
+
+    /// Allocate a pipe to endpoint 0 of the device at `device_address`
+    ///
+    /// Newly attached devices use address 0, and a maximum packet size of 8
+    /// bytes is supported by all devices.
+    pub fn alloc_control_pipe(
+        &mut self,
+        device_address: u8,
+        max_packet_size: u16,
+    ) -> Result<Pipe, Error> {
+        self.alloc_pipe(device_address, 0, TransferType::Control, max_packet_size)
+    }
+
+    /// Allocate a pipe to `endpoint` (the endpoint number, without direction
+    /// bit) of the device at `device_address`
+    ///
+    /// The IN and OUT endpoints with the same number need separate pipes.
+    pub fn alloc_pipe(
+        &mut self,
+        device_address: u8,
+        endpoint: u8,
+        transfer_type: TransferType,
+        max_packet_size: u16,
+    ) -> Result<Pipe, Error> {
+        let channel = (0..CHANNEL_COUNT as u8)
+            .find(|channel| self.allocated_channels & (1 << channel) == 0)
+            .ok_or(Error::NoFreeChannel)?;
+        self.allocated_channels |= 1 << channel;
+
+        Ok(Pipe {
+            channel,
+            device_address,
+            endpoint,
+            transfer_type,
+            max_packet_size,
+            data_toggle: false,
+        })
+    }
+
+    /// Release the channel used by `pipe`
+    pub fn free_pipe(&mut self, pipe: Pipe) {
+        self.allocated_channels &= !(1 << pipe.channel);
+    }
+
+    /// Perform a control transfer reading up to `buffer.len()` bytes, and
+    /// return the number of bytes received
+    pub fn control_in(
+        &mut self,
+        pipe: &mut Pipe,
+        setup: &SetupPacket,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        self.setup_stage(pipe, setup)?;
+
+        let len = buffer.len().min(setup.length as usize);
+        let received = if len > 0 {
+            nb::block!(self.transfer_in(pipe, &mut buffer[..len]))?
+        } else {
+            0
+        };
+
+        // The status stage is a zero-length packet in the other direction
+        pipe.data_toggle = true;
+        nb::block!(self.transfer_out(pipe, &[]))?;
+
+        Ok(received)
+    }
+
+    /// Perform a control transfer writing `data`
+    pub fn control_out(
+        &mut self,
+        pipe: &mut Pipe,
+        setup: &SetupPacket,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.setup_stage(pipe, setup)?;
+
+        if !data.is_empty() {
+            nb::block!(self.transfer_out(pipe, data))?;
+        }
+
+        // The status stage is a zero-length packet in the other direction
+        pipe.data_toggle = true;
+        nb::block!(self.transfer_in(pipe, &mut []))?;
+
+        Ok(())
+    }
+
+    /// Read from the bulk or interrupt IN endpoint of `pipe`, returning the
+    /// number of bytes received
+    ///
+    /// The transfer ends with a short packet or once `buffer` is full.
+    /// Returns [nb::Error::WouldBlock] if the device has no data.
+    pub fn read(&mut self, pipe: &mut Pipe, buffer: &mut [u8]) -> nb::Result<usize, Error> {
+        self.transfer_in(pipe, buffer)
+    }
+
+    /// Write `data` to the bulk or interrupt OUT endpoint of `pipe`
+    ///
+    /// Returns [nb::Error::WouldBlock] if the device cannot accept data.
+    pub fn write(&mut self, pipe: &mut Pipe, data: &[u8]) -> nb::Result<(), Error> {
+        self.transfer_out(pipe, data)
+    }
+
+    /// Return the raw interface to the underlying peripheral
+    pub fn free(self) -> USB<M, P> {
+        modify_hprt(0, HPRT_PRTPWR);
+        self.usb
+    }
+
+    fn setup_stage(&mut self, pipe: &mut Pipe, setup: &SetupPacket) -> Result<(), Error> {
+        let packet = setup.to_bytes();
+        while let Response::Nak =
+            self.transaction(pipe, Direction::Out, PID_SETUP, &packet, &mut [])?
+        {}
+
+        // The data stage starts with DATA1
+        pipe.data_toggle = true;
+
+        Ok(())
+    }
+
+    fn transfer_in(&mut self, pipe: &mut Pipe, buffer: &mut [u8]) -> nb::Result<usize, Error> {
+        let max_packet_size = pipe.max_packet_size as usize;
+        let mut len = 0;
+
+        loop {
+            let pid = pipe.pid();
+            match self.transaction(pipe, Direction::In, pid, &[], &mut buffer[len..])? {
+                Response::Ack(received) => {
+                    pipe.data_toggle = !pipe.data_toggle;
+                    len += received;
+                    if received < max_packet_size || len == buffer.len() {
+                        return Ok(len);
+                    }
+                }
+                Response::Nak if len > 0 => return Ok(len),
+                Response::Nak => return Err(nb::Error::WouldBlock),
+            }
+        }
+    }
+
+    fn transfer_out(&mut self, pipe: &mut Pipe, data: &[u8]) -> nb::Result<(), Error> {
+        let max_packet_size = pipe.max_packet_size as usize;
+        let mut sent = 0;
+
+        loop {
+            let end = (sent + max_packet_size).min(data.len());
+            let pid = pipe.pid();
+            match self.transaction(pipe, Direction::Out, pid, &data[sent..end], &mut [])? {
+                Response::Ack(_) => {
+                    pipe.data_toggle = !pipe.data_toggle;
+                    sent = end;
+                    if sent == data.len() {
+                        return Ok(());
+                    }
+                }
+                // Once part of the data has been accepted, keep trying
+                Response::Nak if sent > 0 => {}
+                Response::Nak => return Err(nb::Error::WouldBlock),
+            }
+        }
+    }
+
+    /// Run a single transaction of at most one packet on the pipe's channel
+    fn transaction(
+        &mut self,
+        pipe: &Pipe,
+        direction: Direction,
+        pid: u32,
+        data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<Response, Error> {
+        let channel = pipe.channel as usize;
+        let mut errors = 0;
+
+        loop {
+            if !self.is_connected() {
+                return Err(Error::Disconnected);
+            }
+
+            let mut characteristics = pipe.max_packet_size as u32
+                | (pipe.endpoint as u32) << HCCHAR_EPNUM_SHIFT
+                | (pipe.transfer_type as u32) << HCCHAR_EPTYPE_SHIFT
+                | 1 << HCCHAR_MC_SHIFT
+                | (pipe.device_address as u32) << HCCHAR_DEVADDR_SHIFT;
+            if direction == Direction::In {
+                characteristics |= HCCHAR_EPDIR_IN;
+            }
+            // Periodic transfers are scheduled for the next frame
+            if pipe.transfer_type == TransferType::Interrupt && read(HFNUM) & 1 == 0 {
+                characteristics |= HCCHAR_ODDFRM;
+            }
+
+            let size = match direction {
+                Direction::In => pipe.max_packet_size as u32,
+                Direction::Out => data.len() as u32,
+            };
+
+            write(channel_reg(channel, HCINT), u32::MAX);
+            write(
+                channel_reg(channel, HCTSIZ),
+                size | 1 << HCTSIZ_PKTCNT_SHIFT | pid << HCTSIZ_PID_SHIFT,
+            );
+            write(channel_reg(channel, HCCHAR), characteristics | HCCHAR_CHENA);
+
+            if direction == Direction::Out {
+                write_fifo(channel, data);
+            }
+
+            let mut receiver = Receiver {
+                buffer: &mut *buffer,
+                len: 0,
+                overflow: false,
+            };
+            let status = self.wait_for_channel(channel, &mut receiver)?;
+
+            if status & HCINT_STALL != 0 {
+                return Err(Error::Stall);
+            }
+            if status & HCINT_BBLERR != 0 {
+                return Err(Error::Babble);
+            }
+            if status & HCINT_DATATGLERR != 0 {
+                return Err(Error::DataToggle);
+            }
+            if status & HCINT_XFERCOMPL != 0 {
+                if receiver.overflow {
+                    return Err(Error::Overflow);
+                }
+                return Ok(Response::Ack(receiver.len));
+            }
+            if status & HCINT_NAK != 0 {
+                return Ok(Response::Nak);
+            }
+
+            // Transaction errors and frame overruns are retried
+            errors += 1;
+            if errors == MAX_TRANSACTION_ERRORS {
+                return Err(Error::Transaction);
+            }
+        }
+    }
+
+    /// Wait for the transaction on `channel` to end, halt the channel and
+    /// return its interrupt status
+    fn wait_for_channel(&mut self, channel: usize, receiver: &mut Receiver) -> Result<u32, Error> {
+        let status = loop {
+            // In slave mode, the transfer only completes once its data has
+            // been popped from the RX FIFO
+            drain_rx_fifo(channel, receiver);
+
+            let status = read(channel_reg(channel, HCINT));
+            if status & HCINT_DONE != 0 {
+                break status;
+            }
+            if !self.is_connected() {
+                self.halt_channel(channel, receiver);
+                return Err(Error::Disconnected);
+            }
+        };
+
+        self.halt_channel(channel, receiver);
+
+        Ok(status)
+    }
+
+    fn halt_channel(&mut self, channel: usize, receiver: &mut Receiver) {
+        let characteristics = channel_reg(channel, HCCHAR);
+
+        if read(characteristics) & HCCHAR_CHENA != 0 {
+            write(
+                characteristics,
+                read(characteristics) | HCCHAR_CHDIS | HCCHAR_CHENA,
+            );
+
+            while read(channel_reg(channel, HCINT)) & HCINT_CHHLTD == 0 && self.is_connected() {
+                drain_rx_fifo(channel, receiver);
+            }
+        }
+
+        write(channel_reg(channel, HCINT), u32::MAX);
+    }
+}
+
+fn reg(offset: usize) -> *mut u32 {
+    (pac::USB0::PTR as usize + offset) as *mut u32
+}
+
+fn read(offset: usize) -> u32 {
+    unsafe { reg(offset).read_volatile() }
+}
+
+fn write(offset: usize, value: u32) {
+    unsafe { reg(offset).write_volatile(value) }
+}
+
+fn channel_reg(channel: usize, offset: usize) -> usize {
+    CHANNEL_BASE + channel * CHANNEL_STRIDE + offset
+}
+
+/// Set and clear bits of HPRT without disturbing its write-1-to-clear bits
+fn modify_hprt(set: u32, clear: u32) {
+    write(HPRT, (read(HPRT) & !HPRT_W1C_MASK & !clear) | set);
+}
+
+fn write_fifo(channel: usize, data: &[u8]) {
+    let fifo = FIFO_BASE + channel * FIFO_STRIDE;
+
+    for chunk in data.chunks(4) {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        write(fifo, u32::from_le_bytes(word));
+    }
+}
+
+/// Pop all entries of the RX FIFO, storing data received on `channel`
+fn drain_rx_fifo(channel: usize, receiver: &mut Receiver) {
+    while read(GINTSTS) & GINTSTS_RXFLVL != 0 {
+        let status = read(GRXSTSP);
+        let count = ((status >> GRXSTSP_BCNT_SHIFT) & GRXSTSP_BCNT_MASK) as usize;
+        let packet_status = (status >> GRXSTSP_PKTSTS_SHIFT) & GRXSTSP_PKTSTS_MASK;
+
+        if packet_status != PKTSTS_IN_DATA {
+            continue;
+        }
+
+        for offset in (0..count).step_by(4) {
+            let word = read(FIFO_BASE).to_le_bytes();
+            if (status & 0xf) as usize == channel {
+                for byte in word.iter().take(count - offset) {
+                    receiver.push(*byte);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! USB OTG full-speed peripheral
+//!
+//! The OTG_FS peripheral is a Synopsys DWC2 core. Device mode is provided by
+//! the `esp-synopsys-usb-otg` crate, which implements the `usb-device`
+//! `UsbBus` trait, so any class from the `usb-device` ecosystem (CDC-ACM, HID,
+//! MSC, ...) can be used on top of it.
+//!
+//! On the ESP32-S3, the peripheral can also act as a USB host, see [host].
+//!
+//! The peripheral is connected to the internal USB PHY, whose D- and D+
+//! lines are fixed to GPIO19 and GPIO20.
+//!
+//! # Example
+//!
+//! ```no_run
+//! let usb = USB::new(
+//!     peripherals.USB0,
+//!     io.pins.gpio19,
+//!     io.pins.gpio20,
+//!     &mut system.peripheral_clock_control,
+//! );
+//!
+//! static mut EP_MEMORY: [u32; 1024] = [0; 1024];
+//! let usb_bus = UsbBus::new(usb, unsafe { &mut EP_MEMORY });
+//!
+//! let mut serial = usbd_serial::SerialPort::new(&usb_bus);
+//! let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x303A, 0x3001))
+//!     .device_class(usbd_serial::USB_CLASS_CDC)
+//!     .build();
+//! ```
+
+pub use esp_synopsys_usb_otg::UsbBus;
+use esp_synopsys_usb_otg::UsbPeripheral;
+
+use crate::{
+    gpio::{connect_high_to_peripheral, connect_low_to_peripheral},
+    pac,
+    system::{Peripheral, PeripheralClockControl},
+    types::InputSignal,
+};
+
+#[cfg(feature = "esp32s3")]
+pub mod host;
+
+/// Implemented by the pin connected to the internal PHY's D- line
+#[doc(hidden)]
+pub trait UsbDm {}
+
+/// Implemented by the pin connected to the internal PHY's D+ line
+#[doc(hidden)]
+pub trait UsbDp {}
+
+/// The OTG_FS peripheral, to be handed to [UsbBus::new]
+pub struct USB<M, P> {
+    _usb0: pac::USB0,
+    _usb_dm: M,
+    _usb_dp: P,
+}
+
+impl<M, P> USB<M, P>
+where
+    M: UsbDm,
+    P: UsbDp,
+{
+    /// Create a new USB peripheral, taking ownership of the D- and D+ pins
+    pub fn new(
+        usb0: pac::USB0,
+        usb_dm: M,
+        usb_dp: P,
+        peripheral_clock_control: &mut PeripheralClockControl,
+    ) -> Self {
+        peripheral_clock_control.enable(Peripheral::Usb);
+
+        Self {
+            _usb0: usb0,
+            _usb_dm: usb_dm,
+            _usb_dp: usb_dp,
+        }
+    }
+}
+
+unsafe impl<M, P> Sync for USB<M, P> {}
+
+unsafe impl<M, P> UsbPeripheral for USB<M, P>
+where
+    M: UsbDm + Send,
+    P: UsbDp + Send,
+{
+    const REGISTERS: *const () = pac::USB0::PTR as *const ();
+
+    const HIGH_SPEED: bool = false;
+    const FIFO_DEPTH_WORDS: usize = 256;
+    const ENDPOINT_COUNT: usize = 5;
+
+    fn enable() {
+        let usb_wrap = unsafe { &*pac::USB_WRAP::PTR };
+
+        usb_wrap.otg_conf.modify(|_, w| {
+            w.usb_pad_enable()
+                .set_bit()
+                .phy_sel()
+                .clear_bit()
+                .clk_en()
+                .set_bit()
+                .ahb_clk_force_on()
+                .set_bit()
+                .phy_clk_force_on()
+                .set_bit()
+        });
+
+        // The internal PHY is shared with the USB Serial/JTAG controller
+        #[cfg(feature = "esp32s3")]
+        unsafe { &*pac::RTC_CNTL::PTR }
+            .usb_conf
+            .modify(|_, w| w.sw_hw_usb_phy_sel().set_bit().sw_usb_phy_sel().set_bit());
+
+        // Force device mode: B-device (mini-B connector side), valid session
+        // and valid VBUS
+        connect_high_to_peripheral(InputSignal::USB_OTG_IDDIG);
+        connect_high_to_peripheral(InputSignal::USB_SRP_BVALID);
+        connect_high_to_peripheral(InputSignal::USB_OTG_VBUSVALID);
+        connect_low_to_peripheral(InputSignal::USB_OTG_AVALID);
+
+        // Signal a full-speed device to the host
+        usb_wrap.otg_conf.modify(|_, w| {
+            w.pad_pull_override()
+                .set_bit()
+                .dp_pullup()
+                .set_bit()
+                .dp_pulldown()
+                .clear_bit()
+                .dm_pullup()
+                .clear_bit()
+                .dm_pulldown()
+                .clear_bit()
+        });
+    }
+
+    fn ahb_frequency_hz(&self) -> u32 {
+        // The core is clocked from APB
+        80_000_000
+    }
+}
@@ -0,0 +1,199 @@
+//! Peripheral ownership and unchecked access
+//!
+//! # Ownership
+//!
+//! Drivers take their peripheral instance and pins as
+//! `impl Peripheral<P = T> + 'd`. They can be passed by value, handing them to
+//! the driver for good, or by mutable reference, lending them to the driver
+//! for as long as it exists:
+//!
+//! ```no_run
+//! {
+//!     // Temporarily use GPIO1 and GPIO2 as a UART
+//!     let mut serial1 = Serial::new(&mut peripherals.UART1)
+//!         .with_tx(&mut io.pins.gpio1)
+//!         .with_rx(&mut io.pins.gpio2);
+//!     serial1.write_bytes(b"setup").ok();
+//! }
+//!
+//! // The UART and the pins can be used again once `serial1` is dropped
+//! let led = io.pins.gpio1.into_push_pull_output();
+//! ```
+//!
+//! Drivers keep the peripheral as a [PeripheralRef], which carries the
+//! lifetime of the borrow.
+//!
+//! # Unchecked access
+//!
+//! `Peripherals::take()` hands out the peripherals only once. Code which runs
+//! after they have been consumed, such as panic and exception handlers or
+//! subsystems initialized late, can get hold of them again with the `unsafe`
+//! [Peripherals::steal], or of a single peripheral with [Steal::steal]:
+//!
+//! ```no_run
+//! // e.g. in a panic handler, after UART0 has been moved into a driver
+//! let mut serial0 = Serial::new(unsafe { UART0::steal() });
+//! ```
+//!
+//! This bypasses the ownership tracking of the HAL: the caller must make sure
+//! the stolen peripheral is not used concurrently with its owner.
+
+use core::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::pac;
+pub use crate::pac::Peripherals;
+
+/// Unchecked access to a single peripheral
+pub trait Steal {
+    /// Create an instance of the peripheral, whether or not it has already
+    /// been taken
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the returned peripheral isn't used at the same
+    /// time as any other instance of it.
+    unsafe fn steal() -> Self;
+}
+
+/// A peripheral instance or pin, either owned or mutably borrowed
+///
+/// Implemented for the peripheral instances, the GPIO pins and mutable
+/// references to them.
+pub trait Peripheral: Sized {
+    /// The type of the peripheral
+    type P;
+
+    /// Create another instance of the peripheral
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the two instances aren't used at the same time.
+    unsafe fn clone_unchecked(&mut self) -> Self::P;
+
+    /// Convert into a [PeripheralRef], which is valid as long as `self` is
+    fn into_ref<'a>(mut self) -> PeripheralRef<'a, Self::P>
+    where
+        Self: 'a,
+    {
+        PeripheralRef::new(unsafe { self.clone_unchecked() })
+    }
+}
+
+impl<T> Peripheral for &mut T
+where
+    T: Peripheral,
+{
+    type P = T::P;
+
+    unsafe fn clone_unchecked(&mut self) -> Self::P {
+        T::clone_unchecked(self)
+    }
+}
+
+/// A peripheral, owned by or lent to a driver for the lifetime `'a`
+pub struct PeripheralRef<'a, T> {
+    inner: T,
+    _lifetime: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> PeripheralRef<'a, T> {
+    /// Wrap a peripheral
+    #[inline]
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Borrow the peripheral for a shorter lifetime, e.g. to construct a
+    /// temporary driver from it
+    #[inline]
+    pub fn reborrow(&mut self) -> PeripheralRef<'_, T>
+    where
+        T: Peripheral<P = T>,
+    {
+        PeripheralRef::new(unsafe { self.inner.clone_unchecked() })
+    }
+}
+
+impl<'a, T> Peripheral for PeripheralRef<'a, T>
+where
+    T: Peripheral<P = T>,
+{
+    type P = T;
+
+    unsafe fn clone_unchecked(&mut self) -> Self::P {
+        self.inner.clone_unchecked()
+    }
+}
+
+impl<'a, T> Deref for PeripheralRef<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T> DerefMut for PeripheralRef<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+/// Convert the given driver arguments into [PeripheralRef]s, shadowing them
+#[macro_export]
+macro_rules! into_ref {
+    ($($name:ident),+ $(,)?) => {
+        $(
+            #[allow(unused_mut)]
+            let mut $name = $crate::peripherals::Peripheral::into_ref($name);
+        )+
+    };
+}
+
+macro_rules! impl_steal {
+    ($($peripheral:ident),+ $(,)?) => {
+        $(
+            impl Steal for pac::$peripheral {
+                unsafe fn steal() -> Self {
+                    Peripherals::steal().$peripheral
+                }
+            }
+
+            impl Peripheral for pac::$peripheral {
+                type P = pac::$peripheral;
+
+                unsafe fn clone_unchecked(&mut self) -> Self::P {
+                    Self::steal()
+                }
+            }
+        )+
+    };
+}
+
+impl_steal!(
+    APB_CTRL, EFUSE, GPIO, I2C0, IO_MUX, LEDC, RMT, RNG, RSA, RTC_CNTL, SPI2, TIMG0, TIMG1, TWAI,
+    UART0, UART1,
+);
+
+#[cfg(feature = "esp32")]
+impl_steal!(DPORT, I2C1, RTCIO, SENS, SPI3, UART2);
+
+#[cfg(feature = "esp32c3")]
+impl_steal!(APB_SARADC, DS, HMAC, SYSTEM, SYSTIMER, USB_DEVICE);
+
+#[cfg(feature = "esp32s2")]
+impl_steal!(I2C1, RTCIO, RTC_I2C, SENS, SPI3, SYSTEM, SYSTIMER, USB0, USB_WRAP);
+
+#[cfg(feature = "esp32s3")]
+impl_steal!(
+    APB_SARADC, DMA, DS, HMAC, I2C1, LCD_CAM, RTCIO, RTC_I2C, SENS, SPI3, SYSTEM, SYSTIMER, UART2,
+    USB0, USB_DEVICE, USB_WRAP,
+);
@@ -22,7 +22,7 @@ pub use nb;
 
 #[cfg(any(feature = "esp32", feature = "esp32s2"))]
 pub use crate::analog::SensExt;
-pub use crate::system::SystemExt;
+pub use crate::{peripherals::Steal, system::SystemExt};
 
 /// All traits required for using the 1.0.0-alpha.x release of embedded-hal
 #[cfg(feature = "eh1")]
@@ -58,7 +58,7 @@ pub mod eh1 {
     };
     pub use nb;
 
-    pub use crate::system::SystemExt;
+    pub use crate::{peripherals::Steal, system::SystemExt};
 }
 
 pub use crate::macros::*;
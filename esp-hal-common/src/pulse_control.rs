@@ -91,6 +91,7 @@ use crate::{
 
 /// Errors that can occur when the peripheral is configured
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SetupError {
     /// The global configuration for the RMT peripheral is invalid
     /// (e.g. the fractional parameters are outOfBound)
@@ -102,6 +103,7 @@ pub enum SetupError {
 
 /// Errors that can occur during a transmission attempt
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum TransmissionError {
     /// Generic Transmission Error
     Failure(bool, bool, bool, bool),
@@ -115,6 +117,7 @@ pub enum TransmissionError {
 
 /// Specifies the mode with which pulses are sent out in transmitter channels
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum RepeatMode {
     /// Send sequence once
     SingleShot,
@@ -128,6 +131,7 @@ pub enum RepeatMode {
 /// Specify the clock source for the RMT peripheral
 #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ClockSource {
     /// Application-level clock
     APB    = 1,
@@ -141,6 +145,7 @@ pub enum ClockSource {
 /// variants
 #[cfg(any(feature = "esp32s2", feature = "esp32"))]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ClockSource {
     /// Reference Tick (usually configured to 1 us)
     RefTick = 0,
@@ -169,6 +174,7 @@ const RMT_RAM_START: usize = 0x60016800;
 ///
 /// Allows for the assignment of two levels and their lenghts
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct PulseCode {
     /// Logical output level in the first pulse code interval
     pub level1: bool,
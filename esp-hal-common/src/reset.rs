@@ -0,0 +1,40 @@
+//! # Software reset
+//!
+//! Resets the chip or a single CPU from software, like `esp_restart()` in
+//! ESP-IDF:
+//!
+//! ```no_run
+//! reset::software_reset();
+//! ```
+//!
+//! The reason of the last reset can be read with
+//! [reset_reason](crate::rtc_cntl::reset_reason).
+
+use crate::{pac::RTC_CNTL, Cpu};
+
+/// Reset the digital system, including the CPUs and peripherals, but not the
+/// RTC domain
+pub fn software_reset() -> ! {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl.options0.modify(|_, w| w.sw_sys_rst().set_bit());
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Reset `cpu`, leaving the peripherals untouched
+///
+/// Resetting the APP CPU does nothing on the chips which only have a PRO CPU.
+pub fn software_reset_cpu(cpu: Cpu) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    match cpu {
+        Cpu::ProCpu => rtc_cntl.options0.modify(|_, w| w.sw_procpu_rst().set_bit()),
+        #[cfg(any(feature = "esp32", feature = "esp32s3"))]
+        Cpu::AppCpu => rtc_cntl.options0.modify(|_, w| w.sw_appcpu_rst().set_bit()),
+        #[cfg(not(any(feature = "esp32", feature = "esp32s3")))]
+        Cpu::AppCpu => {}
+    }
+}
@@ -0,0 +1,143 @@
+//! ESP32 entropy source: the SAR ADC samples an internal reference voltage in
+//! test mode, and the readings are routed through I2S0 into the RNG.
+
+use crate::pac::{DPORT, I2S0, RTC_CNTL, SENS, SYSCON};
+
+/// Bit of `DPORT_WIFI_CLK_EN_REG` which clocks the RNG, the PAC does not
+/// split this register into fields
+const DPORT_WIFI_CLK_RNG_EN: u32 = 1 << 15;
+
+// Test pattern 0xad: channel 10 (test input), 12 bit, 3 dB attenuation
+const SAR2_PATTERN_TABLE: u32 = 0xadad_adad;
+
+pub(super) fn enable() {
+    let dport = unsafe { &*DPORT::ptr() };
+    let i2s0 = unsafe { &*I2S0::ptr() };
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let sens = unsafe { &*SENS::ptr() };
+    let syscon = unsafe { &*SYSCON::ptr() };
+
+    dport
+        .wifi_clk_en
+        .modify(|r, w| unsafe { w.bits(r.bits() | DPORT_WIFI_CLK_RNG_EN) });
+
+    // Enable the SAR ADC in test mode, sampling the internal reference
+    rtc_cntl
+        .test_mux
+        .modify(|_, w| unsafe { w.dtest_rtc().bits(2).ent_rtc().set_bit() });
+    sens.sar_start_force
+        .modify(|_, w| w.sar2_en_test().set_bit());
+    dport.perip_clk_en.modify(|_, w| w.i2s0_clk_en().set_bit());
+    sens.sar_start_force.modify(|_, w| {
+        w.ulp_cp_force_start_top()
+            .clear_bit()
+            .ulp_cp_start_top()
+            .clear_bit()
+    });
+
+    syscon
+        .saradc_sar2_patt_tab1
+        .write(|w| unsafe { w.bits(SAR2_PATTERN_TABLE) });
+    syscon
+        .saradc_sar2_patt_tab2
+        .write(|w| unsafe { w.bits(SAR2_PATTERN_TABLE) });
+    syscon
+        .saradc_sar2_patt_tab3
+        .write(|w| unsafe { w.bits(SAR2_PATTERN_TABLE) });
+    syscon
+        .saradc_sar2_patt_tab4
+        .write(|w| unsafe { w.bits(SAR2_PATTERN_TABLE) });
+
+    sens.sar_meas_wait2
+        .modify(|_, w| unsafe { w.force_xpd_sar().bits(3) });
+    sens.sar_read_ctrl
+        .modify(|_, w| w.sar1_dig_force().set_bit());
+    sens.sar_read_ctrl2
+        .modify(|_, w| w.sar2_dig_force().set_bit());
+    syscon.saradc_ctrl.modify(|_, w| unsafe {
+        w.saradc_sar2_mux()
+            .set_bit()
+            .saradc_sar_clk_div()
+            .bits(4)
+            .saradc_work_mode()
+            .bits(0)
+            .saradc_sar_sel()
+            .set_bit()
+            .saradc_data_sar_sel()
+            .clear_bit()
+    });
+    syscon
+        .saradc_fsm
+        .modify(|_, w| unsafe { w.saradc_rstb_wait().bits(8).saradc_start_wait().bits(10) });
+
+    // Route the ADC readings through I2S0, which mixes them into the RNG
+    i2s0.sample_rate_conf
+        .modify(|_, w| unsafe { w.rx_bck_div_num().bits(20) });
+    syscon
+        .saradc_ctrl
+        .modify(|_, w| w.saradc_data_to_i2s().set_bit());
+    i2s0.conf2.modify(|_, w| {
+        w.camera_en()
+            .clear_bit()
+            .lcd_en()
+            .set_bit()
+            .data_enable()
+            .set_bit()
+            .data_enable_test_en()
+            .set_bit()
+    });
+    i2s0.conf.modify(|_, w| w.rx_start().set_bit());
+}
+
+pub(super) fn disable() {
+    let dport = unsafe { &*DPORT::ptr() };
+    let i2s0 = unsafe { &*I2S0::ptr() };
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let sens = unsafe { &*SENS::ptr() };
+    let syscon = unsafe { &*SYSCON::ptr() };
+
+    i2s0.conf.modify(|_, w| w.rx_start().clear_bit());
+    i2s0.conf.modify(|_, w| w.rx_reset().set_bit());
+    i2s0.conf.modify(|_, w| w.rx_reset().clear_bit());
+    i2s0.conf2.modify(|_, w| {
+        w.camera_en()
+            .clear_bit()
+            .lcd_en()
+            .clear_bit()
+            .data_enable_test_en()
+            .clear_bit()
+            .data_enable()
+            .clear_bit()
+    });
+    dport
+        .perip_clk_en
+        .modify(|_, w| w.i2s0_clk_en().clear_bit());
+
+    sens.sar_read_ctrl
+        .modify(|_, w| w.sar1_dig_force().clear_bit());
+    sens.sar_read_ctrl2
+        .modify(|_, w| w.sar2_dig_force().clear_bit());
+
+    sens.sar_start_force
+        .modify(|_, w| w.sar2_en_test().clear_bit());
+    syscon.saradc_ctrl.modify(|_, w| {
+        w.saradc_sar2_mux()
+            .clear_bit()
+            .saradc_sar_sel()
+            .clear_bit()
+            .saradc_data_to_i2s()
+            .clear_bit()
+    });
+    sens.sar_meas_wait2
+        .modify(|_, w| unsafe { w.force_xpd_sar().bits(0) });
+    syscon
+        .saradc_fsm
+        .modify(|_, w| unsafe { w.saradc_start_wait().bits(8) });
+
+    dport.perip_rst_en.modify(|_, w| w.i2s0_rst().set_bit());
+    dport.perip_rst_en.modify(|_, w| w.i2s0_rst().clear_bit());
+
+    rtc_cntl
+        .test_mux
+        .modify(|_, w| unsafe { w.ent_rtc().clear_bit().dtest_rtc().bits(0) });
+}
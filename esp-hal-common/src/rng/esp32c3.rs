@@ -0,0 +1,134 @@
+//! ESP32-C3 entropy sources: the jitter of the internal 8 MHz RC oscillator,
+//! and the SAR ADC periodically sampling an internal reference voltage.
+
+use paste::paste;
+
+use crate::{
+    pac::{APB_SARADC, RTC_CNTL, SYSTEM},
+    regi2c_write_mask,
+    rom::regi2c_ctrl_write_reg_mask,
+};
+
+const I2C_SAR_ADC: u32 = 0x69;
+const I2C_SAR_ADC_HOSTID: u32 = 0;
+
+const ADC_SARADC_DTEST_RTC_ADDR: u32 = 0x7;
+const ADC_SARADC_DTEST_RTC_ADDR_MSB: u32 = 1;
+const ADC_SARADC_DTEST_RTC_ADDR_LSB: u32 = 0;
+
+const ADC_SARADC_ENT_RTC_ADDR: u32 = 0x7;
+const ADC_SARADC_ENT_RTC_ADDR_MSB: u32 = 3;
+const ADC_SARADC_ENT_RTC_ADDR_LSB: u32 = 3;
+
+const ADC_SARADC1_ENCAL_GND_ADDR: u32 = 0x7;
+const ADC_SARADC1_ENCAL_GND_ADDR_MSB: u32 = 5;
+const ADC_SARADC1_ENCAL_GND_ADDR_LSB: u32 = 5;
+
+const ADC_SARADC2_ENCAL_GND_ADDR: u32 = 0x7;
+const ADC_SARADC2_ENCAL_GND_ADDR_MSB: u32 = 7;
+const ADC_SARADC2_ENCAL_GND_ADDR_LSB: u32 = 7;
+
+// Sample SAR ADC2 channel 9 and SAR ADC1 channel 0, both with 2.5 dB
+// attenuation; the inputs are disconnected so only noise is measured.
+const SAR2_CHANNEL: u32 = 9;
+const SAR2_ATTEN: u32 = 1;
+const SAR1_ATTEN: u32 = 1;
+const PATTERN_BIT_WIDTH: u32 = 6;
+
+pub(super) fn enable() {
+    let apb_saradc = unsafe { &*APB_SARADC::ptr() };
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let system = unsafe { &*SYSTEM::ptr() };
+
+    rtc_cntl.clk_conf.modify(|_, w| w.dig_clk8m_en().set_bit());
+
+    system
+        .perip_clk_en0
+        .modify(|_, w| w.apb_saradc_clk_en().set_bit());
+    system
+        .perip_rst_en0
+        .modify(|_, w| w.apb_saradc_rst().clear_bit());
+
+    // Clock the ADC controller from the APB clock
+    apb_saradc
+        .apb_adc_clkm_conf
+        .modify(|_, w| unsafe { w.clk_sel().bits(2).clk_en().set_bit() });
+
+    rtc_cntl
+        .sensor_ctrl
+        .modify(|_, w| unsafe { w.force_xpd_sar().bits(0b11) });
+    apb_saradc
+        .ctrl
+        .modify(|_, w| unsafe { w.saradc_xpd_sar_force().bits(0b11) });
+
+    // Sample the internal reference voltage instead of the pads
+    rtc_cntl.ana_conf.modify(|_, w| w.sar_i2c_pu().set_bit());
+    unsafe {
+        regi2c_write_mask!(I2C_SAR_ADC, ADC_SARADC_DTEST_RTC_ADDR, 2);
+        regi2c_write_mask!(I2C_SAR_ADC, ADC_SARADC_ENT_RTC_ADDR, 1);
+        regi2c_write_mask!(I2C_SAR_ADC, ADC_SARADC1_ENCAL_GND_ADDR, 1);
+        regi2c_write_mask!(I2C_SAR_ADC, ADC_SARADC2_ENCAL_GND_ADDR, 1);
+    }
+
+    apb_saradc
+        .ctrl
+        .modify(|_, w| unsafe { w.saradc_sar_clk_div().bits(15) });
+
+    let pattern_one = (SAR2_CHANNEL << 2) | SAR2_ATTEN;
+    let pattern_two = SAR1_ATTEN;
+    let pattern_table =
+        (pattern_two << (3 * PATTERN_BIT_WIDTH)) | (pattern_one << (2 * PATTERN_BIT_WIDTH));
+    apb_saradc
+        .sar_patt_tab1
+        .write(|w| unsafe { w.saradc_sar_patt_tab1().bits(pattern_table) });
+
+    // Two pattern table entries, the field counts from 0
+    apb_saradc
+        .ctrl
+        .modify(|_, w| unsafe { w.saradc_sar_patt_len().bits(1) });
+
+    // Let the ADC timer trigger a conversion every 200 ADC clock cycles
+    apb_saradc.ctrl2.modify(|_, w| unsafe {
+        w.saradc_timer_target()
+            .bits(200)
+            .saradc_timer_en()
+            .set_bit()
+    });
+}
+
+pub(super) fn disable() {
+    let apb_saradc = unsafe { &*APB_SARADC::ptr() };
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let system = unsafe { &*SYSTEM::ptr() };
+
+    unsafe {
+        regi2c_write_mask!(I2C_SAR_ADC, ADC_SARADC1_ENCAL_GND_ADDR, 0);
+        regi2c_write_mask!(I2C_SAR_ADC, ADC_SARADC2_ENCAL_GND_ADDR, 0);
+        regi2c_write_mask!(I2C_SAR_ADC, ADC_SARADC_DTEST_RTC_ADDR, 0);
+        regi2c_write_mask!(I2C_SAR_ADC, ADC_SARADC_ENT_RTC_ADDR, 0);
+    }
+
+    apb_saradc
+        .ctrl2
+        .modify(|_, w| w.saradc_timer_en().clear_bit());
+    apb_saradc
+        .ctrl
+        .modify(|_, w| unsafe { w.saradc_xpd_sar_force().bits(0) });
+    rtc_cntl
+        .sensor_ctrl
+        .modify(|_, w| unsafe { w.force_xpd_sar().bits(0) });
+    apb_saradc
+        .apb_adc_clkm_conf
+        .modify(|_, w| w.clk_en().clear_bit());
+
+    system
+        .perip_rst_en0
+        .modify(|_, w| w.apb_saradc_rst().set_bit());
+    system
+        .perip_clk_en0
+        .modify(|_, w| w.apb_saradc_clk_en().clear_bit());
+
+    rtc_cntl
+        .clk_conf
+        .modify(|_, w| w.dig_clk8m_en().clear_bit());
+}
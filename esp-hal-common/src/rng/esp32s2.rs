@@ -0,0 +1,16 @@
+//! ESP32-S2 entropy source: the jitter of the internal 8 MHz RC oscillator is
+//! mixed into the RNG once the oscillator is enabled for the digital domain.
+
+use crate::pac::RTC_CNTL;
+
+pub(super) fn enable() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    rtc_cntl.clk_conf.modify(|_, w| w.dig_clk8m_en().set_bit());
+}
+
+pub(super) fn disable() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    rtc_cntl
+        .clk_conf
+        .modify(|_, w| w.dig_clk8m_en().clear_bit());
+}
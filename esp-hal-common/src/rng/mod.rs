@@ -0,0 +1,115 @@
+//! Random number generator driver
+
+use core::convert::Infallible;
+
+use embedded_hal::blocking::rng::Read;
+
+use crate::{pac::RNG, rom::esp_rom_delay_us};
+
+#[cfg_attr(feature = "esp32", path = "esp32.rs")]
+#[cfg_attr(feature = "esp32c3", path = "esp32c3.rs")]
+#[cfg_attr(feature = "esp32s2", path = "esp32s2.rs")]
+#[cfg_attr(feature = "esp32s3", path = "esp32s3.rs")]
+mod entropy;
+
+/// Minimum time between two reads for the second one to contain fresh
+/// entropy, in microseconds
+#[cfg(feature = "esp32")]
+const READ_INTERVAL_US: u32 = 23;
+/// Minimum time between two reads for the second one to contain fresh
+/// entropy, in microseconds
+#[cfg(not(feature = "esp32"))]
+const READ_INTERVAL_US: u32 = 1;
+
+/// Number of reads discarded by [Rng::wait_for_entropy]
+const WARMUP_READS: usize = 32;
+
+/// Random Number Generator
+///
+/// It should be noted that there are certain pre-conditions which must be met
+/// in order for the RNG to produce *true* random numbers. The hardware RNG
+/// produces true random numbers under any of the following conditions:
+///
+/// - RF subsystem is enabled (i.e. Wi-Fi or Bluetooth are enabled).
+/// - An internal entropy source has been enabled by calling
+///   [Rng::enable_entropy_source] (or `bootloader_random_enable()` in ESP-IDF)
+///   and not yet disabled.
+/// - While the ESP-IDF Second stage bootloader is running. This is because the
+///   default ESP-IDF bootloader implementation calls
+///   `bootloader_random_enable()` when the bootloader starts, and
+///   `bootloader_random_disable()` before executing the app.
+///
+/// When any of these conditions are true, samples of physical noise are
+/// continuously mixed into the internal hardware RNG state to provide entropy.
+/// If none of the above conditions are true, the output of the RNG should be
+/// considered pseudo-random only.
+///
+/// For more information, please refer to the ESP-IDF documentation:
+/// <https://docs.espressif.com/projects/esp-idf/en/latest/esp32/api-reference/system/random.html>
+#[derive(Debug)]
+pub struct Rng {
+    rng: RNG,
+}
+
+impl Rng {
+    /// Create a new random number generator instance
+    pub fn new(rng: RNG) -> Self {
+        Self { rng }
+    }
+
+    #[inline]
+    /// Reads currently available `u32` integer from `RNG`
+    pub fn random(&mut self) -> u32 {
+        self.rng.data.read().bits()
+    }
+
+    /// Enable the internal entropy source
+    ///
+    /// Makes the output of the RNG truly random while the RF subsystem is
+    /// disabled. Depending on the chip, the source is the SAR ADC sampling an
+    /// internal reference voltage (ESP32, ESP32-C3, ESP32-S3) and/or the
+    /// internal 8 MHz RC oscillator (ESP32-C3, ESP32-S2, ESP32-S3).
+    ///
+    /// While enabled, the SAR ADC (and on the ESP32 also I2S0) must not be
+    /// used for anything else. Call [Rng::disable_entropy_source] before
+    /// using the ADC or enabling the RF subsystem.
+    pub fn enable_entropy_source(&mut self) {
+        entropy::enable();
+    }
+
+    /// Disable the internal entropy source again
+    pub fn disable_entropy_source(&mut self) {
+        entropy::disable();
+    }
+
+    /// Wait until enough entropy has been mixed into the RNG state
+    ///
+    /// Call this after enabling an entropy source (or early after boot),
+    /// before generating keys or other secrets. It discards a number of
+    /// outputs, leaving the hardware enough time in between to mix in fresh
+    /// noise samples.
+    pub fn wait_for_entropy(&mut self) {
+        for _ in 0..WARMUP_READS {
+            unsafe { esp_rom_delay_us(READ_INTERVAL_US) };
+            self.random();
+        }
+    }
+
+    /// Return the raw interface to the underlying `Rng` instance
+    pub fn free(self) -> RNG {
+        self.rng
+    }
+}
+
+impl Read for Rng {
+    type Error = Infallible;
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        for chunk in buffer.chunks_mut(4) {
+            let bytes = self.random().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+
+        Ok(())
+    }
+}
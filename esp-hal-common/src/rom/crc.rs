@@ -0,0 +1,98 @@
+//! CRC functions provided by the mask ROM
+//!
+//! The ROM implements table-driven CRC8/16/32 in both bit orders. Using these
+//! saves the flash space for the lookup tables of a software implementation.
+//!
+//! The `crc` argument is the CRC of the preceding data, so a checksum can be
+//! computed over multiple buffers by chaining calls. Pass `0` (for the
+//! little-endian variants) or `!0` (for the big-endian variants) as the
+//! initial value to get the standard checksums:
+//!
+//! ```no_run
+//! // CRC-32 as used by Ethernet, zlib, PNG, ...
+//! let crc = crc32_le(0, b"123456789");
+//! assert_eq!(crc, 0xcbf4_3926);
+//! ```
+
+#[cfg(feature = "esp32")]
+mod addresses {
+    pub const CRC32_LE: u32 = 0x4005_cfec;
+    pub const CRC32_BE: u32 = 0x4005_d024;
+    pub const CRC16_LE: u32 = 0x4005_d05c;
+    pub const CRC16_BE: u32 = 0x4005_d09c;
+    pub const CRC8_LE: u32 = 0x4005_d0dc;
+    pub const CRC8_BE: u32 = 0x4005_d114;
+}
+
+#[cfg(feature = "esp32s2")]
+mod addresses {
+    pub const CRC32_LE: u32 = 0x4000_ff64;
+    pub const CRC32_BE: u32 = 0x4000_ff94;
+    pub const CRC16_LE: u32 = 0x4000_ffc8;
+    pub const CRC16_BE: u32 = 0x4000_fff8;
+    pub const CRC8_LE: u32 = 0x4001_002c;
+    pub const CRC8_BE: u32 = 0x4001_0058;
+}
+
+#[cfg(feature = "esp32s3")]
+mod addresses {
+    pub const CRC32_LE: u32 = 0x4000_1c98;
+    pub const CRC32_BE: u32 = 0x4000_1ce0;
+    pub const CRC16_LE: u32 = 0x4000_1cb0;
+    pub const CRC16_BE: u32 = 0x4000_1cf8;
+    pub const CRC8_LE: u32 = 0x4000_1cc8;
+    pub const CRC8_BE: u32 = 0x4000_1d10;
+}
+
+#[cfg(feature = "esp32c3")]
+mod addresses {
+    pub const CRC32_LE: u32 = 0x4000_0318;
+    pub const CRC32_BE: u32 = 0x4000_0324;
+    pub const CRC16_LE: u32 = 0x4000_031c;
+    pub const CRC16_BE: u32 = 0x4000_0328;
+    pub const CRC8_LE: u32 = 0x4000_0320;
+    pub const CRC8_BE: u32 = 0x4000_032c;
+}
+
+use addresses::*;
+
+type Crc32Fn = unsafe extern "C" fn(crc: u32, buf: *const u8, len: u32) -> u32;
+type Crc16Fn = unsafe extern "C" fn(crc: u16, buf: *const u8, len: u32) -> u16;
+type Crc8Fn = unsafe extern "C" fn(crc: u8, buf: *const u8, len: u32) -> u8;
+
+/// CRC-32 with the reflected polynomial `0xedb88320`
+pub fn crc32_le(crc: u32, buf: &[u8]) -> u32 {
+    // cast to usize is just needed because of the way we run clippy in CI
+    let f: Crc32Fn = unsafe { core::mem::transmute(CRC32_LE as usize) };
+    unsafe { f(crc, buf.as_ptr(), buf.len() as u32) }
+}
+
+/// CRC-32 with the polynomial `0x04c11db7`
+pub fn crc32_be(crc: u32, buf: &[u8]) -> u32 {
+    let f: Crc32Fn = unsafe { core::mem::transmute(CRC32_BE as usize) };
+    unsafe { f(crc, buf.as_ptr(), buf.len() as u32) }
+}
+
+/// CRC-16 with the reflected polynomial `0x8408`
+pub fn crc16_le(crc: u16, buf: &[u8]) -> u16 {
+    let f: Crc16Fn = unsafe { core::mem::transmute(CRC16_LE as usize) };
+    unsafe { f(crc, buf.as_ptr(), buf.len() as u32) }
+}
+
+/// CRC-16 with the polynomial `0x1021`
+pub fn crc16_be(crc: u16, buf: &[u8]) -> u16 {
+    let f: Crc16Fn = unsafe { core::mem::transmute(CRC16_BE as usize) };
+    unsafe { f(crc, buf.as_ptr(), buf.len() as u32) }
+}
+
+/// CRC-8 with the reflected polynomial `0x8c`
+pub fn crc8_le(crc: u8, buf: &[u8]) -> u8 {
+    let f: Crc8Fn = unsafe { core::mem::transmute(CRC8_LE as usize) };
+    unsafe { f(crc, buf.as_ptr(), buf.len() as u32) }
+}
+
+/// CRC-8 with the polynomial `0x31`
+pub fn crc8_be(crc: u8, buf: &[u8]) -> u8 {
+    let f: Crc8Fn = unsafe { core::mem::transmute(CRC8_BE as usize) };
+    unsafe { f(crc, buf.as_ptr(), buf.len() as u32) }
+}
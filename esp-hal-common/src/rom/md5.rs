@@ -0,0 +1,121 @@
+//! MD5 message digest provided by the mask ROM
+//!
+//! MD5 is not a secure hash function; only use it for integrity checks where
+//! the counterpart mandates it (e.g. OTA images or the espflash protocol).
+//!
+//! ```no_run
+//! let mut context = Context::new();
+//! context.consume(b"hello ");
+//! context.consume(b"world");
+//! let digest = context.compute();
+//!
+//! // or in one go
+//! let digest = compute(b"hello world");
+//! ```
+
+#[cfg(feature = "esp32")]
+mod addresses {
+    pub const MD5_INIT: u32 = 0x4005_da7c;
+    pub const MD5_UPDATE: u32 = 0x4005_da9c;
+    pub const MD5_FINAL: u32 = 0x4005_db1c;
+}
+
+#[cfg(feature = "esp32s2")]
+mod addresses {
+    pub const MD5_INIT: u32 = 0x4000_526c;
+    pub const MD5_UPDATE: u32 = 0x4000_528c;
+    pub const MD5_FINAL: u32 = 0x4000_530c;
+}
+
+#[cfg(feature = "esp32s3")]
+mod addresses {
+    pub const MD5_INIT: u32 = 0x4000_1c5c;
+    pub const MD5_UPDATE: u32 = 0x4000_1c68;
+    pub const MD5_FINAL: u32 = 0x4000_1c74;
+}
+
+#[cfg(feature = "esp32c3")]
+mod addresses {
+    pub const MD5_INIT: u32 = 0x4000_0308;
+    pub const MD5_UPDATE: u32 = 0x4000_030c;
+    pub const MD5_FINAL: u32 = 0x4000_0310;
+}
+
+use addresses::*;
+
+/// State of the ROM MD5 implementation, matches `struct MD5Context`
+#[repr(C)]
+#[derive(Clone)]
+struct RawContext {
+    buf: [u32; 4],
+    bits: [u32; 2],
+    input: [u8; 64],
+}
+
+/// A 128-bit MD5 digest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Digest(pub [u8; 16]);
+
+impl core::ops::Deref for Digest {
+    type Target = [u8; 16];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// An incremental MD5 computation
+#[derive(Clone)]
+pub struct Context {
+    raw: RawContext,
+}
+
+impl Context {
+    /// Create a new MD5 context
+    pub fn new() -> Self {
+        let mut raw = RawContext {
+            buf: [0; 4],
+            bits: [0; 2],
+            input: [0; 64],
+        };
+
+        // cast to usize is just needed because of the way we run clippy in CI
+        let md5_init: unsafe extern "C" fn(context: *mut RawContext) =
+            unsafe { core::mem::transmute(MD5_INIT as usize) };
+        unsafe { md5_init(&mut raw) };
+
+        Self { raw }
+    }
+
+    /// Feed data into the digest
+    pub fn consume(&mut self, data: &[u8]) {
+        let md5_update: unsafe extern "C" fn(context: *mut RawContext, buf: *const u8, len: u32) =
+            unsafe { core::mem::transmute(MD5_UPDATE as usize) };
+        unsafe { md5_update(&mut self.raw, data.as_ptr(), data.len() as u32) };
+    }
+
+    /// Finish the computation and return the digest
+    pub fn compute(mut self) -> Digest {
+        let mut digest = [0u8; 16];
+
+        let md5_final: unsafe extern "C" fn(digest: *mut u8, context: *mut RawContext) =
+            unsafe { core::mem::transmute(MD5_FINAL as usize) };
+        unsafe { md5_final(digest.as_mut_ptr(), &mut self.raw) };
+
+        Digest(digest)
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Compute the MD5 digest of `data` in one go
+pub fn compute(data: &[u8]) -> Digest {
+    let mut context = Context::new();
+    context.consume(data);
+    context.compute()
+}
@@ -1,5 +1,8 @@
 pub use paste::paste;
 
+pub mod crc;
+pub mod md5;
+
 /// Pauses execution for us microseconds
 #[inline(always)]
 pub unsafe fn esp_rom_delay_us(us: u32) {
@@ -38,6 +41,44 @@ pub unsafe fn ets_update_cpu_frequency(ticks_per_us: u32) {
     rom_ets_update_cpu_frequency(ticks_per_us);
 }
 
+/// The reason of the last reset of CPU `cpu_num`, e.g. 5 for a wakeup from
+/// deep sleep
+#[inline(always)]
+pub unsafe fn rtc_get_reset_reason(cpu_num: u32) -> u32 {
+    #[cfg(feature = "esp32")]
+    const RTC_GET_RESET_REASON: u32 = 0x4000_81d4;
+    #[cfg(feature = "esp32s2")]
+    const RTC_GET_RESET_REASON: u32 = 0x4000_ff58;
+    #[cfg(feature = "esp32s3")]
+    const RTC_GET_RESET_REASON: u32 = 0x4000_057c;
+    #[cfg(feature = "esp32c3")]
+    const RTC_GET_RESET_REASON: u32 = 0x4000_0018;
+
+    // cast to usize is just needed because of the way we run clippy in CI
+    let rom_rtc_get_reset_reason: fn(cpu_num: u32) -> u32 =
+        core::mem::transmute(RTC_GET_RESET_REASON as usize);
+
+    rom_rtc_get_reset_reason(cpu_num)
+}
+
+/// Store the CRC of the RTC fast memory, which the ROM checks before running
+/// the deep sleep wake stub
+#[cfg(not(feature = "esp32s2"))]
+#[inline(always)]
+pub unsafe fn set_rtc_memory_crc() {
+    #[cfg(feature = "esp32")]
+    const SET_RTC_MEMORY_CRC: u32 = 0x4000_8208;
+    #[cfg(feature = "esp32s3")]
+    const SET_RTC_MEMORY_CRC: u32 = 0x4000_05d0;
+    #[cfg(feature = "esp32c3")]
+    const SET_RTC_MEMORY_CRC: u32 = 0x4000_0034;
+
+    // cast to usize is just needed because of the way we run clippy in CI
+    let rom_set_rtc_memory_crc: fn() = core::mem::transmute(SET_RTC_MEMORY_CRC as usize);
+
+    rom_set_rtc_memory_crc();
+}
+
 #[inline(always)]
 pub unsafe fn regi2c_ctrl_write_reg(block: u32, block_hostid: u32, reg_add: u32, indata: u32) {
     #[cfg(feature = "esp32")]
@@ -0,0 +1,420 @@
+//! RSA accelerator driver
+//!
+//! The RSA accelerator provides hardware support for the big-number operations
+//! used by RSA and Diffie-Hellman: modular exponentiation, modular
+//! multiplication and (non-modular) multiplication.
+//!
+//! All operands are passed as big-endian byte slices, which is the encoding
+//! used by most TLS and ASN.1 libraries. Internally the operands are converted
+//! to the little-endian word order the peripheral expects, and the Montgomery
+//! parameters (`M'` and `R² mod M`) are derived from the modulus.
+//!
+//! # Example
+//!
+//! ```no_run
+//! let mut rsa = Rsa::new(peripherals.RSA, &mut system.peripheral_clock_control);
+//!
+//! let mut result = [0u8; 64];
+//! rsa.modular_exponentiation(&BASE, &EXPONENT, &MODULUS, &mut result)
+//!     .unwrap();
+//! ```
+
+use crate::{
+    pac::RSA,
+    system::{Peripheral, PeripheralClockControl},
+};
+
+/// Maximum supported operand size, in 32-bit words
+#[cfg(feature = "esp32c3")]
+pub const MAX_OPERAND_WORDS: usize = 96;
+/// Maximum supported operand size, in 32-bit words
+#[cfg(not(feature = "esp32c3"))]
+pub const MAX_OPERAND_WORDS: usize = 128;
+
+// Offsets of the operand memory blocks, relative to the peripheral base
+const M_MEM: usize = 0x000;
+const Z_MEM: usize = 0x200;
+const Y_MEM: usize = 0x400;
+const X_MEM: usize = 0x600;
+
+/// RSA-specific errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// An operand is empty, or larger than the hardware supports
+    InvalidOperandLength,
+    /// The modulus is even; Montgomery arithmetic requires an odd modulus
+    EvenModulus,
+    /// The result buffer does not have the required length
+    InvalidResultLength,
+}
+
+/// RSA accelerator
+pub struct Rsa {
+    rsa: RSA,
+}
+
+impl Rsa {
+    /// Create a new RSA accelerator instance
+    ///
+    /// Enables the peripheral clock, takes the peripheral out of reset and
+    /// powers up its operand memory.
+    pub fn new(rsa: RSA, peripheral_clock_control: &mut PeripheralClockControl) -> Self {
+        peripheral_clock_control.enable(Peripheral::Rsa);
+
+        let mut rsa = Self { rsa };
+        rsa.wait_for_memory_init();
+        rsa
+    }
+
+    /// Return the raw interface to the underlying RSA instance
+    pub fn free(self) -> RSA {
+        self.rsa
+    }
+
+    /// Enable or disable the constant-time option
+    ///
+    /// When enabled (the default), the duration of an exponentiation does not
+    /// depend on the value of the exponent. Disabling it speeds up operations
+    /// with short or sparse exponents, but must only be done when the exponent
+    /// is not secret (e.g. signature verification with `e = 65537`).
+    #[cfg(not(feature = "esp32"))]
+    pub fn set_constant_time(&mut self, enable: bool) {
+        self.rsa
+            .constant_time
+            .write(|w| unsafe { w.bits(enable as u32) });
+    }
+
+    /// Configure the search acceleration option
+    ///
+    /// When a position is given, the hardware skips all exponent bits above
+    /// it, which speeds up exponentiation with short exponents. Like
+    /// [`Rsa::set_constant_time`], this leaks the length of the exponent and
+    /// must only be used with public exponents.
+    #[cfg(not(feature = "esp32"))]
+    pub fn set_search_position(&mut self, position: Option<u32>) {
+        match position {
+            Some(position) => {
+                self.rsa.search_pos.write(|w| unsafe { w.bits(position) });
+                self.rsa.search_enable.write(|w| unsafe { w.bits(1) });
+            }
+            None => {
+                self.rsa.search_enable.write(|w| unsafe { w.bits(0) });
+            }
+        }
+    }
+
+    /// Compute `result = base ^ exponent mod modulus`
+    ///
+    /// `base` and `exponent` must not be longer than `modulus`, and `result`
+    /// must have the same length as `modulus`.
+    pub fn modular_exponentiation(
+        &mut self,
+        base: &[u8],
+        exponent: &[u8],
+        modulus: &[u8],
+        result: &mut [u8],
+    ) -> Result<(), Error> {
+        let words = operand_words(modulus)?;
+        check_operand(base, modulus)?;
+        check_operand(exponent, modulus)?;
+        check_result(result, modulus.len())?;
+
+        let (m, m_prime, r) = montgomery_parameters(modulus, words)?;
+
+        self.write_words(M_MEM, &m[..words]);
+        self.write_operand(X_MEM, words, base);
+        self.write_operand(Y_MEM, words, exponent);
+        self.write_words(Z_MEM, &r[..words]);
+        self.rsa.m_prime.write(|w| unsafe { w.bits(m_prime) });
+
+        #[cfg(feature = "esp32")]
+        {
+            self.rsa
+                .modexp_mode
+                .write(|w| unsafe { w.bits((words / 16 - 1) as u32) });
+            self.rsa.modexp_start.write(|w| unsafe { w.bits(1) });
+        }
+
+        #[cfg(not(feature = "esp32"))]
+        {
+            self.rsa
+                .mode
+                .write(|w| unsafe { w.bits((words - 1) as u32) });
+            self.rsa.set_start_modexp.write(|w| unsafe { w.bits(1) });
+        }
+
+        self.wait_for_idle();
+        self.read_operand(Z_MEM, words, result);
+
+        Ok(())
+    }
+
+    /// Compute `result = x * y mod modulus`
+    ///
+    /// `x` and `y` must not be longer than `modulus`, and `result` must have
+    /// the same length as `modulus`.
+    pub fn modular_multiplication(
+        &mut self,
+        x: &[u8],
+        y: &[u8],
+        modulus: &[u8],
+        result: &mut [u8],
+    ) -> Result<(), Error> {
+        let words = operand_words(modulus)?;
+        check_operand(x, modulus)?;
+        check_operand(y, modulus)?;
+        check_result(result, modulus.len())?;
+
+        let (m, m_prime, r) = montgomery_parameters(modulus, words)?;
+
+        self.write_words(M_MEM, &m[..words]);
+        self.rsa.m_prime.write(|w| unsafe { w.bits(m_prime) });
+
+        // The ESP32 performs modular multiplication in two steps: the first
+        // one multiplies X by R² mod M, the second one multiplies the
+        // intermediate result by Y.
+        #[cfg(feature = "esp32")]
+        {
+            self.write_operand(X_MEM, words, x);
+            self.write_words(Y_MEM, &r[..words]);
+            self.rsa
+                .mult_mode
+                .write(|w| unsafe { w.bits((words / 16 - 1) as u32) });
+            self.rsa.mult_start.write(|w| unsafe { w.bits(1) });
+            self.wait_for_idle();
+
+            self.write_operand(X_MEM, words, y);
+            self.rsa.mult_start.write(|w| unsafe { w.bits(1) });
+            self.wait_for_idle();
+        }
+
+        #[cfg(not(feature = "esp32"))]
+        {
+            self.write_operand(X_MEM, words, x);
+            self.write_operand(Y_MEM, words, y);
+            self.write_words(Z_MEM, &r[..words]);
+            self.rsa
+                .mode
+                .write(|w| unsafe { w.bits((words - 1) as u32) });
+            self.rsa.set_start_modmult.write(|w| unsafe { w.bits(1) });
+            self.wait_for_idle();
+        }
+
+        self.read_operand(Z_MEM, words, result);
+
+        Ok(())
+    }
+
+    /// Compute `result = x * y`
+    ///
+    /// `x` and `y` must have the same length, and `result` must be twice as
+    /// long as the operands.
+    pub fn multiplication(&mut self, x: &[u8], y: &[u8], result: &mut [u8]) -> Result<(), Error> {
+        let words = operand_words(x)?;
+        if y.len() != x.len() || words * 2 > MAX_OPERAND_WORDS {
+            return Err(Error::InvalidOperandLength);
+        }
+        check_result(result, x.len() * 2)?;
+
+        // Y is placed in the upper half of the Z memory block
+        self.write_operand(X_MEM, words, x);
+        self.write_operand(Z_MEM + words * 4, words, y);
+
+        #[cfg(feature = "esp32")]
+        {
+            self.rsa
+                .mult_mode
+                .write(|w| unsafe { w.bits((words / 16 - 1 + 8) as u32) });
+            self.rsa.mult_start.write(|w| unsafe { w.bits(1) });
+        }
+
+        #[cfg(not(feature = "esp32"))]
+        {
+            self.rsa
+                .mode
+                .write(|w| unsafe { w.bits((words * 2 - 1) as u32) });
+            self.rsa.set_start_mult.write(|w| unsafe { w.bits(1) });
+        }
+
+        self.wait_for_idle();
+        self.read_operand(Z_MEM, words * 2, result);
+
+        Ok(())
+    }
+
+    fn wait_for_memory_init(&mut self) {
+        #[cfg(feature = "esp32")]
+        while self.rsa.clean.read().bits() == 0 {}
+
+        #[cfg(not(feature = "esp32"))]
+        while self.rsa.query_clean.read().bits() == 0 {}
+    }
+
+    fn wait_for_idle(&mut self) {
+        #[cfg(feature = "esp32")]
+        {
+            while self.rsa.interrupt.read().bits() == 0 {}
+            self.rsa.interrupt.write(|w| unsafe { w.bits(1) });
+        }
+
+        #[cfg(not(feature = "esp32"))]
+        {
+            while self.rsa.query_idle.read().bits() == 0 {}
+            self.rsa.clear_interrupt.write(|w| unsafe { w.bits(1) });
+        }
+    }
+
+    fn block_ptr(&self, offset: usize) -> *mut u32 {
+        (RSA::PTR as usize + offset) as *mut u32
+    }
+
+    /// Write a big-endian byte operand into a memory block, zero-extended to
+    /// `words` words
+    fn write_operand(&mut self, offset: usize, words: usize, bytes: &[u8]) {
+        let block = self.block_ptr(offset);
+
+        for i in 0..words {
+            unsafe { block.add(i).write_volatile(word_from_be_bytes(bytes, i)) };
+        }
+    }
+
+    fn write_words(&mut self, offset: usize, words: &[u32]) {
+        let block = self.block_ptr(offset);
+
+        for (i, word) in words.iter().enumerate() {
+            unsafe { block.add(i).write_volatile(*word) };
+        }
+    }
+
+    /// Read `words` words from a memory block into a big-endian byte buffer
+    fn read_operand(&self, offset: usize, words: usize, bytes: &mut [u8]) {
+        let block = self.block_ptr(offset);
+        let len = bytes.len();
+
+        for i in 0..words {
+            let word = unsafe { block.add(i).read_volatile() }.to_le_bytes();
+
+            for (j, byte) in word.iter().enumerate() {
+                let pos = i * 4 + j;
+                if pos < len {
+                    bytes[len - 1 - pos] = *byte;
+                }
+            }
+        }
+    }
+}
+
+/// Number of words the hardware has to operate on for the given modulus
+fn operand_words(modulus: &[u8]) -> Result<usize, Error> {
+    if modulus.is_empty() {
+        return Err(Error::InvalidOperandLength);
+    }
+
+    let words = (modulus.len() + 3) / 4;
+
+    // The ESP32 only supports operand lengths in multiples of 512 bits
+    #[cfg(feature = "esp32")]
+    let words = (words + 15) / 16 * 16;
+
+    if words > MAX_OPERAND_WORDS {
+        Err(Error::InvalidOperandLength)
+    } else {
+        Ok(words)
+    }
+}
+
+fn check_operand(operand: &[u8], modulus: &[u8]) -> Result<(), Error> {
+    if operand.len() > modulus.len() {
+        Err(Error::InvalidOperandLength)
+    } else {
+        Ok(())
+    }
+}
+
+fn check_result(result: &[u8], len: usize) -> Result<(), Error> {
+    if result.len() != len {
+        Err(Error::InvalidResultLength)
+    } else {
+        Ok(())
+    }
+}
+
+/// Extract the little-endian word `index` from a big-endian byte slice
+pub(crate) fn word_from_be_bytes(bytes: &[u8], index: usize) -> u32 {
+    let mut word = [0u8; 4];
+
+    for (j, byte) in word.iter_mut().enumerate() {
+        let pos = index * 4 + j;
+        if pos < bytes.len() {
+            *byte = bytes[bytes.len() - 1 - pos];
+        }
+    }
+
+    u32::from_le_bytes(word)
+}
+
+/// Derive the modulus words, `M' = -M⁻¹ mod 2³²` and `R² mod M` (with
+/// `R = 2^(32 * words)`) for a big-endian modulus
+fn montgomery_parameters(
+    modulus: &[u8],
+    words: usize,
+) -> Result<([u32; MAX_OPERAND_WORDS], u32, [u32; MAX_OPERAND_WORDS]), Error> {
+    let mut m = [0u32; MAX_OPERAND_WORDS];
+    for (i, word) in m.iter_mut().take(words).enumerate() {
+        *word = word_from_be_bytes(modulus, i);
+    }
+
+    if m[0] & 1 == 0 {
+        return Err(Error::EvenModulus);
+    }
+
+    // Newton's iteration doubles the number of correct bits on every step,
+    // starting from 1 correct bit; 5 steps are enough for 32 bits.
+    let mut inverse = 1u32;
+    for _ in 0..5 {
+        inverse = inverse.wrapping_mul(2u32.wrapping_sub(m[0].wrapping_mul(inverse)));
+    }
+    let m_prime = inverse.wrapping_neg();
+
+    // Compute R² mod M by doubling 1 modulo M, 2 * 32 * words times
+    let mut r = [0u32; MAX_OPERAND_WORDS];
+    r[0] = 1;
+    for _ in 0..(64 * words) {
+        let mut carry = 0u32;
+        for word in r.iter_mut().take(words) {
+            let next_carry = *word >> 31;
+            *word = (*word << 1) | carry;
+            carry = next_carry;
+        }
+
+        if carry != 0 || !less_than(&r[..words], &m[..words]) {
+            subtract(&mut r[..words], &m[..words]);
+        }
+    }
+
+    Ok((m, m_prime, r))
+}
+
+/// `a < b` for little-endian word slices of the same length
+fn less_than(a: &[u32], b: &[u32]) -> bool {
+    for (a, b) in a.iter().rev().zip(b.iter().rev()) {
+        if a != b {
+            return a < b;
+        }
+    }
+
+    false
+}
+
+/// `a -= b` (wrapping) for little-endian word slices of the same length
+fn subtract(a: &mut [u32], b: &[u32]) {
+    let mut borrow = false;
+
+    for (a, b) in a.iter_mut().zip(b.iter()) {
+        let (diff, borrow1) = a.overflowing_sub(*b);
+        let (diff, borrow2) = diff.overflowing_sub(borrow as u32);
+        *a = diff;
+        borrow = borrow1 || borrow2;
+    }
+}
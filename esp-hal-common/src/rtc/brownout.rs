@@ -0,0 +1,227 @@
+//! # Brownout detector
+//!
+//! The brownout detector monitors the supply voltage and either resets the
+//! chip or raises the `RTC_CORE` interrupt when it drops below a threshold.
+//! It is part of the RTC and is handed out as [Rtc::brownout]:
+//!
+//! ```no_run
+//! let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+//!
+//! // reset the chip when the supply voltage drops below level 7
+//! rtc.brownout.enable(BrownoutLevel::Level7, BrownoutAction::Reset);
+//! ```
+//!
+//! With [BrownoutAction::Interrupt], a handler can save state before the
+//! supply collapses. The flash stays powered, while the RF circuits are
+//! powered down to save power:
+//!
+//! ```no_run
+//! fn on_brownout() {
+//!     // save state, then reset
+//!     reset::software_reset();
+//! }
+//!
+//! rtc.brownout.set_handler(Some(on_brownout));
+//! rtc.brownout.enable(BrownoutLevel::Level7, BrownoutAction::Interrupt);
+//! interrupt::enable(pac::Interrupt::RTC_CORE, interrupt::Priority::Priority3).unwrap();
+//!
+//! #[interrupt]
+//! fn RTC_CORE() {
+//!     critical_section::with(|cs| {
+//!         RTC.borrow_ref_mut(cs).as_mut().unwrap().brownout.on_interrupt();
+//!     });
+//! }
+//! ```
+//!
+//! The voltage of each threshold level is listed in the datasheet. On the
+//! ESP32, higher levels are higher voltages, on the other chips they are
+//! lower voltages.
+//!
+//! [Rtc::brownout]: crate::Rtc::brownout
+
+use crate::pac::RTC_CNTL;
+
+/// Cycles of RTC_SLOW_CLK to wait before resetting the chip
+const BROWNOUT_RESET_WAIT_CYCLES: u16 = 0x3ff;
+/// Cycles of RTC_SLOW_CLK the voltage has to stay below the threshold
+/// before the interrupt is raised
+#[cfg(not(feature = "esp32"))]
+const BROWNOUT_INT_WAIT_CYCLES: u16 = 2;
+
+/// Threshold level of the brownout detector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BrownoutLevel {
+    Level0,
+    Level1,
+    Level2,
+    Level3,
+    Level4,
+    Level5,
+    Level6,
+    Level7,
+}
+
+/// What happens when the supply voltage drops below the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BrownoutAction {
+    /// Reset the chip
+    Reset,
+    /// Raise the `RTC_CORE` interrupt
+    Interrupt,
+}
+
+/// Brownout detector
+pub struct BrownoutDetector {
+    handler: Option<fn()>,
+}
+
+impl BrownoutDetector {
+    pub(crate) fn new() -> Self {
+        Self { handler: None }
+    }
+
+    /// Start monitoring the supply voltage
+    pub fn enable(&mut self, level: BrownoutLevel, action: BrownoutAction) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let reset = action == BrownoutAction::Reset;
+
+        super::rtc::set_brownout_threshold(level as u8);
+
+        rtc_cntl.brown_out.modify(|_, w| unsafe {
+            #[cfg(not(feature = "esp32"))]
+            w.brown_out_int_wait()
+                .bits(BROWNOUT_INT_WAIT_CYCLES)
+                // reset the whole chip, not only the digital core
+                .brown_out_rst_sel()
+                .set_bit();
+
+            w.brown_out_close_flash_ena()
+                .clear_bit()
+                .brown_out_pd_rf_ena()
+                .set_bit()
+                .brown_out_rst_wait()
+                .bits(BROWNOUT_RESET_WAIT_CYCLES)
+                .brown_out_rst_ena()
+                .bit(reset)
+                .brown_out_ena()
+                .set_bit()
+        });
+
+        if reset {
+            self.unlisten();
+        } else {
+            self.clear_interrupt();
+            self.listen();
+        }
+    }
+
+    /// Stop monitoring the supply voltage
+    pub fn disable(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        self.unlisten();
+
+        rtc_cntl.brown_out.modify(|_, w| {
+            w.brown_out_ena()
+                .clear_bit()
+                .brown_out_rst_ena()
+                .clear_bit()
+        });
+    }
+
+    /// Set the function which [BrownoutDetector::on_interrupt] calls when the
+    /// supply voltage has dropped, or remove it with `None`
+    pub fn set_handler(&mut self, handler: Option<fn()>) {
+        self.handler = handler;
+    }
+
+    /// Handle the `RTC_CORE` interrupt, calling the handler if the supply
+    /// voltage has dropped
+    ///
+    /// This has to be called from the `RTC_CORE` interrupt handler when the
+    /// detector is enabled with [BrownoutAction::Interrupt].
+    pub fn on_interrupt(&mut self) {
+        if !self.is_interrupt_set() {
+            return;
+        }
+
+        if let Some(handler) = self.handler {
+            handler();
+        }
+
+        self.clear_interrupt();
+    }
+
+    /// Whether the supply voltage has dropped below the threshold
+    pub fn is_interrupt_set(&self) -> bool {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "esp32")] {
+                rtc_cntl.int_st.read().brown_out_int_st().bit_is_set()
+            } else if #[cfg(feature = "esp32s2")] {
+                rtc_cntl.int_st_rtc.read().brown_out_int_st().bit_is_set()
+            } else if #[cfg(any(feature = "esp32c3", feature = "esp32s3"))] {
+                rtc_cntl.int_st_rtc.read().rtc_brown_out_int_st().bit_is_set()
+            }
+        }
+    }
+
+    /// Clear the brownout interrupt
+    pub fn clear_interrupt(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        #[cfg(feature = "esp32")]
+        rtc_cntl.int_clr.write(|w| w.brown_out_int_clr().set_bit());
+
+        #[cfg(feature = "esp32s2")]
+        rtc_cntl
+            .int_clr_rtc
+            .write(|w| w.brown_out_int_clr().set_bit());
+
+        #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+        rtc_cntl
+            .int_clr_rtc
+            .write(|w| w.rtc_brown_out_int_clr().set_bit());
+    }
+
+    fn listen(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        #[cfg(feature = "esp32")]
+        rtc_cntl
+            .int_ena
+            .modify(|_, w| w.brown_out_int_ena().set_bit());
+
+        #[cfg(feature = "esp32s2")]
+        rtc_cntl
+            .int_ena_rtc
+            .modify(|_, w| w.brown_out_int_ena().set_bit());
+
+        #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+        rtc_cntl
+            .int_ena_rtc
+            .modify(|_, w| w.rtc_brown_out_int_ena().set_bit());
+    }
+
+    fn unlisten(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        #[cfg(feature = "esp32")]
+        rtc_cntl
+            .int_ena
+            .modify(|_, w| w.brown_out_int_ena().clear_bit());
+
+        #[cfg(feature = "esp32s2")]
+        rtc_cntl
+            .int_ena_rtc
+            .modify(|_, w| w.brown_out_int_ena().clear_bit());
+
+        #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+        rtc_cntl
+            .int_ena_rtc
+            .modify(|_, w| w.rtc_brown_out_int_ena().clear_bit());
+    }
+}
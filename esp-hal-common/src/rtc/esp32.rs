@@ -1,15 +1,42 @@
 use crate::{
     clock::XtalClock,
-    pac::RTC_CNTL,
+    gpio::{
+        types::{OutputSignal, OutputSignalType},
+        AlternateFunction,
+    },
+    pac::{GPIO, IO_MUX, RTCIO, RTC_CNTL},
+    rom::esp_rom_delay_us,
     rtc_cntl::{RtcCalSel, RtcClock, RtcFastClock, RtcSlowClock},
 };
 
+/// Current of the amplifier of the 32 kHz crystal oscillator
+const XTAL_32K_DAC: u8 = 1;
+/// Resistance of the amplifier of the 32 kHz crystal oscillator
+const XTAL_32K_DRES: u8 = 3;
+/// Bias voltage of the 32 kHz crystal oscillator
+const XTAL_32K_DBIAS: u8 = 0;
+/// Current of the amplifier when it buffers an external 32 kHz clock
+const XTAL_32K_EXT_DAC: u8 = 2;
+/// Resistance of the amplifier when it buffers an external 32 kHz clock
+const XTAL_32K_EXT_DRES: u8 = 3;
+/// Bias voltage of the amplifier when it buffers an external 32 kHz clock
+const XTAL_32K_EXT_DBIAS: u8 = 1;
+/// Time to discharge the pins of the 32 kHz crystal before starting the
+/// oscillator
+const XTAL_32K_DISCHARGE_TIME_US: u32 = 7;
+
+/// Bits of the 32K_XP (GPIO32) and 32K_XN (GPIO33) pads in the registers of
+/// the GPIOs 32 to 39
+const XTAL_32K_P_MASK: u32 = 1 << (32 - 32);
+const XTAL_32K_N_MASK: u32 = 1 << (33 - 32);
+
 pub(crate) fn init() {}
 
 pub(crate) fn configure_clock() {
-    assert!(matches!(
+    // the PLL can only be configured for the known crystal frequencies
+    assert!(!matches!(
         RtcClock::get_xtal_freq(),
-        XtalClock::RtcXtalFreq40M
+        XtalClock::RtcXtalFreqOther(_)
     ));
 
     RtcClock::set_fast_freq(RtcFastClock::RtcFastClock8m);
@@ -28,3 +55,305 @@ pub(crate) fn configure_clock() {
         rtc_cntl.store1.write(|w| w.bits(cal_val));
     }
 }
+
+/// The RTC IO numbers of the GPIOs which are connected to the RTC IO_MUX, as
+/// `(gpio, rtc_io)`
+const RTC_IO_NUMBERS: [(u8, u8); 18] = [
+    (36, 0),
+    (37, 1),
+    (38, 2),
+    (39, 3),
+    (34, 4),
+    (35, 5),
+    (25, 6),
+    (26, 7),
+    (33, 8),
+    (32, 9),
+    (4, 10),
+    (0, 11),
+    (2, 12),
+    (15, 13),
+    (13, 14),
+    (12, 15),
+    (14, 16),
+    (27, 17),
+];
+
+/// The RTC IO number of `gpio`, which has to be connected to the RTC IO_MUX
+pub(crate) fn rtc_io_number(gpio: u8) -> u8 {
+    RTC_IO_NUMBERS
+        .iter()
+        .find(|(pin, _)| *pin == gpio)
+        .map(|(_, rtc_io)| *rtc_io)
+        .expect("GPIO is not connected to the RTC IO_MUX")
+}
+
+/// The bits in `RTC_CNTL_HOLD_FORCE_REG` of the RTC IOs, by RTC IO number
+const RTC_IO_HOLD_BITS: [u8; 18] = [4, 5, 6, 7, 0, 1, 2, 3, 16, 17, 8, 9, 10, 11, 12, 13, 14, 15];
+
+/// Latch the state of the RTC IO `rtc_io`, or release it
+pub(crate) fn set_pad_hold(rtc_io: u8, hold: bool) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let mask = 1 << RTC_IO_HOLD_BITS[rtc_io as usize];
+
+    rtc_cntl.hold_force.modify(|r, w| unsafe {
+        if hold {
+            w.bits(r.bits() | mask)
+        } else {
+            w.bits(r.bits() & !mask)
+        }
+    });
+}
+
+/// The bits in `RTC_IO_DIG_PAD_HOLD_REG` of the digital pads, as
+/// `(gpio, bit)`
+const DIG_PAD_HOLD_BITS: [(u8, u8); 17] = [
+    (1, 1),
+    (3, 0),
+    (5, 8),
+    (6, 2),
+    (7, 3),
+    (8, 4),
+    (9, 5),
+    (10, 6),
+    (11, 7),
+    (16, 9),
+    (17, 10),
+    (18, 11),
+    (19, 12),
+    (20, 13),
+    (21, 14),
+    (22, 15),
+    (23, 16),
+];
+
+/// Latch the state of the pad of `gpio`, or release it
+pub(crate) fn set_gpio_hold(gpio: u8, hold: bool) {
+    if RTC_IO_NUMBERS.iter().any(|(pin, _)| *pin == gpio) {
+        set_pad_hold(rtc_io_number(gpio), hold);
+        return;
+    }
+
+    let rtcio = unsafe { &*RTCIO::ptr() };
+    let mask = 1 << dig_pad_hold_bit(gpio);
+
+    rtcio.dig_pad_hold.modify(|r, w| unsafe {
+        if hold {
+            w.bits(r.bits() | mask)
+        } else {
+            w.bits(r.bits() & !mask)
+        }
+    });
+
+    if hold {
+        enable_digital_pad_autohold();
+    }
+}
+
+/// Let the held digital pads keep their state in deep sleep as well, where
+/// the digital domain is powered down
+fn enable_digital_pad_autohold() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl.dig_iso.modify(|_, w| {
+        w.dg_pad_force_unhold()
+            .clear_bit()
+            .dg_pad_autohold_en()
+            .set_bit()
+    });
+}
+
+fn dig_pad_hold_bit(gpio: u8) -> u8 {
+    DIG_PAD_HOLD_BITS
+        .iter()
+        .find(|(pin, _)| *pin == gpio)
+        .map(|(_, bit)| *bit)
+        .expect("GPIO has no pad hold")
+}
+
+/// The addresses of the RTC fast memory the CPU can execute code from
+pub(crate) const RTC_FAST_TEXT: core::ops::Range<usize> = 0x400c_0000..0x400c_2000;
+
+/// The reason of a reset, as reported by the ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SocResetReason {
+    /// Power-on reset
+    ChipPowerOn   = 0x01,
+    /// Software reset of the digital core
+    CoreSw        = 0x03,
+    /// Wakeup from deep sleep
+    CoreDeepSleep = 0x05,
+    /// The SDIO module reset the digital core
+    CoreSdio      = 0x06,
+    /// The MWDT of timer group 0 reset the digital core
+    CoreMwdt0     = 0x07,
+    /// The MWDT of timer group 1 reset the digital core
+    CoreMwdt1     = 0x08,
+    /// The RWDT reset the digital core
+    CoreRtcWdt    = 0x09,
+    /// The MWDT of timer group 0 reset the CPU
+    CpuMwdt0      = 0x0b,
+    /// Software reset of the CPU
+    CpuSw         = 0x0c,
+    /// The RWDT reset the CPU
+    CpuRtcWdt     = 0x0d,
+    /// The PRO CPU reset the APP CPU
+    CpuCpu0       = 0x0e,
+    /// The brownout detector reset the chip
+    SysBrownOut   = 0x0f,
+    /// The RWDT reset the digital core and the RTC
+    SysRtcWdt     = 0x10,
+}
+
+impl SocResetReason {
+    pub(crate) fn from_raw(reason: u32) -> Option<Self> {
+        match reason {
+            0x01 => Some(Self::ChipPowerOn),
+            0x03 => Some(Self::CoreSw),
+            0x05 => Some(Self::CoreDeepSleep),
+            0x06 => Some(Self::CoreSdio),
+            0x07 => Some(Self::CoreMwdt0),
+            0x08 => Some(Self::CoreMwdt1),
+            0x09 => Some(Self::CoreRtcWdt),
+            0x0b => Some(Self::CpuMwdt0),
+            0x0c => Some(Self::CpuSw),
+            0x0d => Some(Self::CpuRtcWdt),
+            0x0e => Some(Self::CpuCpu0),
+            0x0f => Some(Self::SysBrownOut),
+            0x10 => Some(Self::SysRtcWdt),
+            _ => None,
+        }
+    }
+}
+
+/// Set the brownout threshold level, from 0 to 7
+pub(crate) fn set_brownout_threshold(level: u8) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .brown_out
+        .modify(|_, w| unsafe { w.dbrown_out_thres().bits(level) });
+}
+
+/// Start the 32 kHz crystal oscillator
+///
+/// If `bootstrap_cycles` is not 0, the crystal is first driven through its
+/// pins for that many cycles, which helps crystals which do not start up on
+/// their own.
+pub(crate) fn start_32k_xtal(bootstrap_cycles: u32) {
+    let rtcio = unsafe { &*RTCIO::ptr() };
+
+    if bootstrap_cycles != 0 {
+        bootstrap_32k_xtal(bootstrap_cycles);
+    }
+
+    // discharge the pins, so that the oscillator starts from a known state
+    rtcio.xtal_32k_pad.modify(|_, w| {
+        w.xpd_xtal_32k()
+            .clear_bit()
+            .x32p_rue()
+            .set_bit()
+            .x32n_rde()
+            .set_bit()
+    });
+    unsafe { esp_rom_delay_us(XTAL_32K_DISCHARGE_TIME_US) };
+
+    configure_32k_oscillator(XTAL_32K_DAC, XTAL_32K_DRES, XTAL_32K_DBIAS);
+}
+
+/// Buffer an external 32 kHz clock on the 32K_XN pin (GPIO33) with the
+/// oscillator
+pub(crate) fn start_32k_external() {
+    configure_32k_oscillator(XTAL_32K_EXT_DAC, XTAL_32K_EXT_DRES, XTAL_32K_EXT_DBIAS);
+}
+
+/// Hand the pins over to the 32 kHz oscillator and power it up
+fn configure_32k_oscillator(dac: u8, dres: u8, dbias: u8) {
+    let rtcio = unsafe { &*RTCIO::ptr() };
+
+    rtcio.xtal_32k_pad.modify(|_, w| unsafe {
+        w.x32p_mux_sel()
+            .set_bit()
+            .x32n_mux_sel()
+            .set_bit()
+            .x32p_rue()
+            .clear_bit()
+            .x32p_rde()
+            .clear_bit()
+            .x32p_fun_ie()
+            .clear_bit()
+            .x32n_rue()
+            .clear_bit()
+            .x32n_rde()
+            .clear_bit()
+            .x32n_fun_ie()
+            .clear_bit()
+            .dac_xtal_32k()
+            .bits(dac)
+            .dres_xtal_32k()
+            .bits(dres)
+            .dbias_xtal_32k()
+            .bits(dbias)
+            .xpd_xtal_32k()
+            .set_bit()
+    });
+}
+
+/// Stop the 32 kHz oscillator and release its pins
+pub(crate) fn stop_32k_xtal() {
+    let rtcio = unsafe { &*RTCIO::ptr() };
+
+    rtcio.xtal_32k_pad.modify(|_, w| {
+        w.xpd_xtal_32k()
+            .clear_bit()
+            .x32p_mux_sel()
+            .clear_bit()
+            .x32n_mux_sel()
+            .clear_bit()
+    });
+}
+
+/// Drive the pins of the 32 kHz crystal in antiphase at 32 kHz for `cycles`
+/// cycles
+fn bootstrap_32k_xtal(cycles: u32) {
+    let rtcio = unsafe { &*RTCIO::ptr() };
+    let io_mux = unsafe { &*IO_MUX::ptr() };
+    let gpio = unsafe { &*GPIO::ptr() };
+    let half_period_us = 1_000_000 / 32_768 / 2;
+
+    // use the pads as plain GPIO outputs
+    rtcio.xtal_32k_pad.modify(|_, w| {
+        w.xpd_xtal_32k()
+            .clear_bit()
+            .x32p_mux_sel()
+            .clear_bit()
+            .x32n_mux_sel()
+            .clear_bit()
+    });
+    io_mux
+        .gpio32
+        .modify(|_, w| unsafe { w.mcu_sel().bits(AlternateFunction::Function2 as u8) });
+    io_mux
+        .gpio33
+        .modify(|_, w| unsafe { w.mcu_sel().bits(AlternateFunction::Function2 as u8) });
+    for pin in [32, 33] {
+        gpio.func_out_sel_cfg[pin]
+            .modify(|_, w| unsafe { w.out_sel().bits(OutputSignal::GPIO as OutputSignalType) });
+    }
+    gpio.enable1_w1ts
+        .write(|w| unsafe { w.bits(XTAL_32K_P_MASK | XTAL_32K_N_MASK) });
+
+    for _ in 0..cycles {
+        gpio.out1_w1ts.write(|w| unsafe { w.bits(XTAL_32K_P_MASK) });
+        gpio.out1_w1tc.write(|w| unsafe { w.bits(XTAL_32K_N_MASK) });
+        unsafe { esp_rom_delay_us(half_period_us) };
+
+        gpio.out1_w1ts.write(|w| unsafe { w.bits(XTAL_32K_N_MASK) });
+        gpio.out1_w1tc.write(|w| unsafe { w.bits(XTAL_32K_P_MASK) });
+        unsafe { esp_rom_delay_us(half_period_us) };
+    }
+
+    gpio.enable1_w1tc
+        .write(|w| unsafe { w.bits(XTAL_32K_P_MASK | XTAL_32K_N_MASK) });
+}
@@ -1,9 +1,29 @@
+use paste::paste;
+
 use crate::{
     clock::XtalClock,
-    pac::RTC_CNTL,
+    pac::{IO_MUX, RTCIO, RTC_CNTL},
+    regi2c_write_mask,
+    rom::regi2c_ctrl_write_reg_mask,
     rtc_cntl::{RtcCalSel, RtcClock, RtcFastClock, RtcSlowClock},
 };
 
+const I2C_BOD: u32 = 0x61;
+const I2C_BOD_HOSTID: u32 = 1;
+
+const I2C_BOD_REG_THRESHOLD: u32 = 5;
+const I2C_BOD_REG_THRESHOLD_MSB: u32 = 2;
+const I2C_BOD_REG_THRESHOLD_LSB: u32 = 0;
+
+/// Current of the amplifier of the 32 kHz crystal oscillator
+const XTAL_32K_DAC: u8 = 3;
+/// Resistance of the amplifier of the 32 kHz crystal oscillator
+const XTAL_32K_DRES: u8 = 3;
+/// Gain of the amplifier of the 32 kHz crystal oscillator
+const XTAL_32K_DGM: u8 = 3;
+/// Buffer of the 32 kHz crystal oscillator
+const XTAL_32K_DBUF: bool = true;
+
 pub(crate) fn init() {}
 
 pub(crate) fn configure_clock() {
@@ -28,3 +48,216 @@ pub(crate) fn configure_clock() {
         rtc_cntl.store1.write(|w| w.bits(cal_val));
     }
 }
+
+/// The RTC IO number of `gpio`, which has to be connected to the RTC IO_MUX
+///
+/// GPIO0 to GPIO21 are RTC IO 0 to 21.
+pub(crate) fn rtc_io_number(gpio: u8) -> u8 {
+    gpio
+}
+
+/// Latch the state of the RTC IO `rtc_io`, or release it
+pub(crate) fn set_pad_hold(rtc_io: u8, hold: bool) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let mask = 1 << rtc_io;
+
+    rtc_cntl.pad_hold.modify(|r, w| unsafe {
+        if hold {
+            w.bits(r.bits() | mask)
+        } else {
+            w.bits(r.bits() & !mask)
+        }
+    });
+}
+
+/// The addresses of the RTC fast memory the CPU can execute code from
+pub(crate) const RTC_FAST_TEXT: core::ops::Range<usize> = 0x600f_e000..0x6010_0000;
+
+/// The reason of a reset, as reported by the ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SocResetReason {
+    /// Power-on reset
+    ChipPowerOn   = 0x01,
+    /// Software reset of the digital core
+    CoreSw        = 0x03,
+    /// Wakeup from deep sleep
+    CoreDeepSleep = 0x05,
+    /// The MWDT of timer group 0 reset the digital core
+    CoreMwdt0     = 0x07,
+    /// The MWDT of timer group 1 reset the digital core
+    CoreMwdt1     = 0x08,
+    /// The RWDT reset the digital core
+    CoreRtcWdt    = 0x09,
+    /// The MWDT of timer group 0 reset the CPU
+    CpuMwdt0      = 0x0b,
+    /// Software reset of the CPU
+    CpuSw         = 0x0c,
+    /// The RWDT reset the CPU
+    CpuRtcWdt     = 0x0d,
+    /// The brownout detector reset the chip
+    SysBrownOut   = 0x0f,
+    /// The RWDT reset the digital core and the RTC
+    SysRtcWdt     = 0x10,
+    /// The MWDT of timer group 1 reset the CPU
+    CpuMwdt1      = 0x11,
+    /// The super watchdog reset the digital core and the RTC
+    SysSuperWdt   = 0x12,
+    /// A glitch on the clock reset the digital core and the RTC
+    SysClkGlitch  = 0x13,
+    /// An eFuse CRC error reset the digital core
+    CoreEfuseCrc  = 0x14,
+    /// The USB UART reset the digital core
+    CoreUsbUart   = 0x15,
+    /// The USB JTAG reset the digital core
+    CoreUsbJtag   = 0x16,
+    /// A glitch on the power supply reset the digital core
+    CorePwrGlitch = 0x17,
+}
+
+impl SocResetReason {
+    pub(crate) fn from_raw(reason: u32) -> Option<Self> {
+        match reason {
+            0x01 => Some(Self::ChipPowerOn),
+            0x03 => Some(Self::CoreSw),
+            0x05 => Some(Self::CoreDeepSleep),
+            0x07 => Some(Self::CoreMwdt0),
+            0x08 => Some(Self::CoreMwdt1),
+            0x09 => Some(Self::CoreRtcWdt),
+            0x0b => Some(Self::CpuMwdt0),
+            0x0c => Some(Self::CpuSw),
+            0x0d => Some(Self::CpuRtcWdt),
+            0x0f => Some(Self::SysBrownOut),
+            0x10 => Some(Self::SysRtcWdt),
+            0x11 => Some(Self::CpuMwdt1),
+            0x12 => Some(Self::SysSuperWdt),
+            0x13 => Some(Self::SysClkGlitch),
+            0x14 => Some(Self::CoreEfuseCrc),
+            0x15 => Some(Self::CoreUsbUart),
+            0x16 => Some(Self::CoreUsbJtag),
+            0x17 => Some(Self::CorePwrGlitch),
+            _ => None,
+        }
+    }
+}
+
+/// Set the brownout threshold level, from 0 to 7
+pub(crate) fn set_brownout_threshold(level: u8) {
+    unsafe {
+        regi2c_write_mask!(I2C_BOD, I2C_BOD_REG_THRESHOLD, level as u32);
+    }
+}
+
+/// Latch the state of the pad of `gpio`, or release it
+pub(crate) fn set_gpio_hold(gpio: u8, hold: bool) {
+    if gpio <= 21 {
+        set_pad_hold(gpio, hold);
+        return;
+    }
+
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let mask = 1 << (gpio - 21);
+
+    rtc_cntl.dig_pad_hold.modify(|r, w| unsafe {
+        if hold {
+            w.bits(r.bits() | mask)
+        } else {
+            w.bits(r.bits() & !mask)
+        }
+    });
+
+    if hold {
+        enable_digital_pad_autohold();
+    }
+}
+
+/// Let the held digital pads keep their state in deep sleep as well, where
+/// the digital domain is powered down
+fn enable_digital_pad_autohold() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl.dig_iso.modify(|_, w| {
+        w.dg_pad_force_unhold()
+            .clear_bit()
+            .dg_pad_autohold_en()
+            .set_bit()
+    });
+}
+
+/// Start the 32 kHz crystal oscillator
+///
+/// Unlike on the ESP32, the oscillator starts up without bootstrapping, so
+/// `_bootstrap_cycles` is ignored.
+pub(crate) fn start_32k_xtal(_bootstrap_cycles: u32) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let rtcio = unsafe { &*RTCIO::ptr() };
+
+    // hand the pads over to the oscillator
+    rtcio.xtal_32p_pad.modify(|_, w| {
+        w.x32p_mux_sel()
+            .set_bit()
+            .x32p_rue()
+            .clear_bit()
+            .x32p_rde()
+            .clear_bit()
+            .x32p_fun_ie()
+            .clear_bit()
+    });
+    rtcio.xtal_32n_pad.modify(|_, w| {
+        w.x32n_mux_sel()
+            .set_bit()
+            .x32n_rue()
+            .clear_bit()
+            .x32n_rde()
+            .clear_bit()
+            .x32n_fun_ie()
+            .clear_bit()
+    });
+
+    rtc_cntl.ext_xtl_conf.modify(|_, w| unsafe {
+        w.xtal32k_gpio_sel()
+            .clear_bit()
+            .dac_xtal_32k()
+            .bits(XTAL_32K_DAC)
+            .dres_xtal_32k()
+            .bits(XTAL_32K_DRES)
+            .dgm_xtal_32k()
+            .bits(XTAL_32K_DGM)
+            .dbuf_xtal_32k()
+            .bit(XTAL_32K_DBUF)
+            .xpd_xtal_32k()
+            .set_bit()
+    });
+}
+
+/// Use an external 32 kHz clock on the 32K_XP pin (GPIO15) instead of the
+/// oscillator
+pub(crate) fn start_32k_external() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let rtcio = unsafe { &*RTCIO::ptr() };
+    let io_mux = unsafe { &*IO_MUX::ptr() };
+
+    io_mux.gpio[15].modify(|_, w| w.fun_ie().set_bit());
+    rtcio.xtal_32p_pad.modify(|_, w| w.x32p_mux_sel().set_bit());
+
+    rtc_cntl
+        .ext_xtl_conf
+        .modify(|_, w| w.xpd_xtal_32k().clear_bit().xtal32k_gpio_sel().set_bit());
+}
+
+/// Stop the 32 kHz oscillator and release its pins
+pub(crate) fn stop_32k_xtal() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let rtcio = unsafe { &*RTCIO::ptr() };
+
+    rtc_cntl
+        .ext_xtl_conf
+        .modify(|_, w| w.xpd_xtal_32k().clear_bit().xtal32k_gpio_sel().clear_bit());
+
+    rtcio
+        .xtal_32p_pad
+        .modify(|_, w| w.x32p_mux_sel().clear_bit());
+    rtcio
+        .xtal_32n_pad
+        .modify(|_, w| w.x32n_mux_sel().clear_bit());
+}
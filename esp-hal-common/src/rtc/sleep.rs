@@ -0,0 +1,978 @@
+//! # Sleep modes
+//!
+//! In deep sleep, the CPUs, the digital peripherals and most of the RAM are
+//! powered down, only the RTC domain keeps running. The chip wakes up from
+//! one of the configured wakeup sources with a reset, so the application
+//! starts again from its entry point:
+//!
+//! ```no_run
+//! let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+//!
+//! let timer = TimerWakeupSource::new(10u64.secs());
+//! rtc.sleep_deep(&[&timer]);
+//! ```
+//!
+//! The RTC memories and the RTC peripherals stay powered, so data in RTC
+//! memory survives deep sleep and the ULP can keep running.
+//!
+//! On the ESP32, ESP32-S2 and ESP32-S3, the chip can also be woken up by the
+//! level of a pin connected to the RTC IO_MUX, e.g. by a button:
+//!
+//! ```no_run
+//! let button = Ext0WakeupSource::new(io.pins.gpio0, WakeupLevel::Low);
+//! rtc.sleep_deep(&[&button]);
+//! ```
+//!
+//! Several of these pins can be combined into one wakeup source with
+//! [Ext1WakeupSource]:
+//!
+//! ```no_run
+//! let buttons = Ext1WakeupSource::new(Ext1WakeupMode::AnyHigh)
+//!     .with_pin(io.pins.gpio2)
+//!     .with_pin(io.pins.gpio4);
+//! rtc.sleep_deep(&[&buttons]);
+//! ```
+//!
+//! A touch on a pad of the [touch sensor](crate::touch) can wake up the chip
+//! with [TouchWakeupSource]:
+//!
+//! ```no_run
+//! let mut touch = TouchSensor::new(&mut rtc.touch, TouchConfig::default());
+//! let pad = touch.enable_pad(io.pins.gpio4);
+//!
+//! let touched = TouchWakeupSource::new(&touch, &pad, 400);
+//! rtc.sleep_deep(&[&touched]);
+//! ```
+//!
+//! In light sleep, the clocks of the CPUs and the digital peripherals are
+//! gated, but they stay powered and keep their state. [Rtc::sleep_light]
+//! returns once one of the wakeup sources has woken up the chip:
+//!
+//! ```no_run
+//! let timer = TimerWakeupSource::new(500u64.millis());
+//! rtc.sleep_light(LightSleepConfig::default(), &[&timer]);
+//! ```
+//!
+//! The CPU runs from XTAL_CLK while entering and leaving light sleep, the
+//! CPU and APB frequencies are restored before [Rtc::sleep_light] returns.
+//!
+//! Output pins can keep their level during sleep, e.g. to keep an external
+//! regulator enabled:
+//!
+//! ```no_run
+//! let mut enable = io.pins.gpio5.into_push_pull_output();
+//! enable.set_high().unwrap();
+//! enable.hold_during_sleep();
+//! ```
+//!
+//! ## Wake stub
+//!
+//! A wake stub is a function in RTC fast memory which the ROM runs right
+//! after the chip has woken up from deep sleep, before the bootloader and the
+//! application are loaded. It can e.g. inspect the wakeup cause with
+//! [wakeup_cause_bits]. Once it returns, the chip continues to boot:
+//!
+//! ```no_run
+//! #[ram(rtc_fast)]
+//! unsafe extern "C" fn wake_stub() {
+//!     let _cause = wakeup_cause_bits();
+//! }
+//!
+//! unsafe { rtc.set_wake_stub(wake_stub) };
+//! rtc.sleep_deep(&[&timer]);
+//! ```
+//!
+//! Wake stubs are not supported on the ESP32-S2.
+//!
+//! ## Power domains
+//!
+//! [RtcSleepConfig] selects which domains stay powered during sleep, trading
+//! sleep current against wakeup latency and what survives sleep. It is passed
+//! to [Rtc::sleep_deep_with_config], or as part of the [LightSleepConfig]:
+//!
+//! ```no_run
+//! let mut config = RtcSleepConfig::deep();
+//! // nothing is kept in RTC slow memory and no RTC peripheral is needed
+//! config.rtc_slow_memory = false;
+//! config.rtc_peripherals = false;
+//! rtc.sleep_deep_with_config(config, &[&timer]);
+//! ```
+//!
+//! Instead of selecting each domain, the analog clocks and the bias, a
+//! [SleepPreset] can be used:
+//!
+//! ```no_run
+//! rtc.sleep_deep_with_config(SleepPreset::UltraLowPower.into(), &[&timer]);
+//! ```
+//!
+//! Wakeup sources which are enabled by other drivers, like the ULP with
+//! [UlpFsm::enable_cpu_wakeup](crate::ulp::UlpFsm::enable_cpu_wakeup), stay
+//! enabled during sleep.
+
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+use core::{cell::RefCell, marker::PhantomData};
+
+use fugit::MicrosDurationU64;
+
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+use crate::{
+    gpio::RtcPin,
+    into_ref,
+    pac::RTCIO,
+    peripherals::{Peripheral, PeripheralRef},
+};
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+use crate::{
+    gpio::TouchPin,
+    touch::{self, TouchPad, TouchSensor},
+};
+use crate::{
+    clock,
+    pac::RTC_CNTL,
+    rtc_cntl::{Rtc, RtcClock, RtcSlowClock},
+    serial,
+};
+
+/// Wakeup source bit of EXT0 in `RTC_CNTL_WAKEUP_ENA`
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+const RTC_EXT0_TRIG_EN: u32 = 1 << 0;
+/// Wakeup source bit of EXT1 in `RTC_CNTL_WAKEUP_ENA`
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+const RTC_EXT1_TRIG_EN: u32 = 1 << 1;
+/// Wakeup source bit of the RTC timer in `RTC_CNTL_WAKEUP_ENA`
+const RTC_TIMER_TRIG_EN: u32 = 1 << 3;
+/// Wakeup source bit of the touch sensor in `RTC_CNTL_WAKEUP_ENA`
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+const RTC_TOUCH_TRIG_EN: u32 = 1 << 8;
+
+/// Minimum sleep time in RTC_SLOW_CLK cycles
+const RTC_CNTL_MIN_SLP_VAL_MIN: u8 = 128;
+/// Digital voltage during deep sleep
+#[cfg(feature = "esp32")]
+const RTC_CNTL_DBIAS_0V90: u8 = 0;
+/// Digital voltage during light sleep, high enough to retain the state of the
+/// digital domain
+#[cfg(feature = "esp32")]
+const RTC_CNTL_DBIAS_1V10: u8 = 4;
+/// Attenuation of the bias during sleep, which lowers the sleep current
+#[cfg(feature = "esp32")]
+const RTC_CNTL_DBG_ATTEN_LOW: u8 = 3;
+#[cfg(not(feature = "esp32"))]
+const RTC_CNTL_DBG_ATTEN_LOW: u8 = 15;
+/// No attenuation of the bias during sleep
+const RTC_CNTL_DBG_ATTEN_NODROP: u8 = 0;
+
+/// A source which has woken up the chip from sleep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SleepSource {
+    /// The level of a single RTC IO
+    Ext0,
+    /// The levels of a set of RTC IOs
+    Ext1,
+    /// A GPIO
+    Gpio,
+    /// The RTC timer
+    Timer,
+    /// Activity on the RX line of a UART
+    Uart,
+    /// The touch sensor
+    Touch,
+    /// The ULP coprocessor
+    Ulp,
+    /// The ULP RISC-V coprocessor
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    UlpRiscv,
+    /// A trap of the ULP RISC-V coprocessor
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    UlpRiscvTrap,
+}
+
+impl SleepSource {
+    /// The first source set in `bits`, as returned by [wakeup_cause_bits]
+    pub(crate) fn from_cause_bits(bits: u32) -> Option<Self> {
+        const SOURCES: &[(u32, SleepSource)] = &[
+            (1 << 0, SleepSource::Ext0),
+            (1 << 1, SleepSource::Ext1),
+            (1 << 2, SleepSource::Gpio),
+            (1 << 3, SleepSource::Timer),
+            (1 << 6 | 1 << 7, SleepSource::Uart),
+            (1 << 8, SleepSource::Touch),
+            (1 << 9, SleepSource::Ulp),
+            #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+            (1 << 11, SleepSource::UlpRiscv),
+            #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+            (1 << 13, SleepSource::UlpRiscvTrap),
+        ];
+
+        SOURCES
+            .iter()
+            .find(|(mask, _)| bits & mask != 0)
+            .map(|(_, source)| *source)
+    }
+}
+
+/// The domains which stay powered during sleep
+///
+/// Powering down a domain lowers the sleep current, but the state of the
+/// domain is lost and powering it up again delays the wakeup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RtcSleepConfig {
+    /// The RTC peripherals, e.g. the RTC IO_MUX, the touch sensor and the
+    /// ULP. The EXT0 and EXT1 wakeup sources need them.
+    pub rtc_peripherals: bool,
+    /// The RTC fast memory, which holds the wake stub and `#[ram(rtc_fast)]`
+    /// statics
+    #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+    pub rtc_fast_memory: bool,
+    /// The RTC slow memory, which holds `#[ram(rtc_slow)]` statics and the
+    /// ULP program
+    #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+    pub rtc_slow_memory: bool,
+    /// The main XTAL, which makes the wakeup faster
+    pub xtal: bool,
+    /// The internal 8 MHz oscillator, which has to stay powered if it is the
+    /// source of RTC_SLOW_CLK
+    pub int_8m: bool,
+    /// The divider of the internal 8 MHz oscillator by 256. It stays enabled
+    /// anyway if it is the source of RTC_SLOW_CLK.
+    pub int_8m_d256: bool,
+    /// The VDD_SDIO regulator, which usually supplies the flash
+    pub vddsdio: bool,
+    /// Attenuate the bias of the analog circuits and lower the bias current,
+    /// which lowers the sleep current but makes the wakeup slower
+    pub low_bias: bool,
+}
+
+impl RtcSleepConfig {
+    /// Defaults for deep sleep: the RTC peripherals and memories stay
+    /// powered
+    pub fn deep() -> Self {
+        Self {
+            rtc_peripherals: true,
+            #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+            rtc_fast_memory: true,
+            #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+            rtc_slow_memory: true,
+            xtal: false,
+            int_8m: false,
+            int_8m_d256: false,
+            vddsdio: false,
+            low_bias: true,
+        }
+    }
+
+    /// Defaults for light sleep: like deep sleep, but the flash stays
+    /// powered, so the application can continue right away
+    pub fn light() -> Self {
+        Self {
+            vddsdio: true,
+            low_bias: false,
+            ..Self::deep()
+        }
+    }
+
+    /// The settings of `preset`
+    pub fn preset(preset: SleepPreset) -> Self {
+        match preset {
+            SleepPreset::UltraLowPower => Self {
+                rtc_peripherals: false,
+                ..Self::deep()
+            },
+            SleepPreset::FastWake => Self {
+                rtc_peripherals: true,
+                #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+                rtc_fast_memory: true,
+                #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+                rtc_slow_memory: true,
+                xtal: true,
+                int_8m: true,
+                int_8m_d256: true,
+                vddsdio: true,
+                low_bias: false,
+            },
+        }
+    }
+}
+
+/// Presets of [RtcSleepConfig] for common tradeoffs between sleep current and
+/// wakeup latency
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SleepPreset {
+    /// Lowest sleep current: only the RTC memories stay powered, the clocks
+    /// and the flash are powered down and the bias is lowered. The wakeup
+    /// sources which need the RTC peripherals, like EXT0, EXT1, the touch
+    /// sensor and the ULP, cannot be used.
+    UltraLowPower,
+    /// Fastest wakeup: the RTC domain, the clocks and the flash stay powered
+    /// and the bias is kept, at the cost of a higher sleep current
+    FastWake,
+}
+
+impl From<SleepPreset> for RtcSleepConfig {
+    fn from(preset: SleepPreset) -> Self {
+        Self::preset(preset)
+    }
+}
+
+/// Light sleep configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LightSleepConfig {
+    /// Wait until the UARTs set up by a [Serial](crate::Serial) driver have
+    /// sent their pending data before entering sleep. Otherwise, the
+    /// transmission is interrupted while their clock is gated.
+    pub flush_uarts: bool,
+    /// The domains which stay powered during sleep
+    pub power: RtcSleepConfig,
+}
+
+impl Default for LightSleepConfig {
+    fn default() -> Self {
+        Self {
+            flush_uarts: true,
+            power: RtcSleepConfig::light(),
+        }
+    }
+}
+
+/// A source which wakes up the chip from sleep
+pub trait WakeSource {
+    /// Configure the source and return its bits in `RTC_CNTL_WAKEUP_ENA`
+    fn apply(&self, rtc: &Rtc) -> u32;
+}
+
+/// Wakes up the chip once a duration has passed
+///
+/// The duration is measured with the RTC timer, converted using the
+/// RTC_SLOW_CLK period calibrated by [Rtc::new].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimerWakeupSource {
+    duration: MicrosDurationU64,
+}
+
+impl TimerWakeupSource {
+    /// Wake up after `duration`
+    pub fn new<T>(duration: T) -> Self
+    where
+        T: Into<MicrosDurationU64>,
+    {
+        Self {
+            duration: duration.into(),
+        }
+    }
+}
+
+impl WakeSource for TimerWakeupSource {
+    fn apply(&self, rtc: &Rtc) -> u32 {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        let ticks = RtcClock::us_to_cycles(self.duration.to_micros());
+        let alarm = rtc.get_time_raw() + ticks;
+
+        trace!("RTC timer wakeup in {} slow clock cycles", ticks);
+
+        rtc_cntl
+            .slp_timer0
+            .write(|w| unsafe { w.slp_val_lo().bits(alarm as u32) });
+        rtc_cntl.slp_timer1.write(|w| unsafe {
+            w.slp_val_hi()
+                .bits((alarm >> 32) as u16)
+                .main_timer_alarm_en()
+                .set_bit()
+        });
+
+        RTC_TIMER_TRIG_EN
+    }
+}
+
+/// The level of a pin which wakes up the chip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeupLevel {
+    Low,
+    High,
+}
+
+/// Wakes up the chip when a pin connected to the RTC IO_MUX is at a level
+///
+/// The pin is handed to the RTC IO_MUX while the wakeup source is applied,
+/// and back to the IO_MUX when it is dropped. Its pull-up or pull-down
+/// resistor has to be configured through the RTC IO_MUX as well.
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+pub struct Ext0WakeupSource<'d, P> {
+    pin: RefCell<PeripheralRef<'d, P>>,
+    level: WakeupLevel,
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+impl<'d, P> Ext0WakeupSource<'d, P>
+where
+    P: RtcPin,
+{
+    /// Wake up when `pin` is at `level`
+    pub fn new(pin: impl Peripheral<P = P> + 'd, level: WakeupLevel) -> Self {
+        into_ref!(pin);
+
+        Self {
+            pin: RefCell::new(pin),
+            level,
+        }
+    }
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+impl<'d, P> WakeSource for Ext0WakeupSource<'d, P>
+where
+    P: RtcPin,
+{
+    fn apply(&self, _rtc: &Rtc) -> u32 {
+        let rtcio = unsafe { &*RTCIO::ptr() };
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        let mut pin = self.pin.borrow_mut();
+        // RTC function 1 (`fun_sel` 0) is the RTC GPIO
+        pin.rtc_set_config(true, true, 0);
+
+        let rtc_io = super::rtc::rtc_io_number(pin.number());
+
+        rtcio
+            .ext_wakeup0
+            .modify(|_, w| unsafe { w.sel().bits(rtc_io) });
+        rtc_cntl
+            .ext_wakeup_conf
+            .modify(|_, w| w.ext_wakeup0_lv().bit(self.level == WakeupLevel::High));
+
+        RTC_EXT0_TRIG_EN
+    }
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+impl<'d, P> Drop for Ext0WakeupSource<'d, P>
+where
+    P: RtcPin,
+{
+    fn drop(&mut self) {
+        // hand the pin back to the IO_MUX
+        self.pin.get_mut().rtc_set_config(true, false, 0);
+    }
+}
+
+/// The condition on the pins of an [Ext1WakeupSource] which wakes up the chip
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Ext1WakeupMode {
+    /// Wake up when all of the pins are low
+    #[cfg(feature = "esp32")]
+    AllLow,
+    /// Wake up when any of the pins is low
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    AnyLow,
+    /// Wake up when any of the pins is high
+    AnyHigh,
+}
+
+/// Wakes up the chip depending on the levels of several pins connected to
+/// the RTC IO_MUX
+///
+/// The pins are added with [Ext1WakeupSource::with_pin], which hands each of
+/// them to the RTC IO_MUX. They stay connected to the RTC IO_MUX until they
+/// are configured again.
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+pub struct Ext1WakeupSource<'d> {
+    /// One bit per RTC IO
+    rtc_ios: u32,
+    mode: Ext1WakeupMode,
+    _pins: PhantomData<&'d mut ()>,
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+impl<'d> Ext1WakeupSource<'d> {
+    /// Wake up when the levels of the pins match `mode`
+    ///
+    /// Without pins, the source never wakes up the chip.
+    pub fn new(mode: Ext1WakeupMode) -> Self {
+        Self {
+            rtc_ios: 0,
+            mode,
+            _pins: PhantomData,
+        }
+    }
+
+    /// Add `pin` to the pins whose levels wake up the chip
+    pub fn with_pin<P>(mut self, pin: impl Peripheral<P = P> + 'd) -> Self
+    where
+        P: RtcPin,
+    {
+        into_ref!(pin);
+
+        // RTC function 1 (`fun_sel` 0) is the RTC GPIO
+        pin.rtc_set_config(true, true, 0);
+        self.rtc_ios |= 1 << super::rtc::rtc_io_number(pin.number());
+
+        self
+    }
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+impl<'d> WakeSource for Ext1WakeupSource<'d> {
+    fn apply(&self, _rtc: &Rtc) -> u32 {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        // forget the pins which woke up the chip the last time
+        rtc_cntl.ext_wakeup1.modify(|_, w| w.status_clr().set_bit());
+        rtc_cntl
+            .ext_wakeup1
+            .modify(|_, w| unsafe { w.sel().bits(self.rtc_ios) });
+        rtc_cntl
+            .ext_wakeup_conf
+            .modify(|_, w| w.ext_wakeup1_lv().bit(self.mode == Ext1WakeupMode::AnyHigh));
+
+        RTC_EXT1_TRIG_EN
+    }
+}
+
+/// Wakes up the chip when a pad of the touch sensor is touched
+///
+/// The touch sensor keeps scanning its pads during sleep, so it has to stay
+/// configured until the chip has woken up.
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+pub struct TouchWakeupSource<'a> {
+    pad: u8,
+    threshold: u32,
+    _touch: PhantomData<&'a ()>,
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+impl<'a> TouchWakeupSource<'a> {
+    /// Wake up when `pad` is touched
+    ///
+    /// On the ESP32, the pad counts as touched when its reading falls below
+    /// `threshold`, which is limited to 16 bits. Several pads can wake up the
+    /// chip, each with its own threshold.
+    ///
+    /// On the ESP32-S2 and ESP32-S3, the pad counts as touched when its
+    /// reading rises `threshold` above its baseline. Only one pad can wake up
+    /// the chip, so the last applied source wins.
+    pub fn new<'d, PIN>(
+        _touch: &'a TouchSensor<'d>,
+        pad: &'a TouchPad<'d, PIN>,
+        threshold: u32,
+    ) -> Self
+    where
+        PIN: TouchPin + RtcPin,
+    {
+        Self {
+            pad: pad.number(),
+            threshold,
+            _touch: PhantomData,
+        }
+    }
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+impl<'a> WakeSource for TouchWakeupSource<'a> {
+    fn apply(&self, _rtc: &Rtc) -> u32 {
+        touch::enable_wakeup(self.pad, self.threshold);
+
+        RTC_TOUCH_TRIG_EN
+    }
+}
+
+/// A function which runs right after the chip has woken up from deep sleep
+#[cfg(not(feature = "esp32s2"))]
+pub type WakeStub = unsafe extern "C" fn();
+
+/// The wakeup sources which have woken up the chip from sleep, as bits of
+/// `RTC_CNTL_WAKEUP_ENA`
+///
+/// This is always inlined, so it can be called from a wake stub.
+#[inline(always)]
+pub fn wakeup_cause_bits() -> u32 {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    #[cfg(feature = "esp32")]
+    let cause = rtc_cntl.wakeup_state.read().wakeup_cause().bits() as u32;
+    #[cfg(not(feature = "esp32"))]
+    let cause = rtc_cntl.slp_wakeup_cause.read().wakeup_cause().bits();
+
+    cause
+}
+
+impl Rtc {
+    /// Run `stub` right after the chip wakes up from deep sleep
+    ///
+    /// # Safety
+    ///
+    /// The stub runs before the caches and the flash are set up, so it must
+    /// be placed in RTC fast memory with `#[ram(rtc_fast)]`, and may only
+    /// access RTC memory, peripheral registers and functions which are
+    /// inlined or in RTC fast memory as well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stub` is not in RTC fast memory.
+    #[cfg(not(feature = "esp32s2"))]
+    pub unsafe fn set_wake_stub(&mut self, stub: WakeStub) {
+        let rtc_cntl = &*RTC_CNTL::ptr();
+        let address = stub as usize;
+
+        assert!(
+            super::rtc::RTC_FAST_TEXT.contains(&address),
+            "the wake stub is not in RTC fast memory"
+        );
+
+        rtc_cntl.store6.write(|w| w.bits(address as u32));
+    }
+
+    /// Boot normally after waking up from deep sleep, without a wake stub
+    #[cfg(not(feature = "esp32s2"))]
+    pub fn clear_wake_stub(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        rtc_cntl.store6.write(|w| unsafe { w.bits(0) });
+    }
+
+    /// Enter deep sleep until one of `wake_sources` wakes up the chip
+    ///
+    /// The chip is reset when it wakes up, so this never returns.
+    pub fn sleep_deep(&mut self, wake_sources: &[&dyn WakeSource]) -> ! {
+        self.sleep_deep_with_config(RtcSleepConfig::deep(), wake_sources)
+    }
+
+    /// Enter deep sleep with the domains selected by `config` powered, until
+    /// one of `wake_sources` wakes up the chip
+    ///
+    /// The chip is reset when it wakes up, so this never returns.
+    pub fn sleep_deep_with_config(
+        &mut self,
+        config: RtcSleepConfig,
+        wake_sources: &[&dyn WakeSource],
+    ) -> ! {
+        let triggers = wake_sources
+            .iter()
+            .fold(0, |triggers, source| triggers | source.apply(self));
+
+        configure_deep_sleep();
+        configure_power_domains(&config);
+
+        // the ROM only runs the wake stub if the RTC fast memory is intact
+        #[cfg(not(feature = "esp32s2"))]
+        unsafe {
+            crate::rom::set_rtc_memory_crc();
+        }
+
+        start_sleep(triggers);
+
+        loop {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Enter light sleep until one of `wake_sources` wakes up the chip
+    ///
+    /// The CPU continues after this call once the chip has woken up, with
+    /// the CPU and APB frequencies it ran at before.
+    pub fn sleep_light(&mut self, config: LightSleepConfig, wake_sources: &[&dyn WakeSource]) {
+        let triggers = wake_sources
+            .iter()
+            .fold(0, |triggers, source| triggers | source.apply(self));
+
+        if config.flush_uarts {
+            serial::flush_all();
+        }
+
+        // the PLL is powered down during sleep
+        let cpu_clock = clock::switch_cpu_to_xtal();
+
+        configure_light_sleep();
+        configure_power_domains(&config.power);
+        let enabled = start_sleep(triggers);
+        wait_for_wakeup();
+        finish_sleep(triggers, enabled);
+
+        if let Some(cpu_clock) = cpu_clock {
+            clock::restore_cpu_clock(cpu_clock);
+        }
+    }
+}
+
+/// Power down the digital domain and the analog blocks during sleep
+#[cfg(feature = "esp32")]
+fn configure_deep_sleep() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .timer5
+        .modify(|_, w| unsafe { w.min_slp_val().bits(RTC_CNTL_MIN_SLP_VAL_MIN) });
+
+    rtc_cntl.dig_iso.modify(|_, w| {
+        w.dg_wrap_force_noiso()
+            .clear_bit()
+            .wifi_force_noiso()
+            .clear_bit()
+            .dg_pad_force_iso()
+            .clear_bit()
+            .dg_pad_force_noiso()
+            .clear_bit()
+    });
+    rtc_cntl.dig_pwc.modify(|_, w| {
+        w.dg_wrap_pd_en()
+            .set_bit()
+            .dg_wrap_force_pu()
+            .clear_bit()
+            .dg_wrap_force_pd()
+            .clear_bit()
+            .wifi_force_pu()
+            .clear_bit()
+            .wifi_pd_en()
+            .set_bit()
+    });
+
+    rtc_cntl.options0.modify(|_, w| {
+        w.bias_force_nosleep()
+            .clear_bit()
+            .bb_i2c_force_pu()
+            .clear_bit()
+    });
+    rtc_cntl.ana_conf.modify(|_, w| {
+        w.ckgen_i2c_pu()
+            .clear_bit()
+            .pll_i2c_pu()
+            .clear_bit()
+            .rfrx_pbus_pu()
+            .clear_bit()
+            .txrf_i2c_pu()
+            .clear_bit()
+    });
+
+    rtc_cntl
+        .reg
+        .modify(|_, w| unsafe { w.dig_dbias_slp().bits(RTC_CNTL_DBIAS_0V90) });
+}
+
+/// Power down the digital domain and the analog blocks during sleep
+#[cfg(not(feature = "esp32"))]
+fn configure_deep_sleep() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .timer5
+        .modify(|_, w| unsafe { w.min_slp_val().bits(RTC_CNTL_MIN_SLP_VAL_MIN) });
+
+    rtc_cntl.dig_iso.modify(|_, w| {
+        w.wifi_force_noiso()
+            .clear_bit()
+            .wifi_force_iso()
+            .clear_bit()
+    });
+    rtc_cntl.dig_pwc.modify(|_, w| {
+        w.dg_wrap_pd_en()
+            .set_bit()
+            .wifi_force_pu()
+            .clear_bit()
+            .wifi_pd_en()
+            .set_bit()
+    });
+
+    rtc_cntl
+        .options0
+        .modify(|_, w| w.bb_i2c_force_pu().clear_bit());
+    rtc_cntl.ana_conf.modify(|_, w| {
+        w.ckgen_i2c_pu()
+            .clear_bit()
+            .pll_i2c_pu()
+            .clear_bit()
+            .rfrx_pbus_pu()
+            .clear_bit()
+            .txrf_i2c_pu()
+            .clear_bit()
+    });
+}
+
+/// Keep the digital domain powered but power down the analog blocks during
+/// sleep
+fn configure_light_sleep() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .timer5
+        .modify(|_, w| unsafe { w.min_slp_val().bits(RTC_CNTL_MIN_SLP_VAL_MIN) });
+
+    rtc_cntl
+        .dig_pwc
+        .modify(|_, w| w.dg_wrap_pd_en().clear_bit());
+
+    rtc_cntl
+        .options0
+        .modify(|_, w| w.bb_i2c_force_pu().clear_bit());
+    rtc_cntl.ana_conf.modify(|_, w| {
+        w.ckgen_i2c_pu()
+            .clear_bit()
+            .pll_i2c_pu()
+            .clear_bit()
+            .rfrx_pbus_pu()
+            .clear_bit()
+            .txrf_i2c_pu()
+            .clear_bit()
+    });
+
+    #[cfg(feature = "esp32")]
+    rtc_cntl
+        .reg
+        .modify(|_, w| unsafe { w.dig_dbias_slp().bits(RTC_CNTL_DBIAS_1V10) });
+}
+
+/// Power down the domains which `config` does not keep powered during sleep
+fn configure_power_domains(config: &RtcSleepConfig) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .pwc
+        .modify(|_, w| w.pd_en().bit(!config.rtc_peripherals));
+
+    #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+    rtc_cntl.pwc.modify(|_, w| {
+        w.fastmem_pd_en()
+            .bit(!config.rtc_fast_memory)
+            .fastmem_force_pu()
+            .bit(config.rtc_fast_memory)
+            .fastmem_force_noiso()
+            .bit(config.rtc_fast_memory)
+            .slowmem_pd_en()
+            .bit(!config.rtc_slow_memory)
+            .slowmem_force_pu()
+            .bit(config.rtc_slow_memory)
+            .slowmem_force_noiso()
+            .bit(config.rtc_slow_memory)
+    });
+
+    rtc_cntl
+        .options0
+        .modify(|_, w| w.xtl_force_pu().bit(config.xtal));
+    rtc_cntl
+        .clk_conf
+        .modify(|_, w| w.ck8m_force_pu().bit(config.int_8m));
+
+    // RTC_FAST_CLK is derived from the 8 MHz oscillator, so it stays enabled
+    // while the chip is awake
+    let d256 =
+        config.int_8m_d256 || matches!(RtcClock::get_slow_freq(), RtcSlowClock::RtcSlowClock8mD256);
+    RtcClock::enable_8m(true, d256);
+
+    let dbg_atten = if config.low_bias {
+        RTC_CNTL_DBG_ATTEN_LOW
+    } else {
+        RTC_CNTL_DBG_ATTEN_NODROP
+    };
+
+    #[cfg(feature = "esp32")]
+    rtc_cntl
+        .bias_conf
+        .modify(|_, w| unsafe { w.dbg_atten().bits(dbg_atten) });
+    #[cfg(not(feature = "esp32"))]
+    rtc_cntl.bias_conf.modify(|_, w| unsafe {
+        w.dbg_atten_deep_slp()
+            .bits(dbg_atten)
+            .bias_sleep_deep_slp()
+            .bit(config.low_bias)
+            .pd_cur_deep_slp()
+            .bit(config.low_bias)
+    });
+
+    // the regulator is controlled by the sleep state machine
+    #[cfg(feature = "esp32")]
+    rtc_cntl
+        .sdio_conf
+        .modify(|_, w| w.sdio_force().clear_bit().sdio_pd_en().bit(!config.vddsdio));
+    #[cfg(not(feature = "esp32"))]
+    rtc_cntl.sdio_conf.modify(|_, w| {
+        w.sdio_force()
+            .clear_bit()
+            .sdio_reg_pd_en()
+            .bit(!config.vddsdio)
+    });
+}
+
+/// Wait until the chip has woken up from light sleep, or has rejected
+/// entering it
+fn wait_for_wakeup() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    #[cfg(feature = "esp32")]
+    while {
+        let raw = rtc_cntl.int_raw.read();
+        !raw.slp_wakeup_int_raw().bit_is_set() && !raw.slp_reject_int_raw().bit_is_set()
+    } {}
+
+    #[cfg(not(feature = "esp32"))]
+    while {
+        let raw = rtc_cntl.int_raw_rtc.read();
+        !raw.slp_wakeup_int_raw().bit_is_set() && !raw.slp_reject_int_raw().bit_is_set()
+    } {}
+
+    clear_sleep_interrupts();
+}
+
+/// Clear the raw sleep wakeup and sleep reject interrupts
+fn clear_sleep_interrupts() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    #[cfg(feature = "esp32")]
+    rtc_cntl.int_clr.write(|w| {
+        w.slp_wakeup_int_clr()
+            .set_bit()
+            .slp_reject_int_clr()
+            .set_bit()
+    });
+
+    #[cfg(not(feature = "esp32"))]
+    rtc_cntl.int_clr_rtc.write(|w| {
+        w.slp_wakeup_int_clr()
+            .set_bit()
+            .slp_reject_int_clr()
+            .set_bit()
+    });
+}
+
+/// Enable the wakeup sources and enter sleep, returning the wakeup sources
+/// enabled before
+fn start_sleep(triggers: u32) -> u32 {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    // keep the wakeup sources enabled by other drivers, e.g. the ULP
+    let enabled = rtc_cntl.wakeup_state.read().wakeup_ena().bits();
+    rtc_cntl
+        .wakeup_state
+        .modify(|_, w| unsafe { w.wakeup_ena().bits(enabled | triggers) });
+
+    // a wakeup or reject left over from an earlier sleep would end this one
+    // right away
+    clear_sleep_interrupts();
+
+    rtc_cntl
+        .state0
+        .modify(|_, w| w.sleep_en().set_bit().slp_wakeup().set_bit());
+
+    enabled
+}
+
+/// Disable the wakeup sources enabled for a light sleep again, so that they
+/// do not wake up later sleeps
+fn finish_sleep(triggers: u32, enabled: u32) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .wakeup_state
+        .modify(|_, w| unsafe { w.wakeup_ena().bits(enabled) });
+
+    if triggers & RTC_TIMER_TRIG_EN != 0 {
+        rtc_cntl
+            .slp_timer1
+            .modify(|_, w| w.main_timer_alarm_en().clear_bit());
+    }
+}
@@ -0,0 +1,113 @@
+//! # Wall-clock time
+//!
+//! The wall-clock time is kept as a reference point, a time since the Unix
+//! epoch and the RTC timer value at that time, in RTC fast memory. It is
+//! advanced with the RTC timer, which keeps running in sleep, so the time
+//! survives deep sleep as well:
+//!
+//! ```no_run
+//! // e.g. from SNTP or a GPS receiver
+//! rtc.set_time(1_666_000_000_000_000);
+//!
+//! rtc.sleep_deep(&[&timer]);
+//!
+//! // after waking up
+//! let now_us = rtc.get_time().unwrap();
+//! ```
+//!
+//! Whenever the RTC_SLOW_CLK period is recalibrated, e.g. by
+//! [SlowClockRecalibration](super::SlowClockRecalibration), the reference
+//! point is moved to the current time, so that the time elapsed so far is
+//! converted with the period which was valid while it elapsed.
+//!
+//! The time is lost on power-up and on resets of the RTC domain. On the
+//! ESP32, RTC fast memory is only accessible by the PRO CPU, so the time can
+//! only be used from there.
+
+use core::ptr::{addr_of, addr_of_mut};
+
+use super::{Rtc, RtcClock};
+
+/// Marks a valid reference point, `"TIME"`
+const REFERENCE_MAGIC: u32 = 0x454d_4954;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Reference {
+    magic: u32,
+    /// Inverted XOR of the other fields, to tell a reference point from the
+    /// random contents of RTC fast memory after power-up
+    check: u32,
+    ticks: u64,
+    epoch_us: u64,
+}
+
+impl Reference {
+    fn checksum(&self) -> u32 {
+        !(self.magic
+            ^ self.ticks as u32
+            ^ (self.ticks >> 32) as u32
+            ^ self.epoch_us as u32
+            ^ (self.epoch_us >> 32) as u32)
+    }
+
+    /// The time since the Unix epoch at RTC timer value `ticks`
+    fn time_at(&self, ticks: u64) -> u64 {
+        self.epoch_us + RtcClock::cycles_to_us(ticks.saturating_sub(self.ticks))
+    }
+}
+
+#[link_section = ".rtc_fast.noinit"]
+static mut REFERENCE: Reference = Reference {
+    magic: 0,
+    check: 0,
+    ticks: 0,
+    epoch_us: 0,
+};
+
+fn read_reference() -> Option<Reference> {
+    let reference = unsafe { addr_of!(REFERENCE).read_volatile() };
+
+    if reference.magic == REFERENCE_MAGIC && reference.check == reference.checksum() {
+        Some(reference)
+    } else {
+        None
+    }
+}
+
+fn write_reference(ticks: u64, epoch_us: u64) {
+    let mut reference = Reference {
+        magic: REFERENCE_MAGIC,
+        check: 0,
+        ticks,
+        epoch_us,
+    };
+    reference.check = reference.checksum();
+
+    unsafe { addr_of_mut!(REFERENCE).write_volatile(reference) };
+}
+
+/// Move the reference point to the current time, before the RTC_SLOW_CLK
+/// period is updated
+pub(crate) fn rebase() {
+    if let Some(reference) = read_reference() {
+        let ticks = RtcClock::get_time_raw();
+
+        write_reference(ticks, reference.time_at(ticks));
+    }
+}
+
+impl Rtc {
+    /// Set the wall-clock time, in microseconds since the Unix epoch
+    pub fn set_time(&mut self, epoch_us: u64) {
+        critical_section::with(|_| write_reference(RtcClock::get_time_raw(), epoch_us));
+    }
+
+    /// The wall-clock time, in microseconds since the Unix epoch, or `None`
+    /// if it has not been set since power-up
+    pub fn get_time(&self) -> Option<u64> {
+        critical_section::with(|_| {
+            read_reference().map(|reference| reference.time_at(RtcClock::get_time_raw()))
+        })
+    }
+}
@@ -1,19 +1,66 @@
+use core::{cell::Cell, fmt, marker::PhantomData};
+
+use critical_section::Mutex;
 use embedded_hal::watchdog::{Watchdog, WatchdogDisable, WatchdogEnable};
 use fugit::{HertzU32, MicrosDurationU64};
 
 #[cfg(not(feature = "esp32"))]
 use crate::efuse::Efuse;
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+use crate::touch::Touch;
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+use crate::ulp::Ulp;
 use crate::{
     clock::{Clock, XtalClock},
     pac::{RTC_CNTL, TIMG0},
     rom::esp_rom_delay_us,
+    time::TimestampSource,
+    timer::{ClockSource, Instance, Timer},
+    Cpu,
 };
 
 #[cfg_attr(feature = "esp32", path = "rtc/esp32.rs")]
 #[cfg_attr(feature = "esp32s2", path = "rtc/esp32s2.rs")]
 #[cfg_attr(feature = "esp32s3", path = "rtc/esp32s3.rs")]
 #[cfg_attr(feature = "esp32c3", path = "rtc/esp32c3.rs")]
-mod rtc;
+pub(crate) mod rtc;
+
+#[path = "rtc/brownout.rs"]
+pub mod brownout;
+#[path = "rtc/sleep.rs"]
+pub mod sleep;
+#[path = "rtc/wall_clock.rs"]
+mod wall_clock;
+
+use self::brownout::BrownoutDetector;
+pub use self::{rtc::SocResetReason, sleep::SleepSource};
+
+/// Whether the last reset was a wakeup from deep sleep
+///
+/// Used by the startup code of the chip crates to keep the statics in RTC
+/// memory across deep sleep.
+#[doc(hidden)]
+pub fn woken_from_deep_sleep() -> bool {
+    reset_reason(Cpu::ProCpu) == Some(SocResetReason::CoreDeepSleep)
+}
+
+/// The reason of the last reset of `cpu`, or `None` if the ROM reports an
+/// unknown reason
+pub fn reset_reason(cpu: Cpu) -> Option<SocResetReason> {
+    let reason = unsafe { crate::rom::rtc_get_reset_reason(cpu as u32) };
+
+    SocResetReason::from_raw(reason)
+}
+
+/// The source which has woken up the chip from deep sleep, or `None` if the
+/// last reset was not a wakeup from deep sleep
+pub fn wakeup_cause() -> Option<SleepSource> {
+    if !woken_from_deep_sleep() {
+        return None;
+    }
+
+    SleepSource::from_cause_bits(sleep::wakeup_cause_bits())
+}
 
 #[allow(unused)]
 #[derive(Debug, Clone, Copy)]
@@ -28,7 +75,7 @@ pub(crate) enum RtcFastClock {
 impl Clock for RtcFastClock {
     fn frequency(&self) -> HertzU32 {
         match self {
-            RtcFastClock::RtcFastClockXtalD4 => HertzU32::Hz(40_000_000 / 4),
+            RtcFastClock::RtcFastClockXtalD4 => HertzU32::Hz(RtcClock::get_xtal_freq().hz() / 4),
             #[cfg(any(feature = "esp32", feature = "esp32s2"))]
             RtcFastClock::RtcFastClock8m => HertzU32::Hz(8_500_000),
             #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
@@ -67,10 +114,11 @@ impl Clock for RtcSlowClock {
     }
 }
 
-#[allow(unused)]
-#[derive(Debug, Clone, Copy)]
-/// Clock source to be calibrated using rtc_clk_cal function
-pub(crate) enum RtcCalSel {
+/// Clock to be calibrated with [RtcClock::calibrate] and
+/// [RtcClock::get_calibration_ratio]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RtcCalSel {
     /// Currently selected RTC SLOW_CLK
     RtcCalRtcMux      = 0,
     /// Internal 8 MHz RC oscillator, divided by 256
@@ -87,11 +135,18 @@ pub struct Rtc {
     pub rwdt: Rwdt,
     #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
     pub swd: Swd,
+    pub brownout: BrownoutDetector,
+    #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+    pub ulp: Ulp,
+    #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+    pub touch: Touch,
 }
 
 impl Rtc {
     pub fn new(rtc_cntl: RTC_CNTL) -> Self {
         rtc::init();
+        // the period calibrated before deep sleep is about to be replaced
+        wall_clock::rebase();
         rtc::configure_clock();
 
         Self {
@@ -99,17 +154,592 @@ impl Rtc {
             rwdt: Rwdt::default(),
             #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
             swd: Swd::new(),
+            brownout: BrownoutDetector::new(),
+            #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+            ulp: Ulp::new(),
+            #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+            touch: Touch::new(),
         }
     }
 
+    /// Estimate the XTAL frequency in MHz against the internal 8 MHz
+    /// oscillator
+    ///
+    /// The estimate is only accurate to a few MHz. On the ESP32, it can be
+    /// used to tell 40 MHz and 26 MHz crystals apart, see
+    /// `XtalFrequency::Detect`.
     pub fn estimate_xtal_frequency(&mut self) -> u32 {
         RtcClock::estimate_xtal_frequency()
     }
+
+    /// Start estimating the XTAL frequency in the background
+    ///
+    /// Unlike [Rtc::estimate_xtal_frequency], this does not wait for the
+    /// measurement. The estimated frequency in MHz is returned by
+    /// [Calibration::poll_result] once it is done.
+    pub fn start_xtal_estimation(&mut self) -> Calibration<'_> {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let clk_8m_enabled = rtc_cntl.clk_conf.read().enb_ck8m().bit_is_clear();
+        let clk_8md256_enabled = rtc_cntl.clk_conf.read().enb_ck8m_div().bit_is_clear();
+
+        if !clk_8md256_enabled {
+            RtcClock::enable_8m(true, true);
+        }
+
+        Calibration {
+            _rtc: PhantomData,
+            state: Some(RtcClock::start_calibration(
+                RtcCalSel::RtcCal8mD256,
+                XTAL_FREQ_EST_CYCLES,
+            )),
+            kind: CalibrationKind::XtalEstimation {
+                clk_8m_enabled,
+                clk_8md256_enabled,
+            },
+            result: None,
+        }
+    }
+
+    /// Measure the frequency of APB_CLK against XTAL_CLK
+    ///
+    /// `timer` counts APB_CLK cycles while a calibration counts the XTAL
+    /// cycles within a number of cycles of the 8 MHz internal oscillator,
+    /// divided by 256, which takes about 32 ms. The result is accurate to
+    /// about 0.1%, e.g. to check the clock configuration, provided that the
+    /// XTAL frequency is known.
+    ///
+    /// The timer has to be clocked by APB_CLK. Its configuration is restored
+    /// afterwards, see [Error::TimerNotClockedByApb].
+    pub fn measure_apb_frequency<T>(&mut self, timer: &mut Timer<T>) -> Result<HertzU32, Error>
+    where
+        T: Instance,
+    {
+        if timer.clock_source() != ClockSource::Apb {
+            return Err(Error::TimerNotClockedByApb);
+        }
+
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let clk_8m_enabled = rtc_cntl.clk_conf.read().enb_ck8m().bit_is_clear();
+        let clk_8md256_enabled = rtc_cntl.clk_conf.read().enb_ck8m_div().bit_is_clear();
+
+        if !clk_8md256_enabled {
+            RtcClock::enable_8m(true, true);
+        }
+
+        // count at the highest rate the divider allows
+        let (xtal_cycles, apb_cycles) = timer.with_counter(2, |counter| {
+            let calibration =
+                RtcClock::start_calibration(RtcCalSel::RtcCal8mD256, APB_FREQ_EST_CYCLES);
+            let start = counter.read_raw();
+            let xtal_cycles = loop {
+                if let Some(xtal_cycles) = RtcClock::poll_calibration(&calibration) {
+                    break xtal_cycles;
+                }
+            };
+            let apb_cycles = (counter.read_raw() - start) * 2;
+
+            RtcClock::finish_calibration(&calibration);
+
+            (xtal_cycles, apb_cycles)
+        });
+
+        RtcClock::enable_8m(clk_8m_enabled, clk_8md256_enabled);
+
+        if xtal_cycles == 0 {
+            return Err(Error::CalibrationTimeout);
+        }
+
+        let xtal_hz = RtcClock::get_xtal_freq().hz() as u64;
+        let apb_hz = apb_cycles * xtal_hz / xtal_cycles as u64;
+
+        trace!("Measured APB frequency: {} Hz", apb_hz);
+
+        Ok(HertzU32::Hz(apb_hz as u32))
+    }
+
+    /// Start measuring the period of RTC_SLOW_CLK over `slowclk_cycles` of
+    /// its cycles in the background
+    ///
+    /// The period in microseconds, as Q13.19 fixed point number, is returned
+    /// by [Calibration::poll_result] once the measurement is done. The
+    /// measurement takes about `slowclk_cycles` RTC_SLOW_CLK cycles.
+    pub fn start_slow_clock_calibration(&mut self, slowclk_cycles: u32) -> Calibration<'_> {
+        Calibration {
+            _rtc: PhantomData,
+            state: Some(RtcClock::start_calibration(
+                RtcCalSel::RtcCalRtcMux,
+                slowclk_cycles,
+            )),
+            kind: CalibrationKind::SlowClock { slowclk_cycles },
+            result: None,
+        }
+    }
+
+    /// Clock RTC_SLOW_CLK from a 32.768 kHz crystal on the 32K_XP and 32K_XN
+    /// pins instead of the internal RC oscillator
+    ///
+    /// The crystal is more accurate and drifts less with temperature than the
+    /// RC oscillator. It is checked up to three times whether the crystal has
+    /// started to oscillate, bootstrapping it first on the ESP32. If it never
+    /// starts, e.g. because there is no crystal on the board, it is powered
+    /// down again, RTC_SLOW_CLK stays on the RC oscillator and
+    /// [Error::OscillatorNotStarted] is returned.
+    pub fn use_32k_xtal(&mut self) -> Result<(), Error> {
+        RtcClock::select_32k(|| rtc::start_32k_xtal(XTAL_32K_BOOTSTRAP_CYCLES))
+    }
+
+    /// Clock RTC_SLOW_CLK from an external 32.768 kHz clock, e.g. from a
+    /// TCXO, instead of the internal RC oscillator
+    ///
+    /// The square wave is fed into the 32K_XN pin on the ESP32, and into the
+    /// 32K_XP pin on the other chips, where the oscillator is bypassed. Like
+    /// with [Rtc::use_32k_xtal], RTC_SLOW_CLK stays on the RC oscillator and
+    /// [Error::OscillatorNotStarted] is returned if no clock is detected.
+    pub fn use_32k_external_clock(&mut self) -> Result<(), Error> {
+        RtcClock::select_32k(rtc::start_32k_external)
+    }
+
+    /// Read the 48-bit RTC timer, in RTC_SLOW_CLK cycles
+    ///
+    /// The RTC timer counts from power-up and, unlike the timers of the timer
+    /// groups, keeps running in light and deep sleep.
+    pub fn get_time_raw(&self) -> u64 {
+        RtcClock::get_time_raw()
+    }
+
+    /// Read the RTC timer, in microseconds
+    ///
+    /// The RTC_SLOW_CLK cycles are converted using the slow clock period
+    /// calibrated by [Rtc::new].
+    pub fn get_time_us(&self) -> u64 {
+        RtcClock::get_time_us()
+    }
+
+    /// Read the RTC timer, in milliseconds
+    ///
+    /// See [Rtc::get_time_us].
+    pub fn get_time_ms(&self) -> u64 {
+        RtcClock::get_time_us() / 1_000
+    }
+
+    /// Set the alarm of the RTC timer to the absolute RTC time `time_us`, in
+    /// microseconds
+    ///
+    /// The alarm raises the `RTC_CORE` interrupt once it is enabled with
+    /// [Rtc::listen]. The alarm fires once, it has to be set again for
+    /// periodic interrupts. Entering sleep with a
+    /// [TimerWakeupSource](sleep::TimerWakeupSource) replaces the alarm.
+    pub fn set_alarm(&mut self, time_us: u64) {
+        self.set_alarm_raw(RtcClock::us_to_cycles(time_us));
+    }
+
+    /// Set the alarm of the RTC timer to the absolute RTC time `time`, in
+    /// RTC_SLOW_CLK cycles
+    ///
+    /// See [Rtc::set_alarm].
+    pub fn set_alarm_raw(&mut self, time: u64) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        rtc_cntl
+            .slp_timer0
+            .write(|w| unsafe { w.slp_val_lo().bits(time as u32) });
+        rtc_cntl.slp_timer1.write(|w| unsafe {
+            w.slp_val_hi()
+                .bits((time >> 32) as u16)
+                .main_timer_alarm_en()
+                .set_bit()
+        });
+    }
+
+    /// Disarm the alarm of the RTC timer
+    pub fn cancel_alarm(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        rtc_cntl
+            .slp_timer1
+            .modify(|_, w| w.main_timer_alarm_en().clear_bit());
+    }
+
+    /// Enable the interrupt of the RTC timer alarm
+    pub fn listen(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        #[cfg(feature = "esp32")]
+        rtc_cntl
+            .int_ena
+            .modify(|_, w| w.main_timer_int_ena().set_bit());
+
+        #[cfg(feature = "esp32s2")]
+        rtc_cntl
+            .int_ena_rtc
+            .modify(|_, w| w.main_timer_int_ena().set_bit());
+
+        #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+        rtc_cntl
+            .int_ena_rtc
+            .modify(|_, w| w.rtc_main_timer_int_ena().set_bit());
+    }
+
+    /// Disable the interrupt of the RTC timer alarm
+    pub fn unlisten(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        #[cfg(feature = "esp32")]
+        rtc_cntl
+            .int_ena
+            .modify(|_, w| w.main_timer_int_ena().clear_bit());
+
+        #[cfg(feature = "esp32s2")]
+        rtc_cntl
+            .int_ena_rtc
+            .modify(|_, w| w.main_timer_int_ena().clear_bit());
+
+        #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+        rtc_cntl
+            .int_ena_rtc
+            .modify(|_, w| w.rtc_main_timer_int_ena().clear_bit());
+    }
+
+    /// Clear the interrupt of the RTC timer alarm
+    pub fn clear_interrupt(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        #[cfg(feature = "esp32")]
+        rtc_cntl.int_clr.write(|w| w.main_timer_int_clr().set_bit());
+
+        #[cfg(feature = "esp32s2")]
+        rtc_cntl
+            .int_clr_rtc
+            .write(|w| w.main_timer_int_clr().set_bit());
+
+        #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+        rtc_cntl
+            .int_clr_rtc
+            .write(|w| w.rtc_main_timer_int_clr().set_bit());
+    }
+
+    /// Whether the RTC timer alarm has fired
+    pub fn is_interrupt_set(&self) -> bool {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "esp32")] {
+                rtc_cntl.int_st.read().main_timer_int_st().bit_is_set()
+            } else if #[cfg(feature = "esp32s2")] {
+                rtc_cntl.int_st_rtc.read().main_timer_int_st().bit_is_set()
+            } else if #[cfg(any(feature = "esp32c3", feature = "esp32s3"))] {
+                rtc_cntl.int_st_rtc.read().rtc_main_timer_int_st().bit_is_set()
+            }
+        }
+    }
 }
 
-/// RTC Watchdog Timer
+/// RTC errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The calibrated clock did not complete its cycles in time, e.g.
+    /// because the oscillator has not started up
+    CalibrationTimeout,
+    /// The 32 kHz oscillator did not start up, RTC_SLOW_CLK is still clocked
+    /// by the internal RC oscillator
+    OscillatorNotStarted,
+    /// The timer passed to [Rtc::measure_apb_frequency] is not clocked by
+    /// APB_CLK
+    TimerNotClockedByApb,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::CalibrationTimeout => write!(f, "Clock calibration timed out"),
+            Error::OscillatorNotStarted => write!(f, "32 kHz oscillator did not start up"),
+            Error::TimerNotClockedByApb => write!(f, "Timer is not clocked by APB_CLK"),
+        }
+    }
+}
+
+/// A clock measurement running in the background
+///
+/// Started with [Rtc::start_xtal_estimation] or
+/// [Rtc::start_slow_clock_calibration]. Dropping it before the result is
+/// available aborts the measurement.
+pub struct Calibration<'a> {
+    _rtc: PhantomData<&'a mut Rtc>,
+    state: Option<CalibrationState>,
+    kind: CalibrationKind,
+    result: Option<Result<u32, Error>>,
+}
+
+enum CalibrationKind {
+    XtalEstimation {
+        clk_8m_enabled: bool,
+        clk_8md256_enabled: bool,
+    },
+    SlowClock {
+        slowclk_cycles: u32,
+    },
+}
+
+impl<'a> Calibration<'a> {
+    /// The result of the measurement, once it is done
+    ///
+    /// Returns [nb::Error::WouldBlock] while the measurement is still
+    /// running.
+    pub fn poll_result(&mut self) -> nb::Result<u32, Error> {
+        if let Some(result) = self.result {
+            return result.map_err(nb::Error::Other);
+        }
+
+        let state = self.state.as_ref().ok_or(nb::Error::WouldBlock)?;
+        let xtal_cycles = RtcClock::poll_calibration(state).ok_or(nb::Error::WouldBlock)?;
+
+        self.finish();
+
+        let result = match self.kind {
+            _ if xtal_cycles == 0 => Err(Error::CalibrationTimeout),
+            CalibrationKind::XtalEstimation { .. } => {
+                let ratio = RtcClock::calibration_ratio(xtal_cycles, XTAL_FREQ_EST_CYCLES);
+                let freq_mhz = RtcClock::xtal_frequency_from_ratio(ratio);
+
+                trace!("Estimated XTAL frequency: {} MHz", freq_mhz);
+
+                Ok(freq_mhz)
+            }
+            CalibrationKind::SlowClock { slowclk_cycles } => {
+                Ok(RtcClock::calibration_period(xtal_cycles, slowclk_cycles))
+            }
+        };
+        self.result = Some(result);
+
+        result.map_err(nb::Error::Other)
+    }
+
+    /// Wait for the result of the measurement
+    ///
+    /// The calibration cannot interrupt, so the future asks to be polled
+    /// again until the result is available.
+    #[cfg(feature = "async")]
+    pub async fn result(mut self) -> Result<u32, Error> {
+        crate::asynch::PollingFuture::new(move || self.poll_result()).await
+    }
+
+    fn finish(&mut self) {
+        if let Some(state) = self.state.take() {
+            RtcClock::finish_calibration(&state);
+
+            if let CalibrationKind::XtalEstimation {
+                clk_8m_enabled,
+                clk_8md256_enabled,
+            } = self.kind
+            {
+                RtcClock::enable_8m(clk_8m_enabled, clk_8md256_enabled);
+            }
+        }
+    }
+}
+
+impl<'a> Drop for Calibration<'a> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Number of RTC_SLOW_CLK cycles measured by [SlowClockRecalibration]
+const RECALIBRATION_CYCLES: u32 = 1024;
+
+/// Recalibration triggered by temperature changes, see
+/// [SlowClockRecalibration::with_temperature]
+#[derive(Clone, Copy)]
+struct TemperatureTrigger {
+    read_temperature: fn() -> i32,
+    threshold: u32,
+    last_temperature: i32,
+}
+
+/// Keeps the RTC_SLOW_CLK period up to date
+///
+/// The frequency of the internal slow RC oscillator drifts with temperature.
+/// [Rtc::new] measures its period once, which is used to convert
+/// RTC_SLOW_CLK cycles to time, e.g. by [Rtc::get_time_us] and by the RWDT
+/// timeouts. This service measures the period again in the background every
+/// `interval`, and optionally whenever the temperature changed, and stores
+/// the new period:
+///
+/// ```no_run
+/// let mut recalibration = SlowClockRecalibration::new(60u64.secs());
+///
+/// loop {
+///     recalibration.poll(&mut rtc);
+///     // do work
+/// }
+/// ```
+pub struct SlowClockRecalibration {
+    interval: MicrosDurationU64,
+    temperature: Option<TemperatureTrigger>,
+    last_calibration: u64,
+    running: Option<CalibrationState>,
+}
+
+impl SlowClockRecalibration {
+    /// Recalibrate every `interval`
+    pub fn new<T>(interval: T) -> Self
+    where
+        T: Into<MicrosDurationU64>,
+    {
+        Self {
+            interval: interval.into(),
+            temperature: None,
+            last_calibration: RtcClock::get_time_us(),
+            running: None,
+        }
+    }
+
+    /// Also recalibrate as soon as the temperature returned by
+    /// `read_temperature`, in °C, differs by at least `threshold` from the
+    /// temperature of the last calibration
+    ///
+    /// `read_temperature` is called on each [SlowClockRecalibration::poll],
+    /// e.g. to read the internal temperature sensor.
+    pub fn with_temperature(mut self, read_temperature: fn() -> i32, threshold: u32) -> Self {
+        self.temperature = Some(TemperatureTrigger {
+            read_temperature,
+            threshold,
+            last_temperature: read_temperature(),
+        });
+        self
+    }
+
+    /// Start a calibration when one is due, and store the period once it is
+    /// done
+    ///
+    /// This does not wait for the calibration, so it has to be called
+    /// regularly, e.g. from the main loop. If a calibration times out, the
+    /// previous period is kept and the next calibration is started after
+    /// `interval`.
+    ///
+    /// The timeout of the RWDT, if it was started with [Rwdt], is converted
+    /// again with the new period.
+    pub fn poll(&mut self, rtc: &mut Rtc) {
+        if let Some(state) = &self.running {
+            if let Some(xtal_cycles) = RtcClock::poll_calibration(state) {
+                RtcClock::finish_calibration(state);
+                self.running = None;
+
+                if xtal_cycles != 0 {
+                    RtcClock::update_slow_clock_period(RtcClock::calibration_period(
+                        xtal_cycles,
+                        RECALIBRATION_CYCLES,
+                    ));
+                    rtc.rwdt.update_timeout();
+                }
+                self.last_calibration = RtcClock::get_time_us();
+            }
+
+            return;
+        }
+
+        let elapsed = RtcClock::get_time_us().saturating_sub(self.last_calibration);
+        let mut due = elapsed >= self.interval.to_micros();
+
+        let temperature = self.temperature.map(|trigger| {
+            let temperature = (trigger.read_temperature)();
+            due |= temperature.abs_diff(trigger.last_temperature) >= trigger.threshold;
+            temperature
+        });
+
+        if due {
+            if let (Some(trigger), Some(temperature)) = (&mut self.temperature, temperature) {
+                trigger.last_temperature = temperature;
+            }
+
+            self.running = Some(RtcClock::start_calibration(
+                RtcCalSel::RtcCalRtcMux,
+                RECALIBRATION_CYCLES,
+            ));
+        }
+    }
+}
+
+impl Drop for SlowClockRecalibration {
+    fn drop(&mut self) {
+        if let Some(state) = self.running.take() {
+            RtcClock::finish_calibration(&state);
+        }
+    }
+}
+
+/// A calibration started by [RtcClock::start_calibration]
+struct CalibrationState {
+    cal_clk: RtcCalSel,
+    dig_32k_xtal_enabled: bool,
+    us_time_estimate: u32,
+    /// RTC timer value after which the calibration has timed out
+    #[cfg(feature = "esp32")]
+    deadline: u64,
+}
+
+/// The calibrated RTC timer as the global timestamp source
+///
+/// Unlike the timers clocked from APB_CLK, the RTC timer keeps running in
+/// light-sleep, so timestamps stay monotonic across sleep periods, at the
+/// cost of the lower resolution of RTC_SLOW_CLK.
+impl TimestampSource for &Rtc {
+    // `now` counts in microseconds, but only advances once per RTC_SLOW_CLK
+    // cycle
+    const FINE_GRAINED: bool = false;
+
+    fn start(&mut self) -> u64 {
+        // `now` already converts the cycles to microseconds
+        1_000_000
+    }
+
+    fn now() -> u64 {
+        RtcClock::get_time_us()
+    }
+}
+
+/// RTC timer value and time in microseconds when the stored RTC_SLOW_CLK
+/// period was last updated
+static TIME_BASE: Mutex<Cell<(u64, u64)>> = Mutex::new(Cell::new((0, 0)));
+
+/// Number of 8M/256 clock cycles to use for XTAL frequency estimation.
+const XTAL_FREQ_EST_CYCLES: u32 = 10;
+/// Number of 8M/256 clock cycles to measure the 8 MHz oscillator over for
+/// each tuning value
+const TUNE_8M_CAL_CYCLES: u32 = 50;
+/// Number of 8M/256 clock cycles to measure the APB frequency over, long
+/// enough for the uncertain start of the calibration not to matter
+const APB_FREQ_EST_CYCLES: u32 = 1000;
+
+/// Number of cycles to calibrate the 32 kHz clock with
+const XTAL_32K_CAL_CYCLES: u32 = 1024;
+/// Number of cycles the 32 kHz crystal is driven to bootstrap it
+const XTAL_32K_BOOTSTRAP_CYCLES: u32 = 5;
+/// Number of times the 32 kHz clock is started before giving up
+const XTAL_32K_STARTUP_ATTEMPTS: u32 = 3;
+/// Shortest plausible period of the 32 kHz clock in microseconds, as Q13.19
+/// fixed point number (about 28.6 us, the nominal period is 30.5 us)
+const XTAL_32K_MIN_PERIOD: u32 = 15_000_000;
+
+/// Clocks of the RTC
+///
+/// The periods of the RTC clocks are measured by counting the XTAL cycles
+/// within a number of their cycles. [Rtc::new] measures the period of
+/// RTC_SLOW_CLK once and stores it, e.g. for [Rtc::get_time_us] and the RWDT
+/// timeouts. Applications and other crates can measure the periods
+/// themselves:
+///
+/// ```no_run
+/// let period = RtcClock::calibrate(RtcCalSel::RtcCalRtcMux, 1024);
+/// let cycles_per_ms = RtcClock::cycles_to_1ms();
+/// ```
+///
+/// A calibration blocks until it is done, see
+/// [Rtc::start_slow_clock_calibration] for running it in the background.
 pub struct RtcClock;
-/// RTC Watchdog Timer driver
+
 impl RtcClock {
     const CAL_FRACT: u32 = 19;
 
@@ -153,7 +783,7 @@ impl RtcClock {
     /// Get main XTAL frequency
     /// This is the value stored in RTC register RTC_XTAL_FREQ_REG by the
     /// bootloader, as passed to rtc_clk_init function.
-    fn get_xtal_freq() -> XtalClock {
+    pub(crate) fn get_xtal_freq() -> XtalClock {
         let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
         let xtal_freq_reg = rtc_cntl.store4.read().bits();
 
@@ -181,6 +811,18 @@ impl RtcClock {
         }
     }
 
+    /// Store the main XTAL frequency in RTC_XTAL_FREQ_REG, where
+    /// [RtcClock::get_xtal_freq] reads it
+    #[cfg(feature = "esp32")]
+    pub(crate) fn set_xtal_freq(freq: XtalClock) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let mhz = freq.mhz() & u16::MAX as u32;
+
+        rtc_cntl
+            .store4
+            .write(|w| unsafe { w.bits(mhz | (mhz << 16)) });
+    }
+
     /// Get the RTC_SLOW_CLK source
     fn get_slow_freq() -> RtcSlowClock {
         let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
@@ -221,6 +863,31 @@ impl RtcClock {
         };
     }
 
+    /// Start the 32 kHz crystal or external clock with `start`, and select it
+    /// as RTC_SLOW_CLK once it oscillates, see [Rtc::use_32k_xtal]
+    fn select_32k(start: impl Fn()) -> Result<(), Error> {
+        for _ in 0..XTAL_32K_STARTUP_ATTEMPTS {
+            start();
+
+            // a calibration times out and returns 0 if there is no clock
+            let period = RtcClock::calibrate(RtcCalSel::RtcCal32kXtal, XTAL_32K_CAL_CYCLES);
+            if period >= XTAL_32K_MIN_PERIOD {
+                // convert the cycles counted so far with the period of the
+                // RC oscillator, and the ones from now on with the new one
+                RtcClock::update_slow_clock_period(period);
+                RtcClock::set_slow_freq(RtcSlowClock::RtcSlowClock32kXtal);
+
+                return Ok(());
+            }
+
+            trace!("32 kHz clock did not start up, period {}", period);
+        }
+
+        rtc::stop_32k_xtal();
+
+        Err(Error::OscillatorNotStarted)
+    }
+
     /// Select source for RTC_FAST_CLK
     fn set_fast_freq(fast_freq: RtcFastClock) {
         unsafe {
@@ -240,29 +907,65 @@ impl RtcClock {
     /// TIMG0. This feature counts the number of XTAL clock cycles within a
     /// given number of RTC_SLOW_CLK cycles.
     fn calibrate_internal(cal_clk: RtcCalSel, slowclk_cycles: u32) -> u32 {
-        // Except for ESP32, choosing RTC_CAL_RTC_MUX results in calibration of
-        // the 150k RTC clock (90k on ESP32-S2) regardless of the currently selected
-        // SLOW_CLK. On the ESP32, it uses the currently selected SLOW_CLK.
-        // The following code emulates ESP32 behavior for the other chips:
-        #[cfg(not(feature = "esp32"))]
-        let cal_clk = match cal_clk {
-            RtcCalSel::RtcCalRtcMux => match RtcClock::get_slow_freq() {
-                RtcSlowClock::RtcSlowClock32kXtal => RtcCalSel::RtcCal32kXtal,
-                RtcSlowClock::RtcSlowClock8mD256 => RtcCalSel::RtcCal8mD256,
-                _ => cal_clk,
-            },
-            RtcCalSel::RtcCalInternalOsc => RtcCalSel::RtcCalRtcMux,
-            _ => cal_clk,
-        };
         let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
-        let timg0 = unsafe { &*TIMG0::ptr() };
 
-        // Enable requested clock (150k clock is always on)
-        let dig_32k_xtal_enabled = rtc_cntl.clk_conf.read().dig_xtal32k_en().bit_is_set();
+        // the divided 8 MHz clock only runs if it is enabled
+        let clk_8m_enabled = rtc_cntl.clk_conf.read().enb_ck8m().bit_is_clear();
+        let clk_8md256_enabled = rtc_cntl.clk_conf.read().enb_ck8m_div().bit_is_clear();
+        let enable_8md256 = cal_clk == RtcCalSel::RtcCal8mD256 && !clk_8md256_enabled;
 
-        if matches!(cal_clk, RtcCalSel::RtcCal32kXtal) && !dig_32k_xtal_enabled {
-            rtc_cntl
-                .clk_conf
+        if enable_8md256 {
+            RtcClock::enable_8m(true, true);
+        }
+
+        let calibration = RtcClock::start_calibration(cal_clk, slowclk_cycles);
+
+        // Wait for calibration to finish up to another us_time_estimate
+        unsafe {
+            esp_rom_delay_us(calibration.us_time_estimate);
+        }
+
+        let cal_val = loop {
+            if let Some(cal_val) = RtcClock::poll_calibration(&calibration) {
+                break cal_val;
+            }
+        };
+
+        RtcClock::finish_calibration(&calibration);
+
+        if enable_8md256 {
+            RtcClock::enable_8m(clk_8m_enabled, clk_8md256_enabled);
+        }
+
+        cal_val
+    }
+
+    /// Start counting the XTAL clock cycles within `slowclk_cycles` cycles of
+    /// `cal_clk`, see [RtcClock::calibrate_internal]
+    fn start_calibration(cal_clk: RtcCalSel, slowclk_cycles: u32) -> CalibrationState {
+        // Except for ESP32, choosing RTC_CAL_RTC_MUX results in calibration of
+        // the 150k RTC clock (90k on ESP32-S2) regardless of the currently selected
+        // SLOW_CLK. On the ESP32, it uses the currently selected SLOW_CLK.
+        // The following code emulates ESP32 behavior for the other chips:
+        #[cfg(not(feature = "esp32"))]
+        let cal_clk = match cal_clk {
+            RtcCalSel::RtcCalRtcMux => match RtcClock::get_slow_freq() {
+                RtcSlowClock::RtcSlowClock32kXtal => RtcCalSel::RtcCal32kXtal,
+                RtcSlowClock::RtcSlowClock8mD256 => RtcCalSel::RtcCal8mD256,
+                _ => cal_clk,
+            },
+            RtcCalSel::RtcCalInternalOsc => RtcCalSel::RtcCalRtcMux,
+            _ => cal_clk,
+        };
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let timg0 = unsafe { &*TIMG0::ptr() };
+
+        // Enable requested clock (150k clock is always on)
+        let dig_32k_xtal_enabled = rtc_cntl.clk_conf.read().dig_xtal32k_en().bit_is_set();
+
+        if matches!(cal_clk, RtcCalSel::RtcCal32kXtal) && !dig_32k_xtal_enabled {
+            rtc_cntl
+                .clk_conf
                 .modify(|_, w| w.dig_xtal32k_en().set_bit());
         }
 
@@ -331,66 +1034,159 @@ impl RtcClock {
 
         let us_time_estimate = HertzU32::MHz(slowclk_cycles) / expected_freq.frequency();
 
+        // The ESP32 has no calibration timeout, time out on the RTC timer
+        // after twice the expected time instead
+        #[cfg(feature = "esp32")]
+        let deadline = RtcClock::get_time_raw()
+            + 2 * us_time_estimate as u64 * RtcClock::get_slow_freq().hz() as u64 / 1_000_000
+            + 1;
+
         // Start calibration
         timg0
             .rtccalicfg
             .modify(|_, w| w.rtc_cali_start().clear_bit().rtc_cali_start().set_bit());
 
-        // Wait for calibration to finish up to another us_time_estimate
-        unsafe {
-            esp_rom_delay_us(us_time_estimate);
+        CalibrationState {
+            cal_clk,
+            dig_32k_xtal_enabled,
+            us_time_estimate,
+            #[cfg(feature = "esp32")]
+            deadline,
         }
+    }
 
-        #[cfg(feature = "esp32")]
-        let mut timeout_us = us_time_estimate;
+    /// The number of XTAL clock cycles counted by a calibration, or 0 if it
+    /// timed out, once it has finished
+    fn poll_calibration(_calibration: &CalibrationState) -> Option<u32> {
+        let timg0 = unsafe { &*TIMG0::ptr() };
 
-        let cal_val = loop {
-            if timg0.rtccalicfg.read().rtc_cali_rdy().bit_is_set() {
-                break timg0.rtccalicfg1.read().rtc_cali_value().bits();
-            }
+        if timg0.rtccalicfg.read().rtc_cali_rdy().bit_is_set() {
+            return Some(timg0.rtccalicfg1.read().rtc_cali_value().bits());
+        }
 
-            #[cfg(not(feature = "esp32"))]
-            if timg0.rtccalicfg2.read().rtc_cali_timeout().bit_is_set() {
-                // Timed out waiting for calibration
-                break 0;
-            }
+        #[cfg(not(feature = "esp32"))]
+        if timg0.rtccalicfg2.read().rtc_cali_timeout().bit_is_set() {
+            // Timed out waiting for calibration
+            return Some(0);
+        }
 
-            #[cfg(feature = "esp32")]
-            if timeout_us > 0 {
-                timeout_us -= 1;
-                unsafe {
-                    esp_rom_delay_us(1);
-                }
-            } else {
-                // Timed out waiting for calibration
-                break 0;
-            }
-        };
+        #[cfg(feature = "esp32")]
+        if RtcClock::get_time_raw() > _calibration.deadline {
+            // Timed out waiting for calibration
+            return Some(0);
+        }
+
+        None
+    }
+
+    /// Stop a calibration and disable the clocks enabled for it
+    fn finish_calibration(calibration: &CalibrationState) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let timg0 = unsafe { &*TIMG0::ptr() };
 
         timg0
             .rtccalicfg
             .modify(|_, w| w.rtc_cali_start().clear_bit());
         rtc_cntl
             .clk_conf
-            .modify(|_, w| w.dig_xtal32k_en().bit(dig_32k_xtal_enabled));
+            .modify(|_, w| w.dig_xtal32k_en().bit(calibration.dig_32k_xtal_enabled));
 
-        if matches!(cal_clk, RtcCalSel::RtcCal8mD256) {
+        if matches!(calibration.cal_clk, RtcCalSel::RtcCal8mD256) {
             rtc_cntl
                 .clk_conf
                 .modify(|_, w| w.dig_clk8m_d256_en().clear_bit());
         }
+    }
 
-        cal_val
+    /// Measure the ratio between the XTAL frequency and the frequency of
+    /// `cal_clk`, over `slowclk_cycles` cycles of `cal_clk`
+    ///
+    /// The ratio is returned as Q13.19 fixed point number, or 0 if the
+    /// calibration timed out, see [RtcClock::calibrate].
+    pub fn get_calibration_ratio(cal_clk: RtcCalSel, slowclk_cycles: u32) -> u32 {
+        let xtal_cycles = RtcClock::calibrate_internal(cal_clk, slowclk_cycles);
+
+        RtcClock::calibration_ratio(xtal_cycles, slowclk_cycles)
     }
 
-    /// Measure ratio between XTAL frequency and RTC slow clock frequency
-    fn get_calibration_ratio(cal_clk: RtcCalSel, slowclk_cycles: u32) -> u32 {
-        let xtal_cycles = RtcClock::calibrate_internal(cal_clk, slowclk_cycles) as u64;
-        let ratio = (xtal_cycles << RtcClock::CAL_FRACT) / slowclk_cycles as u64;
+    fn calibration_ratio(xtal_cycles: u32, slowclk_cycles: u32) -> u32 {
+        let ratio = ((xtal_cycles as u64) << RtcClock::CAL_FRACT) / slowclk_cycles as u64;
 
         (ratio & (u32::MAX as u64)) as u32
     }
 
+    /// The frequency tuning value of the 8 MHz internal oscillator
+    pub fn get_8m_tuning() -> u8 {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        rtc_cntl.clk_conf.read().ck8m_dfreq().bits()
+    }
+
+    /// Set the frequency tuning value of the 8 MHz internal oscillator,
+    /// higher values giving higher frequencies
+    pub fn set_8m_tuning(dfreq: u8) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        rtc_cntl
+            .clk_conf
+            .modify(|_, w| unsafe { w.ck8m_dfreq().bits(dfreq) });
+    }
+
+    /// Trim the 8 MHz internal oscillator as close as possible to its nominal
+    /// frequency, 8.5 MHz on the ESP32 and ESP32-S2 and 17.5 MHz on the
+    /// ESP32-C3 and ESP32-S3, and return the frequency it runs at then
+    ///
+    /// The oscillator is measured against XTAL_CLK for each tuning value of
+    /// a binary search, which takes a few milliseconds. This improves the
+    /// accuracy of RTC_SLOW_CLK when it is the oscillator divided by 256. As
+    /// the period of RTC_SLOW_CLK changes, trim the oscillator before
+    /// [Rtc::new] measures it.
+    pub fn tune_8m() -> Result<HertzU32, Error> {
+        let target = RtcFastClock::RtcFastClock8m.hz();
+        let mut dfreq = 0u8;
+
+        for bit in (0..8).rev() {
+            let candidate = dfreq | (1 << bit);
+            RtcClock::set_8m_tuning(candidate);
+
+            if RtcClock::measure_8m()? <= target {
+                dfreq = candidate;
+            }
+        }
+
+        // the search ends on the highest value below the target, the next one
+        // may be closer to it
+        RtcClock::set_8m_tuning(dfreq);
+        let below = RtcClock::measure_8m()?;
+
+        if dfreq < u8::MAX {
+            RtcClock::set_8m_tuning(dfreq + 1);
+            let above = RtcClock::measure_8m()?;
+
+            if above.abs_diff(target) < below.abs_diff(target) {
+                return Ok(HertzU32::Hz(above));
+            }
+
+            RtcClock::set_8m_tuning(dfreq);
+        }
+
+        Ok(HertzU32::Hz(below))
+    }
+
+    /// The frequency of the 8 MHz internal oscillator in Hz, measured against
+    /// XTAL_CLK
+    fn measure_8m() -> Result<u32, Error> {
+        let xtal_cycles = RtcClock::calibrate_internal(RtcCalSel::RtcCal8mD256, TUNE_8M_CAL_CYCLES);
+
+        if xtal_cycles == 0 {
+            return Err(Error::CalibrationTimeout);
+        }
+
+        let xtal_hz = RtcClock::get_xtal_freq().hz() as u64;
+
+        Ok((xtal_hz * 256 * TUNE_8M_CAL_CYCLES as u64 / xtal_cycles as u64) as u32)
+    }
+
     /// Measure RTC slow clock's period, based on main XTAL frequency
     ///
     /// This function will time out and return 0 if the time for the given
@@ -398,25 +1194,128 @@ impl RtcClock {
     /// may happen if 32k XTAL is being calibrated, but the oscillator has
     /// not started up (due to incorrect loading capacitance, board design
     /// issue, or lack of 32 XTAL on board).
-    fn calibrate(cal_clk: RtcCalSel, slowclk_cycles: u32) -> u32 {
+    ///
+    /// The period is returned in microseconds, as Q13.19 fixed point number.
+    /// It is not stored, use [SlowClockRecalibration] to keep the stored
+    /// RTC_SLOW_CLK period up to date.
+    pub fn calibrate(cal_clk: RtcCalSel, slowclk_cycles: u32) -> u32 {
+        let xtal_cycles = RtcClock::calibrate_internal(cal_clk, slowclk_cycles);
+
+        RtcClock::calibration_period(xtal_cycles, slowclk_cycles)
+    }
+
+    /// The period of the calibrated clock in microseconds, as Q13.19 fixed
+    /// point number
+    fn calibration_period(xtal_cycles: u32, slowclk_cycles: u32) -> u32 {
         let xtal_freq = RtcClock::get_xtal_freq();
-        let xtal_cycles = RtcClock::calibrate_internal(cal_clk, slowclk_cycles) as u64;
+        let xtal_cycles = xtal_cycles as u64;
         let divider = xtal_freq.mhz() as u64 * slowclk_cycles as u64;
         let period_64 = ((xtal_cycles << RtcClock::CAL_FRACT) + divider / 2u64 - 1u64) / divider;
 
+        trace!(
+            "RTC calibration: {} XTAL cycles in {} slow clock cycles, period {}",
+            xtal_cycles,
+            slowclk_cycles,
+            period_64
+        );
+
         (period_64 & u32::MAX as u64) as u32
     }
 
+    /// Read the RTC timer, in RTC_SLOW_CLK cycles
+    fn get_time_raw() -> u64 {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        #[cfg(feature = "esp32")]
+        let (lo, hi) = {
+            rtc_cntl.time_update.write(|w| w.time_update().set_bit());
+            while rtc_cntl.time_update.read().time_valid().bit_is_clear() {
+                // this may take one RTC_SLOW_CLK cycle, don't flood the RTC bus
+                unsafe { esp_rom_delay_us(1) };
+            }
+            let hi = rtc_cntl.time1.read().time_hi().bits();
+            let lo = rtc_cntl.time0.read().time_lo().bits();
+            (lo, hi)
+        };
+
+        #[cfg(not(feature = "esp32"))]
+        let (lo, hi) = {
+            rtc_cntl.time_update.write(|w| w.time_update().set_bit());
+            let hi = rtc_cntl.time_high0.read().timer_value0_high().bits();
+            let lo = rtc_cntl.time_low0.read().timer_value0_low().bits();
+            (lo, hi)
+        };
+
+        // the timer is 48 bits wide
+        (((hi as u64) & 0xffff) << 32) | lo as u64
+    }
+
+    /// Read the RTC timer, in microseconds
+    ///
+    /// Uses the RTC_SLOW_CLK period stored in RTC_SLOW_CLK_CAL_REG by
+    /// `rtc::configure_clock`, for the cycles since the period was last
+    /// updated.
+    fn get_time_us() -> u64 {
+        let (base_ticks, base_us) = critical_section::with(|cs| TIME_BASE.borrow(cs).get());
+        let ticks = RtcClock::get_time_raw().saturating_sub(base_ticks);
+
+        base_us + RtcClock::cycles_to_us(ticks)
+    }
+
+    /// Store a newly calibrated RTC_SLOW_CLK period in RTC_SLOW_CLK_CAL_REG
+    ///
+    /// The time elapsed so far is kept, so that [RtcClock::get_time_us] stays
+    /// monotonic.
+    fn update_slow_clock_period(period_13q19: u32) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        critical_section::with(|cs| {
+            let ticks = RtcClock::get_time_raw();
+            let us = RtcClock::get_time_us();
+
+            TIME_BASE.borrow(cs).set((ticks, us));
+            wall_clock::rebase();
+            rtc_cntl.store1.write(|w| unsafe { w.bits(period_13q19) });
+        });
+
+        trace!("RTC slow clock period updated to {}", period_13q19);
+    }
+
+    /// Convert RTC_SLOW_CLK cycles to microseconds
+    ///
+    /// Uses the RTC_SLOW_CLK period stored in RTC_SLOW_CLK_CAL_REG by
+    /// `rtc::configure_clock`.
+    fn cycles_to_us(ticks: u64) -> u64 {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let period_13q19 = rtc_cntl.store1.read().bits() as u64;
+
+        // split the multiplication by the Q13.19 period so that it cannot
+        // overflow
+        let mask = (1 << RtcClock::CAL_FRACT) - 1;
+        (ticks >> RtcClock::CAL_FRACT) * period_13q19
+            + (((ticks & mask) * period_13q19) >> RtcClock::CAL_FRACT)
+    }
+
+    /// Convert microseconds to RTC_SLOW_CLK cycles
+    ///
+    /// Uses the RTC_SLOW_CLK period stored in RTC_SLOW_CLK_CAL_REG.
+    pub(crate) fn us_to_cycles(us: u64) -> u64 {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let period_13q19 = rtc_cntl.store1.read().bits() as u64;
+
+        // split the division by the Q13.19 period so that the shift cannot
+        // overflow
+        ((us / period_13q19) << RtcClock::CAL_FRACT)
+            + (((us % period_13q19) << RtcClock::CAL_FRACT) / period_13q19)
+    }
+
     /// Calculate the necessary RTC_SLOW_CLK cycles to complete 1 millisecond.
-    fn cycles_to_1ms() -> u16 {
-        let period_13q19 = RtcClock::calibrate(
-            match RtcClock::get_slow_freq() {
-                RtcSlowClock::RtcSlowClockRtc => RtcCalSel::RtcCalRtcMux,
-                RtcSlowClock::RtcSlowClock32kXtal => RtcCalSel::RtcCal32kXtal,
-                RtcSlowClock::RtcSlowClock8mD256 => RtcCalSel::RtcCal8mD256,
-            },
-            1024,
-        );
+    ///
+    /// Uses the RTC_SLOW_CLK period stored in RTC_SLOW_CLK_CAL_REG, which is
+    /// kept up to date by [SlowClockRecalibration].
+    pub fn cycles_to_1ms() -> u16 {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let period_13q19 = rtc_cntl.store1.read().bits();
 
         let q_to_float = |val| (val as f32) / ((1 << RtcClock::CAL_FRACT) as f32);
         let period = q_to_float(period_13q19);
@@ -424,10 +1323,7 @@ impl RtcClock {
         (1000f32 / period) as u16
     }
 
-    fn estimate_xtal_frequency() -> u32 {
-        // Number of 8M/256 clock cycles to use for XTAL frequency estimation.
-        const XTAL_FREQ_EST_CYCLES: u32 = 10;
-
+    pub(crate) fn estimate_xtal_frequency() -> u32 {
         let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
         let clk_8m_enabled = rtc_cntl.clk_conf.read().enb_ck8m().bit_is_clear();
         let clk_8md256_enabled = rtc_cntl.clk_conf.read().enb_ck8m_div().bit_is_clear();
@@ -437,42 +1333,159 @@ impl RtcClock {
         }
 
         let ratio = RtcClock::get_calibration_ratio(RtcCalSel::RtcCal8mD256, XTAL_FREQ_EST_CYCLES);
-        let freq_mhz =
-            ((ratio as u64 * RtcFastClock::RtcFastClock8m.hz() as u64 / 1_000_000u64 / 256u64)
-                >> RtcClock::CAL_FRACT) as u32;
+        let freq_mhz = RtcClock::xtal_frequency_from_ratio(ratio);
 
         RtcClock::enable_8m(clk_8m_enabled, clk_8md256_enabled);
 
+        trace!("Estimated XTAL frequency: {} MHz", freq_mhz);
+
         freq_mhz
     }
+
+    /// The XTAL frequency in MHz, from the ratio between the XTAL frequency
+    /// and the 8 MHz internal oscillator, divided by 256
+    fn xtal_frequency_from_ratio(ratio: u32) -> u32 {
+        ((ratio as u64 * RtcFastClock::RtcFastClock8m.hz() as u64 / 1_000_000u64 / 256u64)
+            >> RtcClock::CAL_FRACT) as u32
+    }
 }
 
 /// Behavior of the RWDT stage if it times out
-#[allow(unused)]
-#[derive(Debug, Clone, Copy)]
-enum RwdtStageAction {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RwdtStageAction {
+    /// Do nothing, the stage is disabled
     RwdtStageActionOff         = 0,
+    /// Raise the `RTC_CORE` interrupt
     RwdtStageActionInterrupt   = 1,
+    /// Reset the CPUs
     RwdtStageActionResetCpu    = 2,
+    /// Reset the digital core
     RwdtStageActionResetSystem = 3,
+    /// Reset the digital core and the RTC
     RwdtStageActionResetRtc    = 4,
 }
 
+/// A stage of the RWDT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RwdtStage {
+    Stage0,
+    Stage1,
+    Stage2,
+    Stage3,
+}
+
+impl RwdtStage {
+    const ALL: [RwdtStage; 4] = [
+        RwdtStage::Stage0,
+        RwdtStage::Stage1,
+        RwdtStage::Stage2,
+        RwdtStage::Stage3,
+    ];
+}
+
+/// Actions and timeouts of the RWDT stages
+///
+/// The stages run one after another: once a stage times out, its action is
+/// taken and the next stage starts. Feeding the watchdog restarts the first
+/// stage. E.g. to get an interrupt first, reset the CPUs if the watchdog is
+/// still not fed and reset the whole chip as a last resort:
+///
+/// ```no_run
+/// let config = RwdtConfig::default()
+///     .stage(RwdtStage::Stage0, RwdtStageAction::RwdtStageActionInterrupt, 1u64.secs())
+///     .stage(RwdtStage::Stage1, RwdtStageAction::RwdtStageActionResetCpu, 500u64.millis())
+///     .stage(RwdtStage::Stage2, RwdtStageAction::RwdtStageActionResetRtc, 500u64.millis());
+///
+/// rtc.rwdt.start_with_config(config);
+/// ```
+///
+/// The default configuration resets the chip when the first stage times out,
+/// its timeout is set by [WatchdogEnable::start].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RwdtConfig {
+    actions: [RwdtStageAction; 4],
+    timeouts: [MicrosDurationU64; 4],
+}
+
+impl Default for RwdtConfig {
+    fn default() -> Self {
+        Self {
+            actions: [
+                RwdtStageAction::RwdtStageActionResetRtc,
+                RwdtStageAction::RwdtStageActionOff,
+                RwdtStageAction::RwdtStageActionOff,
+                RwdtStageAction::RwdtStageActionOff,
+            ],
+            timeouts: [MicrosDurationU64::from_ticks(0); 4],
+        }
+    }
+}
+
+impl RwdtConfig {
+    /// Take `action` when `stage` times out after `timeout`
+    pub fn stage<T>(mut self, stage: RwdtStage, action: RwdtStageAction, timeout: T) -> Self
+    where
+        T: Into<MicrosDurationU64>,
+    {
+        self.actions[stage as usize] = action;
+        self.timeouts[stage as usize] = timeout.into();
+        self
+    }
+
+    /// The action taken when `stage` times out
+    pub fn action(&self, stage: RwdtStage) -> RwdtStageAction {
+        self.actions[stage as usize]
+    }
+
+    /// The timeout of `stage`
+    pub fn timeout(&self, stage: RwdtStage) -> MicrosDurationU64 {
+        self.timeouts[stage as usize]
+    }
+}
+
+/// Key which lifts the write protection of the RWDT registers
+const RWDT_WKEY: u32 = 0x50D8_3AA1;
+
+/// RTC timer value when the RWDT was last started or fed
+static RWDT_FED_AT: Mutex<Cell<u64>> = Mutex::new(Cell::new(0));
+
+/// Feed the RWDT
+///
+/// The write protection is restored afterwards, so this can interrupt
+/// [Rwdt] while it configures the watchdog.
+fn feed_rwdt() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    critical_section::with(|cs| {
+        let wkey = rtc_cntl.wdtwprotect.read().bits();
+
+        rtc_cntl.wdtwprotect.write(|w| unsafe { w.bits(RWDT_WKEY) });
+        rtc_cntl.wdtfeed.write(|w| unsafe { w.bits(1) });
+        rtc_cntl.wdtwprotect.write(|w| unsafe { w.bits(wkey) });
+
+        RWDT_FED_AT.borrow(cs).set(RtcClock::get_time_raw());
+    });
+}
+
+/// RTC timer value when the RWDT was last started or fed
+fn rwdt_fed_at() -> u64 {
+    critical_section::with(|cs| RWDT_FED_AT.borrow(cs).get())
+}
+
 /// RTC Watchdog Timer
 pub struct Rwdt {
-    stg0_action: RwdtStageAction,
-    stg1_action: RwdtStageAction,
-    stg2_action: RwdtStageAction,
-    stg3_action: RwdtStageAction,
+    config: RwdtConfig,
+    /// Whether the watchdog was started by [Rwdt]
+    started: bool,
 }
 
 impl Default for Rwdt {
     fn default() -> Self {
         Self {
-            stg0_action: RwdtStageAction::RwdtStageActionResetRtc,
-            stg1_action: RwdtStageAction::RwdtStageActionOff,
-            stg2_action: RwdtStageAction::RwdtStageActionOff,
-            stg3_action: RwdtStageAction::RwdtStageActionOff,
+            config: RwdtConfig::default(),
+            started: false,
         }
     }
 }
@@ -482,14 +1495,14 @@ impl Rwdt {
     pub fn listen(&mut self) {
         let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
 
-        self.stg0_action = RwdtStageAction::RwdtStageActionInterrupt;
+        self.config.actions[0] = RwdtStageAction::RwdtStageActionInterrupt;
 
         self.set_write_protection(false);
 
         // Configure STAGE0 to trigger an interrupt upon expiration
         rtc_cntl
             .wdtconfig0
-            .modify(|_, w| unsafe { w.wdt_stg0().bits(self.stg0_action as u8) });
+            .modify(|_, w| unsafe { w.wdt_stg0().bits(self.config.actions[0] as u8) });
 
         #[cfg(feature = "esp32")]
         rtc_cntl.int_ena.modify(|_, w| w.wdt_int_ena().set_bit());
@@ -510,14 +1523,14 @@ impl Rwdt {
     pub fn unlisten(&mut self) {
         let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
 
-        self.stg0_action = RwdtStageAction::RwdtStageActionResetRtc;
+        self.config.actions[0] = RwdtStageAction::RwdtStageActionResetRtc;
 
         self.set_write_protection(false);
 
         // Configure STAGE0 to reset the main system and the RTC upon expiration.
         rtc_cntl
             .wdtconfig0
-            .modify(|_, w| unsafe { w.wdt_stg0().bits(self.stg0_action as u8) });
+            .modify(|_, w| unsafe { w.wdt_stg0().bits(self.config.actions[0] as u8) });
 
         #[cfg(feature = "esp32")]
         rtc_cntl.int_ena.modify(|_, w| w.wdt_int_ena().clear_bit());
@@ -568,10 +1581,249 @@ impl Rwdt {
         }
     }
 
+    /// The configured timeout of `stage`
+    ///
+    /// The RTC_SLOW_CLK cycles are converted using the slow clock period
+    /// calibrated by [Rtc::new].
+    pub fn stage_timeout(&self, stage: RwdtStage) -> MicrosDurationU64 {
+        MicrosDurationU64::micros(RtcClock::cycles_to_us(Self::stage_cycles(stage)))
+    }
+
+    /// Time elapsed since the watchdog was last started or fed
+    ///
+    /// The RWDT counter cannot be read back, so this is measured with the RTC
+    /// timer, which counts the same RTC_SLOW_CLK cycles. If the watchdog has
+    /// not been started or fed since [Rtc::new], e.g. because the bootloader
+    /// started it, the time is counted from power-up.
+    pub fn elapsed_time(&self) -> MicrosDurationU64 {
+        let elapsed = RtcClock::get_time_raw().wrapping_sub(rwdt_fed_at());
+
+        MicrosDurationU64::micros(RtcClock::cycles_to_us(elapsed))
+    }
+
+    /// Time left until the currently armed stage fires, or `None` if the
+    /// watchdog is disabled
+    ///
+    /// See [Rwdt::elapsed_time] on how the time is measured. This can be used
+    /// to warn about a late feed, e.g. when the first stage is about to fire.
+    pub fn remaining_time(&self) -> Option<MicrosDurationU64> {
+        self.armed_stage()
+            .map(|(_, remaining)| MicrosDurationU64::micros(RtcClock::cycles_to_us(remaining)))
+    }
+
+    /// The stage which is currently armed, or `None` if the watchdog is
+    /// disabled
+    ///
+    /// The first stage is armed when the watchdog is started or fed, and each
+    /// stage arms the next one when it fires. See [Rwdt::elapsed_time] on how
+    /// the time is measured.
+    pub fn current_stage(&self) -> Option<RwdtStage> {
+        self.armed_stage().map(|(stage, _)| stage)
+    }
+
+    /// The armed stage and the RTC_SLOW_CLK cycles left until it fires
+    fn armed_stage(&self) -> Option<(RwdtStage, u64)> {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        if rtc_cntl.wdtconfig0.read().wdt_en().bit_is_clear() {
+            return None;
+        }
+
+        let total: u64 = RwdtStage::ALL
+            .iter()
+            .map(|&stage| Self::stage_cycles(stage))
+            .sum();
+        if total == 0 {
+            return Some((RwdtStage::Stage0, 0));
+        }
+
+        // after the last stage, the watchdog starts over with the first one
+        let mut elapsed = RtcClock::get_time_raw().wrapping_sub(rwdt_fed_at()) % total;
+
+        for stage in RwdtStage::ALL {
+            let hold = Self::stage_cycles(stage);
+            if elapsed < hold {
+                return Some((stage, hold - elapsed));
+            }
+            elapsed -= hold;
+        }
+
+        Some((RwdtStage::Stage3, 0))
+    }
+
+    /// The configured timeout of `stage` in RTC_SLOW_CLK cycles
+    fn stage_cycles(stage: RwdtStage) -> u64 {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        match stage {
+            RwdtStage::Stage0 => {
+                let hold = rtc_cntl.wdtconfig1.read().wdt_stg0_hold().bits() as u64;
+
+                // the hold time of the first stage is scaled by the eFuse
+                // multiplier, see `Rwdt::write_stage_holds`
+                #[cfg(not(feature = "esp32"))]
+                let hold = hold << (1 + Efuse::get_rwdt_multiplier());
+
+                hold
+            }
+            RwdtStage::Stage1 => rtc_cntl.wdtconfig2.read().wdt_stg1_hold().bits() as u64,
+            RwdtStage::Stage2 => rtc_cntl.wdtconfig3.read().wdt_stg2_hold().bits() as u64,
+            RwdtStage::Stage3 => rtc_cntl.wdtconfig4.read().wdt_stg3_hold().bits() as u64,
+        }
+    }
+
+    /// Start the watchdog with the actions and timeouts of all stages set by
+    /// `config`
+    ///
+    /// Timeouts longer than the hold fields of the stages are saturated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a non-zero timeout is shorter than the hold field of its
+    /// stage can express, e.g. the first stage is scaled down by an eFuse
+    /// multiplier on the chips other than the ESP32.
+    pub fn start_with_config(&mut self, config: RwdtConfig) {
+        self.config = config;
+        self.apply_config();
+    }
+
+    /// The configuration the watchdog was last started with
+    pub fn config(&self) -> RwdtConfig {
+        self.config
+    }
+
+    /// Enable/disable the flash boot protection of the watchdog
+    ///
+    /// The ROM bootloader enables it to reset the chip if booting from flash
+    /// hangs. It keeps the watchdog running independently of the stages set
+    /// with [Rwdt::start_with_config], so it has to be disabled by
+    /// applications which do not feed the watchdog.
+    pub fn set_flashboot_protection(&mut self, enable: bool) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        self.set_write_protection(false);
+
+        rtc_cntl
+            .wdtconfig0
+            .modify(|_, w| w.wdt_flashboot_mod_en().bit(enable));
+
+        self.set_write_protection(true);
+    }
+
+    /// Whether the flash boot protection of the watchdog is enabled
+    pub fn is_flashboot_protection_enabled(&self) -> bool {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        rtc_cntl
+            .wdtconfig0
+            .read()
+            .wdt_flashboot_mod_en()
+            .bit_is_set()
+    }
+
+    /// A handle which feeds the watchdog from anywhere, see [RwdtFeeder]
+    pub fn feeder(&self) -> RwdtFeeder {
+        RwdtFeeder { _private: () }
+    }
+
+    /// Write the configuration to the watchdog and start it
+    fn apply_config(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let actions = self.config.actions;
+
+        self.set_write_protection(false);
+
+        self.write_stage_holds();
+
+        rtc_cntl.wdtconfig0.modify(|_, w| unsafe {
+            w.wdt_stg0()
+                .bits(actions[0] as u8)
+                .wdt_cpu_reset_length()
+                .bits(7)
+                .wdt_sys_reset_length()
+                .bits(7)
+                .wdt_stg1()
+                .bits(actions[1] as u8)
+                .wdt_stg2()
+                .bits(actions[2] as u8)
+                .wdt_stg3()
+                .bits(actions[3] as u8)
+                .wdt_en()
+                .set_bit()
+        });
+
+        self.set_write_protection(true);
+
+        critical_section::with(|cs| RWDT_FED_AT.borrow(cs).set(RtcClock::get_time_raw()));
+        self.started = true;
+    }
+
+    /// Convert the timeouts of the stages to RTC_SLOW_CLK cycles again, with
+    /// the period stored by [SlowClockRecalibration]
+    ///
+    /// This does not feed the watchdog.
+    fn update_timeout(&mut self) {
+        if self.started {
+            self.set_write_protection(false);
+            self.write_stage_holds();
+            self.set_write_protection(true);
+        }
+    }
+
+    /// Set the hold times of the stages to their configured timeouts
+    ///
+    /// Timeouts longer than the 32-bit hold fields are saturated. The write
+    /// protection has to be disabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a non-zero timeout is too short to be held for at least one
+    /// unit of its hold field.
+    fn write_stage_holds(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        for stage in RwdtStage::ALL {
+            let timeout = self.config.timeout(stage).to_micros();
+            let cycles = RtcClock::us_to_cycles(timeout).min(u32::MAX as u64) as u32;
+
+            // the hold time of the first stage is scaled by the eFuse
+            // multiplier
+            #[cfg(not(feature = "esp32"))]
+            let hold = match stage {
+                RwdtStage::Stage0 => cycles >> (1 + Efuse::get_rwdt_multiplier()),
+                _ => cycles,
+            };
+            #[cfg(feature = "esp32")]
+            let hold = cycles;
+
+            assert!(
+                timeout == 0 || hold != 0,
+                "RWDT timeout is too short for the hold time of its stage"
+            );
+
+            unsafe {
+                match stage {
+                    RwdtStage::Stage0 => rtc_cntl
+                        .wdtconfig1
+                        .modify(|_, w| w.wdt_stg0_hold().bits(hold)),
+                    RwdtStage::Stage1 => rtc_cntl
+                        .wdtconfig2
+                        .modify(|_, w| w.wdt_stg1_hold().bits(hold)),
+                    RwdtStage::Stage2 => rtc_cntl
+                        .wdtconfig3
+                        .modify(|_, w| w.wdt_stg2_hold().bits(hold)),
+                    RwdtStage::Stage3 => rtc_cntl
+                        .wdtconfig4
+                        .modify(|_, w| w.wdt_stg3_hold().bits(hold)),
+                }
+            }
+        }
+    }
+
     /// Enable/disable write protection for WDT registers
     fn set_write_protection(&mut self, enable: bool) {
         let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
-        let wkey = if enable { 0u32 } else { 0x50D8_3AA1 };
+        let wkey = if enable { 0u32 } else { RWDT_WKEY };
 
         rtc_cntl.wdtwprotect.write(|w| unsafe { w.bits(wkey) });
     }
@@ -588,6 +1840,8 @@ impl WatchdogDisable for Rwdt {
             .modify(|_, w| w.wdt_en().clear_bit().wdt_flashboot_mod_en().clear_bit());
 
         self.set_write_protection(true);
+
+        self.started = false;
     }
 }
 
@@ -598,54 +1852,51 @@ impl WatchdogEnable for Rwdt {
     where
         T: Into<Self::Time>,
     {
-        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
-        let timeout_raw = (period.into().to_millis() * (RtcClock::cycles_to_1ms() as u64)) as u32;
-
-        self.set_write_protection(false);
-
-        unsafe {
-            #[cfg(feature = "esp32")]
-            rtc_cntl
-                .wdtconfig1
-                .modify(|_, w| w.wdt_stg0_hold().bits(timeout_raw));
-
-            #[cfg(not(feature = "esp32"))]
-            rtc_cntl.wdtconfig1.modify(|_, w| {
-                w.wdt_stg0_hold()
-                    .bits(timeout_raw >> (1 + Efuse::get_rwdt_multiplier()))
-            });
-
-            rtc_cntl.wdtconfig0.modify(|_, w| {
-                w.wdt_stg0()
-                    .bits(self.stg0_action as u8)
-                    .wdt_cpu_reset_length()
-                    .bits(7)
-                    .wdt_sys_reset_length()
-                    .bits(7)
-                    .wdt_stg1()
-                    .bits(self.stg1_action as u8)
-                    .wdt_stg2()
-                    .bits(self.stg2_action as u8)
-                    .wdt_stg3()
-                    .bits(self.stg3_action as u8)
-                    .wdt_en()
-                    .set_bit()
-            });
-        }
-
-        self.set_write_protection(true);
+        self.config.timeouts[0] = period.into();
+        self.apply_config();
     }
 }
 
 impl Watchdog for Rwdt {
     fn feed(&mut self) {
-        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        feed_rwdt();
+    }
+}
 
-        self.set_write_protection(false);
+/// A handle which feeds the RWDT, e.g. from an interrupt handler or another
+/// task
+///
+/// Unlike [Rwdt], it can be copied and stored in a static, so the watchdog
+/// can be fed without sharing the whole [Rtc]:
+///
+/// ```no_run
+/// static FEEDER: Mutex<Cell<Option<RwdtFeeder>>> = Mutex::new(Cell::new(None));
+///
+/// rtc.rwdt.start(2u64.secs());
+/// critical_section::with(|cs| FEEDER.borrow(cs).set(Some(rtc.rwdt.feeder())));
+///
+/// #[interrupt]
+/// fn TG0_T0_LEVEL() {
+///     if let Some(feeder) = critical_section::with(|cs| FEEDER.borrow(cs).get()) {
+///         feeder.feed();
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RwdtFeeder {
+    _private: (),
+}
 
-        rtc_cntl.wdtfeed.write(|w| unsafe { w.bits(1) });
+impl RwdtFeeder {
+    /// Feed the watchdog
+    pub fn feed(&self) {
+        feed_rwdt();
+    }
+}
 
-        self.set_write_protection(true);
+impl Watchdog for RwdtFeeder {
+    fn feed(&mut self) {
+        feed_rwdt();
     }
 }
 
@@ -0,0 +1,426 @@
+//! RTC I2C Driver
+//!
+//! The RTC I2C controller is part of the RTC domain, so it can be used by the
+//! ULP coprocessor while the main CPUs are in deep sleep. This driver lets the
+//! main CPUs use it as a master, e.g. to configure a sensor before going to
+//! sleep:
+//!
+//! ```no_run
+//! let mut i2c = RtcI2c::new(peripherals.RTC_I2C, io.pins.gpio1, io.pins.gpio0, 100u32.kHz())
+//!     .unwrap();
+//!
+//! i2c.write(0x77, 0xf4, &[0x2e]).unwrap();
+//! let mut data = [0u8; 2];
+//! i2c.read(0x77, 0xf6, &mut data).unwrap();
+//! ```
+//!
+//! The controller always sends a register address after the device address,
+//! so it only works with devices which are accessed through registers. It is
+//! clocked by the internal fast RC oscillator, and the SDA and SCL pins are
+//! open drain without internal pull-ups, so external pull-ups are needed.
+
+use core::fmt;
+
+use fugit::HertzU32;
+
+use crate::{
+    gpio::{RtcI2cScl, RtcI2cSda},
+    pac::{RTCIO, RTC_I2C, SENS},
+    peripherals::{Peripheral, PeripheralRef},
+};
+
+/// Frequency of the internal fast RC oscillator which clocks the controller
+#[cfg(feature = "esp32s2")]
+const RC_FAST_CLK: u32 = 8_500_000;
+#[cfg(feature = "esp32s3")]
+const RC_FAST_CLK: u32 = 17_500_000;
+
+/// RTC function of the pads which connects them to the RTC I2C controller
+const RTC_FUNC_I2C: u8 = 3;
+
+/// Largest value of the SCL low and high period fields
+const MAX_SCL_PERIOD: u32 = (1 << 19) - 1;
+
+/// Timeout of a transmission, in RC_FAST_CLK cycles
+const TIMEOUT_CYCLES: u32 = (1 << 19) - 1;
+
+/// RTC I2C-specific errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The bus frequency can't be derived from RC_FAST_CLK
+    InvalidClkConfig,
+    /// The data is empty or longer than a single transmission allows
+    InvalidLength,
+    /// The addressed device didn't acknowledge
+    AckCheckFailed,
+    /// The transmission didn't complete in time
+    TimeOut,
+    /// Another master took over the bus
+    ArbitrationLost,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::InvalidClkConfig => "unsupported bus frequency",
+            Error::InvalidLength => "invalid transmission length",
+            Error::AckCheckFailed => "no acknowledgement from the device",
+            Error::TimeOut => "transmission timed out",
+            Error::ArbitrationLost => "bus arbitration lost",
+        })
+    }
+}
+
+/// A command of the RTC I2C controller
+enum Command {
+    Start,
+    Stop,
+    Write {
+        /// Check that the device acknowledges each byte
+        ack_check_en: bool,
+        /// Number of bytes to write, including the address bytes
+        length: u8,
+    },
+    Read {
+        /// Acknowledge the bytes read
+        ack: bool,
+        /// Number of bytes to read
+        length: u8,
+    },
+}
+
+impl From<Command> for u32 {
+    fn from(c: Command) -> u32 {
+        let (opcode, length): (u32, u8) = match c {
+            Command::Start => (0, 0),
+            Command::Write { length, .. } => (1, length),
+            Command::Read { length, .. } => (2, length),
+            Command::Stop => (3, 0),
+        };
+
+        let mut cmd = length as u32;
+
+        if let Command::Write {
+            ack_check_en: true, ..
+        } = c
+        {
+            cmd |= 1 << 8;
+        }
+
+        // NACK the last byte read, so the device releases the bus
+        if let Command::Read { ack: false, .. } = c {
+            cmd |= 1 << 10;
+        }
+
+        cmd | (opcode << 11)
+    }
+}
+
+/// RTC I2C controller, used as a master
+///
+/// Every transmission addresses a register of the device: [RtcI2c::write]
+/// sends the device address, the register address and the data, and
+/// [RtcI2c::read] sends the device and register addresses and reads the data
+/// after a repeated start condition.
+pub struct RtcI2c<'d> {
+    _i2c: PeripheralRef<'d, RTC_I2C>,
+}
+
+impl<'d> RtcI2c<'d> {
+    /// Create a new RTC I2C master on the given pins
+    pub fn new<SDA: RtcI2cSda, SCL: RtcI2cScl>(
+        i2c: impl Peripheral<P = RTC_I2C> + 'd,
+        sda: impl Peripheral<P = SDA> + 'd,
+        scl: impl Peripheral<P = SCL> + 'd,
+        frequency: HertzU32,
+    ) -> Result<Self, Error> {
+        crate::into_ref!(i2c, sda, scl);
+
+        let half_period = RC_FAST_CLK / (2 * frequency.raw()).max(1);
+        if half_period == 0 || half_period > MAX_SCL_PERIOD {
+            return Err(Error::InvalidClkConfig);
+        }
+
+        let rtcio = unsafe { &*RTCIO::ptr() };
+        let sens = unsafe { &*SENS::ptr() };
+
+        // The SCL pads are GPIO0 and GPIO2, the SDA pads GPIO1 and GPIO3, each
+        // selected by the upper bits of the pin number
+        let sda_num = sda.number();
+        let scl_num = scl.number();
+
+        for num in [sda_num, scl_num] {
+            rtcio.pin[num as usize].modify(|_, w| w.pin_pad_driver().set_bit());
+            rtcio
+                .enable_w1ts
+                .write(|w| unsafe { w.enable_w1ts().bits(1 << num) });
+        }
+        sda.rtc_set_config(true, true, RTC_FUNC_I2C);
+        scl.rtc_set_config(true, true, RTC_FUNC_I2C);
+
+        rtcio.sar_i2c_io.write(|w| unsafe {
+            w.sar_i2c_sda_sel()
+                .bits(sda_num >> 1)
+                .sar_i2c_scl_sel()
+                .bits(scl_num >> 1)
+        });
+
+        // Reset the controller, then enable its clock
+        sens.sar_peri_reset_conf
+            .modify(|_, w| w.sar_rtc_i2c_reset().set_bit());
+        i2c.ctrl.modify(|_, w| w.i2c_reset().set_bit());
+        i2c.ctrl.modify(|_, w| w.i2c_reset().clear_bit());
+        sens.sar_peri_reset_conf
+            .modify(|_, w| w.sar_rtc_i2c_reset().clear_bit());
+
+        sens.sar_peri_clk_gate_conf
+            .modify(|_, w| w.rtc_i2c_clk_en().set_bit());
+
+        // Open drain master
+        i2c.ctrl.modify(|_, w| {
+            w.sda_force_out()
+                .clear_bit()
+                .scl_force_out()
+                .clear_bit()
+                .ms_mode()
+                .set_bit()
+                .i2c_ctrl_clk_gate_en()
+                .set_bit()
+        });
+
+        i2c.scl_low
+            .write(|w| unsafe { w.scl_low_period().bits(half_period) });
+        i2c.scl_high
+            .write(|w| unsafe { w.scl_high_period().bits(half_period) });
+        i2c.sda_duty
+            .write(|w| unsafe { w.sda_duty_num().bits(half_period / 2) });
+        i2c.scl_start_period
+            .write(|w| unsafe { w.scl_start_period().bits(half_period) });
+        i2c.scl_stop_period
+            .write(|w| unsafe { w.scl_stop_period().bits(half_period) });
+        i2c.to
+            .write(|w| unsafe { w.time_out_reg().bits(TIMEOUT_CYCLES) });
+
+        Ok(Self { _i2c: i2c })
+    }
+
+    /// Write `data` to the register `register` of the device at `address`
+    pub fn write(&mut self, address: u8, register: u8, data: &[u8]) -> Result<(), Error> {
+        // device address + register address + data
+        if data.is_empty() || data.len() > u8::MAX as usize - 2 {
+            return Err(Error::InvalidLength);
+        }
+
+        self.write_cmd(
+            0,
+            Command::Write {
+                ack_check_en: true,
+                length: 2 + data.len() as u8,
+            },
+        );
+        self.write_cmd(1, Command::Stop);
+
+        self.clear_interrupts();
+        self.start(address, register, Some(data[0]));
+
+        let mut result = Ok(());
+        for &byte in &data[1..] {
+            result = self.wait_for_data(true);
+            if result.is_err() {
+                break;
+            }
+
+            // hand over the next byte
+            let sens = unsafe { &*SENS::ptr() };
+            sens.sar_i2c_ctrl.modify(|r, w| {
+                let ctrl = (r.sar_i2c_ctrl().bits() & !(0xff << 19)) | ((byte as u32) << 19);
+                unsafe { w.sar_i2c_ctrl().bits(ctrl) }
+            });
+
+            let i2c = unsafe { &*RTC_I2C::ptr() };
+            i2c.int_clr.write(|w| w.tx_data_int_clr().set_bit());
+        }
+
+        if result.is_ok() {
+            result = self.wait_for_completion();
+        }
+
+        self.stop();
+        result
+    }
+
+    /// Read `buffer.len()` bytes from the register `register` of the device at
+    /// `address`
+    pub fn read(&mut self, address: u8, register: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        if buffer.is_empty() || buffer.len() > u8::MAX as usize {
+            return Err(Error::InvalidLength);
+        }
+
+        // device address + register address, then the device address again
+        // after a repeated start
+        self.write_cmd(
+            2,
+            Command::Write {
+                ack_check_en: true,
+                length: 2,
+            },
+        );
+        self.write_cmd(3, Command::Start);
+        self.write_cmd(
+            4,
+            Command::Write {
+                ack_check_en: true,
+                length: 1,
+            },
+        );
+
+        let mut idx = 5;
+        if buffer.len() > 1 {
+            self.write_cmd(
+                idx,
+                Command::Read {
+                    ack: true,
+                    length: (buffer.len() - 1) as u8,
+                },
+            );
+            idx += 1;
+        }
+        self.write_cmd(
+            idx,
+            Command::Read {
+                ack: false,
+                length: 1,
+            },
+        );
+        self.write_cmd(idx + 1, Command::Stop);
+
+        self.clear_interrupts();
+        self.start(address, register, None);
+
+        let i2c = unsafe { &*RTC_I2C::ptr() };
+        let mut result = Ok(());
+        for byte in buffer.iter_mut() {
+            result = self.wait_for_data(false);
+            if result.is_err() {
+                break;
+            }
+
+            *byte = i2c.data.read().i2c_rdata().bits();
+            i2c.int_clr.write(|w| w.rx_data_int_clr().set_bit());
+        }
+
+        if result.is_ok() {
+            result = self.wait_for_completion();
+        }
+
+        self.stop();
+        result
+    }
+
+    fn write_cmd(&mut self, idx: usize, command: Command) {
+        let i2c = unsafe { &*RTC_I2C::ptr() };
+        let cmd: u32 = command.into();
+        i2c.cmd[idx].write(|w| unsafe { w.command().bits(cmd as u16) });
+    }
+
+    fn clear_interrupts(&mut self) {
+        let i2c = unsafe { &*RTC_I2C::ptr() };
+        i2c.int_clr.write(|w| {
+            w.trans_complete_int_clr()
+                .set_bit()
+                .tx_data_int_clr()
+                .set_bit()
+                .rx_data_int_clr()
+                .set_bit()
+                .ack_err_int_clr()
+                .set_bit()
+                .time_out_int_clr()
+                .set_bit()
+                .arbitration_lost_int_clr()
+                .set_bit()
+        });
+    }
+
+    /// Start the transmission, which is controlled by SENS
+    ///
+    /// `SAR_I2C_CTRL` holds the device address, the register address, the
+    /// byte to write and whether to write or read.
+    fn start(&mut self, address: u8, register: u8, data: Option<u8>) {
+        let sens = unsafe { &*SENS::ptr() };
+
+        let mut ctrl = address as u32 | (register as u32) << 11;
+        if let Some(byte) = data {
+            ctrl |= (byte as u32) << 19 | 1 << 27;
+        }
+
+        sens.sar_i2c_ctrl
+            .write(|w| unsafe { w.sar_i2c_ctrl().bits(ctrl) });
+        sens.sar_i2c_ctrl
+            .modify(|_, w| w.sar_i2c_start_force().set_bit().sar_i2c_start().set_bit());
+    }
+
+    fn stop(&mut self) {
+        let sens = unsafe { &*SENS::ptr() };
+        sens.sar_i2c_ctrl.modify(|_, w| {
+            w.sar_i2c_start_force()
+                .clear_bit()
+                .sar_i2c_start()
+                .clear_bit()
+        });
+    }
+
+    /// Wait until the controller asks for the next byte to write (`tx`) or has
+    /// received a byte
+    fn wait_for_data(&self, tx: bool) -> Result<(), Error> {
+        let i2c = unsafe { &*RTC_I2C::ptr() };
+
+        loop {
+            let int_raw = i2c.int_raw.read();
+            if tx && int_raw.tx_data_int_raw().bit_is_set() {
+                return Ok(());
+            } else if !tx && int_raw.rx_data_int_raw().bit_is_set() {
+                return Ok(());
+            }
+
+            check_errors(&int_raw)?;
+        }
+    }
+
+    fn wait_for_completion(&self) -> Result<(), Error> {
+        let i2c = unsafe { &*RTC_I2C::ptr() };
+
+        loop {
+            let int_raw = i2c.int_raw.read();
+            if int_raw.trans_complete_int_raw().bit_is_set() {
+                return Ok(());
+            }
+
+            check_errors(&int_raw)?;
+        }
+    }
+}
+
+impl<'d> Drop for RtcI2c<'d> {
+    fn drop(&mut self) {
+        let sens = unsafe { &*SENS::ptr() };
+
+        sens.sar_peri_reset_conf
+            .modify(|_, w| w.sar_rtc_i2c_reset().set_bit());
+        sens.sar_peri_clk_gate_conf
+            .modify(|_, w| w.rtc_i2c_clk_en().clear_bit());
+    }
+}
+
+fn check_errors(int_raw: &crate::pac::rtc_i2c::int_raw::R) -> Result<(), Error> {
+    if int_raw.time_out_int_raw().bit_is_set() {
+        Err(Error::TimeOut)
+    } else if int_raw.ack_err_int_raw().bit_is_set() {
+        Err(Error::AckCheckFailed)
+    } else if int_raw.arbitration_lost_int_raw().bit_is_set() {
+        Err(Error::ArbitrationLost)
+    } else {
+        Ok(())
+    }
+}
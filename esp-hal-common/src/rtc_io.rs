@@ -0,0 +1,197 @@
+//! # RTC GPIO
+//!
+//! The pins connected to the RTC IO_MUX can be controlled from the RTC
+//! domain, bypassing the IO_MUX and the GPIO matrix. They keep working while
+//! the digital domain is powered down, e.g. in deep sleep, and can be read
+//! and driven by the ULP.
+//!
+//! ```no_run
+//! let mut led = RtcIo::new(io.pins.gpio2);
+//! led.enable_output(true);
+//! led.set_output_high(true);
+//!
+//! // keep the LED on while the chip is in deep sleep
+//! led.set_hold(true);
+//! ```
+
+use core::convert::Infallible;
+
+use crate::{
+    gpio::RtcPin,
+    into_ref,
+    pac::RTCIO,
+    peripherals::{Peripheral, PeripheralRef},
+    rtc_cntl::rtc,
+};
+
+/// Offset of the RTC IO bits in the `RTC_GPIO_*` registers
+#[cfg(feature = "esp32")]
+const RTC_GPIO_SHIFT: u8 = 14;
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+const RTC_GPIO_SHIFT: u8 = 10;
+
+/// Pull resistor of a pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Pull {
+    None,
+    Up,
+    Down,
+}
+
+/// A pin controlled from the RTC domain
+///
+/// Input and output are disabled when the pin is handed to the RTC IO_MUX.
+/// The pin is handed back to the IO_MUX when the driver is dropped.
+pub struct RtcIo<'d, P>
+where
+    P: RtcPin,
+{
+    pin: PeripheralRef<'d, P>,
+}
+
+impl<'d, P> RtcIo<'d, P>
+where
+    P: RtcPin,
+{
+    /// Hand `pin` to the RTC IO_MUX
+    pub fn new(pin: impl Peripheral<P = P> + 'd) -> Self {
+        into_ref!(pin);
+
+        crate::gpio::enable_iomux_clk_gate();
+
+        // RTC function 1 is the RTC GPIO
+        pin.rtc_set_config(false, true, 0);
+
+        let mut rtc_io = Self { pin };
+        rtc_io.enable_output(false);
+
+        rtc_io
+    }
+
+    /// Enable or disable the input of the pin
+    pub fn enable_input(&mut self, on: bool) {
+        self.pin.rtc_set_config(on, true, 0);
+    }
+
+    /// Enable or disable the output of the pin
+    pub fn enable_output(&mut self, on: bool) {
+        let rtcio = unsafe { &*RTCIO::ptr() };
+        let mask = self.mask();
+
+        if on {
+            rtcio.enable_w1ts.write(|w| unsafe { w.bits(mask) });
+        } else {
+            rtcio.enable_w1tc.write(|w| unsafe { w.bits(mask) });
+        }
+    }
+
+    /// Drive the pin high or low, if its output is enabled
+    pub fn set_output_high(&mut self, high: bool) {
+        let rtcio = unsafe { &*RTCIO::ptr() };
+        let mask = self.mask();
+
+        if high {
+            rtcio.out_w1ts.write(|w| unsafe { w.bits(mask) });
+        } else {
+            rtcio.out_w1tc.write(|w| unsafe { w.bits(mask) });
+        }
+    }
+
+    /// Whether the pin is driven high
+    pub fn is_output_high(&self) -> bool {
+        let rtcio = unsafe { &*RTCIO::ptr() };
+
+        rtcio.out.read().bits() & self.mask() != 0
+    }
+
+    /// Whether the level of the pin is high, if its input is enabled
+    pub fn is_input_high(&self) -> bool {
+        let rtcio = unsafe { &*RTCIO::ptr() };
+
+        rtcio.in_.read().bits() & self.mask() != 0
+    }
+
+    /// Only drive the pin low and let it float otherwise
+    pub fn set_open_drain(&mut self, on: bool) {
+        let rtcio = unsafe { &*RTCIO::ptr() };
+
+        rtcio.pin[self.pin.rtc_number() as usize].modify(|_, w| w.pin_pad_driver().bit(on));
+    }
+
+    /// Configure the pull resistor of the pin
+    ///
+    /// Some pins, e.g. GPIO34 to GPIO39 of the ESP32, have no pull
+    /// resistors, for them this has no effect.
+    pub fn set_pull(&mut self, pull: Pull) {
+        self.pin.rtc_set_pull(pull == Pull::Up, pull == Pull::Down);
+    }
+
+    /// Latch the current configuration and level of the pin, or release it
+    ///
+    /// A held pin keeps its state while the chip is in deep sleep and is
+    /// reset, until it is released.
+    pub fn set_hold(&mut self, hold: bool) {
+        rtc::set_pad_hold(self.pin.rtc_number(), hold);
+    }
+
+    fn mask(&self) -> u32 {
+        1 << (self.pin.rtc_number() + RTC_GPIO_SHIFT)
+    }
+}
+
+impl<'d, P> Drop for RtcIo<'d, P>
+where
+    P: RtcPin,
+{
+    fn drop(&mut self) {
+        self.enable_output(false);
+        // hand the pin back to the IO_MUX
+        self.pin.rtc_set_config(true, false, 0);
+    }
+}
+
+impl<'d, P> embedded_hal::digital::v2::InputPin for RtcIo<'d, P>
+where
+    P: RtcPin,
+{
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.is_input_high())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.is_input_high())
+    }
+}
+
+impl<'d, P> embedded_hal::digital::v2::OutputPin for RtcIo<'d, P>
+where
+    P: RtcPin,
+{
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.set_output_high(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.set_output_high(true);
+        Ok(())
+    }
+}
+
+impl<'d, P> embedded_hal::digital::v2::StatefulOutputPin for RtcIo<'d, P>
+where
+    P: RtcPin,
+{
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.is_output_high())
+    }
+
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.is_output_high())
+    }
+}
@@ -1,4 +1,23 @@
 //! UART driver
+//!
+//! The pins and the configuration are set up with the `with_*` methods:
+//!
+//! ```no_run
+//! let mut serial1 = Serial::new(peripherals.UART1)
+//!     .with_tx(io.pins.gpio1)
+//!     .with_rx(io.pins.gpio2)
+//!     .with_config(Config::default().baudrate(9600), &clocks);
+//! ```
+//!
+//! The baud rate is divided from APB_CLK by default. To keep it when APB_CLK
+//! changes, e.g. with [Clocks::switch_cpu_to_xtal], divide it from REF_TICK
+//! (ESP32, ESP32-S2) or XTAL_CLK (ESP32-C3, ESP32-S3) instead:
+//!
+//! ```no_run
+//! let config = Config::default().clock_source(ClockSource::RefTick);
+//! ```
+
+use core::sync::atomic::{AtomicU8, Ordering};
 
 use self::config::Config;
 #[cfg(any(feature = "esp32", feature = "esp32s3"))]
@@ -10,6 +29,7 @@ use crate::{
         UART0,
         UART1,
     },
+    peripherals::{Peripheral, PeripheralRef},
     types::{InputSignal, OutputSignal},
     InputPin,
     OutputPin,
@@ -17,14 +37,32 @@ use crate::{
 
 const UART_FIFO_SIZE: u16 = 128;
 
-/// Custom serial error type
-#[derive(Debug)]
+/// Frequency of REF_TICK
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+const REF_TICK_HZ: u32 = 1_000_000;
+
+/// The UARTs which have been set up by a driver, one bit per UART
+static UARTS_IN_USE: AtomicU8 = AtomicU8::new(0);
+
+/// UART-specific errors
+///
+/// No error conditions are currently reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
 pub enum Error {}
 
+impl core::fmt::Display for Error {
+    fn fmt(&self, _f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {}
+    }
+}
+
 /// UART configuration
 pub mod config {
     /// Number of data bits
     #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub enum DataBits {
         DataBits5 = 0,
         DataBits6 = 1,
@@ -34,6 +72,7 @@ pub mod config {
 
     /// Parity check
     #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub enum Parity {
         ParityNone,
         ParityEven,
@@ -42,6 +81,7 @@ pub mod config {
 
     /// Number of stop bits
     #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub enum StopBits {
         /// 1 stop bit
         STOP1   = 1,
@@ -51,13 +91,36 @@ pub mod config {
         STOP2   = 3,
     }
 
+    /// Clock the baud rate is divided from
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum ClockSource {
+        /// APB_CLK, which changes with the CPU clock
+        Apb,
+        /// REF_TICK, 1 MHz regardless of APB_CLK
+        ///
+        /// The baud rate stays correct when APB_CLK changes, e.g. in light
+        /// sleep, but it is limited to low baud rates.
+        #[cfg(any(feature = "esp32", feature = "esp32s2"))]
+        RefTick,
+        /// XTAL_CLK, which does not change with the CPU clock
+        #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+        Xtal,
+    }
+
     /// UART configuration
+    ///
+    /// Fields may be added in the future, so build it from
+    /// [Config::default] with the setters.
     #[derive(Debug, Copy, Clone)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[non_exhaustive]
     pub struct Config {
         pub baudrate: u32,
         pub data_bits: DataBits,
         pub parity: Parity,
         pub stop_bits: StopBits,
+        pub clock_source: ClockSource,
     }
 
     impl Config {
@@ -90,6 +153,11 @@ pub mod config {
             self.stop_bits = stop_bits;
             self
         }
+
+        pub fn clock_source(mut self, clock_source: ClockSource) -> Self {
+            self.clock_source = clock_source;
+            self
+        }
     }
 
     impl Default for Config {
@@ -99,6 +167,7 @@ pub mod config {
                 data_bits: DataBits::DataBits8,
                 parity: Parity::ParityNone,
                 stop_bits: StopBits::STOP1,
+                clock_source: ClockSource::Apb,
             }
         }
     }
@@ -232,18 +301,25 @@ impl embedded_hal_1::serial::Error for Error {
     }
 }
 
+#[cfg(feature = "embedded-hal-nb")]
+impl embedded_hal_nb::serial::Error for Error {
+    fn kind(&self) -> embedded_hal_nb::serial::ErrorKind {
+        embedded_hal_nb::serial::ErrorKind::Other
+    }
+}
+
 /// UART driver
-pub struct Serial<T> {
-    uart: T,
+pub struct Serial<'d, T> {
+    uart: PeripheralRef<'d, T>,
 }
 
-impl<T> Serial<T>
+impl<'d, T> Serial<'d, T>
 where
     T: Instance,
 {
     /// Create a new UART instance with defaults
     pub fn new_with_config<P>(
-        uart: T,
+        uart: impl Peripheral<P = T> + 'd,
         config: Option<Config>,
         mut pins: Option<P>,
         clocks: &Clocks,
@@ -251,9 +327,11 @@ where
     where
         P: UartPins,
     {
+        crate::into_ref!(uart);
         let mut serial = Serial { uart };
         serial.uart.disable_rx_interrupts();
         serial.uart.disable_tx_interrupts();
+        UARTS_IN_USE.fetch_or(1 << serial.uart.uart_number(), Ordering::Relaxed);
 
         if let Some(ref mut pins) = pins {
             pins.configure_pins(
@@ -268,24 +346,62 @@ where
             serial.change_data_bits(config.data_bits);
             serial.change_parity(config.parity);
             serial.change_stop_bits(config.stop_bits);
-            serial.change_baud(config.baudrate, clocks);
+            serial.change_baud(config.baudrate, config.clock_source, clocks);
         });
 
         serial
     }
 
     /// Create a new UART instance with defaults
-    pub fn new(uart: T) -> Self {
+    pub fn new(uart: impl Peripheral<P = T> + 'd) -> Self {
+        crate::into_ref!(uart);
         let mut serial = Serial { uart };
         serial.uart.disable_rx_interrupts();
         serial.uart.disable_tx_interrupts();
+        UARTS_IN_USE.fetch_or(1 << serial.uart.uart_number(), Ordering::Relaxed);
 
         serial
     }
 
-    /// Return the raw interface to the underlying UART instance
-    pub fn free(self) -> T {
-        self.uart
+    /// Use `tx` as the TX pin
+    pub fn with_tx<TX: OutputPin>(self, tx: impl Peripheral<P = TX> + 'd) -> Self {
+        crate::into_ref!(tx);
+        tx.set_to_push_pull_output()
+            .connect_peripheral_to_output(self.uart.tx_signal());
+        self
+    }
+
+    /// Use `rx` as the RX pin
+    pub fn with_rx<RX: InputPin>(self, rx: impl Peripheral<P = RX> + 'd) -> Self {
+        crate::into_ref!(rx);
+        rx.set_to_input()
+            .connect_input_to_peripheral(self.uart.rx_signal());
+        self
+    }
+
+    /// Use `cts` as the CTS pin
+    pub fn with_cts<CTS: InputPin>(self, cts: impl Peripheral<P = CTS> + 'd) -> Self {
+        crate::into_ref!(cts);
+        cts.set_to_input()
+            .connect_input_to_peripheral(self.uart.cts_signal());
+        self
+    }
+
+    /// Use `rts` as the RTS pin
+    pub fn with_rts<RTS: OutputPin>(self, rts: impl Peripheral<P = RTS> + 'd) -> Self {
+        crate::into_ref!(rts);
+        rts.set_to_push_pull_output()
+            .connect_peripheral_to_output(self.uart.rts_signal());
+        self
+    }
+
+    /// Apply the baud rate and frame format of `config`
+    pub fn with_config(mut self, config: Config, clocks: &Clocks) -> Self {
+        self.change_data_bits(config.data_bits);
+        self.change_parity(config.parity);
+        self.change_stop_bits(config.stop_bits);
+        self.change_baud(config.baudrate, config.clock_source, clocks);
+        self
     }
 
     /// Writes bytes
@@ -552,16 +668,18 @@ where
     }
 
     #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
-    fn change_baud(&self, baudrate: u32, clocks: &Clocks) {
-        // we force the clock source to be APB and don't use the decimal part of the
-        // divider
-        let clk = clocks.apb_clock.to_Hz();
+    fn change_baud(&self, baudrate: u32, clock_source: config::ClockSource, clocks: &Clocks) {
+        // we don't use the decimal part of the divider
+        let (clk, sclk_sel) = match clock_source {
+            config::ClockSource::Apb => (clocks.apb_clock.to_Hz(), 1),
+            config::ClockSource::Xtal => (clocks.xtal_clock.to_Hz(), 3),
+        };
         let max_div = 0b1111_1111_1111 - 1;
         let clk_div = ((clk) + (max_div * baudrate) - 1) / (max_div * baudrate);
 
         self.uart.register_block().clk_conf.write(|w| unsafe {
             w.sclk_sel()
-                .bits(1) // APB
+                .bits(sclk_sel)
                 .sclk_div_a()
                 .bits(0)
                 .sclk_div_b()
@@ -578,6 +696,13 @@ where
         let divider = clk / baudrate;
         let divider = divider as u16;
 
+        trace!(
+            "UART baud rate {}: sclk divider {}, baud divider {}",
+            baudrate,
+            clk_div,
+            divider
+        );
+
         self.uart
             .register_block()
             .clkdiv
@@ -585,28 +710,78 @@ where
     }
 
     #[cfg(any(feature = "esp32", feature = "esp32s2"))]
-    fn change_baud(&self, baudrate: u32, clocks: &Clocks) {
-        // we force the clock source to be APB and don't use the decimal part of the
-        // divider
-        let clk = clocks.apb_clock.to_Hz();
+    fn change_baud(&self, baudrate: u32, clock_source: config::ClockSource, clocks: &Clocks) {
+        let (clk, use_apb) = match clock_source {
+            config::ClockSource::Apb => (clocks.apb_clock.to_Hz(), true),
+            config::ClockSource::RefTick => (REF_TICK_HZ, false),
+        };
 
         self.uart
             .register_block()
             .conf0
-            .modify(|_, w| w.tick_ref_always_on().bit(true));
-        let divider = clk / baudrate;
+            .modify(|_, w| w.tick_ref_always_on().bit(use_apb));
 
-        self.uart
-            .register_block()
-            .clkdiv
-            .write(|w| unsafe { w.clkdiv().bits(divider).frag().bits(0) });
+        // the divider has a fractional part of 4 bits, which matters for the
+        // small dividers of REF_TICK
+        let divider = (clk << 4) / baudrate;
+
+        trace!("UART baud rate {}: baud divider {}/16", baudrate, divider);
+
+        self.uart.register_block().clkdiv.write(|w| unsafe {
+            w.clkdiv()
+                .bits(divider >> 4)
+                .frag()
+                .bits((divider & 0xf) as u8)
+        });
+    }
+}
+
+/// Wait until the UARTs which have been set up by a driver have sent all
+/// pending data, e.g. before their clock is gated in light sleep
+pub(crate) fn flush_all() {
+    let in_use = UARTS_IN_USE.load(Ordering::Relaxed);
+
+    for number in 0..UART_COUNT {
+        if in_use & (1 << number) != 0 {
+            let register_block = unsafe { &*uart_register_block(number) };
+
+            while register_block.status.read().txfifo_cnt().bits() != 0
+                || !tx_idle(register_block)
+            {}
+        }
     }
 }
 
+#[cfg(any(feature = "esp32", feature = "esp32s3"))]
+const UART_COUNT: usize = 3;
+#[cfg(not(any(feature = "esp32", feature = "esp32s3")))]
+const UART_COUNT: usize = 2;
+
+fn uart_register_block(number: usize) -> *const RegisterBlock {
+    match number {
+        0 => UART0::ptr(),
+        #[cfg(any(feature = "esp32", feature = "esp32s3"))]
+        2 => UART2::ptr(),
+        _ => UART1::ptr(),
+    }
+}
+
+fn tx_idle(register_block: &RegisterBlock) -> bool {
+    #[cfg(feature = "esp32")]
+    let idle = register_block.status.read().st_utx_out().bits() == 0x0u8;
+    #[cfg(not(feature = "esp32"))]
+    let idle = register_block.fsm_status.read().st_utx_out().bits() == 0x0u8;
+
+    idle
+}
+
 /// UART peripheral instance
 pub trait Instance {
     fn register_block(&self) -> &RegisterBlock;
 
+    /// The number of the UART, e.g. 1 for UART1
+    fn uart_number(&self) -> usize;
+
     fn disable_tx_interrupts(&mut self) {
         self.register_block().int_clr.write(|w| {
             w.txfifo_empty_int_clr()
@@ -670,12 +845,7 @@ pub trait Instance {
     }
 
     fn is_tx_idle(&self) -> bool {
-        #[cfg(feature = "esp32")]
-        let idle = self.register_block().status.read().st_utx_out().bits() == 0x0u8;
-        #[cfg(not(feature = "esp32"))]
-        let idle = self.register_block().fsm_status.read().st_utx_out().bits() == 0x0u8;
-
-        idle
+        tx_idle(self.register_block())
     }
 
     fn is_rx_idle(&self) -> bool {
@@ -702,6 +872,10 @@ impl Instance for UART0 {
         self
     }
 
+    fn uart_number(&self) -> usize {
+        0
+    }
+
     fn tx_signal(&self) -> OutputSignal {
         OutputSignal::U0TXD
     }
@@ -725,6 +899,10 @@ impl Instance for UART1 {
         self
     }
 
+    fn uart_number(&self) -> usize {
+        1
+    }
+
     fn tx_signal(&self) -> OutputSignal {
         OutputSignal::U1TXD
     }
@@ -749,6 +927,10 @@ impl Instance for UART2 {
         self
     }
 
+    fn uart_number(&self) -> usize {
+        2
+    }
+
     fn tx_signal(&self) -> OutputSignal {
         OutputSignal::U2TXD
     }
@@ -767,7 +949,7 @@ impl Instance for UART2 {
 }
 
 #[cfg(feature = "ufmt")]
-impl<T> ufmt_write::uWrite for Serial<T>
+impl<'d, T> ufmt_write::uWrite for Serial<'d, T>
 where
     T: Instance,
 {
@@ -785,7 +967,7 @@ where
     }
 }
 
-impl<T> core::fmt::Write for Serial<T>
+impl<'d, T> core::fmt::Write for Serial<'d, T>
 where
     T: Instance,
 {
@@ -795,7 +977,7 @@ where
     }
 }
 
-impl<T> embedded_hal::serial::Write<u8> for Serial<T>
+impl<'d, T> embedded_hal::serial::Write<u8> for Serial<'d, T>
 where
     T: Instance,
 {
@@ -810,7 +992,7 @@ where
     }
 }
 
-impl<T> embedded_hal::serial::Read<u8> for Serial<T>
+impl<'d, T> embedded_hal::serial::Read<u8> for Serial<'d, T>
 where
     T: Instance,
 {
@@ -822,12 +1004,12 @@ where
 }
 
 #[cfg(feature = "eh1")]
-impl<T> embedded_hal_1::serial::ErrorType for Serial<T> {
+impl<'d, T> embedded_hal_1::serial::ErrorType for Serial<'d, T> {
     type Error = Error;
 }
 
 #[cfg(feature = "eh1")]
-impl<T> embedded_hal_1::serial::nb::Read for Serial<T>
+impl<'d, T> embedded_hal_1::serial::nb::Read for Serial<'d, T>
 where
     T: Instance,
 {
@@ -837,7 +1019,36 @@ where
 }
 
 #[cfg(feature = "eh1")]
-impl<T> embedded_hal_1::serial::nb::Write for Serial<T>
+impl<'d, T> embedded_hal_1::serial::nb::Write for Serial<'d, T>
+where
+    T: Instance,
+{
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.write_byte(word)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.flush_tx()
+    }
+}
+
+#[cfg(feature = "embedded-hal-nb")]
+impl<'d, T> embedded_hal_nb::serial::ErrorType for Serial<'d, T> {
+    type Error = Error;
+}
+
+#[cfg(feature = "embedded-hal-nb")]
+impl<'d, T> embedded_hal_nb::serial::Read for Serial<'d, T>
+where
+    T: Instance,
+{
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.read_byte()
+    }
+}
+
+#[cfg(feature = "embedded-hal-nb")]
+impl<'d, T> embedded_hal_nb::serial::Write for Serial<'d, T>
 where
     T: Instance,
 {
@@ -21,20 +21,61 @@
 //!     &mut clocks,
 //! );
 //! ```
-
-use core::convert::Infallible;
+//!
+//! Alternatively, only the pins which are needed can be connected with the
+//! `with_*` methods:
+//! ```rust
+//! let mut spi = hal::spi::Spi::new_without_pins(
+//!     peripherals.SPI2,
+//!     100u32.kHz(),
+//!     SpiMode::Mode0,
+//!     &mut peripheral_clock_control,
+//!     &clocks,
+//! )
+//! .with_sck(io.pins.gpio12)
+//! .with_mosi(io.pins.gpio13);
+//! ```
 
 use fugit::HertzU32;
 
 use crate::{
     clock::Clocks,
     pac::spi2::RegisterBlock,
+    peripherals::{Peripheral, PeripheralRef},
     system::PeripheralClockControl,
     types::{InputSignal, OutputSignal},
     InputPin,
     OutputPin,
 };
 
+/// SPI-specific errors
+///
+/// No error conditions are currently reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, _f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl embedded_hal_1::spi::Error for Error {
+    fn kind(&self) -> embedded_hal_1::spi::ErrorKind {
+        embedded_hal_1::spi::ErrorKind::Other
+    }
+}
+
+#[cfg(feature = "embedded-hal-nb")]
+impl embedded_hal_nb::spi::Error for Error {
+    fn kind(&self) -> embedded_hal_nb::spi::ErrorKind {
+        embedded_hal_nb::spi::ErrorKind::Other
+    }
+}
+
 /// The size of the FIFO buffer for SPI
 #[cfg(not(feature = "esp32s2"))]
 const FIFO_SIZE: usize = 64;
@@ -44,6 +85,7 @@ const FIFO_SIZE: usize = 72;
 const EMPTY_WRITE_PAD: u8 = 0x00u8;
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SpiMode {
     Mode0,
     Mode1,
@@ -51,26 +93,27 @@ pub enum SpiMode {
     Mode3,
 }
 
-pub struct Spi<T> {
-    spi: T,
+pub struct Spi<'d, T> {
+    spi: PeripheralRef<'d, T>,
 }
 
-impl<T> Spi<T>
+impl<'d, T> Spi<'d, T>
 where
     T: Instance,
 {
     /// Constructs an SPI instance in 8bit dataframe mode.
     pub fn new<SCK: OutputPin, MOSI: OutputPin, MISO: InputPin, CS: OutputPin>(
-        spi: T,
-        mut sck: SCK,
-        mut mosi: MOSI,
-        mut miso: MISO,
-        mut cs: CS,
+        spi: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = SCK> + 'd,
+        mosi: impl Peripheral<P = MOSI> + 'd,
+        miso: impl Peripheral<P = MISO> + 'd,
+        cs: impl Peripheral<P = CS> + 'd,
         frequency: HertzU32,
         mode: SpiMode,
         peripheral_clock_control: &mut PeripheralClockControl,
         clocks: &Clocks,
     ) -> Self {
+        crate::into_ref!(spi, sck, mosi, miso, cs);
         sck.set_to_push_pull_output()
             .connect_peripheral_to_output(spi.sclk_signal());
 
@@ -88,15 +131,16 @@ where
 
     /// Constructs an SPI instance in 8bit dataframe mode without CS pin.
     pub fn new_no_cs<SCK: OutputPin, MOSI: OutputPin, MISO: InputPin>(
-        spi: T,
-        mut sck: SCK,
-        mut mosi: MOSI,
-        mut miso: MISO,
+        spi: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = SCK> + 'd,
+        mosi: impl Peripheral<P = MOSI> + 'd,
+        miso: impl Peripheral<P = MISO> + 'd,
         frequency: HertzU32,
         mode: SpiMode,
         peripheral_clock_control: &mut PeripheralClockControl,
         clocks: &Clocks,
     ) -> Self {
+        crate::into_ref!(spi, sck, mosi, miso);
         sck.set_to_push_pull_output()
             .connect_peripheral_to_output(spi.sclk_signal());
 
@@ -112,14 +156,15 @@ where
     /// Constructs an SPI instance in 8bit dataframe mode without CS and MISO
     /// pin.
     pub fn new_no_cs_no_miso<SCK: OutputPin, MOSI: OutputPin>(
-        spi: T,
-        mut sck: SCK,
-        mut mosi: MOSI,
+        spi: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = SCK> + 'd,
+        mosi: impl Peripheral<P = MOSI> + 'd,
         frequency: HertzU32,
         mode: SpiMode,
         peripheral_clock_control: &mut PeripheralClockControl,
         clocks: &Clocks,
     ) -> Self {
+        crate::into_ref!(spi, sck, mosi);
         sck.set_to_push_pull_output()
             .connect_peripheral_to_output(spi.sclk_signal());
 
@@ -134,21 +179,35 @@ where
     /// other protocols by bitbanging (WS2812B, onewire, generating arbitrary
     /// waveforms…)
     pub fn new_mosi_only<MOSI: OutputPin>(
-        spi: T,
-        mut mosi: MOSI,
+        spi: impl Peripheral<P = T> + 'd,
+        mosi: impl Peripheral<P = MOSI> + 'd,
         frequency: HertzU32,
         mode: SpiMode,
         peripheral_clock_control: &mut PeripheralClockControl,
         clocks: &Clocks,
     ) -> Self {
+        crate::into_ref!(spi, mosi);
         mosi.set_to_push_pull_output()
             .connect_peripheral_to_output(spi.mosi_signal());
 
         Self::new_internal(spi, frequency, mode, peripheral_clock_control, clocks)
     }
 
+    /// Constructs an SPI instance in 8bit dataframe mode without any pins
+    /// connected. The pins can be connected with the `with_*` methods.
+    pub fn new_without_pins(
+        spi: impl Peripheral<P = T> + 'd,
+        frequency: HertzU32,
+        mode: SpiMode,
+        peripheral_clock_control: &mut PeripheralClockControl,
+        clocks: &Clocks,
+    ) -> Self {
+        crate::into_ref!(spi);
+        Self::new_internal(spi, frequency, mode, peripheral_clock_control, clocks)
+    }
+
     pub fn new_internal(
-        spi: T,
+        spi: PeripheralRef<'d, T>,
         frequency: HertzU32,
         mode: SpiMode,
         peripheral_clock_control: &mut PeripheralClockControl,
@@ -164,17 +223,56 @@ where
         spi
     }
 
-    /// Return the raw interface to the underlying peripheral instance
-    pub fn free(self) -> T {
-        self.spi
+    /// Use `sck` as the clock pin
+    pub fn with_sck<SCK: OutputPin>(self, sck: impl Peripheral<P = SCK> + 'd) -> Self {
+        crate::into_ref!(sck);
+        sck.set_to_push_pull_output()
+            .connect_peripheral_to_output(self.spi.sclk_signal());
+        self
+    }
+
+    /// Use `mosi` as the MOSI pin
+    pub fn with_mosi<MOSI: OutputPin>(self, mosi: impl Peripheral<P = MOSI> + 'd) -> Self {
+        crate::into_ref!(spi, mosi);
+        mosi.set_to_push_pull_output()
+            .connect_peripheral_to_output(self.spi.mosi_signal());
+        self
+    }
+
+    /// Use `miso` as the MISO pin
+    pub fn with_miso<MISO: InputPin>(self, miso: impl Peripheral<P = MISO> + 'd) -> Self {
+        crate::into_ref!(miso);
+        miso.set_to_input()
+            .connect_input_to_peripheral(self.spi.miso_signal());
+        self
+    }
+
+    /// Use `cs` as the chip select pin
+    pub fn with_cs<CS: OutputPin>(self, cs: impl Peripheral<P = CS> + 'd) -> Self {
+        crate::into_ref!(cs);
+        cs.set_to_push_pull_output()
+            .connect_peripheral_to_output(self.spi.cs_signal());
+        self
+    }
+
+    /// Change the clock frequency
+    pub fn with_frequency(mut self, frequency: HertzU32, clocks: &Clocks) -> Self {
+        self.spi.setup(frequency, clocks);
+        self
+    }
+
+    /// Change the SPI mode
+    pub fn with_mode(mut self, mode: SpiMode) -> Self {
+        self.spi.set_data_mode(mode);
+        self
     }
 }
 
-impl<T> embedded_hal::spi::FullDuplex<u8> for Spi<T>
+impl<'d, T> embedded_hal::spi::FullDuplex<u8> for Spi<'d, T>
 where
     T: Instance,
 {
-    type Error = Infallible;
+    type Error = Error;
 
     fn read(&mut self) -> nb::Result<u8, Self::Error> {
         self.spi.read_byte()
@@ -185,22 +283,22 @@ where
     }
 }
 
-impl<T> embedded_hal::blocking::spi::Transfer<u8> for Spi<T>
+impl<'d, T> embedded_hal::blocking::spi::Transfer<u8> for Spi<'d, T>
 where
     T: Instance,
 {
-    type Error = Infallible;
+    type Error = Error;
 
     fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
         self.spi.transfer(words)
     }
 }
 
-impl<T> embedded_hal::blocking::spi::Write<u8> for Spi<T>
+impl<'d, T> embedded_hal::blocking::spi::Write<u8> for Spi<'d, T>
 where
     T: Instance,
 {
-    type Error = Infallible;
+    type Error = Error;
 
     fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
         self.spi.write_bytes(words)?;
@@ -221,11 +319,11 @@ mod ehal1 {
 
     use super::*;
 
-    impl<T> embedded_hal_1::spi::ErrorType for Spi<T> {
-        type Error = Infallible;
+    impl<'d, T> embedded_hal_1::spi::ErrorType for Spi<'d, T> {
+        type Error = Error;
     }
 
-    impl<T> FullDuplex for Spi<T>
+    impl<'d, T> FullDuplex for Spi<'d, T>
     where
         T: Instance,
     {
@@ -238,7 +336,7 @@ mod ehal1 {
         }
     }
 
-    impl<T> SpiBusWrite for Spi<T>
+    impl<'d, T> SpiBusWrite for Spi<'d, T>
     where
         T: Instance,
     {
@@ -248,7 +346,7 @@ mod ehal1 {
         }
     }
 
-    impl<T> SpiBusRead for Spi<T>
+    impl<'d, T> SpiBusRead for Spi<'d, T>
     where
         T: Instance,
     {
@@ -258,7 +356,7 @@ mod ehal1 {
         }
     }
 
-    impl<T> SpiBus for Spi<T>
+    impl<'d, T> SpiBus for Spi<'d, T>
     where
         T: Instance,
     {
@@ -331,7 +429,7 @@ mod ehal1 {
         }
     }
 
-    impl<T> SpiBusFlush for Spi<T>
+    impl<'d, T> SpiBusFlush for Spi<'d, T>
     where
         T: Instance,
     {
@@ -341,6 +439,25 @@ mod ehal1 {
     }
 }
 
+#[cfg(feature = "embedded-hal-nb")]
+impl<'d, T> embedded_hal_nb::spi::ErrorType for Spi<'d, T> {
+    type Error = Error;
+}
+
+#[cfg(feature = "embedded-hal-nb")]
+impl<'d, T> embedded_hal_nb::spi::FullDuplex for Spi<'d, T>
+where
+    T: Instance,
+{
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.spi.read_byte()
+    }
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.spi.write_byte(word)
+    }
+}
+
 pub trait Instance {
     fn register_block(&self) -> &RegisterBlock;
 
@@ -473,6 +590,12 @@ pub trait Instance {
                 | ((pre as u32 - 1) << 18);
         }
 
+        trace!(
+            "SPI frequency {} Hz: clock register {}",
+            frequency.raw(),
+            reg_val
+        );
+
         self.register_block()
             .clock
             .write(|w| unsafe { w.bits(reg_val) });
@@ -528,7 +651,7 @@ pub trait Instance {
         self
     }
 
-    fn read_byte(&mut self) -> nb::Result<u8, Infallible> {
+    fn read_byte(&mut self) -> nb::Result<u8, Error> {
         let reg_block = self.register_block();
 
         if reg_block.cmd.read().usr().bit_is_set() {
@@ -538,7 +661,7 @@ pub trait Instance {
         Ok(u32::try_into(reg_block.w0.read().bits()).unwrap_or_default())
     }
 
-    fn write_byte(&mut self, word: u8) -> nb::Result<(), Infallible> {
+    fn write_byte(&mut self, word: u8) -> nb::Result<(), Error> {
         let reg_block = self.register_block();
 
         if reg_block.cmd.read().usr().bit_is_set() {
@@ -565,7 +688,7 @@ pub trait Instance {
     /// you must ensure that the whole messages was written correctly, use
     /// [`flush`].
     // FIXME: See below.
-    fn write_bytes(&mut self, words: &[u8]) -> Result<(), Infallible> {
+    fn write_bytes(&mut self, words: &[u8]) -> Result<(), Error> {
         let reg_block = self.register_block();
         let num_chunks = words.len() / FIFO_SIZE;
 
@@ -611,7 +734,7 @@ pub trait Instance {
     /// Sends out a stuffing byte for every byte to read. This function doesn't
     /// perform flushing. If you want to read the response to something you
     /// have written before, consider using [`transfer`] instead.
-    fn read_bytes(&mut self, words: &mut [u8]) -> Result<(), Infallible> {
+    fn read_bytes(&mut self, words: &mut [u8]) -> Result<(), Error> {
         let empty_array = [EMPTY_WRITE_PAD; FIFO_SIZE];
 
         for chunk in words.chunks_mut(FIFO_SIZE) {
@@ -631,7 +754,7 @@ pub trait Instance {
     // FIXME: Using something like `core::slice::from_raw_parts` and
     // `copy_from_slice` on the receive registers works only for the esp32 and
     // esp32c3 varaints. The reason for this is unknown.
-    fn read_bytes_from_fifo(&mut self, words: &mut [u8]) -> Result<(), Infallible> {
+    fn read_bytes_from_fifo(&mut self, words: &mut [u8]) -> Result<(), Error> {
         let reg_block = self.register_block();
 
         for chunk in words.chunks_mut(FIFO_SIZE) {
@@ -655,7 +778,7 @@ pub trait Instance {
     }
 
     // Check if the bus is busy and if it is wait for it to be idle
-    fn flush(&mut self) -> Result<(), Infallible> {
+    fn flush(&mut self) -> Result<(), Error> {
         let reg_block = self.register_block();
 
         while reg_block.cmd.read().usr().bit_is_set() {
@@ -664,7 +787,7 @@ pub trait Instance {
         Ok(())
     }
 
-    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Error> {
         for chunk in words.chunks_mut(FIFO_SIZE) {
             self.write_bytes(chunk)?;
             self.flush()?;
@@ -14,6 +14,7 @@ type SystemPeripheral = crate::pac::SYSTEM;
 type SystemPeripheral = crate::pac::DPORT;
 
 /// Peripherals which can be enabled via [PeripheralClockControl]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Peripheral {
     Spi2,
     Spi3,
@@ -24,16 +25,54 @@ pub enum Peripheral {
     Ledc,
     #[cfg(feature = "esp32c3")]
     ApbSarAdc,
+    Rsa,
+    #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+    Hmac,
+    #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+    Ds,
+    Twai,
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    Usb,
+    #[cfg(feature = "esp32s3")]
+    Gdma,
+    #[cfg(feature = "esp32s3")]
+    LcdCam,
 }
 
 /// Controls the enablement of peripheral clocks.
+///
+/// The drivers take it in their constructors to enable their peripheral.
+/// Peripherals which are not used can be disabled to save power.
 pub struct PeripheralClockControl {
     _private: (),
 }
 
 impl PeripheralClockControl {
-    /// Enables and resets the given peripheral
+    /// Enables the clock of the given peripheral and releases it from reset
+    ///
+    /// The peripheral keeps its state if it is enabled already, so drivers
+    /// sharing a peripheral can all enable it.
     pub fn enable(&mut self, peripheral: Peripheral) {
+        self.set(peripheral, true, false);
+    }
+
+    /// Gates the clock of the given peripheral and holds it in reset, to save
+    /// power while it is unused
+    ///
+    /// Peripherals which the given one uses internally, e.g. the SHA
+    /// accelerator of the HMAC module, are left running.
+    pub fn disable(&mut self, peripheral: Peripheral) {
+        self.set(peripheral, false, true);
+    }
+
+    /// Resets the given peripheral to its initial state, and leaves it
+    /// enabled
+    pub fn reset(&mut self, peripheral: Peripheral) {
+        self.set(peripheral, true, true);
+        self.set(peripheral, true, false);
+    }
+
+    fn set(&mut self, peripheral: Peripheral, enable: bool, reset: bool) {
         let system = unsafe { &*SystemPeripheral::PTR };
 
         #[cfg(not(feature = "esp32"))]
@@ -43,45 +82,242 @@ impl PeripheralClockControl {
 
         match peripheral {
             Peripheral::Spi2 => {
-                perip_clk_en0.modify(|_, w| w.spi2_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.spi2_rst().clear_bit());
+                perip_clk_en0.modify(|_, w| w.spi2_clk_en().bit(enable));
+                perip_rst_en0.modify(|_, w| w.spi2_rst().bit(reset));
             }
             Peripheral::Spi3 => {
-                perip_clk_en0.modify(|_, w| w.spi3_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.spi3_rst().clear_bit());
+                perip_clk_en0.modify(|_, w| w.spi3_clk_en().bit(enable));
+                perip_rst_en0.modify(|_, w| w.spi3_rst().bit(reset));
             }
             #[cfg(feature = "esp32")]
             Peripheral::I2cExt0 => {
-                perip_clk_en0.modify(|_, w| w.i2c0_ext0_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.i2c0_ext0_rst().clear_bit());
+                perip_clk_en0.modify(|_, w| w.i2c0_ext0_clk_en().bit(enable));
+                perip_rst_en0.modify(|_, w| w.i2c0_ext0_rst().bit(reset));
             }
             #[cfg(not(feature = "esp32"))]
             Peripheral::I2cExt0 => {
-                perip_clk_en0.modify(|_, w| w.i2c_ext0_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.i2c_ext0_rst().clear_bit());
+                perip_clk_en0.modify(|_, w| w.i2c_ext0_clk_en().bit(enable));
+                perip_rst_en0.modify(|_, w| w.i2c_ext0_rst().bit(reset));
             }
             #[cfg(not(feature = "esp32c3"))]
             Peripheral::I2cExt1 => {
-                perip_clk_en0.modify(|_, w| w.i2c_ext1_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.i2c_ext1_rst().clear_bit());
+                perip_clk_en0.modify(|_, w| w.i2c_ext1_clk_en().bit(enable));
+                perip_rst_en0.modify(|_, w| w.i2c_ext1_rst().bit(reset));
             }
             Peripheral::Rmt => {
-                perip_clk_en0.modify(|_, w| w.rmt_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.rmt_rst().clear_bit());
+                perip_clk_en0.modify(|_, w| w.rmt_clk_en().bit(enable));
+                perip_rst_en0.modify(|_, w| w.rmt_rst().bit(reset));
             }
             Peripheral::Ledc => {
-                perip_clk_en0.modify(|_, w| w.ledc_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.ledc_rst().clear_bit());
+                perip_clk_en0.modify(|_, w| w.ledc_clk_en().bit(enable));
+                perip_rst_en0.modify(|_, w| w.ledc_rst().bit(reset));
             }
             #[cfg(feature = "esp32c3")]
             Peripheral::ApbSarAdc => {
-                perip_clk_en0.modify(|_, w| w.apb_saradc_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.apb_saradc_rst().clear_bit());
+                perip_clk_en0.modify(|_, w| w.apb_saradc_clk_en().bit(enable));
+                perip_rst_en0.modify(|_, w| w.apb_saradc_rst().bit(reset));
+            }
+            #[cfg(feature = "esp32")]
+            Peripheral::Rsa => {
+                const RSA: u32 = 1 << 2;
+                let with_rsa = |bits: u32, set: bool| if set { bits | RSA } else { bits & !RSA };
+
+                system
+                    .peri_clk_en
+                    .modify(|r, w| unsafe { w.bits(with_rsa(r.bits(), enable)) });
+                system
+                    .peri_rst_en
+                    .modify(|r, w| unsafe { w.bits(with_rsa(r.bits(), reset)) });
+                system.rsa_pd_ctrl.modify(|_, w| w.rsa_pd().bit(!enable));
+            }
+            #[cfg(not(feature = "esp32"))]
+            Peripheral::Rsa => {
+                system
+                    .perip_clk_en1
+                    .modify(|_, w| w.crypto_rsa_clk_en().bit(enable));
+                system
+                    .perip_rst_en1
+                    .modify(|_, w| w.crypto_rsa_rst().bit(reset));
+                system
+                    .rsa_pd_ctrl
+                    .modify(|_, w| w.rsa_mem_pd().bit(!enable));
+            }
+            #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+            Peripheral::Hmac => {
+                system
+                    .perip_clk_en1
+                    .modify(|_, w| w.crypto_hmac_clk_en().bit(enable));
+                system
+                    .perip_rst_en1
+                    .modify(|_, w| w.crypto_hmac_rst().bit(reset));
+
+                if enable {
+                    // The HMAC module uses the SHA accelerator internally
+                    system
+                        .perip_clk_en1
+                        .modify(|_, w| w.crypto_sha_clk_en().set_bit());
+                    system
+                        .perip_rst_en1
+                        .modify(|_, w| w.crypto_sha_rst().clear_bit());
+                }
             }
+            #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+            Peripheral::Ds => {
+                system
+                    .perip_clk_en1
+                    .modify(|_, w| w.crypto_ds_clk_en().bit(enable));
+                system
+                    .perip_rst_en1
+                    .modify(|_, w| w.crypto_ds_rst().bit(reset));
+
+                if enable {
+                    // The DS peripheral uses the HMAC, SHA and RSA modules internally
+                    system.perip_clk_en1.modify(|_, w| {
+                        w.crypto_hmac_clk_en()
+                            .set_bit()
+                            .crypto_sha_clk_en()
+                            .set_bit()
+                            .crypto_rsa_clk_en()
+                            .set_bit()
+                    });
+                    system.perip_rst_en1.modify(|_, w| {
+                        w.crypto_hmac_rst()
+                            .clear_bit()
+                            .crypto_sha_rst()
+                            .clear_bit()
+                            .crypto_rsa_rst()
+                            .clear_bit()
+                    });
+                    system.rsa_pd_ctrl.modify(|_, w| w.rsa_mem_pd().clear_bit());
+                }
+            }
+            #[cfg(feature = "esp32")]
+            Peripheral::Twai => {
+                perip_clk_en0.modify(|_, w| w.can_clk_en().bit(enable));
+                perip_rst_en0.modify(|_, w| w.can_rst().bit(reset));
+            }
+            #[cfg(not(feature = "esp32"))]
+            Peripheral::Twai => {
+                perip_clk_en0.modify(|_, w| w.twai_clk_en().bit(enable));
+                perip_rst_en0.modify(|_, w| w.twai_rst().bit(reset));
+            }
+            #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+            Peripheral::Usb => {
+                perip_clk_en0.modify(|_, w| w.usb_clk_en().bit(enable));
+                perip_rst_en0.modify(|_, w| w.usb_rst().bit(reset));
+            }
+            #[cfg(feature = "esp32s3")]
+            Peripheral::Gdma => {
+                system
+                    .perip_clk_en1
+                    .modify(|_, w| w.dma_clk_en().bit(enable));
+                system.perip_rst_en1.modify(|_, w| w.dma_rst().bit(reset));
+            }
+            #[cfg(feature = "esp32s3")]
+            Peripheral::LcdCam => {
+                system
+                    .perip_clk_en1
+                    .modify(|_, w| w.lcd_cam_clk_en().bit(enable));
+                system
+                    .perip_rst_en1
+                    .modify(|_, w| w.lcd_cam_rst().bit(reset));
+            }
+        }
+    }
+}
+
+/// Radio blocks whose clocks can be controlled via [RadioClockControl]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RadioPeripheral {
+    /// The PHY and the parts shared by the WiFi and BT MACs
+    Phy,
+    /// The WiFi MAC
+    Wifi,
+    /// The BT MAC
+    #[cfg(not(feature = "esp32s2"))]
+    Bt,
+    /// The RF noise source of the RNG
+    Rng,
+}
+
+#[cfg(feature = "esp32")]
+mod radio {
+    pub(super) const WIFI_CLK_EN_REG: u32 = 0x3ff0_00cc;
+    pub(super) const WIFI_RST_EN_REG: u32 = 0x3ff0_00d0;
+    pub(super) const WIFI_CLK_WIFI_BT_COMMON_M: u32 = 0x0000_03c9;
+    pub(super) const WIFI_CLK_WIFI_EN_M: u32 = 0x0000_0406;
+    pub(super) const WIFI_CLK_BT_EN_M: u32 = 0x0003_0800;
+}
+
+#[cfg(feature = "esp32s2")]
+mod radio {
+    pub(super) const WIFI_CLK_EN_REG: u32 = 0x3f42_6090;
+    pub(super) const WIFI_RST_EN_REG: u32 = 0x3f42_6094;
+    pub(super) const WIFI_CLK_WIFI_BT_COMMON_M: u32 = 0x0000_03c9;
+    pub(super) const WIFI_CLK_WIFI_EN_M: u32 = 0x0000_0406;
+}
+
+#[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+mod radio {
+    pub(super) const WIFI_CLK_EN_REG: u32 = 0x6002_6014;
+    pub(super) const WIFI_RST_EN_REG: u32 = 0x6002_6018;
+    pub(super) const WIFI_CLK_WIFI_BT_COMMON_M: u32 = 0x0078_078f;
+    // the MACs are clocked together with the PHY
+    pub(super) const WIFI_CLK_WIFI_EN_M: u32 = 0;
+    pub(super) const WIFI_CLK_BT_EN_M: u32 = 0;
+}
+
+const WIFI_CLK_RNG_EN: u32 = 1 << 15;
+const WIFI_RST_MAC: u32 = 1 << 2;
+
+/// Controls the clocks of the radio blocks.
+///
+/// The WiFi and BT drivers (i.e. esp-wifi) take it to enable the radio
+/// blocks they use, and disable them again to save power when they are
+/// stopped.
+pub struct RadioClockControl {
+    _private: (),
+}
+
+impl RadioClockControl {
+    /// Enables the clock of the given radio block
+    pub fn enable(&mut self, peripheral: RadioPeripheral) {
+        modify_bits(radio::WIFI_CLK_EN_REG, Self::mask(peripheral), true);
+    }
+
+    /// Gates the clock of the given radio block
+    ///
+    /// The PHY must stay enabled while the WiFi or the BT MAC is enabled.
+    pub fn disable(&mut self, peripheral: RadioPeripheral) {
+        modify_bits(radio::WIFI_CLK_EN_REG, Self::mask(peripheral), false);
+    }
+
+    /// Resets the WiFi MAC
+    pub fn reset_mac(&mut self) {
+        modify_bits(radio::WIFI_RST_EN_REG, WIFI_RST_MAC, true);
+        modify_bits(radio::WIFI_RST_EN_REG, WIFI_RST_MAC, false);
+    }
+
+    fn mask(peripheral: RadioPeripheral) -> u32 {
+        match peripheral {
+            RadioPeripheral::Phy => radio::WIFI_CLK_WIFI_BT_COMMON_M,
+            RadioPeripheral::Wifi => radio::WIFI_CLK_WIFI_EN_M,
+            #[cfg(not(feature = "esp32s2"))]
+            RadioPeripheral::Bt => radio::WIFI_CLK_BT_EN_M,
+            RadioPeripheral::Rng => WIFI_CLK_RNG_EN,
         }
     }
 }
 
+fn modify_bits(reg: u32, mask: u32, set: bool) {
+    let reg = reg as *mut u32;
+
+    unsafe {
+        let bits = reg.read_volatile();
+        reg.write_volatile(if set { bits | mask } else { bits & !mask });
+    }
+}
+
 /// Controls the configuration of the chip's clocks.
 pub struct SystemClockControl {
     _private: (),
@@ -96,6 +332,7 @@ pub struct CpuControl {
 pub struct SystemParts {
     _private: (),
     pub peripheral_clock_control: PeripheralClockControl,
+    pub radio_clock_control: RadioClockControl,
     pub clock_control: SystemClockControl,
     pub cpu_control: CpuControl,
 }
@@ -116,6 +353,7 @@ impl SystemExt for SystemPeripheral {
         Self::Parts {
             _private: (),
             peripheral_clock_control: PeripheralClockControl { _private: () },
+            radio_clock_control: RadioClockControl { _private: () },
             clock_control: SystemClockControl { _private: () },
             cpu_control: CpuControl { _private: () },
         }
@@ -1,19 +1,70 @@
-use core::{intrinsics::transmute, marker::PhantomData};
+//! # System timer (SYSTIMER)
+//!
+//! The SYSTIMER is a 64-bit counter which runs from boot at
+//! [SystemTimer::TICKS_PER_SECOND], independently of the CPU frequency. It
+//! has three comparators, which are handed out as alarms and raise the
+//! `SYSTIMER_TARGET0` to `SYSTIMER_TARGET2` interrupts.
+//!
+//! An alarm fires either once, when the counter reaches a target value:
+//!
+//! ```no_run
+//! let syst = SystemTimer::new(peripherals.SYSTIMER);
+//!
+//! let alarm1 = syst.alarm1;
+//! alarm1.set_target(SystemTimer::now() + SystemTimer::TICKS_PER_SECOND * 2);
+//! alarm1.enable_interrupt();
+//! ```
+//!
+//! or periodically, after being converted with [Alarm::into_periodic]:
+//!
+//! ```no_run
+//! let alarm0 = syst.alarm0.into_periodic();
+//! alarm0.set_period(10u32.Hz());
+//! alarm0.clear_interrupt();
+//! alarm0.enable_interrupt();
+//! ```
+//!
+//! The interrupt handler has to clear the interrupt with
+//! [Alarm::clear_interrupt].
 
-use fugit::MillisDurationU32;
+use core::{intrinsics::transmute, marker::PhantomData};
 
-use crate::pac::{
-    generic::Reg,
-    systimer::{
-        target0_conf::TARGET0_CONF_SPEC,
-        target0_hi::TARGET0_HI_SPEC,
-        target0_lo::TARGET0_LO_SPEC,
+use crate::{
+    pac::{
+        generic::Reg,
+        systimer::{
+            target0_conf::TARGET0_CONF_SPEC,
+            target0_hi::TARGET0_HI_SPEC,
+            target0_lo::TARGET0_LO_SPEC,
+        },
+        SYSTIMER,
     },
-    SYSTIMER,
+    time::TimestampSource,
 };
 
+/// Largest period of an alarm in periodic mode, in ticks
+#[cfg(feature = "esp32s2")]
+const MAX_PERIOD_TICKS: u32 = 0x3fff_ffff;
+#[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+const MAX_PERIOD_TICKS: u32 = 0x3ff_ffff;
+
 // TODO this only handles unit0 of the systimer
 
+/// Let the counter advance at [SystemTimer::TICKS_PER_SECOND] whether APB_CLK
+/// is derived from the PLL (80 MHz) or from the 40 MHz XTAL
+///
+/// The ESP32-S2 only supports a 40 MHz XTAL, and never divides it for
+/// APB_CLK.
+#[cfg(feature = "esp32s2")]
+pub(crate) fn set_fixed_tick_rate() {
+    let systimer = unsafe { &*SYSTIMER::ptr() };
+
+    systimer
+        .step
+        .write(|w| unsafe { w.timer_xtal_step().bits(2).timer_pll_step().bits(1) });
+}
+
+/// System timer driver, owning the three alarms
 #[derive(Debug)]
 pub struct SystemTimer {
     _inner: SYSTIMER,
@@ -23,11 +74,15 @@ pub struct SystemTimer {
 }
 
 impl SystemTimer {
+    /// The counter runs from APB_CLK, but advances by two steps per cycle
+    /// while APB_CLK is derived from the 40 MHz XTAL instead of the PLL (see
+    /// [set_fixed_tick_rate]), so its rate does not depend on the CPU clock.
     #[cfg(feature = "esp32s2")]
-    pub const TICKS_PER_SECOND: u64 = 80_000_000; // TODO this can change when we have support for changing APB frequency
+    pub const TICKS_PER_SECOND: u64 = 80_000_000;
     #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
     pub const TICKS_PER_SECOND: u64 = 16_000_000;
 
+    /// Split the SYSTIMER into its alarms
     pub fn new(p: SYSTIMER) -> Self {
         Self {
             _inner: p,
@@ -38,6 +93,7 @@ impl SystemTimer {
     }
 
     // TODO use fugit types
+    /// The current value of the counter, in ticks since boot
     pub fn now() -> u64 {
         // This should be safe to access from multiple contexts
         // worst case scenario the second accesor ends up reading
@@ -61,11 +117,26 @@ impl SystemTimer {
     }
 }
 
+/// The SYSTIMER counts from boot, so installing it as the timestamp source
+/// does not affect its alarms
+impl TimestampSource for SystemTimer {
+    fn start(&mut self) -> u64 {
+        Self::TICKS_PER_SECOND
+    }
+
+    fn now() -> u64 {
+        SystemTimer::now()
+    }
+}
+
+/// Alarm mode: fire once when the counter reaches a target value
 #[derive(Debug)]
 pub struct Target;
+/// Alarm mode: fire periodically
 #[derive(Debug)]
 pub struct Periodic; // TODO, also impl e-h timer traits
 
+/// One of the three comparators of the SYSTIMER
 #[derive(Debug)]
 pub struct Alarm<MODE, const CHANNEL: u8> {
     _pd: PhantomData<MODE>,
@@ -77,6 +148,7 @@ impl<T, const CHANNEL: u8> Alarm<T, CHANNEL> {
         Self { _pd: PhantomData }
     }
 
+    /// Enable the interrupt of the alarm
     pub fn enable_interrupt(&self) {
         let systimer = unsafe { &*SYSTIMER::ptr() };
         match CHANNEL {
@@ -93,6 +165,24 @@ impl<T, const CHANNEL: u8> Alarm<T, CHANNEL> {
         }
     }
 
+    /// Disable the interrupt of the alarm
+    pub fn disable_interrupt(&self) {
+        let systimer = unsafe { &*SYSTIMER::ptr() };
+        match CHANNEL {
+            0 => systimer
+                .int_ena
+                .modify(|_, w| w.target0_int_ena().clear_bit()),
+            1 => systimer
+                .int_ena
+                .modify(|_, w| w.target1_int_ena().clear_bit()),
+            2 => systimer
+                .int_ena
+                .modify(|_, w| w.target2_int_ena().clear_bit()),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clear the interrupt of the alarm
     pub fn clear_interrupt(&self) {
         let systimer = unsafe { &*SYSTIMER::ptr() };
         match CHANNEL {
@@ -103,6 +193,19 @@ impl<T, const CHANNEL: u8> Alarm<T, CHANNEL> {
         }
     }
 
+    /// Whether the alarm has fired since its interrupt was last cleared
+    pub fn is_interrupt_set(&self) -> bool {
+        let systimer = unsafe { &*SYSTIMER::ptr() };
+        let int_raw = systimer.int_raw.read();
+
+        match CHANNEL {
+            0 => int_raw.target0_int_raw().bit_is_set(),
+            1 => int_raw.target1_int_raw().bit_is_set(),
+            2 => int_raw.target2_int_raw().bit_is_set(),
+            _ => unreachable!(),
+        }
+    }
+
     fn configure(
         &self,
         conf: impl FnOnce(&Reg<TARGET0_CONF_SPEC>, &Reg<TARGET0_HI_SPEC>, &Reg<TARGET0_LO_SPEC>),
@@ -182,6 +285,8 @@ impl<T, const CHANNEL: u8> Alarm<T, CHANNEL> {
 }
 
 impl<const CHANNEL: u8> Alarm<Target, CHANNEL> {
+    /// Fire once when the counter reaches `timestamp`, see
+    /// [SystemTimer::now]
     pub fn set_target(&self, timestamp: u64) {
         self.configure(|tconf, hi, lo| unsafe {
             tconf.write(|w| w.target0_period_mode().clear_bit()); // target mode
@@ -190,27 +295,35 @@ impl<const CHANNEL: u8> Alarm<Target, CHANNEL> {
         })
     }
 
+    /// Switch the alarm to periodic mode
     pub fn into_periodic(self) -> Alarm<Periodic, CHANNEL> {
         Alarm { _pd: PhantomData }
     }
 }
 
 impl<const CHANNEL: u8> Alarm<Periodic, CHANNEL> {
+    /// Fire periodically at the frequency `period`
+    ///
+    /// The period is stored in a 30-bit register on the ESP32-S2 and in a
+    /// 26-bit one on the other chips, which limits it to about 4 s on the
+    /// ESP32-C3 and ESP32-S3.
     pub fn set_period(&self, period: fugit::HertzU32) {
-        let time_period: MillisDurationU32 = period.into_duration();
-        let cycles = time_period.ticks();
+        let ticks = (SystemTimer::TICKS_PER_SECOND / period.raw() as u64) as u32;
+        assert!(ticks <= MAX_PERIOD_TICKS, "SYSTIMER period too long");
+
         self.configure(|tconf, hi, lo| unsafe {
             tconf.write(|w| {
                 w.target0_period_mode()
                     .set_bit()
                     .target0_period()
-                    .bits(cycles * (SystemTimer::TICKS_PER_SECOND as u32 / 1000))
+                    .bits(ticks)
             });
             hi.write(|w| w.timer_target0_hi().bits(0));
             lo.write(|w| w.timer_target0_lo().bits(0));
         })
     }
 
+    /// Switch the alarm to target mode
     pub fn into_target(self) -> Alarm<Target, CHANNEL> {
         Alarm { _pd: PhantomData }
     }
@@ -0,0 +1,159 @@
+//! # Global timestamps
+//!
+//! A free-running counter can be claimed as the global timestamp source with
+//! [init]. Its time can then be read from anywhere with [current_millis] and
+//! [current_micros], e.g. to timestamp log messages:
+//!
+//! ```no_run
+//! let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+//! time::init(timer_group1.timer0);
+//!
+//! // elsewhere
+//! let start = time::current_micros();
+//! ```
+//!
+//! Every general-purpose timer can be used as the source, counting from the
+//! moment it is installed, as well as the SYSTIMER on the chips which have
+//! one, counting from boot. The calibrated RTC timer can be used as well:
+//! it counts from power-up and, unlike the other timers, keeps running in
+//! light-sleep, so that timestamps stay monotonic across sleep periods:
+//!
+//! ```no_run
+//! let rtc = Rtc::new(peripherals.RTC_CNTL);
+//! time::init(&rtc);
+//! ```
+//!
+//! When the `embassy` feature is used, [embassy::init](crate::embassy::init)
+//! installs the SYSTIMER as the source unless another one has already been
+//! installed.
+//!
+//! ## Log timestamps
+//!
+//! The `defmt-timestamp` feature defines the `defmt` timestamp, and the
+//! `println-timestamp` feature provides the timestamp of the `esp-println`
+//! logger (enabled by its `timestamp` feature), so that log messages of the
+//! HAL and the application are timestamped consistently.
+
+use core::cell::Cell;
+
+use critical_section::{CriticalSection, Mutex};
+
+/// A free-running counter which can be used as the global timestamp source
+pub trait TimestampSource {
+    /// Whether the counter advances in steps short enough to time delays of
+    /// a few microseconds with it
+    const FINE_GRAINED: bool = true;
+
+    /// Start the counter and return its frequency in Hz
+    fn start(&mut self) -> u64;
+
+    /// Read the current counter value
+    ///
+    /// This does not take `self`, as the source is owned by this module once
+    /// it has been installed with [init].
+    fn now() -> u64;
+}
+
+#[derive(Clone, Copy)]
+struct Source {
+    now: fn() -> u64,
+    ticks_per_second: u64,
+    fine_grained: bool,
+}
+
+static SOURCE: Mutex<Cell<Option<Source>>> = Mutex::new(Cell::new(None));
+
+/// Claim `source` as the global timestamp source
+///
+/// # Panics
+///
+/// Panics if a timestamp source has already been installed.
+pub fn init<S: TimestampSource>(source: S) {
+    if !try_init(source) {
+        panic!("A timestamp source has already been installed");
+    }
+}
+
+fn try_init<S: TimestampSource>(mut source: S) -> bool {
+    critical_section::with(|cs| {
+        if SOURCE.borrow(cs).get().is_some() {
+            return false;
+        }
+
+        let ticks_per_second = source.start();
+        install_in(cs, S::now, ticks_per_second, S::FINE_GRAINED)
+    })
+}
+
+/// Install a counter which is already running as the timestamp source, unless
+/// one has already been installed
+pub(crate) fn install(now: fn() -> u64, ticks_per_second: u64, fine_grained: bool) -> bool {
+    critical_section::with(|cs| install_in(cs, now, ticks_per_second, fine_grained))
+}
+
+fn install_in(
+    cs: CriticalSection,
+    now: fn() -> u64,
+    ticks_per_second: u64,
+    fine_grained: bool,
+) -> bool {
+    let slot = SOURCE.borrow(cs);
+    if slot.get().is_some() {
+        return false;
+    }
+
+    slot.set(Some(Source {
+        now,
+        ticks_per_second,
+        fine_grained,
+    }));
+
+    trace!("timestamp source installed, {} ticks/s", ticks_per_second);
+
+    true
+}
+
+fn source() -> Option<Source> {
+    critical_section::with(|cs| SOURCE.borrow(cs).get())
+}
+
+/// The current counter value of the timestamp source and its frequency in Hz
+///
+/// Returns `None` if no timestamp source has been installed yet, or if it is
+/// too coarse to time short delays with, like the RTC timer.
+pub(crate) fn current_fine_grained_ticks() -> Option<(u64, u64)> {
+    source()
+        .filter(|source| source.fine_grained)
+        .map(|source| ((source.now)(), source.ticks_per_second))
+}
+
+/// The current time of the timestamp source in microseconds
+///
+/// Returns 0 if no timestamp source has been installed yet.
+pub fn current_micros() -> u64 {
+    match source() {
+        Some(source) => {
+            let ticks = (source.now)();
+            let tps = source.ticks_per_second;
+            // split the conversion so that `ticks * 1_000_000` cannot overflow
+            ticks / tps * 1_000_000 + ticks % tps * 1_000_000 / tps
+        }
+        None => 0,
+    }
+}
+
+/// The current time of the timestamp source in milliseconds
+///
+/// Returns 0 if no timestamp source has been installed yet.
+pub fn current_millis() -> u64 {
+    current_micros() / 1_000
+}
+
+#[cfg(feature = "defmt-timestamp")]
+defmt::timestamp!("{=u64:us}", current_micros());
+
+#[cfg(feature = "println-timestamp")]
+#[no_mangle]
+extern "Rust" fn _esp_println_timestamp() -> u64 {
+    current_millis()
+}
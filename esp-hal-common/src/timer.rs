@@ -1,25 +1,145 @@
 //! General-purpose timers
-
-use core::marker::PhantomData;
-
+//!
+//! With the `async` feature, [Timer] implements the `embedded-hal-async`
+//! `DelayNs` trait. The delay is driven by the timer's alarm interrupt,
+//! whose handler must call [handle_interrupt]:
+//!
+//! ```no_run
+//! interrupt::enable(pac::Interrupt::TG0_T0_LEVEL, interrupt::Priority::Priority1).unwrap();
+//!
+//! // in an async task
+//! timer0.delay_ms(10).await;
+//!
+//! #[interrupt]
+//! fn TG0_T0_LEVEL() {
+//!     timer::handle_interrupt();
+//! }
+//! ```
+//!
+//! Alarms can be awaited as well, e.g. to run a task periodically:
+//!
+//! ```no_run
+//! timer0.start_periodic(100u64.millis());
+//!
+//! loop {
+//!     timer0.wait_for_alarm().await;
+//!     // do work
+//! }
+//! ```
+//!
+//! ## Interrupt handlers
+//!
+//! With the `timer-interrupt-handlers` feature, the HAL defines the
+//! `TGn_Tm_LEVEL` interrupt handlers, which call the function registered for
+//! the timer whose alarm has fired, and then clear its interrupt:
+//!
+//! ```no_run
+//! fn on_tick() {
+//!     // runs every 100 ms
+//! }
+//!
+//! timer0.set_interrupt_handler(Some(on_tick));
+//! timer0.start_periodic(100u64.millis());
+//! interrupt::enable(pac::Interrupt::TG0_T0_LEVEL, interrupt::Priority::Priority1).unwrap();
+//! ```
+//!
+//! These handlers also wake the async operations, so [handle_interrupt] must
+//! not be called with this feature. On the ESP32, the embassy time driver
+//! defines `TG0_T0_LEVEL` itself, so the two can not be combined there.
+//!
+//! ## fugit-timer
+//!
+//! With the `fugit-timer` feature, [Timer] implements the `Timer` and `Delay`
+//! traits of [fugit-timer] at any tick rate, for crates which are generic
+//! over them. Durations and instants are converted from the tick rate of the
+//! trait to the tick rate of the counter:
+//!
+//! ```no_run
+//! let mut timer0 = timer_group0.timer0;
+//! fugit_timer::Timer::<1_000_000>::start(&mut timer0, 500.millis()).unwrap();
+//! nb::block!(fugit_timer::Timer::<1_000_000>::wait(&mut timer0)).unwrap();
+//! ```
+//!
+//! [fugit-timer]: https://docs.rs/fugit-timer/latest/fugit_timer/
+
+use core::{cell::RefCell, marker::PhantomData};
+
+use critical_section::Mutex;
 use embedded_hal::{
     timer::{Cancel, CountDown, Periodic},
     watchdog::{Watchdog, WatchdogDisable, WatchdogEnable},
 };
-use fugit::{HertzU32, MicrosDurationU64};
+use fugit::{HertzU32, MicrosDurationU64, TimerInstantU64};
 use void::Void;
 
 use crate::{
     clock::Clocks,
     pac::{timg0::RegisterBlock, TIMG0, TIMG1},
+    time::TimestampSource,
 };
 
-/// Custom timer error type
-#[derive(Debug)]
+/// Number of general-purpose timers, see [Instance::index]
+const TIMER_COUNT: usize = 4;
+
+/// Bit of `TIMG_TxUPDATE_REG` which latches the counter value, and which the
+/// hardware clears once the value is latched (except on the ESP32)
+const COUNTER_UPDATE: u32 = 1 << 31;
+
+// Indexed by `Instance::index`
+static INTERRUPT_HANDLERS: Mutex<RefCell<[Option<fn()>; TIMER_COUNT]>> =
+    Mutex::new(RefCell::new([None; TIMER_COUNT]));
+
+// Indexed by `Instance::index`, shared with the interrupt handlers so that
+// they can move the alarm on as well
+static DRIFT_FREE_ALARMS: Mutex<RefCell<[Option<DriftFreeAlarm>; TIMER_COUNT]>> =
+    Mutex::new(RefCell::new([None; TIMER_COUNT]));
+
+/// State of a timer in drift-free periodic mode, in ticks
+#[derive(Clone, Copy)]
+struct DriftFreeAlarm {
+    period: u64,
+    value: u64,
+}
+
+/// Timer-specific errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
 pub enum Error {
+    /// The timer is already running
     TimerActive,
+    /// The timer isn't running
     TimerInactive,
+    /// The alarm isn't enabled
     AlarmInactive,
+    /// The timeout can't be represented by the watchdog
+    TimeoutOutOfRange,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Error::TimerActive => "timer already running",
+            Error::TimerInactive => "timer not running",
+            Error::AlarmInactive => "alarm not enabled",
+            Error::TimeoutOutOfRange => "watchdog timeout out of range",
+        })
+    }
+}
+
+/// Clock source of the timers of a timer group
+///
+/// XTAL_CLK keeps its frequency when the APB frequency changes, e.g. due to
+/// dynamic frequency scaling or light sleep, so the timers keep counting at
+/// the same rate. The ESP32 timers can only use APB_CLK.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClockSource {
+    /// APB_CLK
+    Apb,
+    /// XTAL_CLK
+    #[cfg(not(feature = "esp32"))]
+    Xtal,
 }
 
 // A timergroup consisting of up to 2 timers (chip dependent) and a watchdog
@@ -36,6 +156,8 @@ where
 
 pub trait TimerGroupInstance {
     fn register_block() -> *const RegisterBlock;
+
+    fn id() -> usize;
 }
 
 impl TimerGroupInstance for TIMG0 {
@@ -43,6 +165,11 @@ impl TimerGroupInstance for TIMG0 {
     fn register_block() -> *const RegisterBlock {
         crate::pac::TIMG0::PTR
     }
+
+    #[inline(always)]
+    fn id() -> usize {
+        0
+    }
 }
 
 impl TimerGroupInstance for TIMG1 {
@@ -50,18 +177,33 @@ impl TimerGroupInstance for TIMG1 {
     fn register_block() -> *const RegisterBlock {
         crate::pac::TIMG1::PTR
     }
+
+    #[inline(always)]
+    fn id() -> usize {
+        1
+    }
 }
 
 impl<T> TimerGroup<T>
 where
     T: TimerGroupInstance,
 {
-    pub fn new(_timer_group: T, clocks: &Clocks) -> Self {
+    pub fn new(timer_group: T, clocks: &Clocks) -> Self {
+        Self::new_with_clock_source(timer_group, clocks, ClockSource::Apb)
+    }
+
+    /// Create the timer group with its timers running from `clock_source`
+    pub fn new_with_clock_source(
+        _timer_group: T,
+        clocks: &Clocks,
+        clock_source: ClockSource,
+    ) -> Self {
         let timer0 = Timer::new(
             Timer0 {
                 phantom: PhantomData::default(),
             },
-            clocks.apb_clock,
+            clock_source,
+            clocks,
         );
 
         #[cfg(not(feature = "esp32c3"))]
@@ -69,10 +211,11 @@ where
             Timer1 {
                 phantom: PhantomData::default(),
             },
-            clocks.apb_clock,
+            clock_source,
+            clocks,
         );
 
-        let wdt = Wdt::new();
+        let wdt = Wdt::with_apb_clock(clocks.apb_clock);
 
         Self {
             timer0,
@@ -86,7 +229,9 @@ where
 /// General-purpose timer
 pub struct Timer<T> {
     timg: T,
-    apb_clk_freq: HertzU32,
+    clock_source: ClockSource,
+    clk_freq: HertzU32,
+    divider: u32,
 }
 
 /// Timer driver
@@ -94,11 +239,23 @@ impl<T> Timer<T>
 where
     T: Instance,
 {
-    /// Create a new timer instance
-    pub fn new(timg: T, apb_clk_freq: HertzU32) -> Self {
-        // TODO: this currently assumes APB_CLK is being used, as we don't yet have a
-        //       way to select the XTAL_CLK.
-        Self { timg, apb_clk_freq }
+    /// Create a new timer instance, running from `clock_source`
+    pub fn new(mut timg: T, clock_source: ClockSource, clocks: &Clocks) -> Self {
+        let clk_freq = match clock_source {
+            ClockSource::Apb => clocks.apb_clock,
+            #[cfg(not(feature = "esp32"))]
+            ClockSource::Xtal => clocks.xtal_clock,
+        };
+
+        timg.set_clock_source(clock_source);
+        let divider = timg.divider();
+
+        Self {
+            timg,
+            clock_source,
+            clk_freq,
+            divider,
+        }
     }
 
     /// Return the raw interface to the underlying timer instance
@@ -126,15 +283,228 @@ where
         self.timg.is_interrupt_set()
     }
 
+    /// Call `handler` whenever the alarm of the timer fires, or stop calling
+    /// it with `None`
+    ///
+    /// The handler is called from the interrupt of the timer, which is
+    /// enabled or disabled accordingly. It needs the interrupt handlers of the
+    /// `timer-interrupt-handlers` feature, see the [module docs](self).
+    pub fn set_interrupt_handler(&mut self, handler: Option<fn()>) {
+        critical_section::with(|cs| {
+            INTERRUPT_HANDLERS.borrow_ref_mut(cs)[self.timg.index()] = handler;
+        });
+
+        if handler.is_some() {
+            self.timg.listen();
+        } else {
+            self.timg.unlisten();
+        }
+    }
+
     /// Read current raw timer value in timer ticks
     pub fn read_raw(&self) -> u64 {
         self.timg.read_raw()
     }
+
+    /// Load `value` into the counter, e.g. to resume a count saved before
+    /// sleep or to synchronize two timers
+    ///
+    /// The counter has 54 bits, the upper bits of `value` are ignored.
+    pub fn load_value(&mut self, value: u64) {
+        self.timg.load_value(value);
+    }
+
+    /// Start the timer, raising its interrupt once after `timeout`
+    ///
+    /// The counter is not reloaded, so the alarm does not fire again until
+    /// the timer is restarted. Whether it has fired can be checked with
+    /// [Timer::is_interrupt_set].
+    pub fn start_oneshot<D>(&mut self, timeout: D)
+    where
+        D: Into<MicrosDurationU64>,
+    {
+        let ticks = timeout_to_ticks(timeout, self.clk_freq, self.divider);
+        self.start_alarm(ticks, false);
+    }
+
+    /// Start the timer, raising its interrupt every `period`
+    ///
+    /// The counter is reloaded each time the alarm fires. This is what
+    /// [CountDown::start] does.
+    pub fn start_periodic<D>(&mut self, period: D)
+    where
+        D: Into<MicrosDurationU64>,
+    {
+        let ticks = timeout_to_ticks(period, self.clk_freq, self.divider);
+        self.start_alarm(ticks, true);
+    }
+
+    /// Start counting up from zero without an alarm, so that [Timer::now]
+    /// can be used as a monotonic clock
+    ///
+    /// The 54-bit counter takes more than ten years to wrap around, even
+    /// with the smallest prescaler.
+    pub fn start_free_running(&mut self) {
+        set_drift_free_alarm(&self.timg, None);
+
+        self.timg.set_counter_active(false);
+        self.timg.set_alarm_active(false);
+
+        self.timg.reset_counter();
+
+        self.timg.set_counter_decrementing(false);
+        self.timg.set_auto_reload(false);
+        self.timg.set_counter_active(true);
+    }
+
+    /// The time since the timer was started
+    pub fn now(&self) -> TimerInstantU64<1_000_000> {
+        let ticks = self.timg.read_raw();
+        let tick_rate = self.tick_rate();
+
+        // split the conversion so that it does not overflow
+        let micros = ticks / tick_rate * 1_000_000 + ticks % tick_rate * 1_000_000 / tick_rate;
+
+        TimerInstantU64::from_ticks(micros)
+    }
+
+    /// The time which has passed since `since`, taken from [Timer::now]
+    pub fn elapsed_since(&self, since: TimerInstantU64<1_000_000>) -> MicrosDurationU64 {
+        self.now()
+            .checked_duration_since(since)
+            .unwrap_or_else(|| MicrosDurationU64::from_ticks(0))
+    }
+
+    /// Set the prescaler dividing the source clock of the counter, from 2 to
+    /// 65536
+    ///
+    /// The divider trades the resolution of the timer against the longest
+    /// timeout it can count: with the 80 MHz APB_CLK, a divider of 80 gives
+    /// 1 µs ticks, while 65536 gives ticks of 819.2 µs and still lets the 54
+    /// bit counter run for centuries. The timeouts passed to the other
+    /// methods are converted to ticks at the new rate.
+    ///
+    /// This should only be called while the timer is stopped.
+    pub fn set_divider(&mut self, divider: u32) {
+        assert!((2..=65536).contains(&divider), "timer divider out of range");
+
+        // 65536 is written as 0
+        self.timg.set_divider(divider as u16);
+        self.divider = divider;
+    }
+
+    /// The prescaler dividing the source clock of the counter
+    pub fn divider(&self) -> u32 {
+        self.divider
+    }
+
+    /// Frequency of the source clock of the counter
+    pub fn clock_frequency(&self) -> HertzU32 {
+        self.clk_freq
+    }
+
+    /// The source clock of the counter
+    pub fn clock_source(&self) -> ClockSource {
+        self.clock_source
+    }
+
+    /// Counter ticks per second, the source clock divided by the
+    /// [divider](Timer::set_divider)
+    pub fn tick_rate(&self) -> u64 {
+        (self.clk_freq.to_Hz() / self.divider) as u64
+    }
+
+    /// Start the timer, raising its interrupt every `period` without
+    /// accumulating drift
+    ///
+    /// Instead of reloading the counter when the alarm fires, the counter
+    /// keeps running and the alarm is moved on by one period each time it is
+    /// handled with [CountDown::wait], `wait_for_alarm` or the interrupt
+    /// handlers of the `timer-interrupt-handlers` feature. The latency of the
+    /// handling therefore does not shift the following alarms. Periods which
+    /// have passed completely before the alarm was handled are skipped.
+    pub fn start_periodic_drift_free<D>(&mut self, period: D)
+    where
+        D: Into<MicrosDurationU64>,
+    {
+        let period = timeout_to_ticks(period, self.clk_freq, self.divider);
+
+        self.start_free_running();
+        self.timg.clear_interrupt();
+
+        set_drift_free_alarm(
+            &self.timg,
+            Some(DriftFreeAlarm {
+                period,
+                value: period,
+            }),
+        );
+        self.timg.load_alarm_value(period);
+        self.timg.set_alarm_active(true);
+    }
+
+    /// Count source clock cycles divided by `divider` from zero while `f`
+    /// runs, passing it the counter, and restore the configuration of the
+    /// timer afterwards
+    ///
+    /// If the timer was running, its counter continues as if it had been
+    /// counting at its own rate in the meantime. Alarms which would have
+    /// fired during `f` are missed.
+    pub(crate) fn with_counter<R>(&mut self, divider: u16, f: impl FnOnce(&T) -> R) -> R {
+        let was_active = self.timg.is_counter_active();
+        let auto_reload = self.timg.is_auto_reload();
+        let alarm_active = self.timg.is_alarm_active();
+        let value = self.timg.read_raw();
+
+        self.timg.set_counter_active(false);
+        self.timg.set_alarm_active(false);
+        self.timg.set_auto_reload(false);
+        self.timg.set_divider(divider);
+        self.timg.reset_counter();
+        self.timg.set_counter_active(true);
+
+        let result = f(&self.timg);
+
+        self.timg.set_counter_active(false);
+        let elapsed = self.timg.read_raw() * divider as u64 / self.divider as u64;
+
+        // 65536 is written as 0
+        self.timg.set_divider(self.divider as u16);
+        self.timg
+            .load_value(if was_active { value + elapsed } else { value });
+        self.timg.set_auto_reload(auto_reload);
+        self.timg.set_alarm_active(alarm_active);
+        self.timg.set_counter_active(was_active);
+
+        result
+    }
+
+    fn start_alarm(&mut self, ticks: u64, auto_reload: bool) {
+        set_drift_free_alarm(&self.timg, None);
+
+        self.timg.set_counter_active(false);
+        self.timg.set_alarm_active(false);
+
+        self.timg.reset_counter();
+        self.timg.clear_interrupt();
+
+        self.timg.load_alarm_value(ticks);
+
+        self.timg.set_counter_decrementing(false);
+        self.timg.set_auto_reload(auto_reload);
+        self.timg.set_counter_active(true);
+        self.timg.set_alarm_active(true);
+    }
 }
 
 /// Timer peripheral instance
 pub trait Instance {
-    fn reset_counter(&mut self);
+    fn reset_counter(&mut self) {
+        self.load_value(0);
+    }
+
+    /// Load `value` into the 54-bit counter
+    fn load_value(&mut self, value: u64);
 
     fn set_counter_active(&mut self, state: bool);
 
@@ -144,6 +514,8 @@ pub trait Instance {
 
     fn set_auto_reload(&mut self, auto_reload: bool);
 
+    fn is_auto_reload(&self) -> bool;
+
     fn set_alarm_active(&mut self, state: bool);
 
     fn is_alarm_active(&self) -> bool;
@@ -160,7 +532,14 @@ pub trait Instance {
 
     fn divider(&self) -> u32;
 
+    fn set_divider(&mut self, divider: u16);
+
+    fn set_clock_source(&mut self, source: ClockSource);
+
     fn is_interrupt_set(&self) -> bool;
+
+    /// Index of the timer among all timers of all timer groups
+    fn index(&self) -> usize;
 }
 
 pub struct Timer0<TG> {
@@ -172,12 +551,20 @@ impl<TG> Instance for Timer0<TG>
 where
     TG: TimerGroupInstance,
 {
-    fn reset_counter(&mut self) {
+    fn load_value(&mut self, value: u64) {
+        let value = value & 0x3F_FFFF_FFFF_FFFF;
+        let high = (value >> 32) as u32;
+        let low = (value & 0xFFFF_FFFF) as u32;
+
         let reg_block = unsafe { &*TG::register_block() };
 
-        reg_block.t0loadlo.write(|w| unsafe { w.load_lo().bits(0) });
+        reg_block
+            .t0loadlo
+            .write(|w| unsafe { w.load_lo().bits(low) });
 
-        reg_block.t0loadhi.write(|w| unsafe { w.load_hi().bits(0) });
+        reg_block
+            .t0loadhi
+            .write(|w| unsafe { w.load_hi().bits(high) });
 
         reg_block.t0load.write(|w| unsafe { w.load().bits(1) });
     }
@@ -210,6 +597,12 @@ where
             .modify(|_, w| w.autoreload().bit(auto_reload));
     }
 
+    fn is_auto_reload(&self) -> bool {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block.t0config.read().autoreload().bit_is_set()
+    }
+
     fn set_alarm_active(&mut self, state: bool) {
         let reg_block = unsafe { &*TG::register_block() };
 
@@ -267,12 +660,16 @@ where
     fn read_raw(&self) -> u64 {
         let reg_block = unsafe { &*TG::register_block() };
 
-        reg_block.t0update.write(|w| unsafe { w.bits(0) });
-
-        let value_lo = reg_block.t0lo.read().bits() as u64;
-        let value_hi = (reg_block.t0hi.read().bits() as u64) << 32;
-
-        (value_lo | value_hi) as u64
+        reg_block
+            .t0update
+            .write(|w| unsafe { w.bits(COUNTER_UPDATE) });
+        #[cfg(not(feature = "esp32"))]
+        while reg_block.t0update.read().bits() & COUNTER_UPDATE != 0 {}
+
+        read_latched(
+            || reg_block.t0hi.read().bits(),
+            || reg_block.t0lo.read().bits(),
+        )
     }
 
     fn divider(&self) -> u32 {
@@ -291,11 +688,36 @@ where
         }
     }
 
+    fn set_divider(&mut self, divider: u16) {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block
+            .t0config
+            .modify(|_, w| unsafe { w.divider().bits(divider) });
+    }
+
+    #[cfg(not(feature = "esp32"))]
+    fn set_clock_source(&mut self, source: ClockSource) {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block
+            .t0config
+            .modify(|_, w| w.use_xtal().bit(source == ClockSource::Xtal));
+    }
+
+    // the ESP32 timers always run from APB_CLK
+    #[cfg(feature = "esp32")]
+    fn set_clock_source(&mut self, _source: ClockSource) {}
+
     fn is_interrupt_set(&self) -> bool {
         let reg_block = unsafe { &*TG::register_block() };
 
         reg_block.int_raw_timers.read().t0_int_raw().bit_is_set()
     }
+
+    fn index(&self) -> usize {
+        TG::id() * 2
+    }
 }
 
 #[cfg(not(feature = "esp32c3"))]
@@ -309,12 +731,20 @@ impl<TG> Instance for Timer1<TG>
 where
     TG: TimerGroupInstance,
 {
-    fn reset_counter(&mut self) {
+    fn load_value(&mut self, value: u64) {
+        let value = value & 0x3F_FFFF_FFFF_FFFF;
+        let high = (value >> 32) as u32;
+        let low = (value & 0xFFFF_FFFF) as u32;
+
         let reg_block = unsafe { &*TG::register_block() };
 
-        reg_block.t1loadlo.write(|w| unsafe { w.load_lo().bits(0) });
+        reg_block
+            .t1loadlo
+            .write(|w| unsafe { w.load_lo().bits(low) });
 
-        reg_block.t1loadhi.write(|w| unsafe { w.load_hi().bits(0) });
+        reg_block
+            .t1loadhi
+            .write(|w| unsafe { w.load_hi().bits(high) });
 
         reg_block.t1load.write(|w| unsafe { w.load().bits(1) });
     }
@@ -347,6 +777,12 @@ where
             .modify(|_, w| w.autoreload().bit(auto_reload));
     }
 
+    fn is_auto_reload(&self) -> bool {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block.t1config.read().autoreload().bit_is_set()
+    }
+
     fn set_alarm_active(&mut self, state: bool) {
         let reg_block = unsafe { &*TG::register_block() };
 
@@ -404,12 +840,16 @@ where
     fn read_raw(&self) -> u64 {
         let reg_block = unsafe { &*TG::register_block() };
 
-        reg_block.t1update.write(|w| unsafe { w.bits(0) });
-
-        let value_lo = reg_block.t1lo.read().bits() as u64;
-        let value_hi = (reg_block.t1hi.read().bits() as u64) << 32;
-
-        (value_lo | value_hi) as u64
+        reg_block
+            .t1update
+            .write(|w| unsafe { w.bits(COUNTER_UPDATE) });
+        #[cfg(not(feature = "esp32"))]
+        while reg_block.t1update.read().bits() & COUNTER_UPDATE != 0 {}
+
+        read_latched(
+            || reg_block.t1hi.read().bits(),
+            || reg_block.t1lo.read().bits(),
+        )
     }
 
     fn divider(&self) -> u32 {
@@ -428,11 +868,83 @@ where
         }
     }
 
+    fn set_divider(&mut self, divider: u16) {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block
+            .t1config
+            .modify(|_, w| unsafe { w.divider().bits(divider) });
+    }
+
+    #[cfg(not(feature = "esp32"))]
+    fn set_clock_source(&mut self, source: ClockSource) {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block
+            .t1config
+            .modify(|_, w| w.use_xtal().bit(source == ClockSource::Xtal));
+    }
+
+    // the ESP32 timers always run from APB_CLK
+    #[cfg(feature = "esp32")]
+    fn set_clock_source(&mut self, _source: ClockSource) {}
+
     fn is_interrupt_set(&self) -> bool {
         let reg_block = unsafe { &*TG::register_block() };
 
         reg_block.int_raw_timers.read().t1_int_raw().bit_is_set()
     }
+
+    fn index(&self) -> usize {
+        TG::id() * 2 + 1
+    }
+}
+
+/// Combine the halves of a latched counter value
+///
+/// The counter is latched in the clock domain of the timer, so the latch can
+/// still be updating while it is read. The high half is read again after the
+/// low half, and both are read again if it changed in between, so that the
+/// halves never come from different latches.
+fn read_latched(hi: impl Fn() -> u32, lo: impl Fn() -> u32) -> u64 {
+    loop {
+        let value_hi = hi();
+        let value_lo = lo();
+
+        if hi() == value_hi {
+            return ((value_hi as u64) << 32) | value_lo as u64;
+        }
+    }
+}
+
+fn set_drift_free_alarm(timer: &impl Instance, alarm: Option<DriftFreeAlarm>) {
+    critical_section::with(|cs| {
+        DRIFT_FREE_ALARMS.borrow_ref_mut(cs)[timer.index()] = alarm;
+    });
+}
+
+/// In drift-free periodic mode, move the alarm of `timer` on to the next
+/// period which has not passed yet and arm it, returning `false` in the other
+/// modes
+fn advance_alarm(timer: &mut impl Instance) -> bool {
+    critical_section::with(|cs| {
+        let mut alarms = DRIFT_FREE_ALARMS.borrow_ref_mut(cs);
+        let alarm = match &mut alarms[timer.index()] {
+            Some(alarm) => alarm,
+            None => return false,
+        };
+
+        let now = timer.read_raw();
+        alarm.value += alarm.period;
+        if alarm.value <= now {
+            alarm.value += (now - alarm.value) / alarm.period * alarm.period + alarm.period;
+        }
+
+        timer.load_alarm_value(alarm.value);
+        timer.set_alarm_active(true);
+
+        true
+    })
 }
 
 fn timeout_to_ticks<T, F>(timeout: T, clock: F, divider: u32) -> u64
@@ -441,14 +953,13 @@ where
     F: Into<HertzU32>,
 {
     let timeout: MicrosDurationU64 = timeout.into();
-    let micros = timeout.to_micros();
+    let micros = timeout.to_micros() as u128;
 
     let clock: HertzU32 = clock.into();
+    let divisor = divider as u128 * 1_000_000;
 
-    // TODO can we get this to not use doubles/floats
-    let period = 1_000_000f64 / (clock.to_Hz() as f64 / divider as f64); // micros
-
-    (micros as f64 / period) as u64
+    // round up, so that the timer never fires early
+    ((micros * clock.to_Hz() as u128 + divisor - 1) / divisor) as u64
 }
 
 impl<T> CountDown for Timer<T>
@@ -461,21 +972,7 @@ where
     where
         Time: Into<Self::Time>,
     {
-        self.timg.set_counter_active(false);
-        self.timg.set_alarm_active(false);
-
-        self.timg.reset_counter();
-
-        // TODO: this currently assumes APB_CLK is being used, as we don't yet have a
-        //       way to select the XTAL_CLK.
-        // TODO: can we cache the divider (only get it on initialization)?
-        let ticks = timeout_to_ticks(timeout, self.apb_clk_freq, self.timg.divider());
-        self.timg.load_alarm_value(ticks);
-
-        self.timg.set_counter_decrementing(false);
-        self.timg.set_auto_reload(true);
-        self.timg.set_counter_active(true);
-        self.timg.set_alarm_active(true);
+        self.start_periodic(timeout);
     }
 
     fn wait(&mut self) -> nb::Result<(), Void> {
@@ -485,7 +982,9 @@ where
 
         if self.timg.is_interrupt_set() {
             self.timg.clear_interrupt();
-            self.timg.set_alarm_active(true);
+            if !advance_alarm(&mut self.timg) {
+                self.timg.set_alarm_active(true);
+            }
 
             Ok(())
         } else {
@@ -515,9 +1014,449 @@ where
 
 impl<T> Periodic for Timer<T> where T: Instance {}
 
+/// One-shot timer counting at `TIMER_HZ`, with the instants wrapping around
+/// after `u32::MAX` ticks
+#[cfg(feature = "fugit-timer")]
+impl<T, const TIMER_HZ: u32> fugit_timer::Timer<TIMER_HZ> for Timer<T>
+where
+    T: Instance,
+{
+    type Error = Error;
+
+    fn now(&mut self) -> fugit::TimerInstantU32<TIMER_HZ> {
+        let ticks = self.timg.read_raw() as u128 * TIMER_HZ as u128 / self.tick_rate() as u128;
+
+        fugit::TimerInstantU32::from_ticks(ticks as u32)
+    }
+
+    fn start(&mut self, duration: fugit::TimerDurationU32<TIMER_HZ>) -> Result<(), Error> {
+        // round up at both steps, so that the alarm never fires early
+        let micros = (duration.ticks() as u64 * 1_000_000 + TIMER_HZ as u64 - 1) / TIMER_HZ as u64;
+        let ticks = timeout_to_ticks(
+            MicrosDurationU64::micros(micros),
+            self.clk_freq,
+            self.divider,
+        );
+        self.start_alarm(ticks, false);
+
+        Ok(())
+    }
+
+    fn cancel(&mut self) -> Result<(), Error> {
+        Cancel::cancel(self)
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Error> {
+        if !self.timg.is_counter_active() {
+            return Err(nb::Error::Other(Error::TimerInactive));
+        }
+
+        if self.timg.is_interrupt_set() {
+            self.timg.clear_interrupt();
+
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+/// Busy-waits for the one-shot alarm of the timer, which raises its interrupt
+#[cfg(feature = "fugit-timer")]
+impl<T, const TIMER_HZ: u32> fugit_timer::Delay<TIMER_HZ> for Timer<T>
+where
+    T: Instance,
+{
+    type Error = Error;
+
+    fn delay(&mut self, duration: fugit::TimerDurationU32<TIMER_HZ>) -> Result<(), Error> {
+        fugit_timer::Timer::start(self, duration)?;
+        nb::block!(fugit_timer::Timer::wait(self))?;
+        self.timg.set_counter_active(false);
+
+        Ok(())
+    }
+}
+
+/// Busy-waits for the one-shot alarm of the timer, which raises its interrupt
+#[cfg(feature = "eh1")]
+impl<T> embedded_hal_1::delay::blocking::DelayUs for Timer<T>
+where
+    T: Instance,
+{
+    type Error = core::convert::Infallible;
+
+    fn delay_us(&mut self, us: u32) -> Result<(), Self::Error> {
+        if us == 0 {
+            return Ok(());
+        }
+
+        self.start_oneshot(MicrosDurationU64::micros(us as u64));
+        while !self.timg.is_interrupt_set() {}
+
+        self.timg.clear_interrupt();
+        self.timg.set_counter_active(false);
+
+        Ok(())
+    }
+}
+
+impl<TG> TimestampSource for Timer<Timer0<TG>>
+where
+    TG: TimerGroupInstance,
+{
+    fn start(&mut self) -> u64 {
+        self.start_free_running();
+        self.tick_rate()
+    }
+
+    fn now() -> u64 {
+        Timer0::<TG> {
+            phantom: PhantomData,
+        }
+        .read_raw()
+    }
+}
+
+#[cfg(not(feature = "esp32c3"))]
+impl<TG> TimestampSource for Timer<Timer1<TG>>
+where
+    TG: TimerGroupInstance,
+{
+    fn start(&mut self) -> u64 {
+        self.start_free_running();
+        self.tick_rate()
+    }
+
+    fn now() -> u64 {
+        Timer1::<TG> {
+            phantom: PhantomData,
+        }
+        .read_raw()
+    }
+}
+
+#[cfg(all(
+    feature = "timer-interrupt-handlers",
+    feature = "embassy",
+    feature = "esp32"
+))]
+compile_error!(
+    "The `timer-interrupt-handlers` feature can not be combined with the embassy time driver \
+     on the ESP32, as both define the `TG0_T0_LEVEL` interrupt handler"
+);
+
+#[cfg(feature = "timer-interrupt-handlers")]
+mod handlers {
+    use super::*;
+    use crate::macros::interrupt;
+
+    fn on_interrupt(timer: &mut impl Instance) {
+        #[cfg(feature = "async")]
+        let awaited = asynch::service(timer);
+        #[cfg(not(feature = "async"))]
+        let awaited = false;
+
+        dispatch(timer, awaited);
+    }
+
+    /// Call the handler registered for `timer` if its alarm has fired
+    ///
+    /// If an async operation waits for the alarm, it clears the interrupt and
+    /// re-arms the alarm itself once it has seen the interrupt.
+    fn dispatch(timer: &mut impl Instance, awaited: bool) {
+        let handler = critical_section::with(|cs| INTERRUPT_HANDLERS.borrow_ref(cs)[timer.index()]);
+
+        if let Some(handler) = handler {
+            if timer.is_interrupt_set() {
+                handler();
+
+                if !awaited {
+                    timer.clear_interrupt();
+                    if !advance_alarm(timer) && timer.is_auto_reload() {
+                        timer.set_alarm_active(true);
+                    }
+                }
+            }
+        }
+    }
+
+    #[interrupt]
+    fn TG0_T0_LEVEL() {
+        on_interrupt(&mut Timer0::<TIMG0> {
+            phantom: PhantomData,
+        });
+    }
+
+    #[cfg(not(feature = "esp32c3"))]
+    #[interrupt]
+    fn TG0_T1_LEVEL() {
+        on_interrupt(&mut Timer1::<TIMG0> {
+            phantom: PhantomData,
+        });
+    }
+
+    #[interrupt]
+    fn TG1_T0_LEVEL() {
+        on_interrupt(&mut Timer0::<TIMG1> {
+            phantom: PhantomData,
+        });
+    }
+
+    #[cfg(not(feature = "esp32c3"))]
+    #[interrupt]
+    fn TG1_T1_LEVEL() {
+        on_interrupt(&mut Timer1::<TIMG1> {
+            phantom: PhantomData,
+        });
+    }
+}
+
+#[cfg(feature = "async")]
+pub use asynch::handle_interrupt;
+
+#[cfg(feature = "async")]
+mod asynch {
+    use core::{
+        convert::Infallible,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use super::*;
+    use crate::asynch::{NbFuture, WakerSlot};
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NO_ALARM: AtomicBool = AtomicBool::new(false);
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NO_WAKER: WakerSlot = WakerSlot::new();
+
+    // Indexed by `Instance::index`
+    static ALARM_PENDING: [AtomicBool; TIMER_COUNT] = [NO_ALARM; TIMER_COUNT];
+    static ALARM_WAKERS: [WakerSlot; TIMER_COUNT] = [NO_WAKER; TIMER_COUNT];
+
+    /// Wake the async delays and alarm waits whose alarm has fired
+    ///
+    /// Must be called from the `TGn_Tm_LEVEL` interrupt handler of every timer
+    /// which is used for async delays or [Timer::wait_for_alarm]. Timers
+    /// without a pending async operation are left untouched.
+    pub fn handle_interrupt() {
+        service(&mut Timer0::<TIMG0> {
+            phantom: PhantomData,
+        });
+        #[cfg(not(feature = "esp32c3"))]
+        service(&mut Timer1::<TIMG0> {
+            phantom: PhantomData,
+        });
+        service(&mut Timer0::<TIMG1> {
+            phantom: PhantomData,
+        });
+        #[cfg(not(feature = "esp32c3"))]
+        service(&mut Timer1::<TIMG1> {
+            phantom: PhantomData,
+        });
+    }
+
+    /// Wake the async operation waiting for the alarm of `timer`, returning
+    /// whether there is one
+    pub(super) fn service(timer: &mut impl Instance) -> bool {
+        let index = timer.index();
+
+        if ALARM_PENDING[index].load(Ordering::Acquire) && timer.is_interrupt_set() {
+            // The level interrupt stays asserted until the future clears it,
+            // so mask it in the meantime
+            timer.unlisten();
+            ALARM_WAKERS[index].wake();
+
+            true
+        } else {
+            false
+        }
+    }
+
+    impl<T> Timer<T>
+    where
+        T: Instance,
+    {
+        /// Wait for `duration` without blocking the executor
+        ///
+        /// Unlike [DelayNs](embedded_hal_async::delay::DelayNs), this is not
+        /// limited to about 4 s.
+        pub async fn delay_async<D>(&mut self, duration: D)
+        where
+            D: Into<MicrosDurationU64>,
+        {
+            let ticks = timeout_to_ticks(duration, self.clk_freq, self.divider);
+            self.delay_ticks(ticks).await;
+        }
+
+        /// Wait until the alarm started with [Timer::start_oneshot],
+        /// [Timer::start_periodic] or [Timer::start_periodic_drift_free]
+        /// fires, and clear its interrupt
+        ///
+        /// A periodic alarm is re-armed, so this can be awaited in a loop.
+        pub async fn wait_for_alarm(&mut self) {
+            self.wait_for_interrupt().await;
+
+            self.timg.clear_interrupt();
+            if !advance_alarm(&mut self.timg) && self.timg.is_auto_reload() {
+                self.timg.set_alarm_active(true);
+            }
+        }
+
+        async fn delay_ticks(&mut self, ticks: u64) {
+            if ticks == 0 {
+                return;
+            }
+
+            set_drift_free_alarm(&self.timg, None);
+
+            self.timg.set_counter_active(false);
+            self.timg.set_alarm_active(false);
+            self.timg.reset_counter();
+            self.timg.load_alarm_value(ticks);
+            self.timg.set_counter_decrementing(false);
+            self.timg.set_auto_reload(false);
+            self.timg.clear_interrupt();
+            self.timg.set_counter_active(true);
+            self.timg.set_alarm_active(true);
+
+            self.wait_for_interrupt().await;
+
+            self.timg.clear_interrupt();
+            self.timg.set_counter_active(false);
+        }
+
+        async fn wait_for_interrupt(&mut self) {
+            let index = self.timg.index();
+
+            ALARM_PENDING[index].store(true, Ordering::Release);
+            self.timg.listen();
+
+            let timg = &self.timg;
+            NbFuture::new(&ALARM_WAKERS[index], || -> nb::Result<(), Infallible> {
+                if timg.is_interrupt_set() {
+                    Ok(())
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            })
+            .await
+            .ok();
+
+            ALARM_PENDING[index].store(false, Ordering::Release);
+            self.timg.unlisten();
+        }
+    }
+
+    impl<T> embedded_hal_async::delay::DelayNs for Timer<T>
+    where
+        T: Instance,
+    {
+        async fn delay_ns(&mut self, ns: u32) {
+            // round up, so that the delay is never shorter than requested
+            let ticks = (ns as u64 * self.tick_rate() + 999_999_999) / 1_000_000_000;
+            self.delay_ticks(ticks).await;
+        }
+    }
+}
+
+/// A stage of the MWDT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WdtStage {
+    Stage0,
+    Stage1,
+    Stage2,
+    Stage3,
+}
+
+impl WdtStage {
+    const ALL: [WdtStage; 4] = [
+        WdtStage::Stage0,
+        WdtStage::Stage1,
+        WdtStage::Stage2,
+        WdtStage::Stage3,
+    ];
+}
+
+/// Behavior of an MWDT stage if it times out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WdtStageAction {
+    /// Do nothing, the stage is disabled
+    Off         = 0,
+    /// Raise the interrupt of the watchdog, see [Wdt::listen]
+    Interrupt   = 1,
+    /// Reset the CPUs
+    ResetCpu    = 2,
+    /// Reset the digital core
+    ResetSystem = 3,
+}
+
+/// Actions and timeouts of the MWDT stages
+///
+/// The stages run one after another: once a stage times out, its action is
+/// taken and the next stage starts. Feeding the watchdog restarts the first
+/// stage:
+///
+/// ```no_run
+/// let config = WdtConfig::default()
+///     .stage(WdtStage::Stage0, WdtStageAction::Interrupt, 1u64.secs())
+///     .stage(WdtStage::Stage1, WdtStageAction::ResetSystem, 500u64.millis());
+///
+/// wdt.start_with_config(config).unwrap();
+/// wdt.listen();
+/// ```
+///
+/// The default configuration resets the digital core when the first stage
+/// times out, its timeout is set by [WatchdogEnable::start].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WdtConfig {
+    actions: [WdtStageAction; 4],
+    timeouts: [MicrosDurationU64; 4],
+}
+
+impl Default for WdtConfig {
+    fn default() -> Self {
+        Self {
+            actions: [
+                WdtStageAction::ResetSystem,
+                WdtStageAction::Off,
+                WdtStageAction::Off,
+                WdtStageAction::Off,
+            ],
+            timeouts: [MicrosDurationU64::from_ticks(0); 4],
+        }
+    }
+}
+
+impl WdtConfig {
+    /// Take `action` when `stage` times out after `timeout`
+    pub fn stage<T>(mut self, stage: WdtStage, action: WdtStageAction, timeout: T) -> Self
+    where
+        T: Into<MicrosDurationU64>,
+    {
+        self.actions[stage as usize] = action;
+        self.timeouts[stage as usize] = timeout.into();
+        self
+    }
+
+    /// The action taken when `stage` times out
+    pub fn action(&self, stage: WdtStage) -> WdtStageAction {
+        self.actions[stage as usize]
+    }
+
+    /// The timeout of `stage`
+    pub fn timeout(&self, stage: WdtStage) -> MicrosDurationU64 {
+        self.timeouts[stage as usize]
+    }
+}
+
 /// Watchdog timer
 pub struct Wdt<TG> {
     phantom: PhantomData<TG>,
+    config: WdtConfig,
+    apb_clk_freq: HertzU32,
 }
 
 /// Watchdog driver
@@ -526,12 +1465,114 @@ where
     TG: TimerGroupInstance,
 {
     /// Create a new watchdog timer instance
+    ///
+    /// This assumes the default APB_CLK of 80 MHz, the watchdog handed out
+    /// by [TimerGroup::new] uses the actual frequency.
     pub fn new() -> Self {
+        Self::with_apb_clock(HertzU32::MHz(80))
+    }
+
+    pub(crate) fn with_apb_clock(apb_clk_freq: HertzU32) -> Self {
         Self {
             phantom: PhantomData::default(),
+            config: WdtConfig::default(),
+            apb_clk_freq,
         }
     }
 
+    /// Start the watchdog with the actions and timeouts of all stages set by
+    /// `config`
+    ///
+    /// The prescaler of the watchdog is chosen to fit the longest timeout,
+    /// which can be more than a month. Returns
+    /// [Error::TimeoutOutOfRange] if it is even longer, leaving the watchdog
+    /// unchanged.
+    pub fn start_with_config(&mut self, config: WdtConfig) -> Result<(), Error> {
+        let prescaler = self.prescaler(&config)?;
+
+        self.config = config;
+        self.apply_config(prescaler);
+
+        Ok(())
+    }
+
+    /// The configuration the watchdog was last started with
+    pub fn config(&self) -> WdtConfig {
+        self.config
+    }
+
+    /// Enable the interrupt of the watchdog, which is raised by the stages
+    /// with [WdtStageAction::Interrupt]
+    pub fn listen(&mut self) {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block
+            .int_ena_timers
+            .modify(|_, w| w.wdt_int_ena().set_bit());
+    }
+
+    /// Disable the interrupt of the watchdog
+    pub fn unlisten(&mut self) {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block
+            .int_ena_timers
+            .modify(|_, w| w.wdt_int_ena().clear_bit());
+    }
+
+    /// Clear the interrupt of the watchdog
+    pub fn clear_interrupt(&mut self) {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block
+            .int_clr_timers
+            .write(|w| w.wdt_int_clr().set_bit());
+    }
+
+    /// Whether a stage with [WdtStageAction::Interrupt] has timed out
+    pub fn is_interrupt_set(&self) -> bool {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block.int_raw_timers.read().wdt_int_raw().bit_is_set()
+    }
+
+    /// Enable/disable the flash boot protection of the watchdog
+    ///
+    /// The ROM bootloader enables it on the MWDT of timer group 0 to reset
+    /// the chip if booting from flash hangs, so it has to be disabled by
+    /// applications which do not feed the watchdog.
+    pub fn set_flashboot_protection(&mut self, enable: bool) {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block
+            .wdtwprotect
+            .write(|w| unsafe { w.wdt_wkey().bits(0x50D8_3AA1u32) });
+
+        reg_block
+            .wdtconfig0
+            .modify(|_, w| w.wdt_flashboot_mod_en().bit(enable));
+
+        #[cfg(feature = "esp32c3")]
+        reg_block
+            .wdtconfig0
+            .modify(|_, w| w.wdt_conf_update_en().set_bit());
+
+        reg_block
+            .wdtwprotect
+            .write(|w| unsafe { w.wdt_wkey().bits(0u32) });
+    }
+
+    /// Whether the flash boot protection of the watchdog is enabled
+    pub fn is_flashboot_protection_enabled(&self) -> bool {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block
+            .wdtconfig0
+            .read()
+            .wdt_flashboot_mod_en()
+            .bit_is_set()
+    }
+
     fn set_wdt_enabled(&mut self, enabled: bool) {
         let reg_block = unsafe { &*TG::register_block() };
 
@@ -565,9 +1606,34 @@ where
     }
 
     fn set_timeout(&mut self, timeout: MicrosDurationU64) {
-        let timeout_raw = (timeout.to_nanos() * 10 / 125) as u32;
+        let mut config = self.config;
+        config.timeouts[0] = timeout;
 
+        self.start_with_config(config).unwrap();
+    }
+
+    /// The smallest prescaler for which the hold registers of all stages can
+    /// hold their timeout
+    fn prescaler(&self, config: &WdtConfig) -> Result<u16, Error> {
+        let longest = WdtStage::ALL
+            .iter()
+            .map(|&stage| config.timeout(stage).to_nanos())
+            .max()
+            .unwrap_or(0);
+        let cycles = longest as u128 * self.apb_clk_freq.to_Hz() as u128 / 1_000_000_000;
+        let prescaler = cycles / (u32::MAX as u128 + 1) + 1;
+
+        if prescaler > u16::MAX as u128 {
+            return Err(Error::TimeoutOutOfRange);
+        }
+
+        Ok(prescaler as u16)
+    }
+
+    /// Write the configuration to the watchdog and start it
+    fn apply_config(&mut self, prescaler: u16) {
         let reg_block = unsafe { &*TG::register_block() };
+        let actions = self.config.actions;
 
         reg_block
             .wdtwprotect
@@ -575,27 +1641,47 @@ where
 
         reg_block
             .wdtconfig1
-            .write(|w| unsafe { w.wdt_clk_prescale().bits(1) });
-
-        reg_block
-            .wdtconfig2
-            .write(|w| unsafe { w.wdt_stg0_hold().bits(timeout_raw) });
+            .write(|w| unsafe { w.wdt_clk_prescale().bits(prescaler) });
+
+        let tick_rate = self.apb_clk_freq.to_Hz() as u128 / prescaler as u128;
+
+        for stage in WdtStage::ALL {
+            let nanos = self.config.timeout(stage).to_nanos() as u128;
+            let timeout_raw = (nanos * tick_rate / 1_000_000_000) as u32;
+
+            unsafe {
+                match stage {
+                    WdtStage::Stage0 => reg_block
+                        .wdtconfig2
+                        .write(|w| w.wdt_stg0_hold().bits(timeout_raw)),
+                    WdtStage::Stage1 => reg_block
+                        .wdtconfig3
+                        .write(|w| w.wdt_stg1_hold().bits(timeout_raw)),
+                    WdtStage::Stage2 => reg_block
+                        .wdtconfig4
+                        .write(|w| w.wdt_stg2_hold().bits(timeout_raw)),
+                    WdtStage::Stage3 => reg_block
+                        .wdtconfig5
+                        .write(|w| w.wdt_stg3_hold().bits(timeout_raw)),
+                }
+            }
+        }
 
         reg_block.wdtconfig0.write(|w| unsafe {
             w.wdt_en()
                 .bit(true)
                 .wdt_stg0()
-                .bits(3)
+                .bits(actions[0] as u8)
                 .wdt_cpu_reset_length()
                 .bits(1)
                 .wdt_sys_reset_length()
                 .bits(1)
                 .wdt_stg1()
-                .bits(0)
+                .bits(actions[1] as u8)
                 .wdt_stg2()
-                .bits(0)
+                .bits(actions[2] as u8)
                 .wdt_stg3()
-                .bits(0)
+                .bits(actions[3] as u8)
         });
 
         #[cfg(feature = "esp32c3")]
@@ -618,6 +1704,7 @@ where
     }
 }
 
+/// Panics if the timeout is out of range, see [Wdt::start_with_config]
 impl<TG> WatchdogEnable for Wdt<TG>
 where
     TG: TimerGroupInstance,
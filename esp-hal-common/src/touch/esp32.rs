@@ -0,0 +1,263 @@
+//! Touch sensor of the ESP32
+//!
+//! The touch channels T0 to T9 are numbered independently of their GPIOs. The
+//! bits of the channels 8 and 9 are swapped in the enable masks and in the
+//! RTC IO_MUX, but not in the threshold and measurement registers.
+
+use super::TouchConfig;
+use crate::pac::{RTCIO, RTC_CNTL, SENS};
+
+/// Cycles of RTC_FAST_CLK to wait for a pad to power up before measuring it
+const TOUCH_POWER_ON_WAIT_CYCLES: u8 = 0xff;
+
+/// Charge and discharge slope of the pads, `RTC_IO_TOUCH_PADn_DAC`
+const TOUCH_SLOPE: u8 = 7;
+/// `RTC_IO_TOUCH_DREFH` value selecting an upper reference voltage of 2.7 V
+const TOUCH_HVOLT_2V7: u8 = 3;
+/// `RTC_IO_TOUCH_DREFL` value selecting a lower reference voltage of 0.5 V
+const TOUCH_LVOLT_0V5: u8 = 0;
+/// `RTC_IO_TOUCH_DRANGE` value attenuating the upper reference voltage by
+/// 0.5 V
+const TOUCH_HVOLT_ATTEN_0V5: u8 = 2;
+
+pub(super) const DEFAULT_MEASUREMENT_CYCLES: u16 = 0x7fff;
+pub(super) const DEFAULT_SLEEP_CYCLES: u16 = 0x1000;
+
+/// Run `$body` with `$reg` bound to the RTC IO_MUX register of `$channel`
+macro_rules! touch_pad_reg {
+    ($channel:expr, |$reg:ident| $body:expr) => {{
+        let rtcio = unsafe { &*RTCIO::ptr() };
+
+        match $channel {
+            0 => {
+                let $reg = &rtcio.touch_pad0;
+                $body
+            }
+            1 => {
+                let $reg = &rtcio.touch_pad1;
+                $body
+            }
+            2 => {
+                let $reg = &rtcio.touch_pad2;
+                $body
+            }
+            3 => {
+                let $reg = &rtcio.touch_pad3;
+                $body
+            }
+            4 => {
+                let $reg = &rtcio.touch_pad4;
+                $body
+            }
+            5 => {
+                let $reg = &rtcio.touch_pad5;
+                $body
+            }
+            6 => {
+                let $reg = &rtcio.touch_pad6;
+                $body
+            }
+            7 => {
+                let $reg = &rtcio.touch_pad7;
+                $body
+            }
+            8 => {
+                let $reg = &rtcio.touch_pad9;
+                $body
+            }
+            9 => {
+                let $reg = &rtcio.touch_pad8;
+                $body
+            }
+            _ => unreachable!(),
+        }
+    }};
+}
+
+pub(super) fn channel(gpio: u8) -> u8 {
+    match gpio {
+        4 => 0,
+        0 => 1,
+        2 => 2,
+        15 => 3,
+        13 => 4,
+        12 => 5,
+        14 => 6,
+        27 => 7,
+        33 => 8,
+        32 => 9,
+        _ => unreachable!(),
+    }
+}
+
+/// The bit of `channel` in the enable masks of `SENS_SAR_TOUCH_ENABLE`
+fn mask(channel: u8) -> u16 {
+    match channel {
+        8 => 1 << 9,
+        9 => 1 << 8,
+        channel => 1 << channel,
+    }
+}
+
+pub(super) fn init(config: &TouchConfig) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let rtcio = unsafe { &*RTCIO::ptr() };
+    let sens = unsafe { &*SENS::ptr() };
+
+    // stop the FSM and forget the pads enabled before
+    rtc_cntl
+        .state0
+        .modify(|_, w| w.touch_slp_timer_en().clear_bit());
+    sens.sar_touch_enable.write(|w| unsafe {
+        w.touch_pad_worken()
+            .bits(0)
+            .touch_pad_outen1()
+            .bits(0)
+            .touch_pad_outen2()
+            .bits(0)
+    });
+    sens.sar_touch_ctrl2
+        .modify(|_, w| w.touch_meas_en_clr().set_bit());
+
+    sens.sar_touch_ctrl1.modify(|_, w| unsafe {
+        w.touch_meas_delay()
+            .bits(config.measurement_cycles)
+            .touch_xpd_wait()
+            .bits(TOUCH_POWER_ON_WAIT_CYCLES)
+            // a pad counts as touched when its reading falls below its
+            // threshold
+            .touch_out_sel()
+            .clear_bit()
+            // any touched pad of SET1 wakes up the chip
+            .touch_out_1en()
+            .set_bit()
+    });
+
+    sens.sar_touch_ctrl2.modify(|_, w| unsafe {
+        w.touch_sleep_cycles()
+            .bits(config.sleep_cycles)
+            // measurements are started by the timer
+            .touch_start_fsm_en()
+            .set_bit()
+            .touch_start_en()
+            .clear_bit()
+            .touch_start_force()
+            .clear_bit()
+    });
+
+    rtcio.touch_cfg.modify(|_, w| unsafe {
+        w.touch_drefh()
+            .bits(TOUCH_HVOLT_2V7)
+            .touch_drefl()
+            .bits(TOUCH_LVOLT_0V5)
+            .touch_drange()
+            .bits(TOUCH_HVOLT_ATTEN_0V5)
+    });
+
+    rtc_cntl
+        .state0
+        .modify(|_, w| w.touch_slp_timer_en().set_bit());
+}
+
+pub(super) fn deinit() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .state0
+        .modify(|_, w| w.touch_slp_timer_en().clear_bit());
+}
+
+pub(super) fn enable_pad(channel: u8) {
+    let sens = unsafe { &*SENS::ptr() };
+
+    touch_pad_reg!(channel, |pad| pad.modify(|_, w| unsafe {
+        w.xpd()
+            .set_bit()
+            .tie_opt()
+            .clear_bit()
+            .dac()
+            .bits(TOUCH_SLOPE)
+    }));
+
+    sens.sar_touch_enable.modify(|r, w| unsafe {
+        w.touch_pad_worken()
+            .bits(r.touch_pad_worken().bits() | mask(channel))
+    });
+}
+
+pub(super) fn read_raw(channel: u8) -> u32 {
+    let sens = unsafe { &*SENS::ptr() };
+
+    let reading = match channel {
+        0 => sens.sar_touch_out1.read().touch_meas_out0().bits(),
+        1 => sens.sar_touch_out1.read().touch_meas_out1().bits(),
+        2 => sens.sar_touch_out2.read().touch_meas_out2().bits(),
+        3 => sens.sar_touch_out2.read().touch_meas_out3().bits(),
+        4 => sens.sar_touch_out3.read().touch_meas_out4().bits(),
+        5 => sens.sar_touch_out3.read().touch_meas_out5().bits(),
+        6 => sens.sar_touch_out4.read().touch_meas_out6().bits(),
+        7 => sens.sar_touch_out4.read().touch_meas_out7().bits(),
+        8 => sens.sar_touch_out5.read().touch_meas_out8().bits(),
+        9 => sens.sar_touch_out5.read().touch_meas_out9().bits(),
+        _ => unreachable!(),
+    };
+
+    reading as u32
+}
+
+/// Let `channel` wake up the chip from sleep, when its reading falls below
+/// `threshold`
+///
+/// Any number of pads can wake up the chip, each with its own threshold.
+pub(crate) fn enable_wakeup(channel: u8, threshold: u32) {
+    let sens = unsafe { &*SENS::ptr() };
+
+    set_threshold(channel, threshold.min(u16::MAX as u32) as u16);
+
+    sens.sar_touch_enable.modify(|r, w| unsafe {
+        w.touch_pad_outen1()
+            .bits(r.touch_pad_outen1().bits() | mask(channel))
+    });
+
+    // forget the touches detected before
+    sens.sar_touch_ctrl2
+        .modify(|_, w| w.touch_meas_en_clr().set_bit());
+}
+
+fn set_threshold(channel: u8, threshold: u16) {
+    let sens = unsafe { &*SENS::ptr() };
+
+    match channel {
+        0 => sens
+            .sar_touch_thres1
+            .modify(|_, w| unsafe { w.touch_out_th0().bits(threshold) }),
+        1 => sens
+            .sar_touch_thres1
+            .modify(|_, w| unsafe { w.touch_out_th1().bits(threshold) }),
+        2 => sens
+            .sar_touch_thres2
+            .modify(|_, w| unsafe { w.touch_out_th2().bits(threshold) }),
+        3 => sens
+            .sar_touch_thres2
+            .modify(|_, w| unsafe { w.touch_out_th3().bits(threshold) }),
+        4 => sens
+            .sar_touch_thres3
+            .modify(|_, w| unsafe { w.touch_out_th4().bits(threshold) }),
+        5 => sens
+            .sar_touch_thres3
+            .modify(|_, w| unsafe { w.touch_out_th5().bits(threshold) }),
+        6 => sens
+            .sar_touch_thres4
+            .modify(|_, w| unsafe { w.touch_out_th6().bits(threshold) }),
+        7 => sens
+            .sar_touch_thres4
+            .modify(|_, w| unsafe { w.touch_out_th7().bits(threshold) }),
+        8 => sens
+            .sar_touch_thres5
+            .modify(|_, w| unsafe { w.touch_out_th8().bits(threshold) }),
+        9 => sens
+            .sar_touch_thres5
+            .modify(|_, w| unsafe { w.touch_out_th9().bits(threshold) }),
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,179 @@
+//! Touch sensor of the ESP32-S2 and ESP32-S3, which share its register
+//! layout
+//!
+//! The number of a touch channel is the number of its GPIO.
+
+use super::{ProximityChannel, TouchConfig};
+use crate::pac::{RTC_CNTL, SENS};
+
+/// Cycles of RTC_FAST_CLK to wait for a pad to power up before measuring it
+const TOUCH_POWER_ON_WAIT_CYCLES: u8 = 0xff;
+
+/// `SENS_TOUCH_DATA_SEL` value selecting the raw readings
+const TOUCH_DATA_RAW: u8 = 0;
+/// `SENS_TOUCH_DATA_SEL` value selecting the filtered readings
+const TOUCH_DATA_SMOOTH: u8 = 2;
+
+pub(super) const DEFAULT_MEASUREMENT_CYCLES: u16 = 500;
+pub(super) const DEFAULT_SLEEP_CYCLES: u16 = 0xf;
+
+pub(super) fn channel(gpio: u8) -> u8 {
+    gpio
+}
+
+pub(super) fn init(config: &TouchConfig) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let sens = unsafe { &*SENS::ptr() };
+
+    // stop the FSM and forget the pads enabled before
+    rtc_cntl
+        .touch_ctrl2
+        .modify(|_, w| w.touch_slp_timer_en().clear_bit());
+    rtc_cntl
+        .touch_scan_ctrl
+        .modify(|_, w| unsafe { w.touch_scan_pad_map().bits(0) });
+    sens.sar_touch_conf
+        .modify(|_, w| unsafe { w.touch_outen().bits(0).touch_status_clr().set_bit() });
+
+    rtc_cntl.touch_ctrl1.modify(|_, w| unsafe {
+        w.touch_meas_num()
+            .bits(config.measurement_cycles)
+            .touch_sleep_cycles()
+            .bits(config.sleep_cycles)
+    });
+
+    rtc_cntl
+        .touch_filter_ctrl
+        .modify(|_, w| w.touch_filter_en().set_bit());
+
+    rtc_cntl.touch_ctrl2.modify(|_, w| unsafe {
+        w.touch_xpd_wait()
+            .bits(TOUCH_POWER_ON_WAIT_CYCLES)
+            // measurements are started by the timer
+            .touch_start_force()
+            .clear_bit()
+            .touch_clkgate_en()
+            .set_bit()
+    });
+
+    rtc_cntl
+        .touch_ctrl2
+        .modify(|_, w| unsafe { w.touch_timer_force_done().bits(3) });
+    rtc_cntl
+        .touch_ctrl2
+        .modify(|_, w| unsafe { w.touch_timer_force_done().bits(0) });
+    rtc_cntl
+        .touch_ctrl2
+        .modify(|_, w| w.touch_slp_timer_en().set_bit());
+}
+
+pub(super) fn deinit() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl.touch_ctrl2.modify(|_, w| {
+        w.touch_slp_timer_en()
+            .clear_bit()
+            .touch_clkgate_en()
+            .clear_bit()
+    });
+}
+
+pub(super) fn enable_pad(channel: u8) {
+    let mask = 1 << channel;
+
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let sens = unsafe { &*SENS::ptr() };
+
+    sens.sar_touch_conf
+        .modify(|r, w| unsafe { w.touch_outen().bits(r.touch_outen().bits() | mask) });
+    rtc_cntl.touch_scan_ctrl.modify(|r, w| unsafe {
+        w.touch_scan_pad_map()
+            .bits(r.touch_scan_pad_map().bits() | mask)
+    });
+}
+
+pub(super) fn read_raw(channel: u8) -> u32 {
+    read(channel, TOUCH_DATA_RAW)
+}
+
+pub(super) fn read_smooth(channel: u8) -> u32 {
+    read(channel, TOUCH_DATA_SMOOTH)
+}
+
+fn read(channel: u8, data_sel: u8) -> u32 {
+    let sens = unsafe { &*SENS::ptr() };
+
+    sens.sar_touch_conf
+        .modify(|_, w| unsafe { w.touch_data_sel().bits(data_sel) });
+
+    // the status registers of the pads 1 to 14 are contiguous
+    let status = sens
+        .sar_touch_status1
+        .as_ptr()
+        .wrapping_add(channel as usize - 1);
+
+    unsafe { status.read_volatile() & 0x3f_ffff }
+}
+
+/// Use `channel` as the pad which wakes up the chip from sleep, when its
+/// reading rises `threshold` above its baseline
+///
+/// Only one pad can wake up the chip.
+pub(crate) fn enable_wakeup(channel: u8, threshold: u32) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl.touch_slp_thres.modify(|_, w| unsafe {
+        w.touch_slp_pad()
+            .bits(channel)
+            .touch_slp_th()
+            .bits(threshold)
+            .touch_slp_approach_en()
+            .clear_bit()
+    });
+
+    // restart the baseline of the sleep pad
+    rtc_cntl
+        .touch_approach
+        .modify(|_, w| w.touch_slp_channel_clr().set_bit());
+}
+
+pub(super) fn set_proximity_measurements(measurements: u8) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .touch_approach
+        .modify(|_, w| unsafe { w.touch_approach_meas_time().bits(measurements) });
+}
+
+pub(super) fn proximity_measurements() -> u8 {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .touch_approach
+        .read()
+        .touch_approach_meas_time()
+        .bits()
+}
+
+pub(super) fn set_proximity_pad(channel: ProximityChannel, pad: u8) {
+    let sens = unsafe { &*SENS::ptr() };
+
+    sens.sar_touch_conf.modify(|_, w| unsafe {
+        match channel {
+            ProximityChannel::Channel0 => w.touch_approach_pad0().bits(pad),
+            ProximityChannel::Channel1 => w.touch_approach_pad1().bits(pad),
+            ProximityChannel::Channel2 => w.touch_approach_pad2().bits(pad),
+        }
+    });
+}
+
+pub(super) fn proximity_count(channel: ProximityChannel) -> u8 {
+    let sens = unsafe { &*SENS::ptr() };
+    let status = sens.sar_touch_appr_status.read();
+
+    match channel {
+        ProximityChannel::Channel0 => status.touch_approach_pad0_cnt().bits(),
+        ProximityChannel::Channel1 => status.touch_approach_pad1_cnt().bits(),
+        ProximityChannel::Channel2 => status.touch_approach_pad2_cnt().bits(),
+    }
+}
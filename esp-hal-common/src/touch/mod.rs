@@ -0,0 +1,211 @@
+//! # Touch sensor
+//!
+//! The touch sensor measures the capacitance of its pads in the background:
+//! its FSM scans the enabled pads, charging and discharging each of them
+//! repeatedly, and stores the result as the reading of the pad. A finger near
+//! a pad raises its capacitance.
+//!
+//! On the ESP32, the reading is the number of charge and discharge cycles
+//! during a configurable time, so a touch lowers it. On the ESP32-S2 and
+//! ESP32-S3, the reading is the time a configurable number of cycles took, so
+//! a touch raises it.
+//!
+//! The touch sensor is part of the RTC and is handed out as [Rtc::touch]:
+//!
+//! ```no_run
+//! let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+//! let mut touch = TouchSensor::new(&mut rtc.touch, TouchConfig::default());
+//!
+//! let pad = touch.enable_pad(io.pins.gpio4);
+//! let reading = pad.read_raw();
+//! ```
+//!
+//! ## Proximity sensing (ESP32-S2 and ESP32-S3)
+//!
+//! Up to three pads can be used as proximity channels. The reading of a
+//! proximity pad accumulates a configurable number of measurements, which
+//! makes it sensitive enough to detect a hand approaching the pad instead of
+//! touching it:
+//!
+//! ```no_run
+//! let pad = touch.enable_pad(io.pins.gpio1);
+//! touch.set_proximity_measurements(16);
+//! touch.enable_proximity(ProximityChannel::Channel0, &pad);
+//!
+//! if touch.proximity_done(ProximityChannel::Channel0) {
+//!     let accumulated = pad.read_raw();
+//! }
+//! ```
+//!
+//! ## Wakeup from sleep
+//!
+//! Pads can wake up the chip from sleep when they are touched, see
+//! [TouchWakeupSource](crate::rtc_cntl::sleep::TouchWakeupSource).
+//!
+//! [Rtc::touch]: crate::Rtc::touch
+
+pub(crate) use self::ll::enable_wakeup;
+use crate::{
+    gpio::{RtcPin, TouchPin},
+    into_ref,
+    peripherals::{Peripheral, PeripheralRef},
+};
+
+#[cfg_attr(feature = "esp32", path = "esp32.rs")]
+#[cfg_attr(any(feature = "esp32s2", feature = "esp32s3"), path = "esp32s2.rs")]
+mod ll;
+
+/// The touch sensor
+pub struct Touch {
+    _private: (),
+}
+
+impl Touch {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Peripheral for Touch {
+    type P = Touch;
+
+    unsafe fn clone_unchecked(&mut self) -> Self::P {
+        Touch::new()
+    }
+}
+
+/// Touch sensor configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TouchConfig {
+    /// Number of charge and discharge cycles per measurement of a pad. On the
+    /// ESP32, the duration of a measurement in cycles of RTC_FAST_CLK
+    /// instead.
+    pub measurement_cycles: u16,
+    /// Cycles of RTC_SLOW_CLK between two scans of the enabled pads
+    pub sleep_cycles: u16,
+}
+
+impl Default for TouchConfig {
+    fn default() -> Self {
+        Self {
+            measurement_cycles: ll::DEFAULT_MEASUREMENT_CYCLES,
+            sleep_cycles: ll::DEFAULT_SLEEP_CYCLES,
+        }
+    }
+}
+
+/// A proximity channel of the touch sensor
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProximityChannel {
+    Channel0,
+    Channel1,
+    Channel2,
+}
+
+/// Touch sensor driver
+pub struct TouchSensor<'d> {
+    _touch: PeripheralRef<'d, Touch>,
+}
+
+impl<'d> TouchSensor<'d> {
+    /// Configure the touch sensor and start scanning
+    ///
+    /// No pads are scanned until they are enabled with
+    /// [TouchSensor::enable_pad].
+    pub fn new(touch: impl Peripheral<P = Touch> + 'd, config: TouchConfig) -> Self {
+        into_ref!(touch);
+
+        ll::init(&config);
+
+        Self { _touch: touch }
+    }
+
+    /// Add `pin` to the pads scanned by the touch sensor
+    pub fn enable_pad<PIN>(&mut self, pin: impl Peripheral<P = PIN> + 'd) -> TouchPad<'d, PIN>
+    where
+        PIN: TouchPin + RtcPin,
+    {
+        into_ref!(pin);
+
+        pin.rtc_set_config(false, true, 0);
+
+        ll::enable_pad(ll::channel(pin.number()));
+
+        TouchPad { pin }
+    }
+
+    /// Set the number of measurements which are accumulated into the reading
+    /// of each proximity pad
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    pub fn set_proximity_measurements(&mut self, measurements: u8) {
+        ll::set_proximity_measurements(measurements);
+    }
+
+    /// Use `pad` as the proximity pad of `channel`
+    ///
+    /// Its reading then accumulates the number of measurements set with
+    /// [TouchSensor::set_proximity_measurements].
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    pub fn enable_proximity<PIN>(&mut self, channel: ProximityChannel, pad: &TouchPad<'d, PIN>)
+    where
+        PIN: TouchPin + RtcPin,
+    {
+        ll::set_proximity_pad(channel, pad.number());
+    }
+
+    /// Stop using `channel` as a proximity channel
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    pub fn disable_proximity(&mut self, channel: ProximityChannel) {
+        // pad 0 is not a touch pad
+        ll::set_proximity_pad(channel, 0);
+    }
+
+    /// Number of measurements accumulated so far into the reading of the
+    /// proximity pad of `channel`
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    pub fn proximity_count(&self, channel: ProximityChannel) -> u8 {
+        ll::proximity_count(channel)
+    }
+
+    /// Whether the reading of the proximity pad of `channel` has accumulated
+    /// all of its measurements
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    pub fn proximity_done(&self, channel: ProximityChannel) -> bool {
+        self.proximity_count(channel) >= ll::proximity_measurements()
+    }
+}
+
+impl<'d> Drop for TouchSensor<'d> {
+    fn drop(&mut self) {
+        ll::deinit();
+    }
+}
+
+/// A pad scanned by the touch sensor
+pub struct TouchPad<'d, PIN> {
+    pin: PeripheralRef<'d, PIN>,
+}
+
+impl<'d, PIN> TouchPad<'d, PIN>
+where
+    PIN: TouchPin + RtcPin,
+{
+    /// The number of the touch channel of the pad
+    pub(crate) fn number(&self) -> u8 {
+        ll::channel(self.pin.number())
+    }
+
+    /// The latest reading of the pad
+    pub fn read_raw(&self) -> u32 {
+        ll::read_raw(self.number())
+    }
+
+    /// The latest reading of the pad, filtered to suppress noise
+    #[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+    pub fn read_smooth(&self) -> u32 {
+        ll::read_smooth(self.number())
+    }
+}
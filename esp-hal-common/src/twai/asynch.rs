@@ -0,0 +1,83 @@
+//! Interrupt-serviced receive queue and the wakers of the async API
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use embedded_can::StandardId;
+
+use super::{Error, EspTwaiFrame, RX_QUEUE_SIZE};
+use crate::asynch::WakerSlot;
+
+pub(super) static RX_QUEUE: Mutex<RefCell<RxQueue>> = Mutex::new(RefCell::new(RxQueue::new()));
+pub(super) static RX_WAKER: WakerSlot = WakerSlot::new();
+pub(super) static TX_WAKER: WakerSlot = WakerSlot::new();
+
+const EMPTY_FRAME: EspTwaiFrame = EspTwaiFrame {
+    id: embedded_can::Id::Standard(StandardId::ZERO),
+    dlc: 0,
+    data: [0; 8],
+    is_remote: false,
+};
+
+/// Ring buffer of received frames, filled by the interrupt handler
+pub(super) struct RxQueue {
+    frames: [EspTwaiFrame; RX_QUEUE_SIZE],
+    head: usize,
+    len: usize,
+    overrun: bool,
+}
+
+impl RxQueue {
+    const fn new() -> Self {
+        Self {
+            frames: [EMPTY_FRAME; RX_QUEUE_SIZE],
+            head: 0,
+            len: 0,
+            overrun: false,
+        }
+    }
+
+    /// Append a frame; if the queue is full the frame is dropped and an
+    /// overrun is reported by the next [RxQueue::pop]
+    pub(super) fn push(&mut self, frame: EspTwaiFrame) {
+        if self.len == RX_QUEUE_SIZE {
+            self.overrun = true;
+            return;
+        }
+
+        self.frames[(self.head + self.len) % RX_QUEUE_SIZE] = frame;
+        self.len += 1;
+    }
+
+    /// Take the oldest frame, reporting a pending overrun first
+    pub(super) fn pop(&mut self) -> Option<Result<EspTwaiFrame, Error>> {
+        if self.overrun {
+            self.overrun = false;
+            return Some(Err(Error::Overrun));
+        }
+
+        if self.len == 0 {
+            return None;
+        }
+
+        let frame = self.frames[self.head];
+        self.head = (self.head + 1) % RX_QUEUE_SIZE;
+        self.len -= 1;
+
+        Some(Ok(frame))
+    }
+
+    pub(super) fn set_overrun(&mut self) {
+        self.overrun = true;
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+        self.overrun = false;
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+}
@@ -0,0 +1,205 @@
+//! TWAI acceptance filters
+//!
+//! The acceptance filter decides which received frames are stored in the
+//! receive FIFO. It consists of a 32-bit acceptance code and a 32-bit mask,
+//! and is used either as one long filter (single filter mode) or as two
+//! shorter filters (dual filter mode), in which case a frame is accepted if it
+//! matches either of them.
+//!
+//! In the constructors below, a bit set in a `mask` means that the
+//! corresponding bit of the frame must match the given value; cleared mask
+//! bits are "don't care".
+
+use embedded_can::{ExtendedId, StandardId};
+
+/// How the acceptance code and mask registers are interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FilterType {
+    Single,
+    Dual,
+}
+
+/// An acceptance filter configuration
+pub trait Filter {
+    /// The filter mode this filter requires
+    const FILTER_TYPE: FilterType;
+
+    /// Returns the values of the acceptance code registers (first four bytes)
+    /// and the acceptance mask registers (last four bytes)
+    fn to_registers(&self) -> [u8; 8];
+}
+
+/// Builds the register values from the code bits and the bits to compare;
+/// the hardware mask marks the bits to ignore
+fn registers(code: [u8; 4], care: [u8; 4]) -> [u8; 8] {
+    [
+        code[0], code[1], code[2], code[3], !care[0], !care[1], !care[2], !care[3],
+    ]
+}
+
+/// Single filter for standard frames
+///
+/// Compares the 11-bit identifier, the RTR bit and the first two data bytes.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SingleStandardFilter {
+    code: [u8; 4],
+    care: [u8; 4],
+}
+
+impl SingleStandardFilter {
+    /// Accept standard frames whose identifier matches `id` in all bits set
+    /// in the 11-bit `mask`
+    pub fn new(id: StandardId, mask: u16) -> Self {
+        let id = id.as_raw();
+
+        Self {
+            code: [(id >> 3) as u8, ((id & 0x7) << 5) as u8, 0, 0],
+            care: [(mask >> 3) as u8, ((mask & 0x7) << 5) as u8, 0, 0],
+        }
+    }
+
+    /// Only accept remote frames (`true`) or data frames (`false`)
+    pub fn with_rtr(mut self, rtr: bool) -> Self {
+        self.code[1] = (self.code[1] & !(1 << 4)) | ((rtr as u8) << 4);
+        self.care[1] |= 1 << 4;
+        self
+    }
+
+    /// Additionally compare the first two data bytes with `data` in all bits
+    /// set in `mask`
+    pub fn with_data(mut self, data: [u8; 2], mask: [u8; 2]) -> Self {
+        self.code[2..].copy_from_slice(&data);
+        self.care[2..].copy_from_slice(&mask);
+        self
+    }
+}
+
+impl Filter for SingleStandardFilter {
+    const FILTER_TYPE: FilterType = FilterType::Single;
+
+    fn to_registers(&self) -> [u8; 8] {
+        registers(self.code, self.care)
+    }
+}
+
+/// Single filter for extended frames
+///
+/// Compares the 29-bit identifier and the RTR bit.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SingleExtendedFilter {
+    code: [u8; 4],
+    care: [u8; 4],
+}
+
+impl SingleExtendedFilter {
+    /// Accept extended frames whose identifier matches `id` in all bits set
+    /// in the 29-bit `mask`
+    pub fn new(id: ExtendedId, mask: u32) -> Self {
+        let id = id.as_raw();
+
+        Self {
+            code: (id << 3).to_be_bytes(),
+            care: ((mask & 0x1fff_ffff) << 3).to_be_bytes(),
+        }
+    }
+
+    /// Only accept remote frames (`true`) or data frames (`false`)
+    pub fn with_rtr(mut self, rtr: bool) -> Self {
+        self.code[3] = (self.code[3] & !(1 << 2)) | ((rtr as u8) << 2);
+        self.care[3] |= 1 << 2;
+        self
+    }
+}
+
+impl Filter for SingleExtendedFilter {
+    const FILTER_TYPE: FilterType = FilterType::Single;
+
+    fn to_registers(&self) -> [u8; 8] {
+        registers(self.code, self.care)
+    }
+}
+
+/// Two filters for standard frames
+///
+/// Each filter compares the 11-bit identifier; a frame matching either of
+/// them is accepted.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DualStandardFilter {
+    code: [u8; 4],
+    care: [u8; 4],
+}
+
+impl DualStandardFilter {
+    /// Accept standard frames matching `(id, mask)` of either filter
+    pub fn new(first: (StandardId, u16), second: (StandardId, u16)) -> Self {
+        let (id1, mask1) = (first.0.as_raw(), first.1);
+        let (id2, mask2) = (second.0.as_raw(), second.1);
+
+        Self {
+            code: [
+                (id1 >> 3) as u8,
+                ((id1 & 0x7) << 5) as u8,
+                (id2 >> 3) as u8,
+                ((id2 & 0x7) << 5) as u8,
+            ],
+            care: [
+                (mask1 >> 3) as u8,
+                ((mask1 & 0x7) << 5) as u8,
+                (mask2 >> 3) as u8,
+                ((mask2 & 0x7) << 5) as u8,
+            ],
+        }
+    }
+}
+
+impl Filter for DualStandardFilter {
+    const FILTER_TYPE: FilterType = FilterType::Dual;
+
+    fn to_registers(&self) -> [u8; 8] {
+        registers(self.code, self.care)
+    }
+}
+
+/// Two filters for extended frames
+///
+/// In dual filter mode only the 16 most significant bits of an extended
+/// identifier (bits 28 to 13) can be compared.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DualExtendedFilter {
+    code: [u8; 4],
+    care: [u8; 4],
+}
+
+impl DualExtendedFilter {
+    /// Accept extended frames whose identifier bits 28 to 13 match
+    /// `(id, mask)` of either filter
+    ///
+    /// The masks apply to the full 29-bit identifier, of which only bits 28 to
+    /// 13 are used.
+    pub fn new(first: (ExtendedId, u32), second: (ExtendedId, u32)) -> Self {
+        let upper = |value: u32| ((value >> 13) as u16).to_be_bytes();
+
+        let id1 = upper(first.0.as_raw());
+        let id2 = upper(second.0.as_raw());
+        let mask1 = upper(first.1);
+        let mask2 = upper(second.1);
+
+        Self {
+            code: [id1[0], id1[1], id2[0], id2[1]],
+            care: [mask1[0], mask1[1], mask2[0], mask2[1]],
+        }
+    }
+}
+
+impl Filter for DualExtendedFilter {
+    const FILTER_TYPE: FilterType = FilterType::Dual;
+
+    fn to_registers(&self) -> [u8; 8] {
+        registers(self.code, self.care)
+    }
+}
@@ -0,0 +1,743 @@
+//! Two-wire Automotive Interface (TWAI) driver
+//!
+//! The TWAI controller is compatible with ISO 11898-1 (CAN 2.0). It requires
+//! an external transceiver to connect to the bus.
+//!
+//! The driver is configured while the controller is in reset mode
+//! ([TwaiConfiguration]), and then started ([Twai]) to take part in bus
+//! communication. Frames are sent and received through the `embedded-can`
+//! traits, so protocol stacks written against them (e.g. CANopen or J1939)
+//! can be used unchanged.
+//!
+//! Besides normal operation, the controller supports a listen-only mode for
+//! monitoring a bus without acknowledging frames, and a self-test mode in
+//! which transmitted frames need no acknowledgement and are received back,
+//! see [TwaiMode].
+//!
+//! Once [Twai::listen] has been called and the `TWAI` interrupt calls
+//! [handle_interrupt], received frames are moved into a queue of
+//! [RX_QUEUE_SIZE] frames as soon as they arrive, so they are not lost while
+//! the application is busy. This is also required by the async API
+//! ([Twai::receive_async] and [Twai::transmit_async]).
+//!
+//! # Example
+//!
+//! ```no_run
+//! let mut config = TwaiConfiguration::new(
+//!     peripherals.TWAI,
+//!     io.pins.gpio2,
+//!     io.pins.gpio3,
+//!     &mut system.peripheral_clock_control,
+//!     BaudRate::B500K,
+//! );
+//!
+//! // Only accept standard frames with identifier 0x123
+//! config.set_filter(SingleStandardFilter::new(
+//!     StandardId::new(0x123).unwrap(),
+//!     0x7ff,
+//! ));
+//!
+//! let mut twai = config.start();
+//! let frame = block!(twai.receive()).unwrap();
+//! ```
+
+use embedded_can::{ErrorKind, ExtendedId, Frame, Id, StandardId};
+
+use self::{
+    asynch::{RX_QUEUE, RX_WAKER, TX_WAKER},
+    filter::{Filter, FilterType},
+};
+use crate::{
+    asynch::NbFuture,
+    gpio::{InputPin, OutputPin},
+    pac::{twai::RegisterBlock, TWAI},
+    peripherals::{Peripheral, PeripheralRef},
+    system::PeripheralClockControl,
+    types::{InputSignal, OutputSignal},
+};
+
+mod asynch;
+pub mod filter;
+
+/// Number of received frames buffered by [handle_interrupt]
+pub const RX_QUEUE_SIZE: usize = 32;
+
+/// Bit timing of the TWAI bus
+///
+/// The bit time is divided into `1 + tseg_1 + tseg_2` time quanta, each
+/// lasting `baud_rate_prescaler` APB clock cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimingConfig {
+    /// APB clock cycles per time quantum; must be even
+    pub baud_rate_prescaler: u16,
+    /// Maximum number of time quanta a bit may be shortened or lengthened by
+    /// for resynchronization (1 to 4)
+    pub sync_jump_width: u8,
+    /// Time quanta before the sample point, excluding the sync segment (1 to
+    /// 16)
+    pub tseg_1: u8,
+    /// Time quanta after the sample point (1 to 8)
+    pub tseg_2: u8,
+    /// Sample each bit three times instead of once
+    pub triple_sample: bool,
+}
+
+/// Standard bit rates, for an APB clock of 80 MHz
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BaudRate {
+    B125K,
+    B250K,
+    B500K,
+    B1000K,
+    Custom(TimingConfig),
+}
+
+impl BaudRate {
+    const fn timing(self) -> TimingConfig {
+        // 20 time quanta per bit, sample point at 80%
+        let baud_rate_prescaler = match self {
+            BaudRate::B125K => 32,
+            BaudRate::B250K => 16,
+            BaudRate::B500K => 8,
+            BaudRate::B1000K => 4,
+            BaudRate::Custom(timing) => return timing,
+        };
+
+        TimingConfig {
+            baud_rate_prescaler,
+            sync_jump_width: 3,
+            tseg_1: 15,
+            tseg_2: 4,
+            triple_sample: false,
+        }
+    }
+}
+
+/// Operating mode of the TWAI controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TwaiMode {
+    /// Take part in bus communication, acknowledging received frames
+    Normal,
+    /// Transmitted frames don't need to be acknowledged by another node and
+    /// are received back, so the controller can be tested without a second
+    /// node on the bus
+    SelfTest,
+    /// Receive frames without acknowledging them or sending error frames, so
+    /// the bus can be monitored without affecting it. Transmitting is not
+    /// possible.
+    ListenOnly,
+}
+
+/// TWAI-specific errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The controller has entered the bus-off state after too many errors
+    BusOff,
+    /// Received frames were lost because the receive FIFO was full
+    Overrun,
+    /// Frames cannot be transmitted in [TwaiMode::ListenOnly]
+    ListenOnly,
+}
+
+impl embedded_can::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::BusOff => ErrorKind::Other,
+            Error::Overrun => ErrorKind::Overrun,
+            Error::ListenOnly => ErrorKind::Other,
+        }
+    }
+}
+
+/// A TWAI frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EspTwaiFrame {
+    id: Id,
+    dlc: usize,
+    data: [u8; 8],
+    is_remote: bool,
+}
+
+impl Frame for EspTwaiFrame {
+    fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        if data.len() > 8 {
+            return None;
+        }
+
+        let mut frame_data = [0u8; 8];
+        frame_data[..data.len()].copy_from_slice(data);
+
+        Some(Self {
+            id: id.into(),
+            dlc: data.len(),
+            data: frame_data,
+            is_remote: false,
+        })
+    }
+
+    fn new_remote(id: impl Into<Id>, dlc: usize) -> Option<Self> {
+        if dlc > 8 {
+            return None;
+        }
+
+        Some(Self {
+            id: id.into(),
+            dlc,
+            data: [0; 8],
+            is_remote: true,
+        })
+    }
+
+    fn is_extended(&self) -> bool {
+        matches!(self.id, Id::Extended(_))
+    }
+
+    fn is_remote_frame(&self) -> bool {
+        self.is_remote
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn dlc(&self) -> usize {
+        self.dlc
+    }
+
+    fn data(&self) -> &[u8] {
+        if self.is_remote {
+            &[]
+        } else {
+            &self.data[..self.dlc]
+        }
+    }
+}
+
+/// A TWAI controller in reset mode, ready to be configured
+pub struct TwaiConfiguration<'d, T> {
+    peripheral: PeripheralRef<'d, T>,
+    mode: TwaiMode,
+}
+
+impl<'d, T> TwaiConfiguration<'d, T>
+where
+    T: Instance,
+{
+    /// Create a new TWAI configuration, using `tx_pin` and `rx_pin` to connect
+    /// to the transceiver
+    ///
+    /// All frames are accepted until a filter is configured with
+    /// [TwaiConfiguration::set_filter].
+    pub fn new<TX: OutputPin, RX: InputPin>(
+        peripheral: impl Peripheral<P = T> + 'd,
+        tx_pin: impl Peripheral<P = TX> + 'd,
+        rx_pin: impl Peripheral<P = RX> + 'd,
+        peripheral_clock_control: &mut PeripheralClockControl,
+        baud_rate: BaudRate,
+    ) -> Self {
+        crate::into_ref!(peripheral, tx_pin, rx_pin);
+        peripheral_clock_control.enable(crate::system::Peripheral::Twai);
+
+        let mut config = TwaiConfiguration {
+            peripheral,
+            mode: TwaiMode::Normal,
+        };
+        config.enter_reset_mode();
+        config.set_mode(TwaiMode::Normal);
+
+        // Use the PeliCAN register layout, which supports extended frames
+        #[cfg(feature = "esp32")]
+        config
+            .peripheral
+            .register_block()
+            .clock_divider
+            .modify(|_, w| w.ext_mode().set_bit());
+
+        tx_pin
+            .set_to_push_pull_output()
+            .connect_peripheral_to_output(TX_SIGNAL);
+        rx_pin.set_to_input().connect_input_to_peripheral(RX_SIGNAL);
+
+        config.set_baud_rate(baud_rate);
+        config.set_filter(filter::SingleStandardFilter::new(StandardId::ZERO, 0));
+
+        config
+    }
+
+    fn enter_reset_mode(&mut self) {
+        self.peripheral
+            .register_block()
+            .mode
+            .modify(|_, w| w.reset_mode().set_bit());
+    }
+
+    /// Set the operating mode ([TwaiMode::Normal] after creation)
+    pub fn set_mode(&mut self, mode: TwaiMode) {
+        self.peripheral.register_block().mode.modify(|_, w| {
+            w.listen_only_mode()
+                .bit(mode == TwaiMode::ListenOnly)
+                .self_test_mode()
+                .bit(mode == TwaiMode::SelfTest)
+        });
+        self.mode = mode;
+    }
+
+    /// Set the bit timing of the bus
+    pub fn set_baud_rate(&mut self, baud_rate: BaudRate) {
+        let timing = baud_rate.timing();
+        let register_block = self.peripheral.register_block();
+
+        // The time quantum is 2 * (BRP + 1) APB clock cycles
+        let prescaler = (timing.baud_rate_prescaler / 2).saturating_sub(1);
+
+        trace!(
+            "TWAI timing: prescaler {}, tseg_1 {}, tseg_2 {}, sjw {}",
+            timing.baud_rate_prescaler,
+            timing.tseg_1,
+            timing.tseg_2,
+            timing.sync_jump_width
+        );
+
+        register_block.bus_timing_0.write(|w| unsafe {
+            w.baud_presc()
+                .bits(prescaler as _)
+                .sync_jump_width()
+                .bits(timing.sync_jump_width - 1)
+        });
+        register_block.bus_timing_1.write(|w| unsafe {
+            w.time_seg1()
+                .bits(timing.tseg_1 - 1)
+                .time_seg2()
+                .bits(timing.tseg_2 - 1)
+                .time_samp()
+                .bit(timing.triple_sample)
+        });
+    }
+
+    /// Set the acceptance filter, replacing any previous filter
+    pub fn set_filter<F: Filter>(&mut self, filter: F) {
+        let register_block = self.peripheral.register_block();
+
+        register_block
+            .mode
+            .modify(|_, w| w.rx_filter_mode().bit(F::FILTER_TYPE == FilterType::Single));
+
+        // In reset mode, the first eight data registers hold the acceptance
+        // code and mask
+        let data = data_registers(register_block);
+        for (i, byte) in filter.to_registers().iter().enumerate() {
+            unsafe { data.add(i).write_volatile(*byte as u32) };
+        }
+    }
+
+    /// Set the number of errors after which the error warning status is
+    /// raised (96 after reset)
+    pub fn set_error_warning_limit(&mut self, limit: u8) {
+        self.peripheral
+            .register_block()
+            .err_warning_limit
+            .write(|w| unsafe { w.err_warning_limit().bits(limit) });
+    }
+
+    /// Leave reset mode and start taking part in bus communication
+    pub fn start(self) -> Twai<'d, T> {
+        let register_block = self.peripheral.register_block();
+
+        // Clear the error counters and any pending interrupts
+        register_block
+            .rx_err_cnt
+            .write(|w| unsafe { w.rx_err_cnt().bits(0) });
+        register_block
+            .tx_err_cnt
+            .write(|w| unsafe { w.tx_err_cnt().bits(0) });
+        register_block.interrupt.read();
+
+        register_block
+            .mode
+            .modify(|_, w| w.reset_mode().clear_bit());
+
+        Twai {
+            peripheral: self.peripheral,
+            mode: self.mode,
+        }
+    }
+}
+
+/// A started TWAI controller, taking part in bus communication
+pub struct Twai<'d, T> {
+    peripheral: PeripheralRef<'d, T>,
+    mode: TwaiMode,
+}
+
+impl<'d, T> Twai<'d, T>
+where
+    T: Instance,
+{
+    /// Stop taking part in bus communication and return to reset mode, e.g.
+    /// to change the configuration or to recover from bus-off
+    pub fn stop(self) -> TwaiConfiguration<'d, T> {
+        let mut config = TwaiConfiguration {
+            peripheral: self.peripheral,
+            mode: self.mode,
+        };
+        config.enter_reset_mode();
+        config
+    }
+
+    /// The operating mode the controller was started in
+    pub fn mode(&self) -> TwaiMode {
+        self.mode
+    }
+
+    /// Receive error counter
+    pub fn receive_error_count(&self) -> u8 {
+        self.peripheral
+            .register_block()
+            .rx_err_cnt
+            .read()
+            .rx_err_cnt()
+            .bits()
+    }
+
+    /// Transmit error counter
+    pub fn transmit_error_count(&self) -> u8 {
+        self.peripheral
+            .register_block()
+            .tx_err_cnt
+            .read()
+            .tx_err_cnt()
+            .bits()
+    }
+
+    /// Whether the controller is in the bus-off state
+    pub fn is_bus_off(&self) -> bool {
+        self.peripheral
+            .register_block()
+            .status
+            .read()
+            .bus_off_st()
+            .bit_is_set()
+    }
+
+    /// Number of received frames waiting to be read, either in the receive
+    /// FIFO or, when listening, in the RX queue
+    pub fn num_available_messages(&self) -> u8 {
+        if self.is_listening() {
+            return critical_section::with(|cs| RX_QUEUE.borrow(cs).borrow().len() as u8);
+        }
+
+        self.peripheral
+            .register_block()
+            .rx_message_cnt
+            .read()
+            .rx_message_counter()
+            .bits()
+    }
+
+    /// Discard all frames waiting in the receive FIFO and the RX queue
+    pub fn clear_receive_fifo(&mut self) {
+        let register_block = self.peripheral.register_block();
+
+        critical_section::with(|cs| {
+            while register_block.status.read().rx_buf_st().bit_is_set() {
+                register_block.cmd.write(|w| w.release_buf().set_bit());
+            }
+
+            RX_QUEUE.borrow(cs).borrow_mut().clear();
+        });
+    }
+
+    /// Enable the receive, transmit and error interrupts
+    ///
+    /// From now on, [handle_interrupt] must be called from the `TWAI`
+    /// interrupt handler; it moves received frames into the RX queue, from
+    /// which [Twai::receive] takes them.
+    pub fn listen(&mut self) {
+        critical_section::with(|cs| RX_QUEUE.borrow(cs).borrow_mut().clear());
+
+        self.peripheral.register_block().int_ena.modify(|_, w| {
+            w.rx_int_ena()
+                .set_bit()
+                .tx_int_ena()
+                .set_bit()
+                .err_warn_int_ena()
+                .set_bit()
+                .overrun_int_ena()
+                .set_bit()
+        });
+    }
+
+    /// Disable the interrupts enabled by [Twai::listen]
+    ///
+    /// Frames still waiting in the RX queue are discarded.
+    pub fn unlisten(&mut self) {
+        self.peripheral.register_block().int_ena.modify(|_, w| {
+            w.rx_int_ena()
+                .clear_bit()
+                .tx_int_ena()
+                .clear_bit()
+                .err_warn_int_ena()
+                .clear_bit()
+                .overrun_int_ena()
+                .clear_bit()
+        });
+
+        critical_section::with(|cs| RX_QUEUE.borrow(cs).borrow_mut().clear());
+    }
+
+    fn is_listening(&self) -> bool {
+        self.peripheral
+            .register_block()
+            .int_ena
+            .read()
+            .rx_int_ena()
+            .bit_is_set()
+    }
+
+    /// Queue a frame for transmission
+    ///
+    /// Returns [nb::Error::WouldBlock] while a previous transmission is still
+    /// in progress. In [TwaiMode::SelfTest], the frame is also received back.
+    pub fn transmit(&mut self, frame: &EspTwaiFrame) -> nb::Result<(), Error> {
+        if self.mode == TwaiMode::ListenOnly {
+            return Err(nb::Error::Other(Error::ListenOnly));
+        }
+
+        let register_block = self.peripheral.register_block();
+        let status = register_block.status.read();
+
+        if status.bus_off_st().bit_is_set() {
+            return Err(nb::Error::Other(Error::BusOff));
+        }
+        if status.tx_buf_st().bit_is_clear() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        write_frame(register_block, frame);
+        if self.mode == TwaiMode::SelfTest {
+            register_block.cmd.write(|w| w.self_rx_req().set_bit());
+        } else {
+            register_block.cmd.write(|w| w.tx_req().set_bit());
+        }
+
+        Ok(())
+    }
+
+    /// Take the oldest received frame, from the RX queue when listening or
+    /// from the receive FIFO otherwise
+    pub fn receive(&mut self) -> nb::Result<EspTwaiFrame, Error> {
+        let register_block = self.peripheral.register_block();
+
+        if register_block.status.read().bus_off_st().bit_is_set() {
+            return Err(nb::Error::Other(Error::BusOff));
+        }
+
+        if self.is_listening() {
+            return match critical_section::with(|cs| RX_QUEUE.borrow(cs).borrow_mut().pop()) {
+                Some(result) => result.map_err(nb::Error::Other),
+                None => Err(nb::Error::WouldBlock),
+            };
+        }
+
+        read_fifo(register_block)
+    }
+
+    /// Wait until a frame has been received
+    ///
+    /// Requires [Twai::listen] and an interrupt handler calling
+    /// [handle_interrupt].
+    pub async fn receive_async(&mut self) -> Result<EspTwaiFrame, Error> {
+        NbFuture::new(&RX_WAKER, || self.receive()).await
+    }
+
+    /// Wait until the transmit buffer is free and queue `frame` for
+    /// transmission
+    ///
+    /// Requires [Twai::listen] and an interrupt handler calling
+    /// [handle_interrupt].
+    pub async fn transmit_async(&mut self, frame: &EspTwaiFrame) -> Result<(), Error> {
+        NbFuture::new(&TX_WAKER, || self.transmit(frame)).await
+    }
+}
+
+impl<'d, T> embedded_can::nb::Can for Twai<'d, T>
+where
+    T: Instance,
+{
+    type Frame = EspTwaiFrame;
+    type Error = Error;
+
+    /// The hardware has a single transmit buffer and does not support
+    /// replacing a pending frame, so this never returns a frame.
+    fn transmit(&mut self, frame: &Self::Frame) -> nb::Result<Option<Self::Frame>, Self::Error> {
+        Twai::transmit(self, frame)?;
+        Ok(None)
+    }
+
+    fn receive(&mut self) -> nb::Result<Self::Frame, Self::Error> {
+        Twai::receive(self)
+    }
+}
+
+impl<'d, T> embedded_can::blocking::Can for Twai<'d, T>
+where
+    T: Instance,
+{
+    type Frame = EspTwaiFrame;
+    type Error = Error;
+
+    fn transmit(&mut self, frame: &Self::Frame) -> Result<(), Self::Error> {
+        nb::block!(Twai::transmit(self, frame))
+    }
+
+    fn receive(&mut self) -> Result<Self::Frame, Self::Error> {
+        nb::block!(Twai::receive(self))
+    }
+}
+
+#[cfg(feature = "esp32")]
+const TX_SIGNAL: OutputSignal = OutputSignal::CAN_TX;
+#[cfg(not(feature = "esp32"))]
+const TX_SIGNAL: OutputSignal = OutputSignal::TWAI_TX;
+#[cfg(feature = "esp32")]
+const RX_SIGNAL: InputSignal = InputSignal::CAN_RX;
+#[cfg(not(feature = "esp32"))]
+const RX_SIGNAL: InputSignal = InputSignal::TWAI_RX;
+
+/// Service the TWAI interrupt
+///
+/// Call this from the `TWAI` interrupt handler after [Twai::listen]. It moves
+/// all received frames into the RX queue and wakes pending async operations.
+pub fn handle_interrupt() {
+    let register_block = unsafe { &*TWAI::PTR };
+
+    // Reading the interrupt register acknowledges all pending interrupts
+    register_block.interrupt.read();
+
+    critical_section::with(|cs| {
+        let mut queue = RX_QUEUE.borrow(cs).borrow_mut();
+
+        loop {
+            match read_fifo(register_block) {
+                Ok(frame) => queue.push(frame),
+                Err(nb::Error::Other(Error::Overrun)) => queue.set_overrun(),
+                Err(_) => break,
+            }
+        }
+    });
+
+    RX_WAKER.wake();
+    TX_WAKER.wake();
+}
+
+// Frame information byte
+const FRAME_FORMAT_EXTENDED: u8 = 1 << 7;
+const FRAME_RTR: u8 = 1 << 6;
+
+/// The frame buffer is mapped to 13 consecutive registers, one byte each
+fn data_registers(register_block: &RegisterBlock) -> *mut u32 {
+    &register_block.data_0 as *const _ as *mut u32
+}
+
+/// Take the oldest frame out of the hardware receive FIFO
+fn read_fifo(register_block: &RegisterBlock) -> nb::Result<EspTwaiFrame, Error> {
+    let status = register_block.status.read();
+
+    if status.overrun_st().bit_is_set() {
+        register_block.cmd.write(|w| w.clr_overrun().set_bit());
+        return Err(nb::Error::Other(Error::Overrun));
+    }
+    if status.rx_buf_st().bit_is_clear() {
+        return Err(nb::Error::WouldBlock);
+    }
+
+    let frame = read_frame(register_block);
+    register_block.cmd.write(|w| w.release_buf().set_bit());
+
+    Ok(frame)
+}
+
+fn write_frame(register_block: &RegisterBlock, frame: &EspTwaiFrame) {
+    let data = data_registers(register_block);
+    let write = |index: usize, value: u8| unsafe { data.add(index).write_volatile(value as u32) };
+
+    let mut info = frame.dlc as u8;
+    if frame.is_remote {
+        info |= FRAME_RTR;
+    }
+
+    let payload_start = match frame.id {
+        Id::Standard(id) => {
+            let id = id.as_raw();
+            write(0, info);
+            write(1, (id >> 3) as u8);
+            write(2, (id << 5) as u8);
+            3
+        }
+        Id::Extended(id) => {
+            let id = (id.as_raw() << 3).to_be_bytes();
+            write(0, info | FRAME_FORMAT_EXTENDED);
+            for (i, byte) in id.iter().enumerate() {
+                write(1 + i, *byte);
+            }
+            5
+        }
+    };
+
+    if !frame.is_remote {
+        for (i, byte) in frame.data[..frame.dlc].iter().enumerate() {
+            write(payload_start + i, *byte);
+        }
+    }
+}
+
+fn read_frame(register_block: &RegisterBlock) -> EspTwaiFrame {
+    let data = data_registers(register_block);
+    let read = |index: usize| unsafe { data.add(index).read_volatile() as u8 };
+
+    let info = read(0);
+    let dlc = ((info & 0x0f) as usize).min(8);
+    let is_remote = info & FRAME_RTR != 0;
+
+    let (id, payload_start) = if info & FRAME_FORMAT_EXTENDED != 0 {
+        let raw = u32::from_be_bytes([read(1), read(2), read(3), read(4)]) >> 3;
+        // SAFETY: the value is limited to 29 bits by the shift
+        let id = unsafe { ExtendedId::new_unchecked(raw) };
+        (Id::Extended(id), 5)
+    } else {
+        let raw = ((read(1) as u16) << 3) | ((read(2) as u16) >> 5);
+        // SAFETY: the value is limited to 11 bits
+        let id = unsafe { StandardId::new_unchecked(raw) };
+        (Id::Standard(id), 3)
+    };
+
+    let mut frame_data = [0u8; 8];
+    if !is_remote {
+        for (i, byte) in frame_data[..dlc].iter_mut().enumerate() {
+            *byte = read(payload_start + i);
+        }
+    }
+
+    EspTwaiFrame {
+        id,
+        dlc,
+        data: frame_data,
+        is_remote,
+    }
+}
+
+/// TWAI peripheral instance
+pub trait Instance {
+    fn register_block(&self) -> &RegisterBlock;
+}
+
+impl Instance for TWAI {
+    #[inline(always)]
+    fn register_block(&self) -> &RegisterBlock {
+        self
+    }
+}
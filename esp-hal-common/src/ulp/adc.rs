@@ -0,0 +1,230 @@
+//! ADC sampling by the ULP
+//!
+//! The ULP samples an ADC channel periodically while the main CPUs are in deep
+//! sleep, and wakes them up once the reading leaves a window. Each sample is
+//! stored at [ADC_SAMPLE], so the application can read the sample which woke
+//! it up. The ULP FSM stores the sample in the lower 16 bits of the word.
+//!
+//! On the ESP32 and ESP32-S2, [UlpFsm::run_adc_monitor] generates and runs the
+//! ULP FSM program itself:
+//!
+//! ```no_run
+//! let pin34 = io.pins.gpio34.into_analog();
+//! let mut adc1_config = AdcConfig::new();
+//! adc1_config.enable_pin(&pin34, Attenuation::Attenuation11dB);
+//! let adc1 = ADC::<ADC1>::adc(analog.adc1, adc1_config).unwrap();
+//!
+//! let mut ulp = UlpFsm::new(&mut rtc.ulp);
+//! ulp.run_adc_monitor(adc1, &pin34, AdcThresholds { low: 1000, high: 3000 }, 100u64.millis())
+//!     .unwrap();
+//! ```
+//!
+//! With [UlpRiscv::run_adc_monitor], the application provides the program,
+//! which finds the ADC channel and the thresholds at [ADC_CHANNEL],
+//! [ADC_THRESHOLD_LOW] and [ADC_THRESHOLD_HIGH].
+
+use fugit::MicrosDurationU64;
+
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+use embedded_hal::adc::Channel;
+
+use crate::ulp::{set_wakeup_source, write_word, Error, RTC_SLOW_MEM_WORDS};
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+use crate::ulp::{UlpRiscv, RTC_COCPU_TRIG_EN};
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+use crate::{
+    analog::{
+        adc::{RegisterAccess, ADC},
+        ADC1,
+        ADC2,
+    },
+    ulp::{copy_to_slow_mem, UlpFsm, RTC_ULP_TRIG_EN},
+};
+
+/// Word offset of the latest sample in RTC slow memory
+pub const ADC_SAMPLE: usize = RTC_SLOW_MEM_WORDS - 1;
+/// Word offset of the ADC unit (0 for ADC1, 1 for ADC2) and channel, as
+/// `unit << 8 | channel`, for programs provided by the application
+pub const ADC_CHANNEL: usize = RTC_SLOW_MEM_WORDS - 2;
+/// Word offset of the low threshold, for programs provided by the application
+pub const ADC_THRESHOLD_LOW: usize = RTC_SLOW_MEM_WORDS - 3;
+/// Word offset of the high threshold, for programs provided by the
+/// application
+pub const ADC_THRESHOLD_HIGH: usize = RTC_SLOW_MEM_WORDS - 4;
+
+/// The window of ADC readings which don't wake up the CPUs
+///
+/// The CPUs are woken up by a reading below `low` or at or above `high`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AdcThresholds {
+    pub low: u16,
+    pub high: u16,
+}
+
+/// An ADC unit of the SAR ADC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SarAdc {
+    Adc1 = 0,
+    Adc2 = 1,
+}
+
+/// ADC unit which can be sampled by the ULP FSM
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+pub trait UlpAdc: RegisterAccess {
+    #[doc(hidden)]
+    const SAR: SarAdc;
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+impl UlpAdc for ADC1 {
+    const SAR: SarAdc = SarAdc::Adc1;
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+impl UlpAdc for ADC2 {
+    const SAR: SarAdc = SarAdc::Adc2;
+}
+
+/// Encoding of the ULP FSM instructions used by the ADC monitor
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+mod insn {
+    const OPCODE_ADC: u32 = 5;
+    const OPCODE_ST: u32 = 6;
+    const OPCODE_ALU: u32 = 7;
+    const OPCODE_BRANCH: u32 = 8;
+    const OPCODE_END: u32 = 9;
+    const OPCODE_HALT: u32 = 11;
+
+    const SUB_OPCODE_ST: u32 = 4;
+    const SUB_OPCODE_ALU_IMM: u32 = 1;
+    const SUB_OPCODE_BR: u32 = 1;
+    const ALU_SEL_MOV: u32 = 4;
+
+    /// Branch if R0 is lower than the immediate
+    pub const CMP_LT: u32 = 0;
+    /// Branch if R0 is greater than or equal to the immediate
+    pub const CMP_GE: u32 = 1;
+
+    /// `MOVE rdst, imm`
+    pub const fn mov(rdst: u32, imm: u16) -> u32 {
+        OPCODE_ALU << 28 | SUB_OPCODE_ALU_IMM << 25 | ALU_SEL_MOV << 21 | (imm as u32) << 4 | rdst
+    }
+
+    /// `ADC rdst, sar_sel, channel + 1`
+    pub const fn adc(rdst: u32, sar_sel: u32, channel: u8) -> u32 {
+        OPCODE_ADC << 28 | sar_sel << 6 | ((channel as u32 + 1) & 0xf) << 2 | rdst
+    }
+
+    /// `ST rsrc, raddr, offset`, with the offset in words
+    pub const fn st(rsrc: u32, raddr: u32, offset: u16) -> u32 {
+        OPCODE_ST << 28 | SUB_OPCODE_ST << 25 | ((offset as u32) & 0x7ff) << 10 | raddr << 2 | rsrc
+    }
+
+    /// `JUMPR +step, imm, cmp`, with the forward step in words
+    pub const fn jumpr(step: u8, imm: u16, cmp: u32) -> u32 {
+        OPCODE_BRANCH << 28
+            | SUB_OPCODE_BR << 25
+            | ((step as u32) & 0x7f) << 17
+            | cmp << 16
+            | imm as u32
+    }
+
+    /// `WAKE`
+    pub const fn wake() -> u32 {
+        OPCODE_END << 28 | 1
+    }
+
+    /// `HALT`
+    pub const fn halt() -> u32 {
+        OPCODE_HALT << 28
+    }
+}
+
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+impl<'d> UlpFsm<'d> {
+    /// Sample `pin` every `period` and wake up the CPUs once the reading is
+    /// outside of `thresholds`
+    ///
+    /// The configured `adc` is handed over to the ULP, and the generated
+    /// program is loaded at the start of RTC slow memory. The CPUs are only
+    /// woken up from sleep, the ULP keeps sampling while they are running.
+    pub fn run_adc_monitor<ADCI, PIN>(
+        &mut self,
+        adc: ADC<'_, ADCI>,
+        _pin: &PIN,
+        thresholds: AdcThresholds,
+        period: impl Into<MicrosDurationU64>,
+    ) -> Result<(), Error>
+    where
+        ADCI: UlpAdc,
+        PIN: Channel<ADCI, ID = u8>,
+    {
+        // let the ULP start the conversions and select the channel
+        ADCI::clear_start_force();
+        ADCI::clear_en_pad_force();
+        drop(adc);
+
+        let program = [
+            insn::mov(1, 0),
+            insn::adc(0, ADCI::SAR as u32, PIN::channel()),
+            insn::st(0, 1, ADC_SAMPLE as u16),
+            insn::jumpr(3, thresholds.low, insn::CMP_LT),
+            insn::jumpr(2, thresholds.high, insn::CMP_GE),
+            insn::halt(),
+            insn::wake(),
+            insn::halt(),
+        ];
+
+        let mut bytes = [0u8; 8 * 4];
+        for (chunk, word) in bytes.chunks_mut(4).zip(program) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+
+        write_word(ADC_SAMPLE, 0);
+        copy_to_slow_mem(0, &bytes)?;
+
+        set_wakeup_source(RTC_ULP_TRIG_EN, true);
+        self.set_wakeup_period(period);
+        self.run(0);
+
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+impl<'d> UlpRiscv<'d> {
+    /// Run `program`, which samples `channel` of `adc` every `period` and
+    /// wakes up the CPUs once the reading is outside of `thresholds`
+    ///
+    /// The channel and the thresholds are passed to the program in RTC slow
+    /// memory, see [ADC_CHANNEL], [ADC_THRESHOLD_LOW] and
+    /// [ADC_THRESHOLD_HIGH]. The program is expected to store each sample at
+    /// [ADC_SAMPLE] and to wake up the CPUs itself.
+    pub fn run_adc_monitor(
+        &mut self,
+        program: &[u8],
+        adc: SarAdc,
+        channel: u8,
+        thresholds: AdcThresholds,
+        period: impl Into<MicrosDurationU64>,
+    ) -> Result<(), Error> {
+        if program.len() > ADC_THRESHOLD_HIGH * 4 {
+            return Err(Error::ProgramTooLarge);
+        }
+
+        write_word(ADC_SAMPLE, 0);
+        write_word(ADC_CHANNEL, (adc as u32) << 8 | channel as u32);
+        write_word(ADC_THRESHOLD_LOW, thresholds.low as u32);
+        write_word(ADC_THRESHOLD_HIGH, thresholds.high as u32);
+
+        self.load(program)?;
+
+        set_wakeup_source(RTC_COCPU_TRIG_EN, true);
+        self.set_wakeup_period(period);
+        self.run();
+
+        Ok(())
+    }
+}
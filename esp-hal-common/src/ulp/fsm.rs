@@ -0,0 +1,199 @@
+//! ULP FSM driver
+
+use fugit::MicrosDurationU64;
+
+#[cfg(feature = "esp32")]
+use crate::pac::SENS;
+#[cfg(feature = "esp32s2")]
+use crate::ulp::reset_cocpu;
+use crate::{
+    into_ref,
+    pac::RTC_CNTL,
+    peripherals::{Peripheral, PeripheralRef},
+    ulp::{
+        copy_to_slow_mem,
+        read_word,
+        set_timer_period,
+        set_wakeup_source,
+        stop_timer,
+        write_word,
+        Error,
+        Ulp,
+        RTC_SLOW_MEM_WORDS,
+        RTC_ULP_TRIG_EN,
+    },
+};
+
+/// Magic number at the start of a ULP binary, `"ulp\0"`
+const ULP_BINARY_MAGIC: u32 = 0x0070_6c75;
+/// Size of the ULP binary header
+const ULP_BINARY_HEADER_SIZE: usize = 12;
+
+/// ULP FSM driver
+///
+/// Runs programs built with the ULP FSM toolchain (`esp32ulp-elf`), which are
+/// started periodically by the ULP timer.
+pub struct UlpFsm<'d> {
+    _ulp: PeripheralRef<'d, Ulp>,
+}
+
+impl<'d> UlpFsm<'d> {
+    /// Take control of the ULP FSM
+    pub fn new(ulp: impl Peripheral<P = Ulp> + 'd) -> Self {
+        into_ref!(ulp);
+
+        Self { _ulp: ulp }
+    }
+
+    /// Load a ULP binary into RTC slow memory at the word `offset`
+    ///
+    /// The binary is the output of `esp32ulp-elf-objcopy` and related tools:
+    /// a header followed by the text and data sections. The text and data
+    /// sections are copied and the bss section is zeroed.
+    pub fn load_binary(&mut self, offset: usize, binary: &[u8]) -> Result<(), Error> {
+        if binary.len() < ULP_BINARY_HEADER_SIZE {
+            return Err(Error::InvalidSize);
+        }
+
+        let read_u16 = |at: usize| u16::from_le_bytes([binary[at], binary[at + 1]]) as usize;
+        let magic = u32::from_le_bytes([binary[0], binary[1], binary[2], binary[3]]);
+        if magic != ULP_BINARY_MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+
+        let text_offset = read_u16(4);
+        let text_size = read_u16(6);
+        let data_size = read_u16(8);
+        let bss_size = read_u16(10);
+
+        let program = binary
+            .get(text_offset..text_offset + text_size + data_size)
+            .ok_or(Error::InvalidSize)?;
+        let bss_offset = offset + (text_size + data_size) / 4;
+        if bss_offset + bss_size / 4 > RTC_SLOW_MEM_WORDS {
+            return Err(Error::ProgramTooLarge);
+        }
+
+        trace!(
+            "ULP binary: {} bytes text, {} bytes data, {} bytes bss",
+            text_size,
+            data_size,
+            bss_size
+        );
+
+        copy_to_slow_mem(offset, program)?;
+        for word in bss_offset..bss_offset + bss_size / 4 {
+            write_word(word, 0);
+        }
+
+        Ok(())
+    }
+
+    /// Load a raw ULP program (without header) into RTC slow memory at the
+    /// word `offset`
+    pub fn load_raw(&mut self, offset: usize, program: &[u8]) -> Result<(), Error> {
+        copy_to_slow_mem(offset, program)
+    }
+
+    /// Set the period after which the ULP timer restarts the program once it
+    /// has halted
+    ///
+    /// The period is converted to RTC_SLOW_CLK cycles using the calibration
+    /// done by [Rtc::new](crate::Rtc::new).
+    pub fn set_wakeup_period<T>(&mut self, period: T)
+    where
+        T: Into<MicrosDurationU64>,
+    {
+        set_timer_period(period.into());
+    }
+
+    /// Start the program at the word `entry_point`
+    ///
+    /// The program is started by the ULP timer, and restarted after the
+    /// wakeup period each time it halts.
+    #[cfg(feature = "esp32")]
+    pub fn run(&mut self, entry_point: u32) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let sens = unsafe { &*SENS::ptr() };
+
+        self.stop();
+
+        sens.sar_start_force.modify(|_, w| unsafe {
+            w.pc_init()
+                .bits(entry_point as u16)
+                .ulp_cp_force_start_top()
+                .clear_bit()
+        });
+
+        // minimum time until the ULP is allowed to wake up the CPUs
+        rtc_cntl
+            .timer5
+            .modify(|_, w| unsafe { w.min_slp_val().bits(2) });
+
+        // make sure the voltage is raised when the RTC 8 MHz clock is enabled
+        rtc_cntl.options0.modify(|_, w| {
+            w.bias_i2c_folw_8m()
+                .set_bit()
+                .bias_core_folw_8m()
+                .set_bit()
+                .bias_sleep_folw_8m()
+                .set_bit()
+        });
+
+        rtc_cntl
+            .state0
+            .modify(|_, w| w.ulp_cp_slp_timer_en().set_bit());
+    }
+
+    /// Start the program at the word `entry_point`
+    ///
+    /// The program is started by the ULP timer, and restarted after the
+    /// wakeup period each time it halts.
+    #[cfg(feature = "esp32s2")]
+    pub fn run(&mut self, entry_point: u32) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        reset_cocpu();
+
+        self.stop();
+
+        rtc_cntl
+            .ulp_cp_timer
+            .modify(|_, w| unsafe { w.ulp_cp_pc_init().bits(entry_point as u16) });
+
+        // the ULP timer starts the FSM rather than the RISC-V core
+        rtc_cntl.cocpu_ctrl.modify(|_, w| w.cocpu_sel().set_bit());
+        rtc_cntl
+            .ulp_cp_ctrl
+            .modify(|_, w| w.ulp_cp_force_start_top().clear_bit());
+
+        rtc_cntl
+            .ulp_cp_timer
+            .modify(|_, w| w.ulp_cp_slp_timer_en().set_bit());
+    }
+
+    /// Stop the ULP timer
+    ///
+    /// A program which is currently running completes, but is not restarted
+    /// afterwards.
+    pub fn stop(&mut self) {
+        stop_timer();
+    }
+
+    /// Allow the program to wake up the CPUs from deep sleep with `WAKE`
+    pub fn enable_cpu_wakeup(&mut self, enable: bool) {
+        set_wakeup_source(RTC_ULP_TRIG_EN, enable);
+    }
+
+    /// Read the word at `offset` in RTC slow memory
+    ///
+    /// Words stored by the ULP program hold the value in their lower 16 bits.
+    pub fn read_word(&self, offset: usize) -> u32 {
+        read_word(offset)
+    }
+
+    /// Write the word at `offset` in RTC slow memory
+    pub fn write_word(&mut self, offset: usize, value: u32) {
+        write_word(offset, value)
+    }
+}
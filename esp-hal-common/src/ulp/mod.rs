@@ -0,0 +1,212 @@
+//! # ULP coprocessor
+//!
+//! The ultra-low-power coprocessor runs from RTC slow memory and keeps running
+//! while the main CPUs are in deep sleep, e.g. to poll a sensor and only wake
+//! them up when a threshold is crossed.
+//!
+//! The coprocessor is part of the RTC and is handed out as [Rtc::ulp]. The ULP
+//! FSM of the ESP32 and ESP32-S2 is driven by `UlpFsm`, and the RISC-V ULP of
+//! the ESP32-S2 and ESP32-S3 by `UlpRiscv`:
+//!
+//! ```no_run
+//! static ULP_PROGRAM: &[u8] = include_bytes!("ulp_program.bin");
+//!
+//! let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+//! let mut ulp = UlpFsm::new(&mut rtc.ulp);
+//!
+//! ulp.load_binary(0, ULP_PROGRAM).unwrap();
+//! ulp.set_wakeup_period(100u64.millis());
+//! ulp.run(0);
+//!
+//! // the ULP program stores its samples at word 0x100
+//! let sample = ulp.read_word(0x100) & 0xffff;
+//! ```
+//!
+//! ## RTC slow memory
+//!
+//! Programs and data are addressed in 32-bit words from the start of RTC slow
+//! memory, the same way the ULP addresses them, so the offsets of the shared
+//! variables can be taken from the symbol table of the ULP program.
+//!
+//! [Rtc::ulp]: crate::Rtc::ulp
+
+use core::fmt;
+
+use fugit::MicrosDurationU64;
+
+#[cfg(feature = "esp32")]
+use crate::pac::SENS;
+use crate::{pac::RTC_CNTL, rom::esp_rom_delay_us};
+
+pub mod adc;
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+mod fsm;
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+mod riscv;
+
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+pub use fsm::UlpFsm;
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+pub use riscv::UlpRiscv;
+
+/// Start of RTC slow memory
+const RTC_SLOW_MEM: usize = 0x5000_0000;
+/// Size of RTC slow memory in 32-bit words
+const RTC_SLOW_MEM_WORDS: usize = 8 * 1024 / 4;
+
+/// Wakeup source bit of the ULP FSM in `RTC_CNTL_WAKEUP_ENA`
+#[cfg(any(feature = "esp32", feature = "esp32s2"))]
+const RTC_ULP_TRIG_EN: u32 = 1 << 9;
+/// Wakeup source bit of the RISC-V ULP in `RTC_CNTL_WAKEUP_ENA`
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+const RTC_COCPU_TRIG_EN: u32 = 1 << 11;
+
+/// Number of fractional bits of the RTC_SLOW_CLK period calibration
+const RTC_CLK_CAL_FRACT: u32 = 19;
+
+/// ULP errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The program does not start with the magic number of a ULP binary
+    InvalidMagic,
+    /// The program is shorter than its header says
+    InvalidSize,
+    /// The program does not fit into RTC slow memory at the load address
+    ProgramTooLarge,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidMagic => write!(f, "not a ULP binary"),
+            Error::InvalidSize => write!(f, "ULP binary is truncated"),
+            Error::ProgramTooLarge => write!(f, "ULP program does not fit into RTC slow memory"),
+        }
+    }
+}
+
+/// The ULP coprocessor
+pub struct Ulp {
+    _private: (),
+}
+
+impl Ulp {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl crate::peripherals::Peripheral for Ulp {
+    type P = Ulp;
+
+    unsafe fn clone_unchecked(&mut self) -> Self::P {
+        Ulp::new()
+    }
+}
+
+/// Pointer to the word at `offset` in RTC slow memory
+fn slow_mem_word(offset: usize) -> *mut u32 {
+    assert!(offset < RTC_SLOW_MEM_WORDS, "offset out of RTC slow memory");
+
+    (RTC_SLOW_MEM as *mut u32).wrapping_add(offset)
+}
+
+/// Read the word at `offset` in RTC slow memory
+fn read_word(offset: usize) -> u32 {
+    unsafe { slow_mem_word(offset).read_volatile() }
+}
+
+/// Write the word at `offset` in RTC slow memory
+fn write_word(offset: usize, value: u32) {
+    unsafe { slow_mem_word(offset).write_volatile(value) }
+}
+
+/// Copy `program` to RTC slow memory, starting at the word `offset`
+fn copy_to_slow_mem(offset: usize, program: &[u8]) -> Result<(), Error> {
+    let words = (program.len() + 3) / 4;
+    if offset + words > RTC_SLOW_MEM_WORDS {
+        return Err(Error::ProgramTooLarge);
+    }
+
+    for (i, chunk) in program.chunks(4).enumerate() {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        write_word(offset + i, u32::from_le_bytes(word));
+    }
+
+    Ok(())
+}
+
+/// Set the period of the ULP timer, which (re)starts the program
+///
+/// The period is converted to RTC_SLOW_CLK cycles using the calibration done
+/// by [Rtc::new](crate::Rtc::new).
+fn set_timer_period(period: MicrosDurationU64) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let period_13q19 = rtc_cntl.store1.read().bits() as u64;
+    let cycles = ((period.to_micros() << RTC_CLK_CAL_FRACT) / period_13q19) as u32;
+
+    trace!("ULP wakeup period: {} slow clock cycles", cycles);
+
+    #[cfg(feature = "esp32")]
+    {
+        let sens = unsafe { &*SENS::ptr() };
+        sens.ulp_cp_sleep_cyc0
+            .write(|w| unsafe { w.sleep_cycles_s0().bits(cycles) });
+    }
+
+    #[cfg(not(feature = "esp32"))]
+    rtc_cntl
+        .ulp_cp_timer_1
+        .modify(|_, w| unsafe { w.ulp_cp_timer_slp_cycle().bits(cycles) });
+}
+
+/// Enable or disable the wakeup source `source` of the CPUs
+fn set_wakeup_source(source: u32, enable: bool) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl.wakeup_state.modify(|r, w| {
+        let sources = r.wakeup_ena().bits();
+        let sources = if enable {
+            sources | source
+        } else {
+            sources & !source
+        };
+
+        unsafe { w.wakeup_ena().bits(sources) }
+    });
+}
+
+/// Stop the ULP timer
+fn stop_timer() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    #[cfg(feature = "esp32")]
+    rtc_cntl
+        .state0
+        .modify(|_, w| w.ulp_cp_slp_timer_en().clear_bit());
+
+    #[cfg(not(feature = "esp32"))]
+    rtc_cntl
+        .ulp_cp_timer
+        .modify(|_, w| w.ulp_cp_slp_timer_en().clear_bit());
+
+    // wait for at least one RTC_SLOW_CLK cycle
+    unsafe { esp_rom_delay_us(10) };
+}
+
+/// Reset the coprocessor
+#[cfg(not(feature = "esp32"))]
+fn reset_cocpu() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .cocpu_ctrl
+        .modify(|_, w| w.cocpu_shut_reset_en().set_bit());
+    unsafe { esp_rom_delay_us(20) };
+    rtc_cntl
+        .cocpu_ctrl
+        .modify(|_, w| w.cocpu_shut_reset_en().clear_bit());
+}
@@ -0,0 +1,253 @@
+//! RISC-V ULP driver
+
+use fugit::MicrosDurationU64;
+
+use crate::{
+    into_ref,
+    pac::RTC_CNTL,
+    peripherals::{Peripheral, PeripheralRef},
+    rom::esp_rom_delay_us,
+    ulp::{
+        copy_to_slow_mem,
+        read_word,
+        reset_cocpu,
+        set_timer_period,
+        set_wakeup_source,
+        stop_timer,
+        write_word,
+        Error,
+        Ulp,
+        RTC_COCPU_TRIG_EN,
+    },
+};
+
+/// RISC-V ULP driver
+///
+/// Runs programs built for the RISC-V ULP (`riscv32imc`), linked to start at
+/// address 0, which is the start of RTC slow memory. The program is started by
+/// the ULP timer, and restarted after the wakeup period each time it halts
+/// itself by setting `RTC_CNTL_COCPU_DONE`.
+///
+/// Variables shared with the program are read and written with
+/// [UlpRiscv::read_word] and [UlpRiscv::write_word]. The ULP sees RTC slow
+/// memory at address 0, so the word offset of a shared variable is the
+/// address of its symbol in the ULP program divided by 4.
+///
+/// The RISC-V core always starts at address 0, so the entry point of the
+/// program has to be placed there, e.g. with `ENTRY` and a `.text.init`
+/// section at the start of its linker script.
+///
+/// The program can raise the `RTC_CORE` interrupt by setting
+/// `RTC_CNTL_COCPU_SW_INT_TRIGGER`, e.g. to report new data while the CPUs
+/// are running. A trap of the RISC-V core raises it as well:
+///
+/// ```no_run
+/// ulp.listen();
+/// interrupt::enable(pac::Interrupt::RTC_CORE, interrupt::Priority::Priority1).unwrap();
+///
+/// #[interrupt]
+/// fn RTC_CORE() {
+///     critical_section::with(|cs| {
+///         let mut ulp = ULP.borrow_ref_mut(cs);
+///         let ulp = ulp.as_mut().unwrap();
+///
+///         if ulp.is_trap_set() {
+///             // the program crashed
+///         }
+///         ulp.clear_interrupt();
+///     });
+/// }
+/// ```
+pub struct UlpRiscv<'d> {
+    _ulp: PeripheralRef<'d, Ulp>,
+}
+
+impl<'d> UlpRiscv<'d> {
+    /// Take control of the RISC-V ULP
+    pub fn new(ulp: impl Peripheral<P = Ulp> + 'd) -> Self {
+        into_ref!(ulp);
+
+        Self { _ulp: ulp }
+    }
+
+    /// Load a program, as produced by `objcopy -O binary`, to the start of RTC
+    /// slow memory
+    pub fn load(&mut self, program: &[u8]) -> Result<(), Error> {
+        trace!("RISC-V ULP program: {} bytes", program.len());
+
+        copy_to_slow_mem(0, program)
+    }
+
+    /// Set the period after which the ULP timer restarts the program once it
+    /// has halted
+    ///
+    /// The period is converted to RTC_SLOW_CLK cycles using the calibration
+    /// done by [Rtc::new](crate::Rtc::new).
+    pub fn set_wakeup_period<T>(&mut self, period: T)
+    where
+        T: Into<MicrosDurationU64>,
+    {
+        set_timer_period(period.into());
+    }
+
+    /// Start the program
+    pub fn run(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        reset_cocpu();
+
+        // The trap signal of the coprocessor has no stable reset value, force
+        // its clock on so that it doesn't wake up the CPUs
+        rtc_cntl
+            .cocpu_ctrl
+            .modify(|_, w| w.cocpu_clk_fo().set_bit());
+
+        stop_timer();
+
+        // On the ESP32-S3, selecting the RISC-V core before the ULP timer is
+        // enabled can hang the CPU, so it is selected afterwards
+        #[cfg(feature = "esp32s2")]
+        rtc_cntl.cocpu_ctrl.modify(|_, w| w.cocpu_sel().clear_bit());
+
+        // the RISC-V core signals when it is done, and is clock gated then
+        rtc_cntl
+            .cocpu_ctrl
+            .modify(|_, w| w.cocpu_done_force().set_bit().cocpu_clkgate_en().set_bit());
+
+        rtc_cntl
+            .ulp_cp_ctrl
+            .modify(|_, w| w.ulp_cp_force_start_top().clear_bit());
+        rtc_cntl
+            .ulp_cp_timer
+            .modify(|_, w| w.ulp_cp_slp_timer_en().set_bit());
+
+        #[cfg(feature = "esp32s3")]
+        rtc_cntl.cocpu_ctrl.modify(|_, w| w.cocpu_sel().clear_bit());
+
+        // the core may raise spurious interrupts while it starts up
+        unsafe { esp_rom_delay_us(20) };
+        self.clear_interrupt();
+    }
+
+    /// Reset the RISC-V core, which restarts the program at address 0 the
+    /// next time it is started by the ULP timer
+    pub fn reset(&mut self) {
+        reset_cocpu();
+    }
+
+    /// Stop the ULP timer and halt the program
+    pub fn halt(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        stop_timer();
+
+        // suspend and reset the RISC-V core
+        rtc_cntl
+            .cocpu_ctrl
+            .modify(|_, w| w.cocpu_done().set_bit().cocpu_shut_reset_en().set_bit());
+    }
+
+    /// Allow the program to wake up the CPUs from deep sleep
+    ///
+    /// The program wakes up the CPUs by setting
+    /// `RTC_CNTL_COCPU_SW_INT_TRIGGER`.
+    pub fn enable_cpu_wakeup(&mut self, enable: bool) {
+        set_wakeup_source(RTC_COCPU_TRIG_EN, enable);
+    }
+
+    /// Enable the interrupt raised by the program and by traps of the RISC-V
+    /// core
+    pub fn listen(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        #[cfg(feature = "esp32s2")]
+        rtc_cntl
+            .int_ena_rtc
+            .modify(|_, w| w.cocpu_int_ena().set_bit().cocpu_trap_int_ena().set_bit());
+
+        #[cfg(feature = "esp32s3")]
+        rtc_cntl.int_ena_rtc.modify(|_, w| {
+            w.rtc_cocpu_int_ena()
+                .set_bit()
+                .rtc_cocpu_trap_int_ena()
+                .set_bit()
+        });
+    }
+
+    /// Disable the interrupt raised by the program and by traps of the
+    /// RISC-V core
+    pub fn unlisten(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        #[cfg(feature = "esp32s2")]
+        rtc_cntl.int_ena_rtc.modify(|_, w| {
+            w.cocpu_int_ena()
+                .clear_bit()
+                .cocpu_trap_int_ena()
+                .clear_bit()
+        });
+
+        #[cfg(feature = "esp32s3")]
+        rtc_cntl.int_ena_rtc.modify(|_, w| {
+            w.rtc_cocpu_int_ena()
+                .clear_bit()
+                .rtc_cocpu_trap_int_ena()
+                .clear_bit()
+        });
+    }
+
+    /// Clear the interrupt raised by the program and by traps of the RISC-V
+    /// core
+    pub fn clear_interrupt(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        #[cfg(feature = "esp32s2")]
+        rtc_cntl
+            .int_clr_rtc
+            .write(|w| w.cocpu_int_clr().set_bit().cocpu_trap_int_clr().set_bit());
+
+        #[cfg(feature = "esp32s3")]
+        rtc_cntl.int_clr_rtc.write(|w| {
+            w.rtc_cocpu_int_clr()
+                .set_bit()
+                .rtc_cocpu_trap_int_clr()
+                .set_bit()
+        });
+    }
+
+    /// Whether the program has raised the interrupt
+    pub fn is_interrupt_set(&self) -> bool {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "esp32s2")] {
+                rtc_cntl.int_raw_rtc.read().cocpu_int_raw().bit_is_set()
+            } else {
+                rtc_cntl.int_raw_rtc.read().rtc_cocpu_int_raw().bit_is_set()
+            }
+        }
+    }
+
+    /// Whether the RISC-V core has trapped, e.g. on an illegal instruction
+    pub fn is_trap_set(&self) -> bool {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "esp32s2")] {
+                rtc_cntl.int_raw_rtc.read().cocpu_trap_int_raw().bit_is_set()
+            } else {
+                rtc_cntl.int_raw_rtc.read().rtc_cocpu_trap_int_raw().bit_is_set()
+            }
+        }
+    }
+
+    /// Read the word at `offset` in RTC slow memory
+    pub fn read_word(&self, offset: usize) -> u32 {
+        read_word(offset)
+    }
+
+    /// Write the word at `offset` in RTC slow memory
+    pub fn write_word(&mut self, offset: usize, value: u32) {
+        write_word(offset, value)
+    }
+}
@@ -1,25 +1,404 @@
-use crate::pac::USB_DEVICE;
+//! USB Serial/JTAG controller
+//!
+//! The USB Serial/JTAG controller provides a CDC-ACM serial port (and a JTAG
+//! debug interface) on the chip's USB pins, without any external hardware.
+//!
+//! The serial port can be used in blocking mode, or buffered: once
+//! [UsbSerialJtag::listen] has been called and the USB Serial/JTAG interrupt
+//! calls [handle_interrupt], received data is collected in a buffer of
+//! [RX_BUFFER_SIZE] bytes and written data is sent from a buffer of
+//! [TX_BUFFER_SIZE] bytes in the background. The buffered mode is also
+//! required by the async API.
+//!
+//! With the `embedded-io` feature, the `embedded_io::Read` and
+//! `embedded_io::Write` traits are implemented; with the `async` feature, also
+//! their `embedded-io-async` counterparts.
+//!
+//! # Example
+//!
+//! ```no_run
+//! let mut usb_serial = UsbSerialJtag;
+//!
+//! writeln!(usb_serial, "Hello world!").ok();
+//!
+//! if let Ok(byte) = usb_serial.read_byte() {
+//!     usb_serial.write_bytes(&[byte]);
+//! }
+//! ```
 
+use core::{cell::RefCell, convert::Infallible};
+
+use critical_section::{CriticalSection, Mutex};
+
+use crate::{asynch::WakerSlot, pac::USB_DEVICE};
+
+/// Size of the buffer for received data, in bytes
+pub const RX_BUFFER_SIZE: usize = 256;
+/// Size of the buffer for data to be sent, in bytes
+pub const TX_BUFFER_SIZE: usize = 256;
+
+// Bits of the EP1_CONF register
+const WR_DONE: u32 = 0b001;
+const SERIAL_IN_EP_DATA_FREE: u32 = 0b010;
+const SERIAL_OUT_EP_DATA_AVAIL: u32 = 0b100;
+
+// Bits of the interrupt registers
+const SERIAL_OUT_RECV_PKT_INT: u32 = 1 << 2;
+const SERIAL_IN_EMPTY_INT: u32 = 1 << 3;
+
+static RX_BUFFER: Mutex<RefCell<ByteRing<RX_BUFFER_SIZE>>> =
+    Mutex::new(RefCell::new(ByteRing::new()));
+static TX_BUFFER: Mutex<RefCell<ByteRing<TX_BUFFER_SIZE>>> =
+    Mutex::new(RefCell::new(ByteRing::new()));
+static RX_WAKER: WakerSlot = WakerSlot::new();
+static TX_WAKER: WakerSlot = WakerSlot::new();
+
+/// The serial port of the USB Serial/JTAG controller
 pub struct UsbSerialJtag;
 
-impl core::fmt::Write for UsbSerialJtag {
-    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+impl UsbSerialJtag {
+    /// Write all of `data` and wait until it has been handed to the host
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        for &byte in data {
+            nb::block!(self.write_byte_nb(byte)).ok();
+        }
+
+        nb::block!(self.flush_tx_nb()).ok();
+    }
+
+    /// Write a single byte, without waiting for it to be sent
+    ///
+    /// Returns [nb::Error::WouldBlock] while the transmit FIFO (or, when
+    /// listening, the transmit buffer) is full.
+    pub fn write_byte_nb(&mut self, byte: u8) -> nb::Result<(), Infallible> {
         let reg_block = unsafe { &*USB_DEVICE::PTR };
 
-        // TODO: 64 byte chunks max
-        for chunk in s.as_bytes().chunks(32) {
-            unsafe {
-                for &b in chunk {
-                    reg_block.ep1.write(|w| w.bits(b.into()))
+        if is_listening() {
+            return critical_section::with(|cs| {
+                let mut tx_buffer = TX_BUFFER.borrow(cs).borrow_mut();
+                if !tx_buffer.push(byte) {
+                    return Err(nb::Error::WouldBlock);
                 }
-                reg_block.ep1_conf.write(|w| w.bits(0b001));
 
-                while reg_block.ep1_conf.read().bits() & 0b011 == 0b000 {
-                    // wait
+                // The interrupt handler moves the data into the FIFO
+                reg_block
+                    .int_ena
+                    .modify(|r, w| unsafe { w.bits(r.bits() | SERIAL_IN_EMPTY_INT) });
+
+                Ok(())
+            });
+        }
+
+        if reg_block.ep1_conf.read().bits() & SERIAL_IN_EP_DATA_FREE == 0 {
+            // The FIFO is full, send its contents to make room
+            reg_block.ep1_conf.write(|w| unsafe { w.bits(WR_DONE) });
+            return Err(nb::Error::WouldBlock);
+        }
+
+        reg_block.ep1.write(|w| unsafe { w.bits(byte as u32) });
+
+        Ok(())
+    }
+
+    /// Send the data written so far to the host
+    ///
+    /// Returns [nb::Error::WouldBlock] until all data has been handed to the
+    /// hardware.
+    pub fn flush_tx_nb(&mut self) -> nb::Result<(), Infallible> {
+        let reg_block = unsafe { &*USB_DEVICE::PTR };
+
+        if is_listening() {
+            let pending = critical_section::with(|cs| !TX_BUFFER.borrow(cs).borrow().is_empty());
+            return if pending {
+                Err(nb::Error::WouldBlock)
+            } else {
+                Ok(())
+            };
+        }
+
+        reg_block.ep1_conf.write(|w| unsafe { w.bits(WR_DONE) });
+
+        if reg_block.ep1_conf.read().bits() & SERIAL_IN_EP_DATA_FREE == 0 {
+            Err(nb::Error::WouldBlock)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Read a single byte
+    ///
+    /// Returns [nb::Error::WouldBlock] if no data has been received.
+    pub fn read_byte(&mut self) -> nb::Result<u8, Infallible> {
+        let reg_block = unsafe { &*USB_DEVICE::PTR };
+
+        if is_listening() {
+            return critical_section::with(|cs| {
+                let byte = RX_BUFFER.borrow(cs).borrow_mut().pop();
+
+                // The interrupt handler stops reading from the FIFO when the
+                // buffer is full, so continue here now there is room again
+                receive_into_buffer(cs);
+
+                byte.ok_or(nb::Error::WouldBlock)
+            });
+        }
+
+        if reg_block.ep1_conf.read().bits() & SERIAL_OUT_EP_DATA_AVAIL == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        Ok(reg_block.ep1.read().bits() as u8)
+    }
+
+    /// Switch to buffered mode and enable the receive and transmit interrupts
+    ///
+    /// From now on, [handle_interrupt] must be called from the USB
+    /// Serial/JTAG interrupt handler.
+    pub fn listen(&mut self) {
+        let reg_block = unsafe { &*USB_DEVICE::PTR };
+
+        critical_section::with(|cs| {
+            RX_BUFFER.borrow(cs).borrow_mut().clear();
+            TX_BUFFER.borrow(cs).borrow_mut().clear();
+        });
+
+        reg_block
+            .int_clr
+            .write(|w| unsafe { w.bits(SERIAL_OUT_RECV_PKT_INT | SERIAL_IN_EMPTY_INT) });
+        reg_block
+            .int_ena
+            .modify(|r, w| unsafe { w.bits(r.bits() | SERIAL_OUT_RECV_PKT_INT) });
+    }
+
+    /// Disable the interrupts and return to blocking mode
+    ///
+    /// Data which has not been sent yet is discarded.
+    pub fn unlisten(&mut self) {
+        let reg_block = unsafe { &*USB_DEVICE::PTR };
+
+        reg_block.int_ena.modify(|r, w| unsafe {
+            w.bits(r.bits() & !(SERIAL_OUT_RECV_PKT_INT | SERIAL_IN_EMPTY_INT))
+        });
+
+        critical_section::with(|cs| TX_BUFFER.borrow(cs).borrow_mut().clear());
+    }
+}
+
+/// Service the USB Serial/JTAG interrupt
+///
+/// Call this from the interrupt handler after [UsbSerialJtag::listen]. It
+/// moves received data into the receive buffer, moves buffered data into the
+/// transmit FIFO and wakes pending async operations.
+pub fn handle_interrupt() {
+    let reg_block = unsafe { &*USB_DEVICE::PTR };
+    let status = reg_block.int_st.read().bits();
+
+    critical_section::with(|cs| {
+        receive_into_buffer(cs);
+
+        let mut tx_buffer = TX_BUFFER.borrow(cs).borrow_mut();
+        if !tx_buffer.is_empty() {
+            while reg_block.ep1_conf.read().bits() & SERIAL_IN_EP_DATA_FREE != 0 {
+                match tx_buffer.pop() {
+                    Some(byte) => reg_block.ep1.write(|w| unsafe { w.bits(byte as u32) }),
+                    None => break,
                 }
             }
+
+            reg_block.ep1_conf.write(|w| unsafe { w.bits(WR_DONE) });
+        }
+
+        if tx_buffer.is_empty() {
+            reg_block
+                .int_ena
+                .modify(|r, w| unsafe { w.bits(r.bits() & !SERIAL_IN_EMPTY_INT) });
         }
+    });
+
+    reg_block.int_clr.write(|w| unsafe { w.bits(status) });
+
+    RX_WAKER.wake();
+    TX_WAKER.wake();
+}
+
+fn is_listening() -> bool {
+    let reg_block = unsafe { &*USB_DEVICE::PTR };
+
+    reg_block.int_ena.read().bits() & SERIAL_OUT_RECV_PKT_INT != 0
+}
+
+/// Move received data from the FIFO into the receive buffer, as long as there
+/// is room
+fn receive_into_buffer(cs: CriticalSection) {
+    let reg_block = unsafe { &*USB_DEVICE::PTR };
+    let mut rx_buffer = RX_BUFFER.borrow(cs).borrow_mut();
+
+    while !rx_buffer.is_full() && reg_block.ep1_conf.read().bits() & SERIAL_OUT_EP_DATA_AVAIL != 0 {
+        rx_buffer.push(reg_block.ep1.read().bits() as u8);
+    }
+}
+
+/// Fixed-size byte FIFO
+struct ByteRing<const N: usize> {
+    data: [u8; N],
+    head: usize,
+    len: usize,
+}
 
-        core::fmt::Result::Ok(())
+impl<const N: usize> ByteRing<N> {
+    const fn new() -> Self {
+        Self {
+            data: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Append a byte, returning `false` if the buffer is full
+    fn push(&mut self, byte: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+
+        self.data[(self.head + self.len) % N] = byte;
+        self.len += 1;
+
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let byte = self.data[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+
+        Some(byte)
+    }
+
+    fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == N
+    }
+}
+
+impl core::fmt::Write for UsbSerialJtag {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_bytes(s.as_bytes());
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl embedded_io::ErrorType for UsbSerialJtag {
+    type Error = Infallible;
+}
+
+#[cfg(feature = "embedded-io")]
+impl embedded_io::Read for UsbSerialJtag {
+    /// Wait for at least one byte, then read as many as are available
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        buf[0] = nb::block!(self.read_byte())?;
+
+        Ok(1 + self.read_available(&mut buf[1..]))
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl embedded_io::Write for UsbSerialJtag {
+    /// Wait until at least one byte can be written, then write as many as fit
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        nb::block!(self.write_byte_nb(buf[0]))?;
+
+        Ok(1 + self.write_available(&buf[1..]))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        nb::block!(self.flush_tx_nb())
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl UsbSerialJtag {
+    fn read_available(&mut self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+        while count < buf.len() {
+            match self.read_byte() {
+                Ok(byte) => buf[count] = byte,
+                Err(_) => break,
+            }
+            count += 1;
+        }
+
+        count
+    }
+
+    fn write_available(&mut self, buf: &[u8]) -> usize {
+        let mut count = 0;
+        while count < buf.len() {
+            if self.write_byte_nb(buf[count]).is_err() {
+                break;
+            }
+            count += 1;
+        }
+
+        count
+    }
+}
+
+#[cfg(feature = "async")]
+mod asynch {
+    use super::{UsbSerialJtag, RX_WAKER, TX_WAKER};
+    use crate::asynch::NbFuture;
+
+    /// Requires [UsbSerialJtag::listen] and an interrupt handler calling
+    /// [super::handle_interrupt].
+    impl embedded_io_async::Read for UsbSerialJtag {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if buf.is_empty() {
+                return Ok(0);
+            }
+
+            buf[0] = NbFuture::new(&RX_WAKER, || self.read_byte()).await?;
+
+            Ok(1 + self.read_available(&mut buf[1..]))
+        }
+    }
+
+    /// Requires [UsbSerialJtag::listen] and an interrupt handler calling
+    /// [super::handle_interrupt].
+    impl embedded_io_async::Write for UsbSerialJtag {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            if buf.is_empty() {
+                return Ok(0);
+            }
+
+            NbFuture::new(&TX_WAKER, || self.write_byte_nb(buf[0])).await?;
+
+            Ok(1 + self.write_available(&buf[1..]))
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            NbFuture::new(&TX_WAKER, || self.flush_tx_nb()).await
+        }
     }
 }
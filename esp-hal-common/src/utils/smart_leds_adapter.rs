@@ -55,6 +55,7 @@ const SK68XX_T1L_CYCLES: NanosDuration<u32> =
 /// All types of errors that can happen during the conversion and transmission
 /// of LED commands
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum LedAdapterError {
     /// Raised in the event that the provided data container is not large enough
     BufferSizeExceeded,
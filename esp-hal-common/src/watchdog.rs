@@ -0,0 +1,129 @@
+//! # Automatic watchdog feeding
+//!
+//! [AutoFeed] feeds a watchdog, e.g. the RWDT or the MWDT of a timer group,
+//! from the interrupt of a general-purpose timer, so that the application
+//! does not have to feed it from its main loop. To still catch a stuck
+//! application, the watchdog is only fed while a health check passes, e.g.
+//! one checking that the main loop has made progress since the last feed:
+//!
+//! ```no_run
+//! static MAIN_LOOP_ALIVE: AtomicBool = AtomicBool::new(false);
+//! static AUTO_FEED: Mutex<RefCell<Option<AutoFeed<Rwdt, Timer0<TIMG1>>>>> =
+//!     Mutex::new(RefCell::new(None));
+//!
+//! fn healthy() -> bool {
+//!     MAIN_LOOP_ALIVE.swap(false, Ordering::Relaxed)
+//! }
+//!
+//! // feed the RWDT, which times out after 2 s, every 500 ms
+//! rtc.rwdt.start(2u64.secs());
+//! let auto_feed = AutoFeed::new(rtc.rwdt, timer_group1.timer0, 500u64.millis(), healthy);
+//! critical_section::with(|cs| AUTO_FEED.borrow_ref_mut(cs).replace(auto_feed));
+//! interrupt::enable(pac::Interrupt::TG1_T0_LEVEL, interrupt::Priority::Priority1).unwrap();
+//!
+//! loop {
+//!     // do work
+//!     MAIN_LOOP_ALIVE.store(true, Ordering::Relaxed);
+//! }
+//!
+//! #[interrupt]
+//! fn TG1_T0_LEVEL() {
+//!     critical_section::with(|cs| {
+//!         AUTO_FEED.borrow_ref_mut(cs).as_mut().unwrap().on_interrupt();
+//!     });
+//! }
+//! ```
+//!
+//! # Disabling the watchdogs
+//!
+//! The bootloader starts the RWDT and the MWDT of timer group 0 with flash
+//! boot protection, and the SWD on the ESP32-C3 and ESP32-S3. Applications
+//! which do not feed them disable all of them at once with
+//! [disable_watchdogs]:
+//!
+//! ```no_run
+//! let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+//! let mut timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+//! let mut timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+//!
+//! watchdog::disable_watchdogs(&mut rtc, &mut timer_group0.wdt, &mut timer_group1.wdt);
+//! ```
+
+use embedded_hal::{
+    timer::{Cancel, CountDown},
+    watchdog::{Watchdog, WatchdogDisable},
+};
+use fugit::MicrosDurationU64;
+
+use crate::{
+    pac::{TIMG0, TIMG1},
+    timer::{Instance, Timer, Wdt},
+    Rtc,
+};
+
+/// Disable the RWDT, the MWDTs of both timer groups and, on the ESP32-C3 and
+/// ESP32-S3, the SWD, including their flash boot protection
+pub fn disable_watchdogs(rtc: &mut Rtc, wdt0: &mut Wdt<TIMG0>, wdt1: &mut Wdt<TIMG1>) {
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    #[cfg(any(feature = "esp32c3", feature = "esp32s3"))]
+    rtc.swd.disable();
+}
+
+/// Feeds a watchdog from the interrupt of a timer while the system is healthy
+pub struct AutoFeed<W, T> {
+    watchdog: W,
+    timer: Timer<T>,
+    health_check: fn() -> bool,
+}
+
+impl<W, T> AutoFeed<W, T>
+where
+    W: Watchdog,
+    T: Instance,
+{
+    /// Start `timer` with the given feeding `period` and enable its interrupt
+    ///
+    /// The watchdog has to be started by the application, with a timeout
+    /// longer than `period`. [AutoFeed::on_interrupt] has to be called from
+    /// the interrupt handler of `timer`.
+    pub fn new<P>(watchdog: W, mut timer: Timer<T>, period: P, health_check: fn() -> bool) -> Self
+    where
+        P: Into<MicrosDurationU64>,
+    {
+        timer.start(period);
+        timer.listen();
+
+        Self {
+            watchdog,
+            timer,
+            health_check,
+        }
+    }
+
+    /// Handle the interrupt of the timer, feeding the watchdog if the health
+    /// check passes
+    pub fn on_interrupt(&mut self) {
+        // re-arms the alarm, the timer reloads itself
+        let _ = self.timer.wait();
+
+        if (self.health_check)() {
+            self.watchdog.feed();
+        } else {
+            trace!("health check failed, not feeding the watchdog");
+        }
+    }
+
+    /// Stop feeding the watchdog, and return it and the timer
+    ///
+    /// The watchdog keeps running, so it has to be fed or disabled by the
+    /// application from now on.
+    pub fn free(mut self) -> (W, Timer<T>) {
+        self.timer.unlisten();
+        self.timer.cancel().ok();
+
+        (self.watchdog, self.timer)
+    }
+}
@@ -34,6 +34,15 @@ struct RamArgs {
 /// The uninitialized option will skip initialization of the memory
 /// (e.g. to persist it across resets or deep sleep mode for the RTC RAM)
 ///
+/// Initialized and zeroed statics in RTC ram are initialized on power-on and
+/// on other resets, but keep their value when the chip wakes up from deep
+/// sleep, e.g. to count the wakeups:
+///
+/// ```rust
+/// #[ram(rtc_fast)]
+/// static mut WAKEUPS: u32 = 0;
+/// ```
+///
 /// Not all targets support RTC slow ram.
 
 #[proc_macro_attribute]
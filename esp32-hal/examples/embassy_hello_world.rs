@@ -0,0 +1,61 @@
+//! Two embassy tasks printing at different rates, driven by the time driver
+//! on timer 0 of timer group 0.
+
+#![no_std]
+#![no_main]
+
+use embassy_executor::Spawner;
+use embassy_time::{Duration, Timer};
+use esp32_hal::{
+    clock::ClockControl,
+    embassy::{self, Executor},
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use static_cell::StaticCell;
+use xtensa_lx_rt::entry;
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+#[embassy_executor::task]
+async fn run1() {
+    loop {
+        println!("Hello world from embassy using esp-hal-async!");
+        Timer::after(Duration::from_millis(1_000)).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn run2() {
+    loop {
+        println!("Bing!");
+        Timer::after(Duration::from_millis(5_000)).await;
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.DPORT.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    embassy::init(&clocks, timer_group0.timer0);
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner: Spawner| {
+        spawner.must_spawn(run1());
+        spawner.must_spawn(run2());
+    });
+}
@@ -54,6 +54,7 @@ fn main() -> ! {
         Efuse::get_flash_encryption()
     )
     .unwrap();
+    writeln!(serial0, "Secure Boot {:?}", Efuse::is_secure_boot_enabled()).unwrap();
 
     loop {}
 }
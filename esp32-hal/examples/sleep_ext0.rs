@@ -0,0 +1,50 @@
+//! Enters deep sleep until the BOOT button on GPIO0 is pressed, or 30
+//! seconds have passed. The chip is reset when it wakes up, so the message is
+//! printed again.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32_hal::{
+    clock::ClockControl,
+    pac::Peripherals,
+    prelude::*,
+    rtc_cntl::sleep::{Ext0WakeupSource, TimerWakeupSource, WakeupLevel},
+    timer::TimerGroup,
+    Delay,
+    Rtc,
+    Serial,
+    IO,
+};
+use esp_backtrace as _;
+use xtensa_lx_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.DPORT.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let mut serial0 = Serial::new(peripherals.UART0);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let mut delay = Delay::new(&clocks);
+
+    writeln!(serial0, "Up and running, press BOOT to wake up from deep sleep").unwrap();
+    // let the message leave the UART FIFO
+    delay.delay_ms(100u32);
+
+    // the BOOT button pulls GPIO0 low
+    let button = Ext0WakeupSource::new(io.pins.gpio0, WakeupLevel::Low);
+    let timer = TimerWakeupSource::new(30u64.secs());
+    rtc.sleep_deep(&[&button, &timer]);
+}
@@ -0,0 +1,70 @@
+//! This shows how to use the self-test mode of the TWAI (CAN) peripheral to
+//! check the wiring to a transceiver without a second node on the bus.
+//!
+//! In self-test mode, transmitted frames don't need to be acknowledged and are
+//! received back by the controller. Connect the transceiver's TX input to
+//! GPIO2 and its RX output to GPIO3.
+
+#![no_std]
+#![no_main]
+
+use embedded_can::{Frame, StandardId};
+use esp32_hal::{
+    clock::ClockControl,
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    twai::{BaudRate, EspTwaiFrame, TwaiConfiguration, TwaiMode},
+    Delay,
+    Rtc,
+    IO,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use nb::block;
+use xtensa_lx_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.DPORT.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let mut delay = Delay::new(&clocks);
+
+    let mut config = TwaiConfiguration::new(
+        peripherals.TWAI,
+        io.pins.gpio2,
+        io.pins.gpio3,
+        &mut system.peripheral_clock_control,
+        BaudRate::B125K,
+    );
+    config.set_mode(TwaiMode::SelfTest);
+
+    let mut twai = config.start();
+
+    let mut counter: u8 = 0;
+    loop {
+        let frame = EspTwaiFrame::new(StandardId::new(0x123).unwrap(), &[counter]).unwrap();
+        block!(twai.transmit(&frame)).unwrap();
+
+        let received = block!(twai.receive()).unwrap();
+        println!(
+            "Received frame {:?}: {:02x?}",
+            received.id(),
+            received.data()
+        );
+
+        counter = counter.wrapping_add(1);
+        delay.delay_ms(1000u32);
+    }
+}
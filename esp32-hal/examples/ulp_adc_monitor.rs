@@ -0,0 +1,67 @@
+//! Lets the ULP sample a potentiometer connected to GPIO34, and prints the
+//! latest sample. Outside of 1000..3000 the ULP would wake up the CPUs from
+//! deep sleep.
+
+#![no_std]
+#![no_main]
+
+use esp32_hal::{
+    adc::{AdcConfig, Attenuation, ADC, ADC1},
+    clock::ClockControl,
+    gpio::IO,
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    ulp::{
+        adc::{AdcThresholds, ADC_SAMPLE},
+        UlpFsm,
+    },
+    Delay,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.DPORT.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let pin34 = io.pins.gpio34.into_analog();
+
+    let analog = peripherals.SENS.split();
+
+    let mut adc1_config = AdcConfig::new();
+    adc1_config.enable_pin(&pin34, Attenuation::Attenuation11dB);
+    let adc1 = ADC::<ADC1>::adc(analog.adc1, adc1_config).unwrap();
+
+    let mut ulp = UlpFsm::new(&mut rtc.ulp);
+    ulp.run_adc_monitor(
+        adc1,
+        &pin34,
+        AdcThresholds {
+            low: 1000,
+            high: 3000,
+        },
+        100u64.millis(),
+    )
+    .unwrap();
+
+    let mut delay = Delay::new(&clocks);
+
+    loop {
+        println!("ULP sample = {}", ulp.read_word(ADC_SAMPLE) & 0xffff);
+        delay.delay_ms(500u32);
+    }
+}
@@ -74,3 +74,15 @@ analog! {
     Gpio14: (16, touch_pad6,   hold,        mux_sel,        fun_sel,        fun_ie,        slp_ie,        slp_sel,        rue,       rde,       drv,       slp_oe),
     Gpio27: (17, touch_pad7,   hold,        mux_sel,        fun_sel,        fun_ie,        slp_ie,        slp_sel,        rue,       rde,       drv,       slp_oe),
 }
+
+pin_functions! {
+    AdcPin: [Gpio0, Gpio2, Gpio4, Gpio12, Gpio13, Gpio14, Gpio15, Gpio25, Gpio26, Gpio27, Gpio32, Gpio33, Gpio34, Gpio35, Gpio36, Gpio37, Gpio38, Gpio39],
+    TouchPin: [Gpio0, Gpio2, Gpio4, Gpio12, Gpio13, Gpio14, Gpio15, Gpio27, Gpio32, Gpio33],
+    IoMuxSpiSck: [Gpio14],
+    IoMuxSpiMosi: [Gpio13],
+    IoMuxSpiMiso: [Gpio12],
+    IoMuxSpiCs: [Gpio15],
+    ClkOut1Pin: [Gpio0],
+    ClkOut2Pin: [Gpio3],
+    ClkOut3Pin: [Gpio1],
+}
@@ -11,7 +11,7 @@ use esp32c3_hal::{
     pac::Peripherals,
     prelude::*,
     serial::{
-        config::{Config, DataBits, Parity, StopBits},
+        config::{Config, DataBits, StopBits},
         TxRxPins,
     },
     timer::TimerGroup,
@@ -43,12 +43,11 @@ fn main() -> ! {
     wdt0.disable();
     wdt1.disable();
 
-    let config = Config {
-        baudrate: 115200,
-        data_bits: DataBits::DataBits8,
-        parity: Parity::ParityNone,
-        stop_bits: StopBits::STOP1,
-    };
+    let config = Config::default()
+        .baudrate(115200)
+        .data_bits(DataBits::DataBits8)
+        .parity_none()
+        .stop_bits(StopBits::STOP1);
 
     let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
     let pins = TxRxPins::new_tx_rx(
@@ -0,0 +1,64 @@
+//! Panics a few seconds after boot, records the crash and resets. After boot,
+//! the number of crashes so far is printed, and the history is cleared after
+//! three crashes.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32c3_hal::{
+    clock::ClockControl,
+    crash::{self, CrashReason},
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Rtc,
+    Serial,
+};
+use nb::block;
+use riscv_rt::entry;
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    crash::reset_after_crash(CrashReason::Panic)
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let mut serial0 = Serial::new(peripherals.UART0);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut timer0 = timer_group0.timer0;
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    let history = crash::history();
+    writeln!(
+        serial0,
+        "Crashes: {}, last reason: {:?}",
+        history.count, history.last_reason
+    )
+    .unwrap();
+
+    if history.count >= 3 {
+        writeln!(serial0, "Too many crashes, clearing the history").unwrap();
+        crash::clear_history();
+    }
+
+    timer0.start(3u64.secs());
+    block!(timer0.wait()).unwrap();
+
+    panic!("Crashing on purpose");
+}
@@ -11,6 +11,7 @@ use esp32c3_hal::{
     pac::Peripherals,
     prelude::*,
     timer::TimerGroup,
+    watchdog,
     Rtc,
     Serial,
 };
@@ -21,6 +22,7 @@ use riscv_rt::entry;
 #[entry]
 fn main() -> ! {
     let peripherals = Peripherals::take().unwrap();
+
     let system = peripherals.SYSTEM.split();
     let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
 
@@ -32,11 +34,8 @@ fn main() -> ! {
     let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
     let mut wdt1 = timer_group1.wdt;
 
-    // Disable watchdog timers
-    rtc.swd.disable();
-    rtc.rwdt.disable();
-    wdt0.disable();
-    wdt1.disable();
+    // Disable the watchdog timers, including their flash boot protection
+    watchdog::disable_watchdogs(&mut rtc, &mut wdt0, &mut wdt1);
 
     timer0.start(1u64.secs());
 
@@ -0,0 +1,50 @@
+//! This shows how to use the HMAC accelerator to compute an HMAC-SHA256 with
+//! a key stored in eFuse block KEY0.
+//!
+//! KEY0 must have been burnt with the `HMAC_UP` purpose, e.g. using
+//! `espefuse.py burn_key BLOCK_KEY0 key.bin HMAC_UP`.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    clock::ClockControl,
+    hmac::KeyId,
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Hmac,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    let mut hmac = Hmac::new(peripherals.HMAC, &mut system.peripheral_clock_control);
+
+    let mut output = [0u8; 32];
+    match hmac.calculate(KeyId::Key0, b"Hello ESP32-C3", &mut output) {
+        Ok(()) => println!("HMAC: {:02x?}", output),
+        Err(err) => println!("HMAC calculation failed: {:?}", err),
+    }
+
+    loop {}
+}
@@ -0,0 +1,51 @@
+//! This shows how to use the RSA accelerator to compute a modular
+//! exponentiation, as used when verifying an RSA signature.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{clock::ClockControl, pac::Peripherals, prelude::*, timer::TimerGroup, Rsa, Rtc};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+// 512-bit modulus
+const MODULUS: [u8; 64] = [
+    0xd1, 0x7c, 0x3a, 0x52, 0x9e, 0x0b, 0x44, 0x87, 0x6f, 0x21, 0xc9, 0x35, 0x8a, 0x13, 0xe6, 0x70,
+    0x4d, 0xb2, 0x98, 0x0c, 0x5f, 0x71, 0x26, 0xe3, 0xa4, 0x3b, 0x17, 0xd8, 0x62, 0x9f, 0x05, 0xcc,
+    0x38, 0x81, 0x6e, 0xf4, 0x0a, 0xd3, 0x57, 0x29, 0xb6, 0x4c, 0x1e, 0x93, 0x7a, 0xe5, 0x02, 0x6d,
+    0xc1, 0x58, 0x2f, 0x96, 0x3d, 0xaa, 0x74, 0x0e, 0xbb, 0x45, 0x19, 0xe0, 0x67, 0x8c, 0x33, 0x5b,
+];
+
+const EXPONENT: [u8; 3] = [0x01, 0x00, 0x01];
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    let mut rsa = Rsa::new(peripherals.RSA, &mut system.peripheral_clock_control);
+
+    let base = [0x42u8; 32];
+    let mut result = [0u8; 64];
+
+    rsa.modular_exponentiation(&base, &EXPONENT, &MODULUS, &mut result)
+        .unwrap();
+
+    println!("base ^ 65537 mod m = {:02x?}", result);
+
+    loop {}
+}
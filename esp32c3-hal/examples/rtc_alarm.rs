@@ -0,0 +1,65 @@
+//! This demos the alarm of the RTC timer, which keeps counting in sleep.
+//! The alarm triggers an interrupt every second, and is set again from the
+//! interrupt handler.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use esp32c3_hal::{
+    clock::ClockControl,
+    interrupt,
+    pac::{self, Peripherals},
+    prelude::*,
+    Rtc,
+};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+static RTC: Mutex<RefCell<Option<Rtc>>> = Mutex::new(RefCell::new(None));
+
+const PERIOD_US: u64 = 1_000_000;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let _clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+
+    let now = rtc.get_time_us();
+    rtc.set_alarm(now + PERIOD_US);
+    rtc.listen();
+
+    interrupt::enable(pac::Interrupt::RTC_CORE, interrupt::Priority::Priority1).unwrap();
+
+    critical_section::with(|cs| RTC.borrow_ref_mut(cs).replace(rtc));
+
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    loop {}
+}
+
+#[interrupt]
+fn RTC_CORE() {
+    critical_section::with(|cs| {
+        let mut rtc = RTC.borrow_ref_mut(cs);
+        let rtc = rtc.as_mut().unwrap();
+
+        rtc.clear_interrupt();
+
+        let now = rtc.get_time_us();
+        esp_println::println!("RTC alarm at {} us", now);
+
+        rtc.set_alarm(now + PERIOD_US);
+    });
+}
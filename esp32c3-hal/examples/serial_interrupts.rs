@@ -23,7 +23,7 @@ use esp_backtrace as _;
 use nb::block;
 use riscv_rt::entry;
 
-static SERIAL: Mutex<RefCell<Option<Serial<UART0>>>> = Mutex::new(RefCell::new(None));
+static SERIAL: Mutex<RefCell<Option<Serial<'static, UART0>>>> = Mutex::new(RefCell::new(None));
 
 #[entry]
 fn main() -> ! {
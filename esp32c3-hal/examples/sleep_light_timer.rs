@@ -0,0 +1,50 @@
+//! Enters light sleep for 1 second in a loop, printing a message each time it
+//! wakes up. The UART is flushed before entering sleep and the CPU clock is
+//! restored after waking up, so the output continues where it left off.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32c3_hal::{
+    clock::{ClockControl, CpuClock},
+    pac::Peripherals,
+    prelude::*,
+    rtc_cntl::sleep::{LightSleepConfig, TimerWakeupSource},
+    timer::TimerGroup,
+    Rtc,
+    Serial,
+};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::configure(system.clock_control, CpuClock::Clock160MHz).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let mut serial0 = Serial::new(peripherals.UART0);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    let timer = TimerWakeupSource::new(1u64.secs());
+
+    let mut wakeups = 0u32;
+
+    loop {
+        writeln!(serial0, "Woke up {} times, entering light sleep", wakeups).unwrap();
+        rtc.sleep_light(LightSleepConfig::default(), &[&timer]);
+        wakeups += 1;
+    }
+}
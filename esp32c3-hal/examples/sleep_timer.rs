@@ -0,0 +1,74 @@
+//! Prints a message, then enters deep sleep for 5 seconds. The chip is reset
+//! when it wakes up, so the message is printed again. The number of wakeups
+//! is kept in RTC fast memory, which survives deep sleep, and the reset reason
+//! and the wakeup cause are printed after each wakeup.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32c3_hal::{
+    clock::ClockControl,
+    macros::ram,
+    pac::Peripherals,
+    prelude::*,
+    rtc_cntl::{reset_reason, sleep::TimerWakeupSource, wakeup_cause},
+    timer::TimerGroup,
+    Cpu,
+    Delay,
+    Rtc,
+    Serial,
+};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+#[ram(rtc_fast)]
+static mut WAKEUPS: u32 = 0;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let mut serial0 = Serial::new(peripherals.UART0);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    let mut delay = Delay::new(&clocks);
+
+    let wakeups = unsafe {
+        let wakeups = WAKEUPS;
+        WAKEUPS += 1;
+        wakeups
+    };
+
+    writeln!(
+        serial0,
+        "Reset reason: {:?}, wakeup cause: {:?}",
+        reset_reason(Cpu::ProCpu),
+        wakeup_cause()
+    )
+    .unwrap();
+    writeln!(
+        serial0,
+        "Up and running after {} wakeups, entering deep sleep for 5 s",
+        wakeups
+    )
+    .unwrap();
+    // let the message leave the UART FIFO
+    delay.delay_ms(100u32);
+
+    let timer = TimerWakeupSource::new(5u64.secs());
+    rtc.sleep_deep(&[&timer]);
+}
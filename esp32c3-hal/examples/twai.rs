@@ -0,0 +1,74 @@
+//! This shows how to configure the TWAI (CAN) peripheral and echo received
+//! frames back onto the bus.
+//!
+//! An external transceiver is required; connect its TX input to GPIO2 and its
+//! RX output to GPIO3. Only standard frames with an identifier in the range
+//! 0x100 to 0x1ff are accepted.
+
+#![no_std]
+#![no_main]
+
+use embedded_can::{blocking::Can, Frame, StandardId};
+use esp32c3_hal::{
+    clock::ClockControl,
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    twai::{filter::SingleStandardFilter, BaudRate, TwaiConfiguration},
+    Rtc,
+    IO,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+
+    let mut config = TwaiConfiguration::new(
+        peripherals.TWAI,
+        io.pins.gpio2,
+        io.pins.gpio3,
+        &mut system.peripheral_clock_control,
+        BaudRate::B125K,
+    );
+
+    config.set_filter(SingleStandardFilter::new(
+        StandardId::new(0x100).unwrap(),
+        0x700,
+    ));
+
+    let mut twai = config.start();
+
+    loop {
+        echo(&mut twai);
+    }
+}
+
+/// Works with any driver implementing the `embedded-can` traits
+fn echo<C: Can>(can: &mut C)
+where
+    C::Error: core::fmt::Debug,
+{
+    let frame = can.receive().unwrap();
+    println!("Received frame {:?}: {:02x?}", frame.id(), frame.data());
+
+    can.transmit(&frame).unwrap();
+}
@@ -0,0 +1,85 @@
+//! This shows how to buffer received TWAI (CAN) frames from the interrupt
+//! handler, so no frames are lost while the application is busy.
+//!
+//! An external transceiver is required; connect its TX input to GPIO2 and its
+//! RX output to GPIO3.
+
+#![no_std]
+#![no_main]
+
+use embedded_can::Frame;
+use esp32c3_hal::{
+    clock::ClockControl,
+    interrupt,
+    pac::{self, Peripherals},
+    prelude::*,
+    timer::TimerGroup,
+    twai::{self, BaudRate, TwaiConfiguration},
+    Cpu,
+    Delay,
+    Rtc,
+    IO,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+
+    let config = TwaiConfiguration::new(
+        peripherals.TWAI,
+        io.pins.gpio2,
+        io.pins.gpio3,
+        &mut system.peripheral_clock_control,
+        BaudRate::B125K,
+    );
+
+    let mut twai = config.start();
+    twai.listen();
+
+    interrupt::enable(pac::Interrupt::TWAI, interrupt::Priority::Priority1).unwrap();
+    interrupt::set_kind(
+        Cpu::ProCpu,
+        interrupt::CpuInterrupt::Interrupt1, // Interrupt 1 handles priority one interrupts
+        interrupt::InterruptKind::Level,
+    );
+
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    let mut delay = Delay::new(&clocks);
+
+    loop {
+        // Frames arriving in the meantime are stored in the RX queue
+        delay.delay_ms(1000u32);
+
+        println!("{} frames waiting", twai.num_available_messages());
+        while let Ok(frame) = twai.receive() {
+            println!("Received frame {:?}: {:02x?}", frame.id(), frame.data());
+        }
+    }
+}
+
+#[interrupt]
+fn TWAI() {
+    twai::handle_interrupt();
+}
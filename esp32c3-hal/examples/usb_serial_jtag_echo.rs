@@ -0,0 +1,65 @@
+//! This shows how to use the USB Serial/JTAG serial port through the
+//! `embedded-io` traits: every line received is echoed back.
+//! You need to connect via the Serial/JTAG interface to see any output.
+//!
+//! This example requires the `embedded-io` feature.
+
+#![no_std]
+#![no_main]
+
+use embedded_io::{Read, Write};
+use esp32c3_hal::{
+    clock::ClockControl,
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Rtc,
+    UsbSerialJtag,
+};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    let mut usb_serial = UsbSerialJtag;
+    usb_serial
+        .write_all(b"Type something and press enter\r\n")
+        .unwrap();
+
+    let mut line = [0u8; 128];
+    let mut len = 0;
+
+    loop {
+        let mut buf = [0u8; 32];
+        let count = usb_serial.read(&mut buf).unwrap();
+
+        for &byte in &buf[..count] {
+            if len < line.len() {
+                line[len] = byte;
+                len += 1;
+            }
+
+            if byte == b'\r' || byte == b'\n' {
+                usb_serial.write_all(&line[..len]).unwrap();
+                usb_serial.flush().unwrap();
+                len = 0;
+            }
+        }
+    }
+}
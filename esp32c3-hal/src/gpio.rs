@@ -45,3 +45,11 @@ analog! {
     Gpio4 => 4
     Gpio5 => 4
 }
+
+pin_functions! {
+    AdcPin: [Gpio0, Gpio1, Gpio2, Gpio3, Gpio4, Gpio5],
+    IoMuxSpiSck: [Gpio6],
+    IoMuxSpiMosi: [Gpio7],
+    IoMuxSpiMiso: [Gpio2],
+    IoMuxSpiCs: [Gpio10],
+}
@@ -3,27 +3,49 @@
 use core::arch::global_asm;
 
 pub use embedded_hal as ehal;
+#[cfg(feature = "embassy")]
+pub use esp_hal_common::embassy;
+#[cfg(feature = "rtic")]
+pub use esp_hal_common::monotonic;
 pub use esp_hal_common::{
     clock,
+    crash,
+    delay,
+    ds,
     efuse,
+    error,
     gpio as gpio_types,
+    hmac,
     i2c,
     interrupt,
     ledc,
     macros,
     pac,
+    peripherals,
     prelude,
     pulse_control,
+    reset,
+    rom,
+    rsa,
+    rtc_cntl,
     serial,
     spi,
     system,
     systimer,
+    time,
     timer,
+    twai,
+    usb_serial_jtag,
     utils,
+    watchdog,
     Cpu,
     Delay,
+    Ds,
+    Error,
+    Hmac,
     PulseControl,
     Rng,
+    Rsa,
     Rtc,
     Rwdt,
     Serial,
@@ -281,11 +303,14 @@ abort_hal:
 unsafe fn init() {
     r0::init_data(&mut _srwtext, &mut _erwtext, &_irwtext);
 
-    r0::init_data(
-        &mut _rtc_fast_data_start,
-        &mut _rtc_fast_data_end,
-        &_irtc_fast_data,
-    );
+    // keep the RTC data which has survived deep sleep
+    if !esp_hal_common::rtc_cntl::woken_from_deep_sleep() {
+        r0::init_data(
+            &mut _rtc_fast_data_start,
+            &mut _rtc_fast_data_end,
+            &_irtc_fast_data,
+        );
+    }
 
     r0::init_data(&mut _srtc_fast_text, &mut _ertc_fast_text, &_irtc_fast_text);
 }
@@ -294,8 +319,11 @@ unsafe fn init() {
 #[export_name = "_mp_hook"]
 #[doc(hidden)]
 pub fn mp_hook() -> bool {
-    unsafe {
-        r0::zero_bss(&mut _rtc_fast_bss_start, &mut _rtc_fast_bss_end);
+    // keep the RTC data which has survived deep sleep
+    if !esp_hal_common::rtc_cntl::woken_from_deep_sleep() {
+        unsafe {
+            r0::zero_bss(&mut _rtc_fast_bss_start, &mut _rtc_fast_bss_end);
+        }
     }
 
     #[cfg(feature = "direct-boot")]
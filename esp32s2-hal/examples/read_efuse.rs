@@ -8,7 +8,7 @@ use core::fmt::Write;
 
 use esp32s2_hal::{
     clock::ClockControl,
-    efuse::Efuse,
+    efuse::{Efuse, KeyBlock},
     pac::Peripherals,
     prelude::*,
     timer::TimerGroup,
@@ -39,6 +39,13 @@ fn main() -> ! {
         Efuse::get_flash_encryption()
     )
     .unwrap();
+    writeln!(serial0, "Secure Boot {:?}", Efuse::is_secure_boot_enabled()).unwrap();
+    writeln!(
+        serial0,
+        "KEY0 purpose {:?}",
+        Efuse::get_key_purpose(KeyBlock::Key0)
+    )
+    .unwrap();
 
     loop {}
 }
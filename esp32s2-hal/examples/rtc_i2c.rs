@@ -0,0 +1,66 @@
+//! Reads the chip ID of a BMP180 pressure sensor with the RTC I2C controller,
+//! which can also be used by the ULP coprocessor during deep sleep.
+//!
+//! The following wiring is assumed, with external pull-ups on both lines:
+//! - SDA => GPIO1
+//! - SCL => GPIO0
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32s2_hal::{
+    clock::ClockControl,
+    gpio::IO,
+    pac::Peripherals,
+    prelude::*,
+    rtc_i2c::RtcI2c,
+    timer::TimerGroup,
+    Delay,
+    Rtc,
+    Serial,
+};
+use esp_backtrace as _;
+use xtensa_lx_rt::entry;
+
+const BMP180_ADDRESS: u8 = 0x77;
+const BMP180_CHIP_ID: u8 = 0xd0;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let mut serial0 = Serial::new(peripherals.UART0);
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let mut delay = Delay::new(&clocks);
+
+    let mut i2c = RtcI2c::new(
+        peripherals.RTC_I2C,
+        io.pins.gpio1,
+        io.pins.gpio0,
+        100u32.kHz(),
+    )
+    .unwrap();
+
+    loop {
+        let mut chip_id = [0u8];
+        match i2c.read(BMP180_ADDRESS, BMP180_CHIP_ID, &mut chip_id) {
+            Ok(()) => writeln!(serial0, "Chip ID: {:#04x}", chip_id[0]).unwrap(),
+            Err(e) => writeln!(serial0, "Error: {}", e).unwrap(),
+        }
+
+        delay.delay_ms(1000u32);
+    }
+}
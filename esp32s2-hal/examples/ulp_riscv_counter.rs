@@ -0,0 +1,65 @@
+//! Runs a small program on the RISC-V ULP, which increments a counter in RTC
+//! slow memory, and prints the counter.
+//!
+//! The program is hand-assembled here; a real application would build it with
+//! a RISC-V toolchain and `include_bytes!` the binary.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32s2_hal::{
+    clock::ClockControl,
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    ulp::UlpRiscv,
+    Delay,
+    Rtc,
+    Serial,
+};
+use esp_backtrace as _;
+use xtensa_lx_rt::entry;
+
+/// Word offset of the counter in RTC slow memory (address 0x1000 for the ULP)
+const COUNTER: usize = 0x1000 / 4;
+
+#[rustfmt::skip]
+static ULP_PROGRAM: [u8; 20] = [
+    0x37, 0x15, 0x00, 0x00, // lui  a0, 0x1
+    0x83, 0x25, 0x05, 0x00, // lw   a1, 0(a0)
+    0x93, 0x85, 0x15, 0x00, // addi a1, a1, 1
+    0x23, 0x20, 0xb5, 0x00, // sw   a1, 0(a0)
+    0x6f, 0xf0, 0x5f, 0xff, // j    -12
+];
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let mut serial0 = Serial::new(peripherals.UART0);
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let mut delay = Delay::new(&clocks);
+
+    let mut ulp = UlpRiscv::new(&mut rtc.ulp);
+    ulp.write_word(COUNTER, 0);
+    ulp.load(&ULP_PROGRAM).unwrap();
+    ulp.set_wakeup_period(10u64.millis());
+    ulp.run();
+
+    loop {
+        writeln!(serial0, "ULP counter: {}", ulp.read_word(COUNTER)).unwrap();
+        delay.delay_ms(500u32);
+    }
+}
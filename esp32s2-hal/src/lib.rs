@@ -1,26 +1,49 @@
 #![no_std]
 
 pub use embedded_hal as ehal;
+#[cfg(feature = "embassy")]
+pub use esp_hal_common::embassy;
+#[cfg(feature = "rtic")]
+pub use esp_hal_common::monotonic;
+#[cfg(feature = "usb_otg")]
+pub use esp_hal_common::otg_fs;
 pub use esp_hal_common::{
     clock,
+    crash,
+    delay,
     efuse,
+    error,
     gpio as gpio_types,
     i2c::{self, I2C},
     interrupt,
     ledc,
     macros,
     pac,
+    peripherals,
     prelude,
     pulse_control,
+    reset,
+    rom,
+    rsa,
+    rtc_cntl,
+    rtc_i2c,
+    rtc_io,
     serial,
     spi,
     systimer,
+    time,
     timer,
+    touch,
+    twai,
+    ulp,
     utils,
+    watchdog,
     Cpu,
     Delay,
+    Error,
     PulseControl,
     Rng,
+    Rsa,
     Rtc,
     Rwdt,
     Serial,
@@ -70,9 +93,11 @@ pub unsafe extern "C" fn ESP32Reset() -> ! {
     // copying data from flash to various data segments is done by the bootloader
     // initialization to zero needs to be done by the application
 
-    // Initialize RTC RAM
-    xtensa_lx_rt::zero_bss(&mut _rtc_fast_bss_start, &mut _rtc_fast_bss_end);
-    xtensa_lx_rt::zero_bss(&mut _rtc_slow_bss_start, &mut _rtc_slow_bss_end);
+    // Initialize RTC RAM, unless its contents have survived deep sleep
+    if !esp_hal_common::rtc_cntl::woken_from_deep_sleep() {
+        xtensa_lx_rt::zero_bss(&mut _rtc_fast_bss_start, &mut _rtc_fast_bss_end);
+        xtensa_lx_rt::zero_bss(&mut _rtc_slow_bss_start, &mut _rtc_slow_bss_end);
+    }
 
     // continue with default reset handler
     xtensa_lx_rt::Reset();
@@ -13,7 +13,7 @@ use esp32s3_hal::{
     pac::Peripherals,
     prelude::*,
     serial::{
-        config::{Config, DataBits, Parity, StopBits},
+        config::{Config, DataBits, StopBits},
         TxRxPins,
     },
     timer::TimerGroup,
@@ -39,12 +39,11 @@ fn main() -> ! {
     wdt.disable();
     rtc.rwdt.disable();
 
-    let config = Config {
-        baudrate: 115200,
-        data_bits: DataBits::DataBits8,
-        parity: Parity::ParityNone,
-        stop_bits: StopBits::STOP1,
-    };
+    let config = Config::default()
+        .baudrate(115200)
+        .data_bits(DataBits::DataBits8)
+        .parity_none()
+        .stop_bits(StopBits::STOP1);
 
     let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
     let pins = TxRxPins::new_tx_rx(
@@ -0,0 +1,88 @@
+//! This shows how to capture frames from a DVP camera sensor with the camera
+//! controller of the LCD_CAM peripheral.
+//!
+//! The sensor (e.g. an OV2640 configured for QVGA RGB565 over SCCB) is
+//! expected to be connected as follows:
+//! - D0..D7 => GPIO9..GPIO16
+//! - VSYNC  => GPIO6
+//! - HREF   => GPIO7
+//! - PCLK   => GPIO17
+//! - XCLK   => GPIO18
+
+#![no_std]
+#![no_main]
+
+use esp32s3_hal::{
+    clock::ClockControl,
+    gdma::{descriptor_count, DmaDescriptor, Gdma},
+    lcd_cam::{
+        cam::{Camera, CameraConfig, RxEightBits},
+        LcdCam,
+    },
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Rtc,
+    IO,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+// A QVGA (320x240) RGB565 frame
+const FRAME_SIZE: usize = 320 * 240 * 2;
+
+static mut DESCRIPTORS: [DmaDescriptor; descriptor_count(FRAME_SIZE)] =
+    [DmaDescriptor::EMPTY; descriptor_count(FRAME_SIZE)];
+static mut FRAME: [u8; FRAME_SIZE] = [0; FRAME_SIZE];
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+
+    let lcd_cam = LcdCam::new(peripherals.LCD_CAM, &mut system.peripheral_clock_control);
+    let gdma = Gdma::new(peripherals.DMA, &mut system.peripheral_clock_control);
+
+    let pins = RxEightBits::new(
+        io.pins.gpio9,
+        io.pins.gpio10,
+        io.pins.gpio11,
+        io.pins.gpio12,
+        io.pins.gpio13,
+        io.pins.gpio14,
+        io.pins.gpio15,
+        io.pins.gpio16,
+    );
+
+    let mut camera = Camera::new(
+        lcd_cam.cam,
+        gdma.channel1,
+        unsafe { &mut DESCRIPTORS },
+        pins,
+        20u32.MHz(),
+        CameraConfig::default(),
+    )
+    .with_master_clock(io.pins.gpio18)
+    .with_ctrl_pins(io.pins.gpio6, io.pins.gpio7, io.pins.gpio17);
+
+    let frame = unsafe { &mut FRAME };
+
+    loop {
+        match camera.capture(frame) {
+            Ok(len) => println!("Captured frame of {} bytes: {:02x?}", len, &frame[..16]),
+            Err(err) => println!("Capture failed: {:?}", err),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Blinks an LED with delays from a TIMG timer, which are awaited instead of
+//! busy-waiting, while another embassy task keeps running.
+//!
+//! This assumes that a LED is connected to the pin assigned to `led`. (GPIO4)
+
+#![no_std]
+#![no_main]
+
+use embassy_executor::Spawner;
+use embassy_time::{Duration, Ticker};
+use embedded_hal_async::delay::DelayNs;
+use esp32s3_hal::{
+    clock::ClockControl,
+    embassy::{self, Executor},
+    gpio::{Gpio4, Output, PushPull},
+    interrupt,
+    pac::{self, Peripherals, TIMG0},
+    prelude::*,
+    systimer::SystemTimer,
+    timer::{self, Timer, Timer0, TimerGroup},
+    Rtc,
+    IO,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use static_cell::StaticCell;
+use xtensa_lx_rt::entry;
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+#[embassy_executor::task]
+async fn blink(mut led: Gpio4<Output<PushPull>>, mut timer: Timer<Timer0<TIMG0>>) {
+    loop {
+        led.toggle().unwrap();
+        timer.delay_ms(250).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn tick() {
+    let mut ticker = Ticker::every(Duration::from_millis(1_000));
+    loop {
+        println!("Still running while the LED blinks");
+        ticker.next().await;
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+    let timer0 = timer_group0.timer0;
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let led = io.pins.gpio4.into_push_pull_output();
+
+    embassy::init(&clocks, SystemTimer::new(peripherals.SYSTIMER));
+
+    interrupt::enable(pac::Interrupt::TG0_T0_LEVEL, interrupt::Priority::Priority1).unwrap();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner: Spawner| {
+        spawner.must_spawn(blink(led, timer0));
+        spawner.must_spawn(tick());
+    });
+}
+
+#[interrupt]
+fn TG0_T0_LEVEL() {
+    timer::handle_interrupt();
+}
@@ -0,0 +1,81 @@
+//! Runs a task on an interrupt executor next to a busy task on the
+//! thread-mode executor: the interrupt executor preempts the busy task, so
+//! its timer keeps firing on time.
+
+#![no_std]
+#![no_main]
+
+use embassy_executor::Spawner;
+use embassy_time::{Duration, Instant, Timer};
+use esp32s3_hal::{
+    clock::ClockControl,
+    embassy::{self, Executor, InterruptExecutor},
+    interrupt,
+    pac::{self, Peripherals},
+    prelude::*,
+    systimer::SystemTimer,
+    timer::TimerGroup,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use static_cell::StaticCell;
+use xtensa_lx_rt::entry;
+
+static EXECUTOR_HIGH: InterruptExecutor<0> = InterruptExecutor::new();
+static EXECUTOR_LOW: StaticCell<Executor> = StaticCell::new();
+
+#[interrupt]
+fn FROM_CPU_INTR0() {
+    unsafe { EXECUTOR_HIGH.on_interrupt() }
+}
+
+#[embassy_executor::task]
+async fn high_prio() {
+    loop {
+        let start = Instant::now();
+        Timer::after(Duration::from_millis(100)).await;
+        println!(
+            "High priority: woke after {} ms",
+            start.elapsed().as_millis()
+        );
+    }
+}
+
+#[embassy_executor::task]
+async fn low_prio() {
+    loop {
+        // Hog the thread-mode executor for a while
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(500) {}
+        println!("Low priority: done blocking");
+
+        Timer::after(Duration::from_millis(10)).await;
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    embassy::init(&clocks, SystemTimer::new(peripherals.SYSTIMER));
+
+    // The time driver runs at priority 1, the interrupt executor above it
+    let spawner = EXECUTOR_HIGH.start(interrupt::Priority::Priority2);
+    spawner.must_spawn(high_prio());
+
+    let executor = EXECUTOR_LOW.init(Executor::new());
+    executor.run(|spawner: Spawner| {
+        spawner.must_spawn(low_prio());
+    });
+}
@@ -11,6 +11,7 @@ use esp32s3_hal::{
     pac::Peripherals,
     prelude::*,
     timer::TimerGroup,
+    watchdog,
     Rtc,
     Serial,
 };
@@ -21,18 +22,20 @@ use xtensa_lx_rt::entry;
 #[entry]
 fn main() -> ! {
     let peripherals = Peripherals::take().unwrap();
+
     let system = peripherals.SYSTEM.split();
     let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
 
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
     let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
     let mut timer0 = timer_group0.timer0;
-    let mut wdt = timer_group0.wdt;
-    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
     let mut serial0 = Serial::new(peripherals.UART0);
 
-    // Disable MWDT and RWDT (Watchdog) flash boot protection
-    wdt.disable();
-    rtc.rwdt.disable();
+    // Disable the watchdog timers, including their flash boot protection
+    watchdog::disable_watchdogs(&mut rtc, &mut wdt0, &mut wdt1);
 
     timer0.start(1u64.secs());
 
@@ -0,0 +1,46 @@
+//! This shows how to use the HMAC accelerator to compute an HMAC-SHA256 with
+//! a key stored in eFuse block KEY0.
+//!
+//! KEY0 must have been burnt with the `HMAC_UP` purpose, e.g. using
+//! `espefuse.py burn_key BLOCK_KEY0 key.bin HMAC_UP`.
+
+#![no_std]
+#![no_main]
+
+use esp32s3_hal::{
+    clock::ClockControl,
+    hmac::KeyId,
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Hmac,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let mut hmac = Hmac::new(peripherals.HMAC, &mut system.peripheral_clock_control);
+
+    let mut output = [0u8; 32];
+    match hmac.calculate(KeyId::Key0, b"Hello ESP32-S3", &mut output) {
+        Ok(()) => println!("HMAC: {:02x?}", output),
+        Err(err) => println!("HMAC calculation failed: {:?}", err),
+    }
+
+    loop {}
+}
@@ -0,0 +1,115 @@
+//! This shows how to drive a parallel display over the i8080 interface of the
+//! LCD_CAM peripheral, filling the screen with a single color.
+//!
+//! The pins are those of an 8-bit ST7789-like display:
+//! - D0..D7 => GPIO9..GPIO16
+//! - DC     => GPIO17
+//! - WR     => GPIO18
+//! - CS     => GPIO21
+
+#![no_std]
+#![no_main]
+
+use esp32s3_hal::{
+    clock::ClockControl,
+    gdma::{descriptor_count, DmaDescriptor, Gdma},
+    lcd_cam::{
+        lcd::{Command, I8080Config, TxEightBits, I8080},
+        LcdCam,
+    },
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Delay,
+    Rtc,
+    IO,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+const WIDTH: usize = 240;
+const HEIGHT: usize = 320;
+
+// One row of RGB565 pixels per transfer
+const ROW_SIZE: usize = WIDTH * 2;
+
+static mut DESCRIPTORS: [DmaDescriptor; descriptor_count(ROW_SIZE)] =
+    [DmaDescriptor::EMPTY; descriptor_count(ROW_SIZE)];
+static mut ROW: [u8; ROW_SIZE] = [0; ROW_SIZE];
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let mut delay = Delay::new(&clocks);
+
+    let lcd_cam = LcdCam::new(peripherals.LCD_CAM, &mut system.peripheral_clock_control);
+    let gdma = Gdma::new(peripherals.DMA, &mut system.peripheral_clock_control);
+
+    let pins = TxEightBits::new(
+        io.pins.gpio9,
+        io.pins.gpio10,
+        io.pins.gpio11,
+        io.pins.gpio12,
+        io.pins.gpio13,
+        io.pins.gpio14,
+        io.pins.gpio15,
+        io.pins.gpio16,
+    );
+
+    let mut i8080 = I8080::new(
+        lcd_cam.lcd,
+        gdma.channel0,
+        unsafe { &mut DESCRIPTORS },
+        pins,
+        20u32.MHz(),
+        I8080Config::default(),
+    )
+    .with_ctrl_pins(io.pins.gpio17, io.pins.gpio18)
+    .with_cs(io.pins.gpio21);
+
+    // Software reset, leave sleep mode, 16 bits per pixel, display on
+    i8080.send(Command::One(0x01), &[]).unwrap();
+    delay.delay_ms(120u32);
+    i8080.send(Command::One(0x11), &[]).unwrap();
+    delay.delay_ms(120u32);
+    i8080.send(Command::One(0x3a), &[0x55]).unwrap();
+    i8080.send(Command::One(0x29), &[]).unwrap();
+
+    // Full-screen window
+    let [x0, x1] = ((WIDTH - 1) as u16).to_be_bytes();
+    let [y0, y1] = ((HEIGHT - 1) as u16).to_be_bytes();
+    i8080.send(Command::One(0x2a), &[0, 0, x0, x1]).unwrap();
+    i8080.send(Command::One(0x2b), &[0, 0, y0, y1]).unwrap();
+
+    let row = unsafe { &mut ROW };
+    let mut color: u16 = 0xf800;
+
+    loop {
+        for pixel in row.chunks_exact_mut(2) {
+            pixel.copy_from_slice(&color.to_be_bytes());
+        }
+
+        // Memory write, then continue writing row by row
+        i8080.send(Command::One(0x2c), row).unwrap();
+        for _ in 1..HEIGHT {
+            i8080.send(Command::One(0x3c), row).unwrap();
+        }
+
+        println!("Filled screen with {:04x}", color);
+        color = color.rotate_right(5);
+        delay.delay_ms(1000u32);
+    }
+}
@@ -0,0 +1,67 @@
+//! Detects a hand approaching the pad connected to GPIO1, using it as a
+//! proximity channel of the touch sensor which accumulates 16 measurements
+//! per reading.
+//!
+//! The first complete reading is taken as the baseline, a hand near the pad
+//! raises the reading above it.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32s3_hal::{
+    clock::ClockControl,
+    gpio::IO,
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    touch::{ProximityChannel, TouchConfig, TouchSensor},
+    Delay,
+    Rtc,
+    Serial,
+};
+use esp_backtrace as _;
+use xtensa_lx_rt::entry;
+
+/// Increase of the reading over the baseline which counts as an approach
+const APPROACH_THRESHOLD: u32 = 2000;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let mut serial0 = Serial::new(peripherals.UART0);
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let mut delay = Delay::new(&clocks);
+
+    let mut touch = TouchSensor::new(&mut rtc.touch, TouchConfig::default());
+    let pad = touch.enable_pad(io.pins.gpio1);
+    touch.set_proximity_measurements(16);
+    touch.enable_proximity(ProximityChannel::Channel0, &pad);
+
+    while !touch.proximity_done(ProximityChannel::Channel0) {}
+    let baseline = pad.read_raw();
+    writeln!(serial0, "Baseline: {}", baseline).unwrap();
+
+    loop {
+        if touch.proximity_done(ProximityChannel::Channel0) {
+            let reading = pad.read_raw();
+            let near = reading > baseline + APPROACH_THRESHOLD;
+            writeln!(serial0, "Reading: {} near: {}", reading, near).unwrap();
+        }
+
+        delay.delay_ms(200u32);
+    }
+}
@@ -0,0 +1,180 @@
+//! USB host example, reading key presses from a HID boot keyboard
+//!
+//! The keyboard is enumerated, switched to the boot protocol, and its input
+//! reports are printed. Connect the keyboard to the USB D-/D+ lines on
+//! GPIO19/GPIO20 (the native USB port on most development boards), and power
+//! its VBUS line with 5 V.
+//!
+//! This example requires the `usb_otg` feature.
+
+#![no_std]
+#![no_main]
+
+use esp32s3_hal::{
+    clock::ClockControl,
+    otg_fs::{
+        host::{
+            Pipe,
+            SetupPacket,
+            TransferType,
+            UsbHost,
+            DESCRIPTOR_TYPE_CONFIGURATION,
+            DESCRIPTOR_TYPE_DEVICE,
+        },
+        USB,
+    },
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Rtc,
+    IO,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+const DEVICE_ADDRESS: u8 = 1;
+
+const INTERFACE_DESCRIPTOR: u8 = 4;
+const ENDPOINT_DESCRIPTOR: u8 = 5;
+const INTERFACE_CLASS_HID: u8 = 3;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+
+    let usb = USB::new(
+        peripherals.USB0,
+        io.pins.gpio19,
+        io.pins.gpio20,
+        &mut system.peripheral_clock_control,
+    );
+    let mut host = UsbHost::new(usb);
+
+    println!("Waiting for a device...");
+    while !host.is_connected() {}
+
+    let speed = host.reset_port().unwrap();
+    println!("Device connected, {:?} speed", speed);
+
+    // Read the maximum packet size of endpoint 0, then assign an address
+    let mut control = host.alloc_control_pipe(0, 8).unwrap();
+    let mut descriptor = [0; 18];
+    host.control_in(
+        &mut control,
+        &SetupPacket::get_descriptor(DESCRIPTOR_TYPE_DEVICE, 0, 8),
+        &mut descriptor,
+    )
+    .unwrap();
+    control.set_max_packet_size(descriptor[7] as u16);
+
+    host.control_out(&mut control, &SetupPacket::set_address(DEVICE_ADDRESS), &[])
+        .unwrap();
+    control.set_device_address(DEVICE_ADDRESS);
+
+    host.control_in(
+        &mut control,
+        &SetupPacket::get_descriptor(DESCRIPTOR_TYPE_DEVICE, 0, 18),
+        &mut descriptor,
+    )
+    .unwrap();
+    println!(
+        "VID {:04x}, PID {:04x}",
+        u16::from_le_bytes([descriptor[8], descriptor[9]]),
+        u16::from_le_bytes([descriptor[10], descriptor[11]]),
+    );
+
+    let mut configuration = [0; 256];
+    let len = host
+        .control_in(
+            &mut control,
+            &SetupPacket::get_descriptor(DESCRIPTOR_TYPE_CONFIGURATION, 0, 256),
+            &mut configuration,
+        )
+        .unwrap();
+
+    let (interface, mut keyboard) =
+        find_keyboard(&mut host, &configuration[..len]).expect("No HID keyboard found");
+
+    host.control_out(
+        &mut control,
+        &SetupPacket::set_configuration(configuration[5]),
+        &[],
+    )
+    .unwrap();
+
+    // SET_PROTOCOL: boot protocol
+    host.control_out(
+        &mut control,
+        &SetupPacket {
+            request_type: 0x21,
+            request: 0x0b,
+            value: 0,
+            index: interface as u16,
+            length: 0,
+        },
+        &[],
+    )
+    .unwrap();
+
+    let mut report = [0; 8];
+    loop {
+        match host.read(&mut keyboard, &mut report) {
+            Ok(_) => println!("Modifiers {:02x}, keys {:02x?}", report[0], &report[2..]),
+            Err(nb::Error::WouldBlock) => {}
+            Err(nb::Error::Other(err)) => panic!("Transfer failed: {:?}", err),
+        }
+    }
+}
+
+/// Find the first HID interface with an interrupt IN endpoint, and open a pipe
+/// to that endpoint
+fn find_keyboard<M, P>(host: &mut UsbHost<M, P>, configuration: &[u8]) -> Option<(u8, Pipe)>
+where
+    M: esp32s3_hal::otg_fs::UsbDm,
+    P: esp32s3_hal::otg_fs::UsbDp,
+{
+    let mut interface = None;
+    let mut descriptors = configuration;
+
+    while descriptors.len() >= 2 {
+        let (descriptor, rest) =
+            descriptors.split_at((descriptors[0] as usize).clamp(2, descriptors.len()));
+        descriptors = rest;
+
+        match descriptor[1] {
+            INTERFACE_DESCRIPTOR if descriptor.len() >= 9 => {
+                interface = (descriptor[5] == INTERFACE_CLASS_HID).then_some(descriptor[2]);
+            }
+            ENDPOINT_DESCRIPTOR if descriptor.len() >= 7 => {
+                let is_interrupt_in = descriptor[2] & 0x80 != 0 && descriptor[3] & 0b11 == 3;
+                if let (Some(interface), true) = (interface, is_interrupt_in) {
+                    let pipe = host
+                        .alloc_pipe(
+                            DEVICE_ADDRESS,
+                            descriptor[2] & 0x0f,
+                            TransferType::Interrupt,
+                            u16::from_le_bytes([descriptor[4], descriptor[5]]),
+                        )
+                        .ok()?;
+                    return Some((interface, pipe));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
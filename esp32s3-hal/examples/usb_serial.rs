@@ -0,0 +1,79 @@
+//! CDC-ACM serial port example using the USB OTG peripheral
+//!
+//! Characters received from the host are converted to upper case and echoed
+//! back. Connect the USB D-/D+ lines to GPIO19/GPIO20 (the native USB port on
+//! most development boards).
+//!
+//! This example requires the `usb_otg` feature.
+
+#![no_std]
+#![no_main]
+
+use esp32s3_hal::{
+    clock::ClockControl,
+    otg_fs::{UsbBus, USB},
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Rtc,
+    IO,
+};
+use esp_backtrace as _;
+use usb_device::prelude::{UsbDeviceBuilder, UsbVidPid};
+use usbd_serial::{SerialPort, USB_CLASS_CDC};
+use xtensa_lx_rt::entry;
+
+static mut EP_MEMORY: [u32; 1024] = [0; 1024];
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+
+    let usb = USB::new(
+        peripherals.USB0,
+        io.pins.gpio19,
+        io.pins.gpio20,
+        &mut system.peripheral_clock_control,
+    );
+    let usb_bus = UsbBus::new(usb, unsafe { &mut EP_MEMORY });
+
+    let mut serial = SerialPort::new(&usb_bus);
+    let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x303A, 0x3001))
+        .manufacturer("esp-hal")
+        .product("USB serial example")
+        .serial_number("12345678")
+        .device_class(USB_CLASS_CDC)
+        .build();
+
+    loop {
+        if !usb_dev.poll(&mut [&mut serial]) {
+            continue;
+        }
+
+        let mut buf = [0u8; 64];
+        if let Ok(count) = serial.read(&mut buf) {
+            let data = &mut buf[..count];
+            data.make_ascii_uppercase();
+
+            let mut written = 0;
+            while written < count {
+                match serial.write(&data[written..]) {
+                    Ok(len) => written += len,
+                    Err(_) => break,
+                }
+            }
+        }
+    }
+}
@@ -88,3 +88,19 @@ analog! {
     Gpio20: (20,  rtc_pad20,     rtc_pad20_hold,   mux_sel,      fun_sel,      fun_ie,       slp_ie,       slp_sel,       rue,       rde,       drv,       slp_oe),
     Gpio21: (21,  rtc_pad21,     rtc_pad21_hold,   mux_sel,      fun_sel,      fun_ie,       slp_ie,       slp_sel,       rue,       rde,       drv,       slp_oe),
 }
+
+pin_functions! {
+    AdcPin: [Gpio1, Gpio2, Gpio3, Gpio4, Gpio5, Gpio6, Gpio7, Gpio8, Gpio9, Gpio10, Gpio11, Gpio12, Gpio13, Gpio14, Gpio15, Gpio16, Gpio17, Gpio18, Gpio19, Gpio20],
+    TouchPin: [Gpio1, Gpio2, Gpio3, Gpio4, Gpio5, Gpio6, Gpio7, Gpio8, Gpio9, Gpio10, Gpio11, Gpio12, Gpio13, Gpio14],
+    IoMuxSpiSck: [Gpio12],
+    IoMuxSpiMosi: [Gpio11],
+    IoMuxSpiMiso: [Gpio13],
+    IoMuxSpiCs: [Gpio10],
+    RtcI2cScl: [Gpio0, Gpio2],
+    RtcI2cSda: [Gpio1, Gpio3],
+}
+
+#[cfg(feature = "usb_otg")]
+impl<MODE> esp_hal_common::otg_fs::UsbDm for Gpio19<MODE> {}
+#[cfg(feature = "usb_otg")]
+impl<MODE> esp_hal_common::otg_fs::UsbDp for Gpio20<MODE> {}
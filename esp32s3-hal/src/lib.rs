@@ -3,28 +3,57 @@
 #![cfg_attr(feature = "direct-boot", feature(asm_experimental_arch))]
 
 pub use embedded_hal as ehal;
+#[cfg(feature = "embassy")]
+pub use esp_hal_common::embassy;
+#[cfg(feature = "rtic")]
+pub use esp_hal_common::monotonic;
+#[cfg(feature = "usb_otg")]
+pub use esp_hal_common::otg_fs;
 pub use esp_hal_common::{
     clock,
+    crash,
     cpu_control::CpuControl,
+    delay,
+    ds,
     efuse,
+    error,
+    gdma,
     gpio as gpio_types,
+    hmac,
     i2c,
     interrupt,
+    lcd_cam,
     ledc,
     macros,
     pac,
+    peripherals,
     prelude,
     pulse_control,
+    reset,
+    rom,
+    rsa,
+    rtc_cntl,
+    rtc_i2c,
+    rtc_io,
     serial,
     spi,
     systimer,
+    time,
     timer,
+    touch,
+    twai,
+    ulp,
     usb_serial_jtag,
     utils,
+    watchdog,
     Cpu,
     Delay,
+    Ds,
+    Error,
+    Hmac,
     PulseControl,
     Rng,
+    Rsa,
     Rtc,
     Rwdt,
     Serial,
@@ -88,34 +117,36 @@ pub unsafe fn startup_direct_boot() -> ! {
     // set stack pointer to end of memory: no need to retain stack up to this point
     xtensa_lx::set_stack_pointer(&mut _stack_end_cpu0);
 
-    // copy rtc data from flash to destinations
-    r0::init_data(
-        &mut _rtc_fast_data_start,
-        &mut _rtc_fast_data_end,
-        &_irtc_fast_data,
-    );
-
+    // copy rtc code from flash to destinations
     r0::init_data(
         &mut _rtc_fast_text_start,
         &mut _rtc_fast_text_end,
         &_irtc_fast_text,
     );
 
-    r0::init_data(
-        &mut _rtc_slow_data_start,
-        &mut _rtc_slow_data_end,
-        &_irtc_slow_data,
-    );
-
     r0::init_data(
         &mut _rtc_slow_text_start,
         &mut _rtc_slow_text_end,
         &_irtc_slow_text,
     );
 
-    // Initialize RTC RAM
-    xtensa_lx_rt::zero_bss(&mut _rtc_fast_bss_start, &mut _rtc_fast_bss_end);
-    xtensa_lx_rt::zero_bss(&mut _rtc_slow_bss_start, &mut _rtc_slow_bss_end);
+    // Initialize RTC RAM, unless its contents have survived deep sleep
+    if !esp_hal_common::rtc_cntl::woken_from_deep_sleep() {
+        r0::init_data(
+            &mut _rtc_fast_data_start,
+            &mut _rtc_fast_data_end,
+            &_irtc_fast_data,
+        );
+
+        r0::init_data(
+            &mut _rtc_slow_data_start,
+            &mut _rtc_slow_data_end,
+            &_irtc_slow_data,
+        );
+
+        xtensa_lx_rt::zero_bss(&mut _rtc_fast_bss_start, &mut _rtc_fast_bss_end);
+        xtensa_lx_rt::zero_bss(&mut _rtc_slow_bss_start, &mut _rtc_slow_bss_end);
+    }
 
     // first of all copy rwtext
     extern "C" {
@@ -212,9 +243,11 @@ pub unsafe extern "C" fn ESP32Reset() -> ! {
     // copying data from flash to various data segments is done by the bootloader
     // initialization to zero needs to be done by the application
 
-    // Initialize RTC RAM
-    xtensa_lx_rt::zero_bss(&mut _rtc_fast_bss_start, &mut _rtc_fast_bss_end);
-    xtensa_lx_rt::zero_bss(&mut _rtc_slow_bss_start, &mut _rtc_slow_bss_end);
+    // Initialize RTC RAM, unless its contents have survived deep sleep
+    if !esp_hal_common::rtc_cntl::woken_from_deep_sleep() {
+        xtensa_lx_rt::zero_bss(&mut _rtc_fast_bss_start, &mut _rtc_fast_bss_end);
+        xtensa_lx_rt::zero_bss(&mut _rtc_slow_bss_start, &mut _rtc_slow_bss_end);
+    }
 
     // continue with default reset handler
     xtensa_lx_rt::Reset();